@@ -0,0 +1,191 @@
+#![feature(macro_metavar_expr)]
+
+use criterion::{black_box, criterion_main, Criterion};
+use std::io::Cursor;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+use drax::prelude::{DraxReadExt, DraxWriteExt, PacketComponent};
+use drax::testing::generators;
+use drax::transport::packet::primitive::VarInt;
+use drax::transport::packet::vec::VarIntVec;
+
+fn benchmark_var_int_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Variable Number Read Benchmarks");
+    for count in [10, 1_000, 100_000] {
+        let mut bytes = Cursor::new(vec![]);
+        let runtime = Runtime::new().unwrap();
+        runtime.block_on(async {
+            for value in generators::var_ints(count) {
+                bytes.write_var_int(value).await.unwrap();
+            }
+        });
+        let bytes = bytes.into_inner();
+        group.bench_with_input(
+            format!("Read {} mixed width Var Ints", count),
+            &(count, bytes),
+            |b, (count, bytes)| {
+                b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                    let mut cursor = Cursor::new(bytes.as_slice());
+                    for _ in 0..*count {
+                        black_box(cursor.read_var_int().await.unwrap());
+                    }
+                });
+            },
+        );
+    }
+}
+
+fn benchmark_string_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("String Decode Benchmarks");
+    for len in [16, 256, 32_767] {
+        let mut bytes = Cursor::new(vec![]);
+        let runtime = Runtime::new().unwrap();
+        runtime.block_on(async {
+            String::encode(&generators::string(len), &mut (), &mut bytes)
+                .await
+                .unwrap();
+        });
+        let bytes = bytes.into_inner();
+        group.bench_with_input(
+            format!("Decode string of {} characters", len),
+            &bytes,
+            |b, bytes| {
+                b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                    let mut cursor = Cursor::new(bytes.as_slice());
+                    String::decode(&mut (), &mut cursor).await.unwrap()
+                });
+            },
+        );
+    }
+}
+
+fn benchmark_vec_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Vec Decode Benchmarks");
+    for count in [16, 1_024, 65_536] {
+        let mut bytes = Cursor::new(vec![]);
+        let runtime = Runtime::new().unwrap();
+        runtime.block_on(async {
+            Vec::<VarInt>::encode(&generators::var_ints(count), &mut (), &mut bytes)
+                .await
+                .unwrap();
+        });
+        let bytes = bytes.into_inner();
+        group.bench_with_input(
+            format!("Decode Vec<VarInt> of {} elements", count),
+            &bytes,
+            |b, bytes| {
+                b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                    let mut cursor = Cursor::new(bytes.as_slice());
+                    Vec::<VarInt>::decode(&mut (), &mut cursor).await.unwrap()
+                });
+            },
+        );
+        group.bench_with_input(
+            format!("Decode VarIntVec of {} elements", count),
+            &bytes,
+            |b, bytes| {
+                b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                    let mut cursor = Cursor::new(bytes.as_slice());
+                    VarIntVec::decode(&mut (), &mut cursor).await.unwrap()
+                });
+            },
+        );
+    }
+}
+
+drax::struct_packet_components! {
+    Vec3 {
+        x: f64,
+        y: f64,
+        z: f64
+    }
+    Rotation {
+        yaw: f32,
+        pitch: f32
+    }
+    Movement {
+        entity_id: VarInt,
+        position: Vec3,
+        rotation: Rotation,
+        on_ground: bool,
+        velocity: Vec3,
+        flags: u8
+    }
+}
+
+fn benchmark_nested_struct_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Nested Struct Decode Benchmarks");
+    // 11 primitive fields across 3 nested structs, all decoded within a single boxed future
+    let mut bytes = vec![0x2A];
+    bytes.resize(1 + 24 + 8 + 1 + 24 + 1, 0);
+    group.bench_with_input("Decode movement packet", &bytes, |b, bytes| {
+        b.to_async(Runtime::new().unwrap()).iter(|| async move {
+            let mut cursor = Cursor::new(bytes.as_slice());
+            Movement::decode(&mut (), &mut cursor).await.unwrap()
+        });
+    });
+}
+
+fn benchmark_framed_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Frame Encode Benchmarks");
+    for count in [16, 1_024, 65_536] {
+        let values = generators::var_ints(count);
+        group.bench_with_input(
+            format!("Write framed Vec<VarInt> of {} elements", count),
+            &values,
+            |b, values| {
+                b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                    let mut buffer = Vec::new();
+                    buffer
+                        .write_component_framed::<(), Vec<VarInt>>(&mut (), black_box(values))
+                        .await
+                        .unwrap()
+                });
+            },
+        );
+    }
+}
+
+#[cfg(feature = "compression")]
+fn benchmark_compressed_frame_encode(c: &mut Criterion) {
+    use drax::transport::frame::FrameWriter;
+
+    let mut group = c.benchmark_group("Compressed Frame Encode Benchmarks");
+    for count in [16, 1_024, 65_536] {
+        let values = generators::var_ints(count);
+        for threshold in [None, Some(256)] {
+            let mode = match threshold {
+                Some(_) => "with compression",
+                None => "without compression",
+            };
+            group.bench_with_input(
+                format!("Encode frame of Vec<VarInt> of {} elements {}", count, mode),
+                &values,
+                |b, values| {
+                    b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                        let mut writer = FrameWriter::new(Vec::new());
+                        writer.set_compression_threshold(threshold);
+                        writer
+                            .encode_frame::<(), Vec<VarInt>>(&mut (), black_box(values))
+                            .await
+                            .unwrap()
+                    });
+                },
+            );
+        }
+    }
+}
+
+pub fn benches() {
+    let mut criterion = Criterion::default().measurement_time(Duration::from_secs(10));
+    benchmark_var_int_reads(&mut criterion);
+    benchmark_string_decode(&mut criterion);
+    benchmark_vec_decode(&mut criterion);
+    benchmark_nested_struct_decode(&mut criterion);
+    benchmark_framed_encode(&mut criterion);
+    #[cfg(feature = "compression")]
+    benchmark_compressed_frame_encode(&mut criterion);
+}
+
+criterion_main!(benches);
@@ -0,0 +1,87 @@
+use criterion::{black_box, criterion_main, Criterion};
+use std::io::Cursor;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+use drax::nbt::{write_tag, write_tag_buffered, write_tag_with_buffer, EnsuredCompoundTag};
+use drax::prelude::PacketComponent;
+use drax::testing::generators;
+
+fn benchmark_nbt_writes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("NBT Write Benchmarks");
+    for entries in [10, 1_000, 10_000] {
+        let tag = generators::compound(entries);
+        group.bench_with_input(
+            format!("Write compound with {} entries tag by tag", entries),
+            &tag,
+            |b, tag| {
+                b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                    let mut sink = tokio::io::sink();
+                    write_tag(&mut sink, black_box(tag)).await.unwrap();
+                });
+            },
+        );
+        group.bench_with_input(
+            format!("Write compound with {} entries buffered", entries),
+            &tag,
+            |b, tag| {
+                b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                    let mut sink = tokio::io::sink();
+                    write_tag_buffered(&mut sink, black_box(tag)).await.unwrap();
+                });
+            },
+        );
+        group.bench_with_input(
+            format!(
+                "Write compound with {} entries into a reused buffer",
+                entries
+            ),
+            &tag,
+            |b, tag| {
+                let runtime = Runtime::new().unwrap();
+                let mut buffer = vec![];
+                b.iter(|| {
+                    let mut sink = tokio::io::sink();
+                    runtime
+                        .block_on(write_tag_with_buffer(
+                            &mut sink,
+                            black_box(tag),
+                            &mut buffer,
+                        ))
+                        .unwrap();
+                });
+            },
+        );
+    }
+}
+
+fn benchmark_nbt_round_trips(c: &mut Criterion) {
+    let mut group = c.benchmark_group("NBT Round Trip Benchmarks");
+    for entries in [10, 1_000] {
+        let tag = Some(generators::compound(entries));
+        group.bench_with_input(
+            format!("Round trip compound with {} entries", entries),
+            &tag,
+            |b, tag| {
+                b.to_async(Runtime::new().unwrap()).iter(|| async move {
+                    let mut buffer = Cursor::new(vec![]);
+                    EnsuredCompoundTag::<0>::encode(black_box(tag), &mut (), &mut buffer)
+                        .await
+                        .unwrap();
+                    let mut buffer = Cursor::new(buffer.into_inner());
+                    EnsuredCompoundTag::<0>::decode(&mut (), &mut buffer)
+                        .await
+                        .unwrap()
+                });
+            },
+        );
+    }
+}
+
+pub fn benches() {
+    let mut criterion = Criterion::default().measurement_time(Duration::from_secs(10));
+    benchmark_nbt_writes(&mut criterion);
+    benchmark_nbt_round_trips(&mut criterion);
+}
+
+criterion_main!(benches);
@@ -1,4 +1,4 @@
-use criterion::{black_box, criterion_main, Criterion};
+use criterion::{criterion_main, Criterion};
 use std::time::Duration;
 use tokio::runtime::Runtime;
 
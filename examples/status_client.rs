@@ -0,0 +1,63 @@
+//! Pings a server for its status the way a game client's server list does, printing the status
+//! document and the round trip time.
+//!
+//! Run with `cargo run --example status_client --features vanilla-status [host] [port]`.
+
+use std::time::Instant;
+
+use drax::prelude::ProtocolVersion;
+use drax::protocols::vanilla_status::{
+    ClientboundStatus, ServerboundHandshaking, ServerboundStatus, NEXT_STATE_STATUS,
+};
+use drax::transport::frame::{FrameReader, FrameWriter};
+use tokio::net::TcpStream;
+
+#[tokio::main]
+async fn main() -> drax::prelude::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let host = args.next().unwrap_or_else(|| "127.0.0.1".to_string());
+    let port = match args.next() {
+        Some(port) => port
+            .parse()
+            .map_err(|_| drax::err_explain!(format!("Invalid port {port}")))?,
+        None => 25565,
+    };
+
+    let (read, write) = TcpStream::connect((host.as_str(), port))
+        .await?
+        .into_split();
+    let mut reader = FrameReader::new(read);
+    let mut writer = FrameWriter::new(write);
+
+    let handshake = ServerboundHandshaking::Handshake {
+        protocol_version: ProtocolVersion(763),
+        server_address: host.clone(),
+        server_port: port,
+        next_state: NEXT_STATE_STATUS,
+    };
+    writer
+        .encode_frame::<_, ServerboundHandshaking>(&mut (), &handshake)
+        .await?;
+    writer
+        .encode_frame::<_, ServerboundStatus>(&mut (), &ServerboundStatus::StatusRequest)
+        .await?;
+    writer.flush().await?;
+    match reader.decode_frame::<_, ClientboundStatus>(&mut ()).await? {
+        Some(ClientboundStatus::StatusResponse { json }) => println!("{json}"),
+        other => drax::throw_explain!(format!("Expected a status response, got {other:?}")),
+    }
+
+    let sent = Instant::now();
+    let payload = 0x6472_6178;
+    writer
+        .encode_frame::<_, ServerboundStatus>(&mut (), &ServerboundStatus::PingRequest { payload })
+        .await?;
+    writer.flush().await?;
+    match reader.decode_frame::<_, ClientboundStatus>(&mut ()).await? {
+        Some(ClientboundStatus::PongResponse { payload: echoed }) if echoed == payload => {
+            println!("Pong after {:?}", sent.elapsed())
+        }
+        other => drax::throw_explain!(format!("Expected a pong, got {other:?}")),
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! Answers status pings the way a vanilla server does, with a fixed status document.
+//!
+//! Run with `cargo run --example status_server --features vanilla-status [address]`, then point
+//! the `status_client` example or a game client's server list at it.
+
+use drax::prelude::ProtocolVersion;
+use drax::protocols::vanilla_status::{
+    ClientboundStatus, ServerboundHandshaking, ServerboundStatus, NEXT_STATE_STATUS,
+};
+use drax::transport::frame::{FrameReader, FrameWriter};
+use tokio::net::{TcpListener, TcpStream};
+
+const STATUS: &str = r#"{"version":{"name":"drax","protocol":763},"players":{"max":20,"online":0},"description":{"text":"A drax status server"}}"#;
+
+async fn serve(stream: TcpStream) -> drax::prelude::Result<()> {
+    let (read, write) = stream.into_split();
+    let mut reader = FrameReader::new(read);
+    let mut writer = FrameWriter::new(write);
+
+    let Some(ServerboundHandshaking::Handshake {
+        protocol_version: ProtocolVersion(version),
+        next_state,
+        ..
+    }) = reader
+        .decode_frame::<_, ServerboundHandshaking>(&mut ())
+        .await?
+    else {
+        return Ok(());
+    };
+    if next_state != NEXT_STATE_STATUS {
+        println!("Ignoring a login attempt from protocol {version}");
+        return Ok(());
+    }
+
+    while let Some(packet) = reader.decode_frame::<_, ServerboundStatus>(&mut ()).await? {
+        let response = match packet {
+            ServerboundStatus::StatusRequest => ClientboundStatus::StatusResponse {
+                json: STATUS.to_string(),
+            },
+            ServerboundStatus::PingRequest { payload } => {
+                ClientboundStatus::PongResponse { payload }
+            }
+        };
+        writer
+            .encode_frame::<_, ClientboundStatus>(&mut (), &response)
+            .await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> drax::prelude::Result<()> {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:25565".to_string());
+    let listener = TcpListener::bind(&address).await?;
+    println!("Listening on {address}");
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(err) = serve(stream).await {
+                println!("Status request from {peer} failed: {err}");
+            }
+        });
+    }
+}
@@ -0,0 +1,83 @@
+use crate::nbt::EnsuredCompoundTag;
+use crate::transport::packet::option::Maybe;
+use crate::transport::packet::primitive::VarInt;
+
+/// The most bytes of memory the NBT of an `ItemStack` may account for while decoding, matching
+/// the limit vanilla servers apply to NBT read from the network.
+pub const ITEM_NBT_LIMIT: u64 = 2_097_152;
+
+crate::struct_packet_components! {
+    /// A stack of items held in an inventory slot.
+    @derives(Clone, PartialEq)
+    ItemStack {
+        /// The item id from the item registry.
+        id: VarInt,
+        /// The number of items in the stack.
+        count: i8,
+        /// Additional item data such as its display name and enchantments, written as a single
+        /// end tag when there is none. Decoding fails once it accounts for more than
+        /// `ITEM_NBT_LIMIT` bytes.
+        nbt: EnsuredCompoundTag<ITEM_NBT_LIMIT>
+    }
+}
+
+/// An inventory slot, prefixed with a boolean which is `false` when the slot is empty.
+pub type Slot = Maybe<ItemStack>;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{ItemStack, Slot, ITEM_NBT_LIMIT};
+    use crate::nbt::Tag;
+    use crate::prelude::{PacketComponent, Size};
+
+    #[tokio::test]
+    async fn test_slot() -> crate::prelude::Result<()> {
+        let stack = ItemStack {
+            id: 276,
+            count: 1,
+            nbt: Some(Tag::compound_tag(vec![("Damage", Tag::TagInt(3))])),
+        };
+        let slots = [None, Some(stack.clone())];
+
+        let mut cursor = Cursor::new(vec![]);
+        for slot in &slots {
+            Slot::encode(slot, &mut (), &mut cursor).await?;
+        }
+        assert_eq!(cursor.get_ref()[0], 0);
+        assert_eq!(&cursor.get_ref()[1..5], &[1, 0x94, 0x02, 1]);
+        let size = match Slot::size(&slots[1], &mut ())? {
+            Size::Dynamic(x) | Size::Constant(x) => x,
+        };
+        assert_eq!(cursor.get_ref().len(), 1 + size);
+
+        let mut cursor = Cursor::new(cursor.into_inner());
+        for slot in &slots {
+            assert_eq!(&Slot::decode(&mut (), &mut cursor).await?, slot);
+        }
+
+        let empty_nbt = ItemStack { nbt: None, ..stack };
+        let mut cursor = Cursor::new(vec![]);
+        ItemStack::encode(&empty_nbt, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.into_inner(), vec![0x94, 0x02, 1, 0]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oversized_nbt() -> crate::prelude::Result<()> {
+        let stack = ItemStack {
+            id: 1,
+            count: 1,
+            nbt: Some(Tag::compound_tag(vec![(
+                "data",
+                Tag::TagByteArray(vec![0; ITEM_NBT_LIMIT as usize]),
+            )])),
+        };
+        let mut cursor = Cursor::new(vec![]);
+        ItemStack::encode(&stack, &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert!(ItemStack::decode(&mut (), &mut cursor).await.is_err());
+        Ok(())
+    }
+}
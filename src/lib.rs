@@ -18,6 +18,11 @@
 
 use std::pin::Pin;
 
+/// Reference implementations of components common to Minecraft's protocol, built by composing the
+/// delegates this crate provides. This module is only available with the `game` feature.
+#[cfg(feature = "game")]
+pub mod game;
+
 /// NBT is a tree data structure used and defined in Minecraft's protocol. This is extended to this
 /// crate to allow for easy low-level serialization and deserialization of NBT data. This entire
 /// module can be omitted by disabling the `nbt` feature.
@@ -27,6 +32,18 @@ pub mod nbt;
 /// Includes common types used throughout the transport layer
 pub mod prelude;
 
+/// Utilities for exercising packet components against a checked-in corpus of adversarial inputs
+/// and generators for realistic payloads used by the benchmarks. Downstream crates can reuse both
+/// to validate and measure their own delegates. This module is only available with the `test`
+/// feature, or with the `arbitrary` feature for its `arbitrary` generators.
+#[cfg(any(feature = "test", feature = "arbitrary"))]
+pub mod testing;
+
+/// Ready-made packet definitions for parts of existing protocols, built with the component macros.
+/// Each protocol is gated behind its own feature.
+#[cfg(feature = "vanilla-status")]
+pub mod protocols;
+
 /// This module contains all the types and traits necessary for building out a transport layer.
 /// Provides a method of directly interacting with the transport layer. A soft-wrapper will be
 /// available during serialization and deserialization to account for common types.
@@ -38,4 +55,35 @@ pub type PinnedResult<T> =
     Pin<Box<dyn std::future::Future<Output = transport::Result<T>> + Send + Sync>>;
 
 /// Module for quickly accessing builtin delegates.
-pub mod delegates {}
+pub mod delegates {
+    #[cfg(feature = "nbt")]
+    pub use crate::nbt::{
+        AccountedCompoundTag, CappedCompoundTag, EnsuredCompoundTag, SharedCompoundTag,
+    };
+    pub use crate::transport::id::Versioned;
+    pub use crate::transport::packet::bits::{BitSet, FixedBitSet};
+    pub use crate::transport::packet::buf::{ByteBuf, BytesStr};
+    pub use crate::transport::packet::limit::ByteBudget;
+    pub use crate::transport::packet::map::LimitedMap;
+    pub use crate::transport::packet::option::{
+        IdPlusOneOption, Maybe, SentinelOption, TailOptional,
+    };
+    pub use crate::transport::packet::prefixed::{LengthPrefixed, TrailingDataPolicy};
+    pub use crate::transport::packet::primitive::{
+        BoundedVarInt, Ranged, RangedVarInt, StrictVarInt, StrictVarLong, VarInt, VarInt21,
+        VarLong, VarNum,
+    };
+    #[cfg(all(feature = "serde", feature = "compression"))]
+    pub use crate::transport::packet::serde_json::GzJson;
+    #[cfg(feature = "serde")]
+    pub use crate::transport::packet::serde_json::{
+        JsonDelegate, JsonValue, LimitedJsonDelegate, RawJson,
+    };
+    pub use crate::transport::packet::stream::{ByteStream, StreamingBytes};
+    pub use crate::transport::packet::string::LimitedString;
+    pub use crate::transport::packet::vec::{
+        BoundedVec, ByteDrain, LimitedVec, LongPrefixedVec, LongPrefixedVecU8, SliceU8, VarIntVec,
+        VecU8,
+    };
+    pub use crate::transport::secret::SecretBytes;
+}
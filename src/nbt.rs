@@ -1,16 +1,137 @@
-use crate::prelude::{PacketComponent, Size};
-use crate::{throw_explain, PinnedLivelyResult};
-use std::io::Cursor;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::prelude::{ErrorType, PacketComponent, Size};
+use crate::transport::packet::vec::{checked_length, PREALLOCATION_LIMIT};
+use crate::transport::packet::yield_point;
+use crate::{throw, throw_explain, PinnedLivelyResult};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 pub const COMPOUND_TAG_BIT: u8 = 10;
 
+/// The deepest tags may be nested inside lists and compounds, matching vanilla. Read options can
+/// only lower it.
+const MAX_DEPTH: usize = 512;
+
+/// Caps on the shape of a tag being decoded, checked before the structure they cap is read. The
+/// byte limit alone still lets a small tag hold a list of a million empty tags, which cost little
+/// memory but a lot of time to decode. Every cap defaults to unlimited.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NbtReadOptions {
+    max_list_len: Option<usize>,
+    max_compound_entries: Option<usize>,
+    max_string_len: Option<usize>,
+    max_depth: Option<usize>,
+}
+
+impl NbtReadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the most elements a single list may declare.
+    pub fn with_max_list_len(mut self, max_list_len: usize) -> Self {
+        self.max_list_len = Some(max_list_len);
+        self
+    }
+
+    /// Sets the most entries a single compound may hold.
+    pub fn with_max_compound_entries(mut self, max_compound_entries: usize) -> Self {
+        self.max_compound_entries = Some(max_compound_entries);
+        self
+    }
+
+    /// Sets the longest string, in encoded bytes, including compound keys.
+    pub fn with_max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = Some(max_string_len);
+        self
+    }
+
+    /// Sets the deepest tags may be nested inside lists and compounds, below vanilla's 512.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn max_list_len(&self) -> Option<usize> {
+        self.max_list_len
+    }
+
+    pub fn max_compound_entries(&self) -> Option<usize> {
+        self.max_compound_entries
+    }
+
+    pub fn max_string_len(&self) -> Option<usize> {
+        self.max_string_len
+    }
+
+    /// The deepest tags may be nested, never more than vanilla's 512.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.map_or(MAX_DEPTH, |max| max.min(MAX_DEPTH))
+    }
+}
+
+/// Tracks the bytes of memory a tag being decoded is estimated to take against a limit, `0` for no
+/// limit in which case nothing is accounted, along with the caps of its read options.
 pub struct NbtAccounter {
     limit: u64,
     current: u64,
+    options: NbtReadOptions,
 }
 
 impl NbtAccounter {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            current: 0,
+            options: NbtReadOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: NbtReadOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &NbtReadOptions {
+        &self.options
+    }
+
+    fn check_depth(&self, depth: i32) -> crate::prelude::Result<()> {
+        let max = self.options.max_depth();
+        if depth as usize > max {
+            throw_explain!(format!("NBT tag too complex. Depth surpassed {max}."))
+        }
+        Ok(())
+    }
+
+    fn check_list_len(&self, len: usize) -> crate::prelude::Result<()> {
+        match self.options.max_list_len {
+            Some(max) if len > max => throw_explain!(format!(
+                "Nbt list of {len} elements exceeded maximum length {max}."
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_compound_entries(&self, entries: usize) -> crate::prelude::Result<()> {
+        match self.options.max_compound_entries {
+            Some(max) if entries > max => {
+                throw_explain!(format!("Nbt compound exceeded maximum of {max} entries."))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_string_len(&self, len: usize) -> crate::prelude::Result<()> {
+        match self.options.max_string_len {
+            Some(max) if len > max => throw_explain!(format!(
+                "Nbt string of {len} bytes exceeded maximum length {max}."
+            )),
+            _ => Ok(()),
+        }
+    }
+
     pub fn account_bytes(&mut self, bytes: u64) -> crate::prelude::Result<()> {
         if self.limit == 0 {
             return Ok(());
@@ -29,6 +150,51 @@ impl NbtAccounter {
             None => throw_explain!("Overflowed bits in accounter."),
         }
     }
+
+    /// The bytes accounted so far and the limit they were accounted against.
+    pub fn usage(&self) -> NbtUsage {
+        NbtUsage {
+            consumed: self.current,
+            limit: self.limit,
+        }
+    }
+}
+
+/// The bytes a decoded tag was accounted for against its limit, `0` for no limit. Nothing is
+/// accounted without a limit, so `consumed` is always `0` then.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NbtUsage {
+    pub consumed: u64,
+    pub limit: u64,
+}
+
+impl NbtUsage {
+    /// The bytes left under the limit, `None` if there is no limit.
+    pub fn remaining(&self) -> Option<u64> {
+        match self.limit {
+            0 => None,
+            limit => Some(limit.saturating_sub(self.consumed)),
+        }
+    }
+}
+
+/// Lets a context choose the budget of each tag decoded with `AccountedCompoundTag` and observe
+/// how much of it was used, so budgets can be logged and tightened for clients which abuse them.
+pub trait NbtBudget {
+    /// The limit to decode the next tag with, given the limit the component was declared with.
+    /// Defaults to the declared limit.
+    fn nbt_limit(&self, declared: u64) -> u64 {
+        declared
+    }
+
+    /// The caps to decode the next tag with. Defaults to no caps.
+    fn nbt_read_options(&self) -> NbtReadOptions {
+        NbtReadOptions::default()
+    }
+
+    /// Called after every tag is decoded, including tags which failed to decode, with the bytes
+    /// accounted up to that point.
+    fn record_nbt_usage(&mut self, usage: NbtUsage);
 }
 
 macro_rules! define_tags {
@@ -41,6 +207,9 @@ macro_rules! define_tags {
             fn write($writer:ident, $write_ref_ident:ident) {
                 $($writer_tt:tt)*
             },
+            fn put($buffer:ident, $put_ref_ident:ident) {
+                $($put_tt:tt)*
+            },
             fn read($reader:ident, $accounter:ident, $depth:ident) {
                 $($reader_tt:tt)*
             },
@@ -68,19 +237,21 @@ macro_rules! define_tags {
         }
 
         pub fn load_tag<'a, R: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(read: &'a mut R, bit: u8, depth: i32, accounter: &'a mut $crate::nbt::NbtAccounter) -> $crate::PinnedLivelyResult<'a, Tag> {
-            Box::pin(async move {
-                match bit {
-                    $(
-                    ${index(0)} => {
-                        let $reader = read;
-                        let $accounter = accounter;
-                        let $depth = depth;
-                        $($reader_tt)*
-                    }
-                    )*
-                    _ => $crate::throw_explain!(format!("Invalid bit {} found while loading tag.", bit))
-                }
-            })
+            // Each tag is read by its own future so nested tags only pay for the stack frame of the
+            // tag type being read rather than that of every tag type combined.
+            match bit {
+                $(
+                ${index(0)} => Box::pin(async move {
+                    let $reader = read;
+                    let $accounter = accounter;
+                    let $depth = depth;
+                    $($reader_tt)*
+                }),
+                )*
+                _ => Box::pin(async move {
+                    $crate::throw_explain!(format!("Invalid bit {} found while loading tag.", bit))
+                }),
+            }
         }
 
         pub fn write_tag<'a, W: $crate::prelude::AsyncWrite + Unpin + Send + Sync + ?Sized>(write: &'a mut W, tag: &'a Tag) -> $crate::PinnedLivelyResult<'a, ()> {
@@ -96,6 +267,18 @@ macro_rules! define_tags {
             })
         }
 
+        pub fn put_tag(buffer: &mut Vec<u8>, tag: &Tag) -> $crate::prelude::Result<()> {
+            match tag {
+                $(
+                Tag::$tag($put_ref_ident) => {
+                    let $buffer = &mut *buffer;
+                    $($put_tt)*
+                }
+                )*
+            }
+            Ok(())
+        }
+
         pub fn size_tag(tag: &Tag) -> $crate::prelude::Result<usize> {
             match tag {
                 $(
@@ -108,16 +291,26 @@ macro_rules! define_tags {
     };
 }
 
+/// Reads the length prefix of a string and accounts for its encoded bytes, so reading and skipping
+/// a string charge the same.
+async fn read_string_len<R: AsyncRead + Unpin + Send + Sync + ?Sized>(
+    read: &mut R,
+    accounter: &mut NbtAccounter,
+) -> crate::prelude::Result<usize> {
+    let len = read.read_u16().await? as usize;
+    accounter.check_string_len(len)?;
+    accounter.account_bytes(len as u64)?;
+    Ok(len)
+}
+
 async fn read_string<R: AsyncRead + Unpin + Send + Sync + ?Sized>(
     read: &mut R,
     accounter: &mut NbtAccounter,
 ) -> crate::prelude::Result<String> {
-    let len = read.read_u16().await?;
-    let mut bytes = vec![0u8; len as usize];
+    let len = read_string_len(read, accounter).await?;
+    let mut bytes = vec![0u8; len];
     read.read_exact(&mut bytes).await?;
-    let string = cesu8::from_java_cesu8(&bytes)?.to_string();
-    accounter.account_bytes(string.len() as u64)?;
-    Ok(string)
+    Ok(cesu8::from_java_cesu8(&bytes)?.to_string())
 }
 
 async fn write_string<W: AsyncWrite + Unpin + Send + Sync + ?Sized>(
@@ -125,13 +318,99 @@ async fn write_string<W: AsyncWrite + Unpin + Send + Sync + ?Sized>(
     reference: &str,
 ) -> crate::prelude::Result<()> {
     let cesu_8 = &cesu8::to_java_cesu8(reference);
-    write.write_u16(cesu_8.len() as u16).await?;
+    write.write_u16(u16::try_from(cesu_8.len())?).await?;
     write.write_all(cesu_8).await?;
     Ok(())
 }
 
+fn put_string(buffer: &mut Vec<u8>, reference: &str) -> crate::prelude::Result<()> {
+    let cesu_8 = &cesu8::to_java_cesu8(reference);
+    buffer.extend_from_slice(&u16::try_from(cesu_8.len())?.to_be_bytes());
+    buffer.extend_from_slice(cesu_8);
+    Ok(())
+}
+
 fn size_string(reference: &str) -> crate::prelude::Result<usize> {
-    Ok(2 + cesu8::to_java_cesu8(reference).len())
+    let len = cesu8::to_java_cesu8(reference).len();
+    u16::try_from(len)?;
+    Ok(2 + len)
+}
+
+/// Estimates the heap memory retained by the tag, including the spare capacity of its buffers.
+pub fn heap_size_tag(tag: &Tag) -> usize {
+    match tag {
+        Tag::TagByteArray(bytes) => bytes.capacity(),
+        Tag::TagString(string) => string.capacity(),
+        Tag::TagList((_, tags)) => {
+            tags.capacity() * std::mem::size_of::<Tag>()
+                + tags.iter().map(heap_size_tag).sum::<usize>()
+        }
+        Tag::CompoundTag(entries) => {
+            entries.capacity() * std::mem::size_of::<(String, Tag)>()
+                + entries
+                    .iter()
+                    .map(|(key, tag)| key.capacity() + heap_size_tag(tag))
+                    .sum::<usize>()
+        }
+        Tag::TagIntArray(ints) => ints.capacity() * std::mem::size_of::<i32>(),
+        Tag::TagLongArray(longs) => longs.capacity() * std::mem::size_of::<i64>(),
+        _ => 0,
+    }
+}
+
+/// Estimates the heap memory retained by the tag. Shared byte arrays, lists and compounds are
+/// counted in full, even where other tags share them.
+pub fn heap_size_arc_tag(tag: &ArcTag) -> usize {
+    // the strong and weak counts stored alongside the contents of every `Arc`
+    const ARC_COUNTS: usize = 2 * std::mem::size_of::<usize>();
+    match tag {
+        ArcTag::Value(tag) => heap_size_tag(tag),
+        ArcTag::ByteArray(bytes) => ARC_COUNTS + bytes.len(),
+        ArcTag::List(_, tags) => {
+            ARC_COUNTS
+                + std::mem::size_of_val::<[ArcTag]>(tags)
+                + tags.iter().map(heap_size_arc_tag).sum::<usize>()
+        }
+        ArcTag::Compound(entries) => {
+            ARC_COUNTS
+                + std::mem::size_of_val::<[(String, ArcTag)]>(entries)
+                + entries
+                    .iter()
+                    .map(|(key, tag)| key.capacity() + heap_size_arc_tag(tag))
+                    .sum::<usize>()
+        }
+    }
+}
+
+/// Writes the tag by first serializing the entire tree into a single pre-sized buffer and then
+/// performing one write against the underlying writer. This avoids issuing an awaited write for
+/// every primitive within the tree and should be preferred over `write_tag` for large compounds.
+/// Use `write_tag_with_buffer` to reuse the buffer between writes.
+pub async fn write_tag_buffered<W: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+    write: &mut W,
+    tag: &Tag,
+) -> crate::prelude::Result<()> {
+    write_tag_with_buffer(write, tag, &mut vec![]).await
+}
+
+/// Writes the tag like `write_tag_buffered`, serializing it into the scratch buffer, which is
+/// cleared first and grown to fit the tree if needed. Writers of many tags can keep one buffer to
+/// avoid allocating for each.
+///
+/// # Parameters
+/// * `write` - The writer to write the tag to.
+/// * `tag` - The tag to write.
+/// * `buffer` - The scratch buffer to serialize the tag into.
+pub async fn write_tag_with_buffer<W: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+    write: &mut W,
+    tag: &Tag,
+    buffer: &mut Vec<u8>,
+) -> crate::prelude::Result<()> {
+    buffer.clear();
+    buffer.reserve(size_tag(tag)?);
+    put_tag(buffer, tag)?;
+    write.write_all(buffer).await?;
+    Ok(())
 }
 
 define_tags! {
@@ -143,6 +422,7 @@ define_tags! {
         fn write(_w, _s) {
             Ok(())
         },
+        fn put(_b, _s) {},
         fn read(_r, accounter, _d) {
             accounter.account_bytes(8)?;
             Ok(Tag::TagEnd(()))
@@ -157,7 +437,9 @@ define_tags! {
             writer.write_u8(*reference).await?;
             Ok(())
         },
-
+        fn put(buffer, reference) {
+            buffer.push(*reference);
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(9)?;
             Ok(Tag::TagByte(reader.read_u8().await?))
@@ -172,6 +454,9 @@ define_tags! {
             writer.write_u16(*reference).await?;
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.extend_from_slice(&reference.to_be_bytes());
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(10)?;
             Ok(Tag::TagShort(reader.read_u16().await?))
@@ -186,6 +471,9 @@ define_tags! {
             writer.write_i32(*reference).await?;
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.extend_from_slice(&reference.to_be_bytes());
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(12)?;
             Ok(Tag::TagInt(reader.read_i32().await?))
@@ -200,6 +488,9 @@ define_tags! {
             writer.write_i64(*reference).await?;
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.extend_from_slice(&reference.to_be_bytes());
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(16)?;
             Ok(Tag::TagLong(reader.read_i64().await?))
@@ -214,6 +505,9 @@ define_tags! {
             writer.write_f32(*reference).await?;
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.extend_from_slice(&reference.to_be_bytes());
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(12)?;
             Ok(Tag::TagFloat(reader.read_f32().await?))
@@ -228,6 +522,9 @@ define_tags! {
             writer.write_f64(*reference).await?;
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.extend_from_slice(&reference.to_be_bytes());
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(16)?;
             Ok(Tag::TagDouble(reader.read_f64().await?))
@@ -239,16 +536,23 @@ define_tags! {
             Ok(4 + reference.len())
         },
         fn write(writer, reference) {
-            writer.write_i32(reference.len() as i32).await?;
+            writer.write_i32(i32::try_from(reference.len())?).await?;
             writer.write_all(reference).await?;
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.extend_from_slice(&i32::try_from(reference.len())?.to_be_bytes());
+            buffer.extend_from_slice(reference);
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(24)?;
-            let len = reader.read_i32().await?;
+            let len = checked_length(reader.read_i32().await?)?;
             accounter.account_bytes(len as u64)?;
-            let mut bytes = vec![0u8; len as usize];
-            reader.read_exact(&mut bytes).await?;
+            let mut bytes = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            reader.take(len as u64).read_to_end(&mut bytes).await?;
+            if bytes.len() != len {
+                throw!(ErrorType::EOF)
+            }
             Ok(Tag::TagByteArray(bytes))
         },
     },
@@ -260,6 +564,9 @@ define_tags! {
         fn write(writer, reference) {
             write_string(writer, reference).await
         },
+        fn put(buffer, reference) {
+            put_string(buffer, reference)?;
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(36)?;
             Ok(Tag::TagString(read_string(reader, accounter).await?))
@@ -278,22 +585,29 @@ define_tags! {
         },
         fn write(writer, reference) {
             writer.write_u8(reference.0).await?;
-            writer.write_i32(reference.1.len() as i32).await?;
+            writer.write_i32(i32::try_from(reference.1.len())?).await?;
             for tag in &reference.1 {
                 write_tag(writer, tag).await?;
             }
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.push(reference.0);
+            buffer.extend_from_slice(&i32::try_from(reference.1.len())?.to_be_bytes());
+            for tag in &reference.1 {
+                put_tag(buffer, tag)?;
+            }
+        },
         fn read(reader, accounter, depth) {
             accounter.account_bytes(37)?;
-            if depth > 512 {
-                throw_explain!("NBT tag too complex. Depth surpassed 512.")
-            }
+            accounter.check_depth(depth)?;
             let tag_byte = reader.read_u8().await?;
-            let length = reader.read_i32().await?;
-            accounter.account_bytes((4 * length) as u64)?;
-            let mut v = Vec::with_capacity(length as usize);
-            for _ in 0..length {
+            let length = checked_length(reader.read_i32().await?)?;
+            accounter.check_list_len(length)?;
+            accounter.account_bytes(4 * length as u64)?;
+            let mut v = Vec::with_capacity(length.min(PREALLOCATION_LIMIT));
+            for i in 0..length {
+                yield_point(i).await;
                 v.push(load_tag(reader, tag_byte, depth + 1, accounter).await?);
             }
             Ok(Tag::TagList((tag_byte, v)))
@@ -326,17 +640,25 @@ define_tags! {
             writer.write_u8(0).await?;
             Ok(())
         },
+        fn put(buffer, reference) {
+            for (key, value) in reference {
+                buffer.push(value.get_tag_bit());
+                put_string(buffer, key)?;
+                put_tag(buffer, value)?;
+            }
+            buffer.push(0);
+        },
         fn read(reader, accounter, depth) {
             accounter.account_bytes(48)?;
-            if depth > 512 {
-                throw_explain!("NBT tag too complex. Depth surpassed 512.")
-            }
+            accounter.check_depth(depth)?;
             let mut map = Vec::new();
             loop {
+                yield_point(map.len()).await;
                 let tag_byte = reader.read_u8().await?;
                 if tag_byte == 0 {
                     break;
                 }
+                accounter.check_compound_entries(map.len() + 1)?;
                 accounter.account_bytes(28)?;
                 let key = read_string(reader, accounter).await?;
                 let data = load_tag(reader, tag_byte, depth + 1, accounter).await?;
@@ -352,18 +674,25 @@ define_tags! {
             Ok(4 + (4 * reference.len()))
         },
         fn write(writer, reference) {
-            writer.write_i32(reference.len() as i32).await?;
+            writer.write_i32(i32::try_from(reference.len())?).await?;
             for item in reference {
                 writer.write_i32(*item).await?;
             }
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.extend_from_slice(&i32::try_from(reference.len())?.to_be_bytes());
+            for item in reference {
+                buffer.extend_from_slice(&item.to_be_bytes());
+            }
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(24)?;
-            let len = reader.read_i32().await?;
-            accounter.account_bytes((4 * len) as u64)?;
-            let mut i_arr = Vec::with_capacity(len as usize);
-            for _ in 0..len {
+            let len = checked_length(reader.read_i32().await?)?;
+            accounter.account_bytes(4 * len as u64)?;
+            let mut i_arr = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            for i in 0..len {
+                yield_point(i).await;
                 i_arr.push(reader.read_i32().await?);
             }
             Ok(Tag::TagIntArray(i_arr))
@@ -375,18 +704,25 @@ define_tags! {
             Ok(4 + (8 * reference.len()))
         },
         fn write(writer, reference) {
-            writer.write_i32(reference.len() as i32).await?;
+            writer.write_i32(i32::try_from(reference.len())?).await?;
             for item in reference {
                 writer.write_i64(*item).await?;
             }
             Ok(())
         },
+        fn put(buffer, reference) {
+            buffer.extend_from_slice(&i32::try_from(reference.len())?.to_be_bytes());
+            for item in reference {
+                buffer.extend_from_slice(&item.to_be_bytes());
+            }
+        },
         fn read(reader, accounter, _d) {
             accounter.account_bytes(24)?;
-            let len = reader.read_i32().await?;
-            accounter.account_bytes((8 * len) as u64)?;
-            let mut i_arr = Vec::with_capacity(len as usize);
-            for _ in 0..len {
+            let len = checked_length(reader.read_i32().await?)?;
+            accounter.account_bytes(8 * len as u64)?;
+            let mut i_arr = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            for i in 0..len {
+                yield_point(i).await;
                 i_arr.push(reader.read_i64().await?);
             }
             Ok(Tag::TagLongArray(i_arr))
@@ -394,152 +730,175 @@ define_tags! {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::nbt::{load_tag, read_string, write_string, write_tag, NbtAccounter, Tag};
-    use std::io::Cursor;
+/// A tag whose byte arrays, lists and compounds are shared behind an `Arc`, so cloning a large
+/// tree, or any subtree of it, is O(1). Useful for tags such as registries which are sent to every
+/// connection unchanged.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ArcTag {
+    /// A tag without nested tags. Byte arrays, lists and compounds are never stored here when
+    /// converted from a `Tag`.
+    Value(Tag),
+    ByteArray(Arc<[u8]>),
+    List(u8, Arc<[ArcTag]>),
+    Compound(Arc<[(String, ArcTag)]>),
+}
 
-    pub async fn __test_io(value: Tag) -> crate::prelude::Result<()> {
-        let mut cursor = Cursor::new(vec![]);
-        write_tag(&mut cursor, &value).await?;
-        let inner = cursor.into_inner();
-        let mut cursor = Cursor::new(inner);
-        let tag = load_tag(
-            &mut cursor,
-            value.get_tag_bit(),
-            0,
-            &mut NbtAccounter {
-                limit: 0,
-                current: 0,
-            },
-        )
-        .await?;
-        assert_eq!(tag, value);
-        Ok(())
+impl ArcTag {
+    pub fn get_tag_bit(&self) -> u8 {
+        match self {
+            ArcTag::Value(tag) => tag.get_tag_bit(),
+            ArcTag::ByteArray(_) => 7,
+            ArcTag::List(..) => 9,
+            ArcTag::Compound(_) => COMPOUND_TAG_BIT,
+        }
     }
 
-    macro_rules! test_io {
-        ($($test_name:ident, $value:expr),*) => {$(
-            #[tokio::test]
-            pub async fn $test_name() -> crate::prelude::Result<()> {
-                __test_io($value).await
+    /// Returns a copy of this tag with the value at the dot separated path replaced, or inserted if
+    /// the last key is absent, as with `Tag::patch`. Only the compounds along the path are copied;
+    /// every other subtree is shared with this tag, so patching a shared registry per connection
+    /// costs the width of the path rather than the whole tree.
+    ///
+    /// # Parameters
+    /// * `path` - The dot separated path to patch, such as `"dimension.height"`.
+    /// * `value` - The value to put at the path.
+    pub fn patch(&self, path: &str, value: ArcTag) -> crate::prelude::Result<ArcTag> {
+        let (key, rest) = match path.split_once('.') {
+            Some((key, rest)) => (key, Some(rest)),
+            None => (path, None),
+        };
+        let entries = match self {
+            ArcTag::Compound(entries) => entries,
+            _ => throw_explain!(format!(
+                "Patch path {path} passes through a non-compound tag"
+            )),
+        };
+        let mut patched = entries.to_vec();
+        match (patched.iter_mut().find(|(k, _)| k == key), rest) {
+            (Some((_, tag)), Some(rest)) => *tag = tag.patch(rest, value)?,
+            (Some((_, tag)), None) => *tag = value,
+            (None, None) => patched.push((key.to_string(), value)),
+            (None, Some(_)) => {
+                throw_explain!(format!("Missing compound {key} in patch path {path}"))
             }
-        )*};
-    }
-
-    macro_rules! create_map {
-        ($($key:expr, $value:expr),*) => {
-            vec![$(($key, $value)),*]
         }
+        Ok(ArcTag::Compound(patched.into()))
     }
+}
 
-    test_io! {
-        test_tag_end, Tag::TagEnd(()),
-        test_tag_byte, Tag::TagByte(10),
-        test_tag_short, Tag::TagShort(20),
-        test_tag_int, Tag::TagInt(30),
-        test_tag_long, Tag::TagLong(40),
-        test_tag_float, Tag::TagFloat(12.30),
-        test_tag_double, Tag::TagDouble(20.30),
-        test_tag_byte_array, Tag::TagByteArray(vec![10, 20, 0, 5]),
-        test_tag_string, Tag::TagString(format!("test string")),
-        test_tag_list, Tag::TagList((2, vec![Tag::TagShort(10u16), Tag::TagShort(20), Tag::TagShort(9), Tag::TagShort(15)])),
-        test_tag_compound, Tag::CompoundTag(create_map!(format!("abc"), Tag::TagShort(15), format!("def"), Tag::TagFloat(12.30))),
-        test_tag_int_array, Tag::TagIntArray(vec![30, 23, 123, 955]),
-        test_tag_long_array, Tag::TagLongArray(vec![321423, 24312, 123123, 12312])
-    }
-
-    #[tokio::test]
-    pub async fn test_string_read_write_persistence() -> crate::prelude::Result<()> {
-        let ref_string = format!("Example String");
-        let mut cursor = Cursor::new(vec![]);
-        write_string(&mut cursor, &ref_string).await?;
-        let mut cursor = Cursor::new(cursor.into_inner());
-        let back = read_string(
-            &mut cursor,
-            &mut NbtAccounter {
-                limit: 0,
-                current: 0,
-            },
-        )
-        .await?;
-        assert_eq!(ref_string, back);
-        Ok(())
+impl From<Tag> for ArcTag {
+    fn from(tag: Tag) -> Self {
+        match tag {
+            Tag::TagByteArray(bytes) => ArcTag::ByteArray(bytes.into()),
+            Tag::TagList((bit, tags)) => {
+                ArcTag::List(bit, tags.into_iter().map(ArcTag::from).collect())
+            }
+            Tag::CompoundTag(entries) => ArcTag::Compound(
+                entries
+                    .into_iter()
+                    .map(|(key, tag)| (key, ArcTag::from(tag)))
+                    .collect(),
+            ),
+            tag => ArcTag::Value(tag),
+        }
     }
 }
 
-#[macro_export]
-macro_rules! tag {
-    ($(
-        $tag_field_name:ident: $tag_value:expr
-    ),*) => {
-        {
-            let mut data = vec![];
-            $(
-            data.push((stringify!($tag_field_name), $tag_value));
-            )*
-            $crate::nbt::Tag::compound_tag(data)
+impl From<&ArcTag> for Tag {
+    fn from(tag: &ArcTag) -> Self {
+        match tag {
+            ArcTag::Value(tag) => tag.clone(),
+            ArcTag::ByteArray(bytes) => Tag::TagByteArray(bytes.to_vec()),
+            ArcTag::List(bit, tags) => Tag::TagList((*bit, tags.iter().map(Tag::from).collect())),
+            ArcTag::Compound(entries) => Tag::CompoundTag(
+                entries
+                    .iter()
+                    .map(|(key, tag)| (key.clone(), Tag::from(tag)))
+                    .collect(),
+            ),
         }
     }
 }
 
-impl Tag {
-    pub fn string<S: Into<String>>(into: S) -> Tag {
-        Tag::TagString(into.into())
+pub fn size_arc_tag(tag: &ArcTag) -> crate::prelude::Result<usize> {
+    match tag {
+        ArcTag::Value(tag) => size_tag(tag),
+        ArcTag::ByteArray(bytes) => Ok(4 + bytes.len()),
+        ArcTag::List(_, tags) => {
+            let mut size = 5;
+            for tag in tags.iter() {
+                size += size_arc_tag(tag)?;
+            }
+            Ok(size)
+        }
+        ArcTag::Compound(entries) => {
+            let mut size = 1;
+            for (key, tag) in entries.iter() {
+                size += size_string(key)? + 1;
+                size += size_arc_tag(tag)?;
+            }
+            Ok(size)
+        }
     }
+}
 
-    pub fn compound_tag<S: Into<String>>(data: Vec<(S, Tag)>) -> Self {
-        Tag::CompoundTag(data.into_iter().map(|(x, y)| (x.into(), y)).collect())
+pub fn put_arc_tag(buffer: &mut Vec<u8>, tag: &ArcTag) -> crate::prelude::Result<()> {
+    match tag {
+        ArcTag::Value(tag) => put_tag(buffer, tag)?,
+        ArcTag::ByteArray(bytes) => {
+            buffer.extend_from_slice(&i32::try_from(bytes.len())?.to_be_bytes());
+            buffer.extend_from_slice(bytes);
+        }
+        ArcTag::List(bit, tags) => {
+            buffer.push(*bit);
+            buffer.extend_from_slice(&i32::try_from(tags.len())?.to_be_bytes());
+            for tag in tags.iter() {
+                put_arc_tag(buffer, tag)?;
+            }
+        }
+        ArcTag::Compound(entries) => {
+            for (key, tag) in entries.iter() {
+                buffer.push(tag.get_tag_bit());
+                put_string(buffer, key)?;
+                put_arc_tag(buffer, tag)?;
+            }
+            buffer.push(0);
+        }
     }
+    Ok(())
 }
 
-pub struct EnsuredCompoundTag<const LIMIT: u64 = 0>;
+/// An `EnsuredCompoundTag` decoded into and encoded from an `ArcTag`.
+pub struct SharedCompoundTag<const LIMIT: u64 = 0>;
 
-impl<const LIMIT: u64, C: Send + Sync> PacketComponent<C> for EnsuredCompoundTag<LIMIT> {
-    type ComponentType = Option<Tag>;
+impl<const LIMIT: u64, C: Send + Sync> PacketComponent<C> for SharedCompoundTag<LIMIT> {
+    type ComponentType = Option<ArcTag>;
 
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
-        _: &'a mut C,
+        context: &'a mut C,
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType> {
         Box::pin(async move {
-            let b = read.read_u8().await?;
-            if b == 0 {
-                return Ok(None);
-            }
-            if b != 10 {
-                throw_explain!(format!(
-                    "Invalid tag bit. Expected compound tag; received {b}"
-                ));
-            }
-            let mut accounter = NbtAccounter {
-                limit: LIMIT,
-                current: 0,
-            };
-            let _ = read_string(read, &mut accounter).await?;
-            let tag = load_tag(read, b, 0, &mut accounter).await?;
-            Ok(Some(tag))
+            let tag = EnsuredCompoundTag::<LIMIT>::decode(context, read).await?;
+            Ok(tag.map(ArcTag::from))
         })
     }
 
     fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
         component_ref: &'a Self::ComponentType,
-        _: &'a mut C,
+        context: &'a mut C,
         write: &'a mut A,
     ) -> PinnedLivelyResult<'a, ()> {
         Box::pin(async move {
-            let mut buffer = Cursor::new(Vec::with_capacity(
-                match Self::size(component_ref, &mut ())? {
-                    Size::Dynamic(x) | Size::Constant(x) => x,
-                },
-            ));
             match component_ref {
                 Some(tag) => {
-                    buffer.write_u8(10).await?;
-                    write_string(&mut buffer, "").await?;
-                    write_tag(&mut buffer, tag).await?;
-                    let inner = buffer.into_inner();
-                    write.write_all(&inner).await?;
+                    let size = match Self::size(component_ref, context)? {
+                        Size::Dynamic(x) | Size::Constant(x) => x,
+                    };
+                    let mut buffer = Vec::with_capacity(size);
+                    buffer.push(COMPOUND_TAG_BIT);
+                    put_string(&mut buffer, "")?;
+                    put_arc_tag(&mut buffer, tag)?;
+                    write.write_all(&buffer).await?;
                     Ok(())
                 }
                 None => {
@@ -552,11 +911,1327 @@ impl<const LIMIT: u64, C: Send + Sync> PacketComponent<C> for EnsuredCompoundTag
 
     fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
         match input {
-            Some(tag) => {
-                let dynamic_size = Size::Dynamic(3); // short 0 for str + byte tag
-                Ok(dynamic_size + size_tag(tag)?)
-            }
+            Some(tag) => Ok(Size::Dynamic(3 + size_arc_tag(tag)?)),
             None => Ok(Size::Constant(1)),
         }
     }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.as_ref().map_or(0, heap_size_arc_tag)
+    }
+}
+
+async fn discard<R: AsyncRead + Unpin + Send + Sync + ?Sized>(
+    read: &mut R,
+    len: u64,
+) -> crate::prelude::Result<()> {
+    let copied = tokio::io::copy(&mut read.take(len), &mut tokio::io::sink()).await?;
+    if copied != len {
+        throw!(ErrorType::EOF)
+    }
+    Ok(())
+}
+
+/// Reads past the payload of a tag without building it. Skipped tags are accounted exactly as
+/// `load_tag` accounts them, so the limit bounds the work done on a projection, not just the
+/// memory it retains.
+fn skip_tag<'a, R: AsyncRead + Unpin + Send + Sync + ?Sized>(
+    read: &'a mut R,
+    bit: u8,
+    depth: i32,
+    accounter: &'a mut NbtAccounter,
+) -> PinnedLivelyResult<'a, ()> {
+    Box::pin(async move {
+        match bit {
+            0 => accounter.account_bytes(8),
+            1..=6 => {
+                let (cost, width) = match bit {
+                    1 => (9, 1),
+                    2 => (10, 2),
+                    3 | 5 => (12, 4),
+                    _ => (16, 8),
+                };
+                accounter.account_bytes(cost)?;
+                discard(read, width).await
+            }
+            7 | 11 | 12 => {
+                let width = match bit {
+                    7 => 1,
+                    11 => 4,
+                    _ => 8,
+                };
+                accounter.account_bytes(24)?;
+                let len = checked_length(read.read_i32().await?)? as u64;
+                accounter.account_bytes(width * len)?;
+                discard(read, width * len).await
+            }
+            8 => {
+                accounter.account_bytes(36)?;
+                let len = read_string_len(read, accounter).await?;
+                discard(read, len as u64).await
+            }
+            9 => {
+                accounter.account_bytes(37)?;
+                accounter.check_depth(depth)?;
+                let tag_byte = read.read_u8().await?;
+                let length = checked_length(read.read_i32().await?)?;
+                accounter.check_list_len(length)?;
+                accounter.account_bytes(4 * length as u64)?;
+                for i in 0..length {
+                    yield_point(i).await;
+                    skip_tag(read, tag_byte, depth + 1, accounter).await?;
+                }
+                Ok(())
+            }
+            COMPOUND_TAG_BIT => {
+                accounter.account_bytes(48)?;
+                accounter.check_depth(depth)?;
+                let mut i = 0;
+                loop {
+                    yield_point(i).await;
+                    i += 1;
+                    let tag_byte = read.read_u8().await?;
+                    if tag_byte == 0 {
+                        return Ok(());
+                    }
+                    accounter.check_compound_entries(i)?;
+                    accounter.account_bytes(28)?;
+                    let len = read_string_len(read, accounter).await?;
+                    discard(read, len as u64).await?;
+                    skip_tag(read, tag_byte, depth + 1, accounter).await?;
+                    accounter.account_bytes(36)?;
+                }
+            }
+            _ => throw_explain!(format!("Invalid bit {bit} found while skipping tag.")),
+        }
+    })
+}
+
+/// The paths of a projection arranged as a tree of compound keys.
+#[derive(Default)]
+struct ProjectionNode {
+    target: Option<usize>,
+    children: Vec<(String, ProjectionNode)>,
+}
+
+impl ProjectionNode {
+    fn child(&self, key: &str) -> Option<&ProjectionNode> {
+        self.children
+            .iter()
+            .find(|(child, _)| child == key)
+            .map(|(_, node)| node)
+    }
+
+    /// Fills in the values of every path below this node from an already decoded tag.
+    fn fill(&self, tag: &Tag, values: &mut [Option<Tag>]) {
+        if let Some(index) = self.target {
+            values[index] = Some(tag.clone());
+        }
+        if let Tag::CompoundTag(entries) = tag {
+            for (key, tag) in entries {
+                if let Some(node) = self.child(key) {
+                    node.fill(tag, values);
+                }
+            }
+        }
+    }
+}
+
+fn project_compound<'a, R: AsyncRead + Unpin + Send + Sync + ?Sized>(
+    read: &'a mut R,
+    node: &'a ProjectionNode,
+    depth: i32,
+    accounter: &'a mut NbtAccounter,
+    values: &'a mut [Option<Tag>],
+) -> PinnedLivelyResult<'a, ()> {
+    Box::pin(async move {
+        accounter.account_bytes(48)?;
+        accounter.check_depth(depth)?;
+        let mut i = 0;
+        loop {
+            yield_point(i).await;
+            i += 1;
+            let tag_byte = read.read_u8().await?;
+            if tag_byte == 0 {
+                return Ok(());
+            }
+            accounter.check_compound_entries(i)?;
+            accounter.account_bytes(28)?;
+            let key = read_string(read, accounter).await?;
+            match node.child(&key) {
+                Some(child) if child.target.is_some() => {
+                    let tag = load_tag(read, tag_byte, depth + 1, accounter).await?;
+                    child.fill(&tag, values);
+                }
+                Some(child) if tag_byte == COMPOUND_TAG_BIT => {
+                    project_compound(read, child, depth + 1, accounter, values).await?;
+                }
+                _ => skip_tag(read, tag_byte, depth + 1, accounter).await?,
+            }
+            accounter.account_bytes(36)?;
+        }
+    })
+}
+
+/// The tags found by `project`, in the order of the paths they were projected by.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProjectedValues {
+    paths: Vec<String>,
+    values: Vec<Option<Tag>>,
+}
+
+impl ProjectedValues {
+    /// The tag found at the given path, which must be one of the projected paths.
+    pub fn get(&self, path: &str) -> Option<&Tag> {
+        let index = self.paths.iter().position(|p| p == path)?;
+        self.values[index].as_ref()
+    }
+
+    /// The tags found at every projected path, `None` where nothing was found.
+    pub fn into_values(self) -> Vec<Option<Tag>> {
+        self.values
+    }
+}
+
+/// Decodes only the listed paths of a root compound tag, skipping every other subtree without
+/// building it. Paths are `.` separated compound keys, such as `"Data.Player.Name"`; a path may
+/// end at any tag type but can only pass through compounds.
+///
+/// # Parameters
+/// * `read` - The reader positioned at the root compound tag, as read by `EnsuredCompoundTag`.
+/// * `limit` - The accounting limit shared by projected and skipped tags, `0` for no limit.
+/// * `options` - The caps checked against projected and skipped tags alike.
+/// * `schema` - The paths to project.
+pub async fn project<R: AsyncRead + Unpin + Send + Sync + ?Sized>(
+    read: &mut R,
+    limit: u64,
+    options: NbtReadOptions,
+    schema: &[&str],
+) -> crate::prelude::Result<ProjectedValues> {
+    let mut root = ProjectionNode::default();
+    for (index, path) in schema.iter().enumerate() {
+        let mut node = &mut root;
+        for segment in path.split('.') {
+            let position = match node.children.iter().position(|(key, _)| key == segment) {
+                Some(position) => position,
+                None => {
+                    node.children
+                        .push((segment.to_string(), ProjectionNode::default()));
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[position].1;
+        }
+        node.target = Some(index);
+    }
+
+    let mut values = vec![None; schema.len()];
+    let b = read.read_u8().await?;
+    if b != 0 {
+        if b != COMPOUND_TAG_BIT {
+            throw_explain!(format!(
+                "Invalid tag bit. Expected compound tag; received {b}"
+            ));
+        }
+        let mut accounter = NbtAccounter::new(limit).with_options(options);
+        let _ = read_string(read, &mut accounter).await?;
+        project_compound(read, &root, 0, &mut accounter, &mut values).await?;
+    }
+    Ok(ProjectedValues {
+        paths: schema.iter().map(|path| path.to_string()).collect(),
+        values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nbt::{
+        load_tag, read_string, write_string, write_tag, write_tag_buffered, write_tag_with_buffer,
+        NbtAccounter, Tag,
+    };
+    use std::io::Cursor;
+
+    pub async fn __test_io(value: Tag) -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![]);
+        write_tag(&mut cursor, &value).await?;
+        let inner = cursor.into_inner();
+        let mut buffered = Cursor::new(vec![]);
+        write_tag_buffered(&mut buffered, &value).await?;
+        assert_eq!(buffered.into_inner(), inner);
+        let mut buffered = Cursor::new(vec![]);
+        let mut scratch = vec![0xFF; 3];
+        write_tag_with_buffer(&mut buffered, &value, &mut scratch).await?;
+        write_tag_with_buffer(&mut buffered, &value, &mut scratch).await?;
+        assert_eq!(buffered.into_inner(), [inner.as_slice(), &inner].concat());
+        let mut cursor = Cursor::new(inner);
+        let tag = load_tag(
+            &mut cursor,
+            value.get_tag_bit(),
+            0,
+            &mut NbtAccounter::new(0),
+        )
+        .await?;
+        assert_eq!(tag, value);
+        Ok(())
+    }
+
+    macro_rules! test_io {
+        ($($test_name:ident, $value:expr),*) => {$(
+            #[tokio::test]
+            pub async fn $test_name() -> crate::prelude::Result<()> {
+                __test_io($value).await
+            }
+        )*};
+    }
+
+    macro_rules! create_map {
+        ($($key:expr, $value:expr),*) => {
+            vec![$(($key, $value)),*]
+        }
+    }
+
+    test_io! {
+        test_tag_end, Tag::TagEnd(()),
+        test_tag_byte, Tag::TagByte(10),
+        test_tag_short, Tag::TagShort(20),
+        test_tag_int, Tag::TagInt(30),
+        test_tag_long, Tag::TagLong(40),
+        test_tag_float, Tag::TagFloat(12.30),
+        test_tag_double, Tag::TagDouble(20.30),
+        test_tag_byte_array, Tag::TagByteArray(vec![10, 20, 0, 5]),
+        test_tag_string, Tag::TagString(format!("test string")),
+        test_tag_list, Tag::TagList((2, vec![Tag::TagShort(10u16), Tag::TagShort(20), Tag::TagShort(9), Tag::TagShort(15)])),
+        test_tag_compound, Tag::CompoundTag(create_map!(format!("abc"), Tag::TagShort(15), format!("def"), Tag::TagFloat(12.30))),
+        test_tag_int_array, Tag::TagIntArray(vec![30, 23, 123, 955]),
+        test_tag_long_array, Tag::TagLongArray(vec![321423, 24312, 123123, 12312])
+    }
+
+    #[test]
+    pub fn test_nbt_macro() {
+        let id = 7;
+        let key = String::from("runtime");
+        let tag = crate::nbt!({
+            "byte" -> (3u8),
+            "int" -> (id * 2),
+            "long" -> 5i64,
+            "double" -> 0.5,
+            "name" -> "drax",
+            (key.as_str()) -> (-1),
+            "bytes" -> [B; 1, 2],
+            "ints" -> [I; id, 2],
+            "longs" -> [L; 1, (-2)],
+            "empty" -> [],
+            "entries" -> [{ "id" -> 1 }, { "id" -> (id) }]
+        });
+        assert_eq!(
+            tag,
+            Tag::compound_tag(vec![
+                ("byte", Tag::TagByte(3)),
+                ("int", Tag::TagInt(14)),
+                ("long", Tag::TagLong(5)),
+                ("double", Tag::TagDouble(0.5)),
+                ("name", Tag::string("drax")),
+                ("runtime", Tag::TagInt(-1)),
+                ("bytes", Tag::TagByteArray(vec![1, 2])),
+                ("ints", Tag::TagIntArray(vec![7, 2])),
+                ("longs", Tag::TagLongArray(vec![1, -2])),
+                ("empty", Tag::TagList((0, vec![]))),
+                (
+                    "entries",
+                    Tag::TagList((
+                        10,
+                        vec![
+                            Tag::compound_tag(vec![("id", Tag::TagInt(1))]),
+                            Tag::compound_tag(vec![("id", Tag::TagInt(7))]),
+                        ]
+                    ))
+                ),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_accounted_usage() -> crate::prelude::Result<()> {
+        use super::{AccountedCompoundTag, EnsuredCompoundTag, NbtBudget, NbtUsage};
+        use crate::prelude::PacketComponent;
+
+        #[derive(Default)]
+        struct Budget {
+            tightened: Option<u64>,
+            usages: Vec<NbtUsage>,
+        }
+
+        impl NbtBudget for Budget {
+            fn nbt_limit(&self, declared: u64) -> u64 {
+                self.tightened.unwrap_or(declared)
+            }
+
+            fn record_nbt_usage(&mut self, usage: NbtUsage) {
+                self.usages.push(usage);
+            }
+        }
+
+        let tag = crate::nbt!({ "name" -> "drax", "level" -> 7 });
+        let mut cursor = Cursor::new(vec![]);
+        EnsuredCompoundTag::<0>::encode(&Some(tag.clone()), &mut (), &mut cursor).await?;
+        let bytes = cursor.into_inner();
+
+        let mut budget = Budget::default();
+        let decoded =
+            AccountedCompoundTag::<1024>::decode(&mut budget, &mut Cursor::new(bytes.clone()))
+                .await?;
+        assert_eq!(decoded, Some(tag));
+        let usage = budget.usages[0];
+        assert_eq!(usage.limit, 1024);
+        assert!(usage.consumed > 0);
+        assert_eq!(usage.remaining(), Some(1024 - usage.consumed));
+
+        budget.tightened = Some(usage.consumed - 1);
+        assert!(
+            AccountedCompoundTag::<1024>::decode(&mut budget, &mut Cursor::new(bytes))
+                .await
+                .is_err()
+        );
+        assert_eq!(budget.usages.len(), 2);
+        assert!(budget.usages[1].consumed < usage.consumed);
+
+        let mut unlimited = super::NbtAccounter::new(0);
+        unlimited.account_bytes(u64::MAX)?;
+        unlimited.account_bytes(u64::MAX)?;
+        assert_eq!(unlimited.usage().consumed, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_read_options() -> crate::prelude::Result<()> {
+        use super::{load_tag, NbtAccounter, NbtReadOptions, COMPOUND_TAG_BIT};
+        use crate::prelude::PacketComponent;
+
+        // a list declaring a million end tags, a handful of bytes on the wire
+        let mut bytes = vec![9, 0];
+        bytes.extend_from_slice(&1_000_000i32.to_be_bytes());
+        let options = NbtReadOptions::new().with_max_list_len(1024);
+        let mut accounter = NbtAccounter::new(0).with_options(options);
+        assert!(load_tag(&mut Cursor::new(bytes), 9, 0, &mut accounter)
+            .await
+            .is_err());
+
+        let tag = crate::nbt!({ "a" -> 1, "b" -> "drax", "c" -> [1, 2] });
+        let mut bytes = vec![];
+        super::put_tag(&mut bytes, &tag)?;
+        let load = |options: NbtReadOptions| {
+            let bytes = bytes.clone();
+            async move {
+                let mut accounter = NbtAccounter::new(0).with_options(options);
+                load_tag(&mut Cursor::new(bytes), COMPOUND_TAG_BIT, 0, &mut accounter).await
+            }
+        };
+        assert_eq!(load(NbtReadOptions::new()).await?, tag);
+        let at_limit = NbtReadOptions::new()
+            .with_max_list_len(2)
+            .with_max_compound_entries(3)
+            .with_max_string_len(4);
+        assert_eq!(load(at_limit).await?, tag);
+        assert!(load(at_limit.with_max_list_len(1)).await.is_err());
+        assert!(load(at_limit.with_max_compound_entries(2)).await.is_err());
+        assert!(load(at_limit.with_max_string_len(3)).await.is_err());
+        assert_eq!(load(at_limit.with_max_depth(1)).await?, tag);
+        assert!(load(at_limit.with_max_depth(0)).await.is_err());
+
+        struct ShortLists;
+
+        impl super::NbtReadPreset for ShortLists {
+            fn nbt_read_options() -> NbtReadOptions {
+                NbtReadOptions::new().with_max_list_len(1)
+            }
+        }
+
+        let mut bytes = vec![];
+        super::EnsuredCompoundTag::<0>::encode(&Some(tag.clone()), &mut (), &mut bytes).await?;
+        assert!(
+            super::CappedCompoundTag::<ShortLists>::decode(&mut (), &mut Cursor::new(bytes))
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_arc_tag() -> crate::prelude::Result<()> {
+        use super::{ArcTag, EnsuredCompoundTag, SharedCompoundTag};
+        use crate::prelude::PacketComponent;
+
+        let tag = crate::testing::generators::compound(8);
+        let shared = ArcTag::from(tag.clone());
+        assert_eq!(Tag::from(&shared), tag);
+
+        let copy = shared.clone();
+        match (&shared, &copy) {
+            (ArcTag::Compound(a), ArcTag::Compound(b)) => assert!(std::sync::Arc::ptr_eq(a, b)),
+            _ => panic!("Expected a shared compound"),
+        }
+
+        let mut cursor = Cursor::new(vec![]);
+        EnsuredCompoundTag::<0>::encode(&Some(tag.clone()), &mut (), &mut cursor).await?;
+        let expected = cursor.into_inner();
+        let mut cursor = Cursor::new(vec![]);
+        SharedCompoundTag::<0>::encode(&Some(shared.clone()), &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &expected);
+        assert_eq!(
+            SharedCompoundTag::<0>::size(&Some(shared.clone()), &mut ())?,
+            crate::prelude::Size::Dynamic(expected.len())
+        );
+        assert!(
+            <SharedCompoundTag<0> as PacketComponent<()>>::heap_size(&Some(shared.clone()))
+                >= expected.len()
+        );
+
+        let mut cursor = Cursor::new(expected);
+        assert_eq!(
+            SharedCompoundTag::<0>::decode(&mut (), &mut cursor).await?,
+            Some(shared)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_project() -> crate::prelude::Result<()> {
+        use super::{project, EnsuredCompoundTag, NbtReadOptions};
+        use crate::prelude::PacketComponent;
+
+        let tag = crate::nbt!({
+            "skipped" -> {
+                "bytes" -> [B; 1, 2, 3],
+                "ints" -> [I; 4, 5],
+                "longs" -> [L; 6],
+                "list" -> [[1u8, 2u8], [3u8]],
+                "values" -> [1.5f32, 2.5f32],
+            },
+            "Data" -> {
+                "Name" -> "drax",
+                "Level" -> 7,
+                "Pos" -> [1.0, 2.0, 3.0],
+                "Extra" -> (crate::testing::generators::compound(4)),
+            },
+            "Version" -> 3465i64,
+        });
+        let mut cursor = Cursor::new(vec![]);
+        EnsuredCompoundTag::<0>::encode(&Some(tag), &mut (), &mut cursor).await?;
+        let bytes = cursor.into_inner();
+
+        let mut cursor = Cursor::new(bytes.clone());
+        let schema = ["Data.Name", "Data.Pos", "Version", "Data.Missing", "Data"];
+        let projected = project(&mut cursor, 0, NbtReadOptions::new(), &schema[..4]).await?;
+        assert_eq!(cursor.position() as usize, bytes.len());
+        assert_eq!(projected.get("Data.Name"), Some(&Tag::from("drax")));
+        assert_eq!(
+            projected.get("Data.Pos"),
+            Some(&crate::nbt!([1.0, 2.0, 3.0]))
+        );
+        assert_eq!(projected.get("Version"), Some(&Tag::TagLong(3465)));
+        assert_eq!(projected.get("Data.Missing"), None);
+
+        let mut cursor = Cursor::new(bytes.clone());
+        let projected = project(&mut cursor, 0, NbtReadOptions::new(), &schema)
+            .await?
+            .into_values();
+        assert_eq!(projected[0], Some(Tag::from("drax")));
+        assert!(matches!(projected[4], Some(Tag::CompoundTag(_))));
+
+        let mut cursor = Cursor::new(bytes);
+        assert!(
+            project(&mut cursor, 64, NbtReadOptions::new(), &["Version"])
+                .await
+                .is_err()
+        );
+
+        // a list of fifty million end tags in thirteen bytes is charged as load_tag charges it
+        let mut bytes = vec![10, 0, 0, 9, 0, 1, b'a', 0];
+        bytes.extend_from_slice(&50_000_000i32.to_be_bytes());
+        bytes.push(0);
+        let err = project(
+            &mut Cursor::new(bytes.clone()),
+            1024,
+            NbtReadOptions::new(),
+            &["b"],
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("too big"));
+        let options = NbtReadOptions::new().with_max_list_len(1024);
+        let err = project(&mut Cursor::new(bytes.clone()), 0, options, &["b"])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeded maximum length"));
+        let err = load_tag(
+            &mut Cursor::new(bytes[3..].to_vec()),
+            10,
+            0,
+            &mut NbtAccounter::new(1024),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("too big"));
+
+        // strings whose modified UTF-8 is longer than their UTF-8 are charged the same either way
+        let tag = crate::nbt!({ "\u{1F600}" -> "caf\u{E9} \u{1F600}" });
+        let mut bytes = Cursor::new(vec![]);
+        write_tag(&mut bytes, &tag).await?;
+        let bytes = bytes.into_inner();
+        let mut loaded = NbtAccounter::new(u64::MAX);
+        load_tag(&mut Cursor::new(bytes.clone()), 10, 0, &mut loaded).await?;
+        let mut skipped = NbtAccounter::new(u64::MAX);
+        super::skip_tag(&mut Cursor::new(bytes), 10, 0, &mut skipped).await?;
+        assert_eq!(loaded.usage(), skipped.usage());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_conversions() -> crate::prelude::Result<()> {
+        let tag = crate::nbt!({
+            "name" -> "drax",
+            "level" -> 7,
+            "ids" -> [L; 1, 2],
+            "entries" -> [1, 2]
+        });
+        let entries = tag.as_compound().unwrap();
+        assert_eq!(entries[0].1.as_str(), Some("drax"));
+        assert_eq!(i32::try_from(&entries[1].1)?, 7);
+        assert_eq!(Vec::<i64>::try_from(&entries[2].1)?, vec![1, 2]);
+        assert_eq!(String::try_from(&entries[0].1)?, "drax".to_string());
+        assert_eq!(entries[3].1.as_list().map(<[Tag]>::len), Some(2));
+        assert!(i64::try_from(&entries[1].1).is_err());
+        assert_eq!(entries[1].1.as_str(), None);
+        assert_eq!(tag.as_list(), None);
+        assert!(bool::try_from(&Tag::from(true))?);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_patch_and_merge() -> crate::prelude::Result<()> {
+        use super::{ArcTag, MergeStrategy};
+
+        let mut tag = crate::nbt!({
+            "dimension" -> { "height" -> 256, "name" -> "overworld" },
+            "ids" -> [I; 1, 2]
+        });
+        assert_eq!(
+            tag.patch("dimension.height", Tag::from(384))?,
+            Some(Tag::TagInt(256))
+        );
+        assert_eq!(tag.patch("dimension.min_y", Tag::from(-64))?, None);
+        assert!(tag.patch("ids.first", Tag::from(1)).is_err());
+        assert!(tag.patch("missing.key", Tag::from(1)).is_err());
+        assert_eq!(
+            tag,
+            crate::nbt!({
+                "dimension" -> { "height" -> 384, "name" -> "overworld", "min_y" -> (-64) },
+                "ids" -> [I; 1, 2]
+            })
+        );
+
+        let shared = ArcTag::from(crate::nbt!({
+            "dimension" -> { "height" -> 256 },
+            "biome" -> { "name" -> "plains" }
+        }));
+        let patched = shared.patch("dimension.height", ArcTag::from(Tag::from(384)))?;
+        assert_eq!(
+            Tag::from(&patched),
+            crate::nbt!({
+                "dimension" -> { "height" -> 384 },
+                "biome" -> { "name" -> "plains" }
+            })
+        );
+        // untouched subtrees are shared rather than copied
+        match (&shared, &patched) {
+            (ArcTag::Compound(before), ArcTag::Compound(after)) => {
+                match (&before[1].1, &after[1].1) {
+                    (ArcTag::Compound(a), ArcTag::Compound(b)) => {
+                        assert!(std::sync::Arc::ptr_eq(a, b))
+                    }
+                    _ => panic!("Expected a shared compound"),
+                }
+            }
+            _ => panic!("Expected compounds"),
+        }
+        assert!(shared
+            .patch("biome.name.id", ArcTag::from(Tag::from(1)))
+            .is_err());
+
+        let mut base = crate::nbt!({ "a" -> 1, "inner" -> { "b" -> 2 } });
+        let other = crate::nbt!({ "a" -> 3, "inner" -> { "c" -> 4 }, "d" -> 5 });
+        let mut kept = base.clone();
+        kept.merge(other.clone(), MergeStrategy::KeepExisting);
+        assert_eq!(
+            kept,
+            crate::nbt!({ "a" -> 1, "inner" -> { "b" -> 2, "c" -> 4 }, "d" -> 5 })
+        );
+        base.merge(other, MergeStrategy::Overwrite);
+        assert_eq!(
+            base,
+            crate::nbt!({ "a" -> 3, "inner" -> { "b" -> 2, "c" -> 4 }, "d" -> 5 })
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_compound() {
+        use super::Compound;
+
+        let mut compound = Compound::new()
+            .with("name", "drax")
+            .with("level", 7)
+            .with("flag", true)
+            .with("pos", crate::nbt!([1.0, 2.0]))
+            .with("inner", Compound::new().with("id", 1i64));
+        assert_eq!(compound.get_str("name"), Some("drax"));
+        assert_eq!(compound.get_i32("level"), Some(7));
+        assert_eq!(compound.get_i64("level"), None);
+        assert_eq!(compound.get_bool("flag"), Some(true));
+        assert_eq!(compound.get_list("pos").map(<[Tag]>::len), Some(2));
+        assert_eq!(
+            compound.get_compound("inner"),
+            Some(&[("id".to_string(), Tag::TagLong(1))][..])
+        );
+
+        assert_eq!(compound.insert("level", 8), Some(Tag::TagInt(7)));
+        assert_eq!(compound.remove("flag"), Some(Tag::TagByte(1)));
+        assert_eq!(
+            compound.keys().collect::<Vec<_>>(),
+            vec!["name", "level", "pos", "inner"]
+        );
+
+        let tag = Tag::from(compound.clone());
+        assert_eq!(
+            tag,
+            crate::nbt!({
+                "name" -> "drax",
+                "level" -> 8,
+                "pos" -> [1.0, 2.0],
+                "inner" -> { "id" -> 1i64 },
+            })
+        );
+        assert_eq!(Compound::try_from(tag), Ok(compound));
+        assert_eq!(Compound::try_from(Tag::TagInt(1)), Err(Tag::TagInt(1)));
+    }
+
+    #[tokio::test]
+    pub async fn test_string_read_write_persistence() -> crate::prelude::Result<()> {
+        let ref_string = format!("Example String");
+        let mut cursor = Cursor::new(vec![]);
+        write_string(&mut cursor, &ref_string).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        let back = read_string(&mut cursor, &mut NbtAccounter::new(0)).await?;
+        assert_eq!(ref_string, back);
+
+        let too_long = "a".repeat(u16::MAX as usize + 1);
+        assert!(super::put_string(&mut vec![], &too_long).is_err());
+        assert!(write_string(&mut Cursor::new(vec![]), &too_long)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
+
+#[macro_export]
+macro_rules! tag {
+    ($(
+        $tag_field_name:ident: $tag_value:expr
+    ),*) => {
+        {
+            let mut data = vec![];
+            $(
+            data.push((stringify!($tag_field_name), $tag_value));
+            )*
+            $crate::nbt::Tag::compound_tag(data)
+        }
+    }
+}
+
+/// Builds a `Tag` from NBT-like syntax.
+///
+/// * `{ "key" -> value, ... }` builds a compound; keys may be any expression convertible into a
+///   `String` when wrapped in parentheses.
+/// * `[value, ...]` builds a list typed by its first element.
+/// * `[B; ...]`, `[I; ...]` and `[L; ...]` build byte, int and long arrays from expressions.
+/// * A literal, or any expression wrapped in parentheses, is converted with `Tag::from`. Negative
+///   numbers must be wrapped in parentheses.
+///
+/// ```
+/// # use drax::{nbt, nbt::Tag};
+/// let name = "drax";
+/// let tag = nbt!({
+///     "name" -> (name),
+///     "scale" -> 0.5,
+///     "ids" -> [I; 1, 2, 3],
+///     "entries" -> [{ "id" -> 1 }, { "id" -> (-2) }]
+/// });
+/// # assert!(matches!(tag, Tag::CompoundTag(_)));
+/// ```
+#[macro_export]
+macro_rules! nbt {
+    ({ $($key:tt -> $value:tt),* $(,)? }) => {
+        $crate::nbt::Tag::CompoundTag(vec![
+            $(($crate::nbt!(@key $key), $crate::nbt!($value))),*
+        ])
+    };
+    (@key ($key:expr)) => {
+        ::std::string::String::from($key)
+    };
+    (@key $key:literal) => {
+        ::std::string::String::from($key)
+    };
+    ([B; $($value:expr),* $(,)?]) => {
+        $crate::nbt::Tag::TagByteArray(vec![$($value),*])
+    };
+    ([I; $($value:expr),* $(,)?]) => {
+        $crate::nbt::Tag::TagIntArray(vec![$($value),*])
+    };
+    ([L; $($value:expr),* $(,)?]) => {
+        $crate::nbt::Tag::TagLongArray(vec![$($value),*])
+    };
+    ([$($value:tt),* $(,)?]) => {
+        $crate::nbt::Tag::list(vec![$($crate::nbt!($value)),*])
+    };
+    (($value:expr)) => {
+        $crate::nbt::Tag::from($value)
+    };
+    ($value:literal) => {
+        $crate::nbt::Tag::from($value)
+    };
+}
+
+impl Tag {
+    pub fn string<S: Into<String>>(into: S) -> Tag {
+        Tag::TagString(into.into())
+    }
+
+    pub fn compound_tag<S: Into<String>>(data: Vec<(S, Tag)>) -> Self {
+        Tag::CompoundTag(data.into_iter().map(|(x, y)| (x.into(), y)).collect())
+    }
+
+    /// Creates a list typed by its first element, or an empty list of end tags.
+    ///
+    /// # Panics
+    /// Panics in debug builds if the elements are not all of the same type.
+    pub fn list(tags: Vec<Tag>) -> Self {
+        let bit = tags.first().map_or(0, Tag::get_tag_bit);
+        debug_assert!(
+            tags.iter().all(|tag| tag.get_tag_bit() == bit),
+            "List elements must all be of the same type"
+        );
+        Tag::TagList((bit, tags))
+    }
+
+    /// Replaces the value at the dot separated path of compound keys, inserting it if the last key
+    /// is absent, and returns the value it replaced. Every key before the last must name an
+    /// existing compound.
+    ///
+    /// # Parameters
+    /// * `path` - The dot separated path to patch, such as `"dimension.height"`.
+    /// * `value` - The value to put at the path.
+    pub fn patch(&mut self, path: &str, value: Tag) -> crate::prelude::Result<Option<Tag>> {
+        let mut keys = path.split('.');
+        let last = keys.next_back().unwrap_or_default();
+        let mut current = self;
+        for key in keys {
+            current = match current {
+                Tag::CompoundTag(entries) => match entries.iter_mut().find(|(k, _)| k == key) {
+                    Some((_, tag)) => tag,
+                    None => throw_explain!(format!("Missing compound {key} in patch path {path}")),
+                },
+                _ => throw_explain!(format!(
+                    "Patch path {path} passes through a non-compound tag"
+                )),
+            };
+        }
+        match current {
+            Tag::CompoundTag(entries) => match entries.iter_mut().find(|(k, _)| k == last) {
+                Some((_, tag)) => Ok(Some(std::mem::replace(tag, value))),
+                None => {
+                    entries.push((last.to_string(), value));
+                    Ok(None)
+                }
+            },
+            _ => throw_explain!(format!("Patch path {path} ends in a non-compound tag")),
+        }
+    }
+
+    /// Merges the other tag into this one. Compounds on both sides are merged key by key, all other
+    /// values, including lists, are resolved as a whole by the strategy.
+    pub fn merge(&mut self, other: Tag, strategy: MergeStrategy) {
+        match (self, other) {
+            (Tag::CompoundTag(entries), Tag::CompoundTag(others)) => {
+                for (key, other) in others {
+                    match entries.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, tag)) => tag.merge(other, strategy),
+                        None => entries.push((key, other)),
+                    }
+                }
+            }
+            (tag, other) => {
+                if strategy == MergeStrategy::Overwrite {
+                    *tag = other;
+                }
+            }
+        }
+    }
+}
+
+/// How `Tag::merge` resolves values present on both sides which aren't both compounds.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MergeStrategy {
+    /// The incoming value replaces the existing one.
+    Overwrite,
+    /// The existing value is kept; only keys missing from it are added.
+    KeepExisting,
+}
+
+macro_rules! tag_from {
+    ($($from:ty => $tag:ident),*) => {$(
+        impl From<$from> for Tag {
+            fn from(value: $from) -> Self {
+                Tag::$tag(value.into())
+            }
+        }
+    )*};
+}
+
+tag_from!(
+    u8 => TagByte,
+    u16 => TagShort,
+    i32 => TagInt,
+    i64 => TagLong,
+    f32 => TagFloat,
+    f64 => TagDouble,
+    String => TagString,
+    &str => TagString,
+    Vec<u8> => TagByteArray,
+    Vec<i32> => TagIntArray,
+    Vec<i64> => TagLongArray
+);
+
+impl From<bool> for Tag {
+    fn from(value: bool) -> Self {
+        Tag::TagByte(value as u8)
+    }
+}
+
+macro_rules! tag_accessors {
+    ($($(#[$meta:meta])* $as:ident / $get:ident($tag:ident) -> $out:ty => |$value:ident| $convert:expr;)*) => {
+        impl Tag {$(
+            $(#[$meta])*
+            pub fn $as(&self) -> Option<$out> {
+                match self {
+                    Tag::$tag($value) => Some($convert),
+                    _ => None,
+                }
+            }
+        )*}
+
+        impl Compound {$(
+            $(#[$meta])*
+            pub fn $get(&self, key: &str) -> Option<$out> {
+                self.get(key)?.$as()
+            }
+        )*}
+    };
+}
+
+tag_accessors! {
+    as_u8 / get_u8(TagByte) -> u8 => |value| *value;
+    /// A byte tag read as a boolean, anything but `0` is `true`.
+    as_bool / get_bool(TagByte) -> bool => |value| *value != 0;
+    as_u16 / get_u16(TagShort) -> u16 => |value| *value;
+    as_i32 / get_i32(TagInt) -> i32 => |value| *value;
+    as_i64 / get_i64(TagLong) -> i64 => |value| *value;
+    as_f32 / get_f32(TagFloat) -> f32 => |value| *value;
+    as_f64 / get_f64(TagDouble) -> f64 => |value| *value;
+    as_str / get_str(TagString) -> &str => |value| value.as_str();
+    as_byte_array / get_byte_array(TagByteArray) -> &[u8] => |value| value.as_slice();
+    as_int_array / get_int_array(TagIntArray) -> &[i32] => |value| value.as_slice();
+    as_long_array / get_long_array(TagLongArray) -> &[i64] => |value| value.as_slice();
+    /// The elements of a list tag, regardless of their type.
+    as_list / get_list(TagList) -> &[Tag] => |value| value.1.as_slice();
+    /// The entries of a compound tag.
+    as_compound / get_compound(CompoundTag) -> &[(String, Tag)] => |value| value.as_slice();
+}
+
+macro_rules! tag_try_from {
+    ($($to:ty => $as:ident),*) => {$(
+        impl TryFrom<&Tag> for $to {
+            type Error = crate::prelude::TransportError;
+
+            /// Copies the value out of a tag of the matching type, failing for any other tag.
+            fn try_from(tag: &Tag) -> Result<Self, Self::Error> {
+                match tag.$as() {
+                    Some(value) => Ok(value.to_owned()),
+                    None => Err(crate::err_explain!(format!(
+                        "Expected a tag holding {} but found tag type {}",
+                        stringify!($to),
+                        tag.get_tag_bit()
+                    ))),
+                }
+            }
+        }
+    )*};
+}
+
+tag_try_from!(
+    u8 => as_u8,
+    bool => as_bool,
+    u16 => as_u16,
+    i32 => as_i32,
+    i64 => as_i64,
+    f32 => as_f32,
+    f64 => as_f64,
+    String => as_str,
+    Vec<u8> => as_byte_array,
+    Vec<i32> => as_int_array,
+    Vec<i64> => as_long_array
+);
+
+/// An ordered compound tag with typed accessors. Entries keep their insertion order and converting
+/// to or from `Tag::CompoundTag` moves the entries without copying them.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Compound {
+    entries: Vec<(String, Tag)>,
+}
+
+impl Compound {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Inserts the value and returns the compound, for building compounds inline.
+    pub fn with<K: Into<String>, V: Into<Tag>>(mut self, key: K, value: V) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// Inserts the value, replacing and returning the value already at the key in place, or
+    /// appending it otherwise.
+    pub fn insert<K: Into<String>, V: Into<Tag>>(&mut self, key: K, value: V) -> Option<Tag> {
+        let key = key.into();
+        let value = value.into();
+        match self.get_mut(&key) {
+            Some(existing) => Some(std::mem::replace(existing, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Tag> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Tag> {
+        self.entries
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(k, _)| k.as_str())
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, Tag)> {
+        self.entries.iter()
+    }
+
+    pub fn into_inner(self) -> Vec<(String, Tag)> {
+        self.entries
+    }
+}
+
+impl From<Vec<(String, Tag)>> for Compound {
+    fn from(entries: Vec<(String, Tag)>) -> Self {
+        Self { entries }
+    }
+}
+
+impl From<Compound> for Tag {
+    fn from(compound: Compound) -> Self {
+        Tag::CompoundTag(compound.entries)
+    }
+}
+
+impl TryFrom<Tag> for Compound {
+    type Error = Tag;
+
+    /// Unwraps a compound tag, handing back any other tag unchanged.
+    fn try_from(tag: Tag) -> Result<Self, Self::Error> {
+        match tag {
+            Tag::CompoundTag(entries) => Ok(Self { entries }),
+            tag => Err(tag),
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<Tag>> FromIterator<(K, V)> for Compound {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut compound = Compound::new();
+        for (key, value) in iter {
+            compound.insert(key, value);
+        }
+        compound
+    }
+}
+
+impl IntoIterator for Compound {
+    type Item = (String, Tag);
+    type IntoIter = std::vec::IntoIter<(String, Tag)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Compound {
+    type Item = &'a (String, Tag);
+    type IntoIter = std::slice::Iter<'a, (String, Tag)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+async fn load_root<R: AsyncRead + Unpin + Send + Sync + ?Sized>(
+    read: &mut R,
+    bit: u8,
+    accounter: &mut NbtAccounter,
+) -> crate::prelude::Result<Tag> {
+    if bit != COMPOUND_TAG_BIT {
+        throw_explain!(format!(
+            "Invalid tag bit. Expected compound tag; received {bit}"
+        ));
+    }
+    let _ = read_string(read, accounter).await?;
+    load_tag(read, bit, 0, accounter).await
+}
+
+/// Reads the leading tag bit of an optional root compound tag, `None` for the empty tag.
+async fn load_optional_root<R: AsyncRead + Unpin + Send + Sync + ?Sized>(
+    read: &mut R,
+    accounter: &mut NbtAccounter,
+) -> crate::prelude::Result<Option<Tag>> {
+    let b = read.read_u8().await?;
+    if b == 0 {
+        return Ok(None);
+    }
+    Ok(Some(load_root(read, b, accounter).await?))
+}
+
+pub struct EnsuredCompoundTag<const LIMIT: u64 = 0>;
+
+impl<const LIMIT: u64, C: Send + Sync> PacketComponent<C> for EnsuredCompoundTag<LIMIT> {
+    type ComponentType = Option<Tag>;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move { load_optional_root(read, &mut NbtAccounter::new(LIMIT)).await })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            match component_ref {
+                Some(tag) => {
+                    let size = match Self::size(component_ref, &mut ())? {
+                        Size::Dynamic(x) | Size::Constant(x) => x,
+                    };
+                    let mut buffer = Vec::with_capacity(size);
+                    buffer.push(COMPOUND_TAG_BIT);
+                    put_string(&mut buffer, "")?;
+                    put_tag(&mut buffer, tag)?;
+                    write.write_all(&buffer).await?;
+                    Ok(())
+                }
+                None => {
+                    write.write_u8(0).await?;
+                    Ok(())
+                }
+            }
+        })
+    }
+
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        match input {
+            Some(tag) => {
+                let dynamic_size = Size::Dynamic(3); // short 0 for str + byte tag
+                Ok(dynamic_size + size_tag(tag)?)
+            }
+            None => Ok(Size::Constant(1)),
+        }
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.as_ref().map_or(0, heap_size_tag)
+    }
+}
+
+/// Read options fixed by a type, so a component can be capped without its context taking part.
+pub trait NbtReadPreset {
+    fn nbt_read_options() -> NbtReadOptions;
+}
+
+/// An `EnsuredCompoundTag` decoded with the read options of the preset `P`.
+pub struct CappedCompoundTag<P, const LIMIT: u64 = 0>(PhantomData<P>);
+
+impl<P: NbtReadPreset, const LIMIT: u64, C: Send + Sync> PacketComponent<C>
+    for CappedCompoundTag<P, LIMIT>
+{
+    type ComponentType = Option<Tag>;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let mut accounter = NbtAccounter::new(LIMIT).with_options(P::nbt_read_options());
+            load_optional_root(read, &mut accounter).await
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        EnsuredCompoundTag::<LIMIT>::encode(component_ref, context, write)
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        EnsuredCompoundTag::<LIMIT>::size(input, context)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        <EnsuredCompoundTag<LIMIT> as PacketComponent<C>>::heap_size(input)
+    }
+}
+
+/// An `EnsuredCompoundTag` decoded with the limit chosen by its context, which is told the bytes
+/// the tag was accounted for once it has been read, see `NbtBudget`.
+pub struct AccountedCompoundTag<const LIMIT: u64 = 0>;
+
+impl<const LIMIT: u64, C: NbtBudget + Send + Sync> PacketComponent<C>
+    for AccountedCompoundTag<LIMIT>
+{
+    type ComponentType = Option<Tag>;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let mut accounter = NbtAccounter::new(context.nbt_limit(LIMIT))
+                .with_options(context.nbt_read_options());
+            let tag = load_optional_root(read, &mut accounter).await;
+            context.record_nbt_usage(accounter.usage());
+            tag
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        EnsuredCompoundTag::<LIMIT>::encode(component_ref, context, write)
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        EnsuredCompoundTag::<LIMIT>::size(input, context)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        <EnsuredCompoundTag<LIMIT> as PacketComponent<C>>::heap_size(input)
+    }
+}
+
+/// Types which convert to and from the entries of a compound tag, such as structs declared with
+/// `@nbt`, so they can be encoded through `NbtDelegate`.
+pub trait NbtCompound: Sized {
+    fn to_compound(&self) -> Compound;
+
+    fn from_compound(compound: Compound) -> crate::prelude::Result<Self>;
+}
+
+/// A value encoded as the root compound tag of its `NbtCompound` conversion, read with the limit
+/// of an `EnsuredCompoundTag<LIMIT>`. The empty tag is rejected since it has no entries to convert.
+pub struct NbtDelegate<T, const LIMIT: u64 = 0>(PhantomData<T>);
+
+impl<T: NbtCompound + Send + Sync, const LIMIT: u64, C: Send + Sync> PacketComponent<C>
+    for NbtDelegate<T, LIMIT>
+{
+    type ComponentType = T;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            match EnsuredCompoundTag::<LIMIT>::decode(context, read).await? {
+                Some(tag) => match Compound::try_from(tag) {
+                    Ok(compound) => T::from_compound(compound),
+                    Err(tag) => throw_explain!(format!(
+                        "Expected a compound tag but found tag type {}",
+                        tag.get_tag_bit()
+                    )),
+                },
+                None => throw_explain!("Expected a compound tag but found the empty tag"),
+            }
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let tag = Some(Tag::from(component_ref.to_compound()));
+            EnsuredCompoundTag::<LIMIT>::encode(&tag, context, write).await
+        })
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        EnsuredCompoundTag::<LIMIT>::size(&Some(Tag::from(input.to_compound())), context)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        heap_size_tag(&Tag::from(input.to_compound()))
+    }
 }
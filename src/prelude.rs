@@ -1,8 +1,60 @@
 pub use crate::transport::{
     buffer::{DraxReadExt, DraxWriteExt},
-    error::{ErrorType, TransportError, TransportErrorContext},
+    error::{ContextLabel, ErrorType, TransportError, TransportErrorContext},
+    id::{IdRemapper, PacketId, ProtocolVersion, ProtocolVersionCtx, StateId},
     packet::{PacketComponent, Size},
+    source::{ClockSource, RngSource},
     Result,
 };
 pub use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 pub use uuid::Uuid;
+
+pub use crate::delegates::*;
+#[cfg(feature = "macros")]
+pub use crate::{bit_fields, components, enum_packet_components, struct_packet_components};
+pub use crate::{err, err_explain, throw, throw_explain, PinnedLivelyResult, PinnedResult};
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use crate::prelude::*;
+
+    struct_packet_components! {
+        @derives(Clone, PartialEq)
+        Profile {
+            id: Uuid,
+            name: LimitedString<16>,
+            properties: LimitedVec<LimitedString<64>, 8>,
+            skin: Maybe<VarLong>
+        }
+    }
+
+    enum_packet_components! {
+        @derives(Clone, PartialEq)
+        Login {
+            key: VarInt,
+            0x00 => Start {
+                profile: Profile
+            },
+            0x01 => Payload {
+                data: ByteDrain
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prelude_defines_protocols() -> Result<()> {
+        let login = Login::Start {
+            profile: Profile {
+                id: Uuid::nil(),
+                name: "drax".to_string(),
+                properties: vec!["textures".to_string()],
+                skin: Some(7),
+            },
+        };
+        let mut bytes = vec![];
+        Login::encode(&login, &mut (), &mut bytes).await?;
+        let decoded = Login::decode(&mut (), &mut std::io::Cursor::new(bytes)).await?;
+        assert!(decoded == login);
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+/// The handshake and status packets used to ping a Minecraft server for its status. This module is
+/// only available with the `vanilla-status` feature.
+#[cfg(feature = "vanilla-status")]
+pub mod vanilla_status;
@@ -0,0 +1,135 @@
+use crate::transport::id::ProtocolVersion;
+use crate::transport::packet::primitive::VarInt;
+use crate::transport::packet::string::LimitedString;
+
+/// The `next_state` of a handshake which moves the connection into the status state.
+pub const NEXT_STATE_STATUS: i32 = 1;
+/// The `next_state` of a handshake which moves the connection into the login state.
+pub const NEXT_STATE_LOGIN: i32 = 2;
+
+crate::enum_packet_components! {
+    /// Packets sent by the client in the handshaking state.
+    @derives(Clone, PartialEq, Eq)
+    ServerboundHandshaking {
+        key: VarInt,
+        0x00 => Handshake {
+            /// The protocol version the client speaks.
+            protocol_version: ProtocolVersion,
+            /// The address the client used to connect.
+            server_address: LimitedString<255>,
+            /// The port the client used to connect.
+            server_port: u16,
+            /// The state to move to, `NEXT_STATE_STATUS` or `NEXT_STATE_LOGIN`.
+            next_state: VarInt
+        }
+    }
+}
+
+crate::enum_packet_components! {
+    /// Packets sent by the client in the status state.
+    @derives(Clone, PartialEq, Eq)
+    ServerboundStatus {
+        key: VarInt,
+        0x00 => StatusRequest {},
+        0x01 => PingRequest {
+            /// An arbitrary value echoed back by the server.
+            payload: i64
+        }
+    }
+}
+
+crate::enum_packet_components! {
+    /// Packets sent by the server in the status state.
+    @derives(Clone, PartialEq, Eq)
+    ClientboundStatus {
+        key: VarInt,
+        0x00 => StatusResponse {
+            /// The status of the server as a JSON document.
+            json: String
+        },
+        0x01 => PongResponse {
+            /// The payload of the ping being answered.
+            payload: i64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientboundStatus, ServerboundHandshaking, ServerboundStatus, NEXT_STATE_STATUS};
+    use crate::prelude::ProtocolVersion;
+    use crate::transport::frame::{FrameReader, FrameWriter};
+
+    #[tokio::test]
+    async fn test_status_exchange() -> crate::prelude::Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, server_write) = tokio::io::split(server);
+        let mut client_reader = FrameReader::new(client_read);
+        let mut client_writer = FrameWriter::new(client_write);
+        let mut server_reader = FrameReader::new(server_read);
+        let mut server_writer = FrameWriter::new(server_write);
+
+        let handshake = ServerboundHandshaking::Handshake {
+            protocol_version: ProtocolVersion(763),
+            server_address: "localhost".to_string(),
+            server_port: 25565,
+            next_state: NEXT_STATE_STATUS,
+        };
+        client_writer
+            .encode_frame::<_, ServerboundHandshaking>(&mut (), &handshake)
+            .await?;
+        client_writer
+            .encode_frame::<_, ServerboundStatus>(&mut (), &ServerboundStatus::StatusRequest)
+            .await?;
+        client_writer
+            .encode_frame::<_, ServerboundStatus>(
+                &mut (),
+                &ServerboundStatus::PingRequest { payload: 42 },
+            )
+            .await?;
+
+        assert_eq!(
+            server_reader
+                .decode_frame::<_, ServerboundHandshaking>(&mut ())
+                .await?,
+            Some(handshake)
+        );
+        let json = r#"{"version":{"name":"1.20.1","protocol":763}}"#.to_string();
+        loop {
+            match server_reader
+                .decode_frame::<_, ServerboundStatus>(&mut ())
+                .await?
+            {
+                Some(ServerboundStatus::StatusRequest) => {
+                    let response = ClientboundStatus::StatusResponse { json: json.clone() };
+                    server_writer
+                        .encode_frame::<_, ClientboundStatus>(&mut (), &response)
+                        .await?;
+                }
+                Some(ServerboundStatus::PingRequest { payload }) => {
+                    let pong = ClientboundStatus::PongResponse { payload };
+                    server_writer
+                        .encode_frame::<_, ClientboundStatus>(&mut (), &pong)
+                        .await?;
+                    break;
+                }
+                None => panic!("Client disconnected"),
+            }
+        }
+
+        assert_eq!(
+            client_reader
+                .decode_frame::<_, ClientboundStatus>(&mut ())
+                .await?,
+            Some(ClientboundStatus::StatusResponse { json })
+        );
+        assert_eq!(
+            client_reader
+                .decode_frame::<_, ClientboundStatus>(&mut ())
+                .await?,
+            Some(ClientboundStatus::PongResponse { payload: 42 })
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use std::io::Cursor;
+
+use crate::prelude::PacketComponent;
+
+/// Generates values of packet components for property and soak tests, built on the `arbitrary`
+/// crate. Only available with the `arbitrary` feature.
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+
+/// Cross-checks macro defined components against a machine readable protocol spec, such as
+/// minecraft-data. Only available with the `serde` feature.
+#[cfg(feature = "serde")]
+pub mod schema;
+
+macro_rules! corpus {
+    ($($name:literal),*) => {
+        /// Adversarial byte blobs checked in under `tests/corpus`. Each entry is keyed by its file
+        /// name without the `.bin` extension.
+        pub const CORPUS: &[(&str, &[u8])] = &[
+            $(($name, include_bytes!(concat!("../tests/corpus/", $name, ".bin")))),*
+        ];
+    };
+}
+
+corpus!(
+    "empty",
+    "oversized_var_int",
+    "negative_length",
+    "huge_length",
+    "truncated_string",
+    "deep_nbt",
+    "nbt_negative_array",
+    "nbt_negative_list",
+    "nbt_huge_list"
+);
+
+/// Looks up a corpus entry by name.
+///
+/// # Panics
+/// Panics if no corpus entry exists with the given name.
+pub fn corpus_entry(name: &str) -> &'static [u8] {
+    CORPUS
+        .iter()
+        .find(|(entry, _)| *entry == name)
+        .map(|(_, bytes)| *bytes)
+        .unwrap_or_else(|| panic!("Unknown corpus entry {name}"))
+}
+
+/// Decodes every corpus entry with the given delegate, returning each result alongside the name
+/// of the entry it was decoded from. A delegate which panics or aborts on any entry will fail the
+/// calling test.
+pub async fn decode_corpus<C: Send + Sync, P: PacketComponent<C>>(
+    context: &mut C,
+) -> Vec<(&'static str, crate::prelude::Result<P::ComponentType>)> {
+    let mut results = Vec::with_capacity(CORPUS.len());
+    for (name, bytes) in CORPUS {
+        let mut cursor = Cursor::new(*bytes);
+        results.push((*name, P::decode(context, &mut cursor).await));
+    }
+    results
+}
+
+/// Decodes the entire corpus with the given delegate and asserts that every named entry is
+/// rejected with an error.
+///
+/// # Parameters
+/// * `context` - The context to decode with.
+/// * `rejected` - The names of the corpus entries which must fail to decode.
+pub async fn assert_corpus_rejected<C: Send + Sync, P: PacketComponent<C>>(
+    context: &mut C,
+    rejected: &[&str],
+) {
+    for name in rejected {
+        corpus_entry(name);
+    }
+    for (name, result) in decode_corpus::<C, P>(context).await {
+        if rejected.contains(&name) && result.is_ok() {
+            panic!(
+                "Corpus entry `{name}` was accepted by {}",
+                std::any::type_name::<P>()
+            );
+        }
+    }
+}
+
+/// Encodes the value, checks it encoded to the size it reported, then decodes it and asserts the
+/// decoded value consumed every byte and equals the original. Runs without an async runtime, see
+/// `frame::encode_sync`. Used by the tests the component macros generate with `@round_trip`.
+///
+/// # Parameters
+/// * `context` - The context to encode and decode with.
+/// * `value` - The value to round trip.
+pub fn assert_round_trip<C: Send + Sync, P: PacketComponent<C>>(
+    context: &mut C,
+    value: &P::ComponentType,
+) where
+    P::ComponentType: PartialEq + std::fmt::Debug,
+{
+    use crate::prelude::Size;
+    use crate::transport::frame::{decode_from_slice, encode_sync};
+
+    let name = std::any::type_name::<P>();
+    let mut bytes = vec![];
+    if let Err(err) = encode_sync::<C, P, _>(context, value, &mut bytes) {
+        panic!("Failed to encode {name}: {err}");
+    }
+    match P::size(value, context) {
+        Ok(Size::Dynamic(size) | Size::Constant(size)) => assert_eq!(
+            size,
+            bytes.len(),
+            "{name} was sized as {size} bytes but encoded as {} bytes",
+            bytes.len()
+        ),
+        Err(err) => panic!("Failed to size {name}: {err}"),
+    }
+    match decode_from_slice::<C, P>(context, &bytes) {
+        Ok((decoded, consumed)) => {
+            assert_eq!(consumed, bytes.len(), "{name} left trailing bytes");
+            assert_eq!(&decoded, value, "{name} did not round trip");
+        }
+        Err(err) => panic!("Failed to decode {name}: {err}"),
+    }
+}
+
+/// Generators for realistic payloads, shared by the benchmarks and available to downstream crates
+/// which want to measure their own protocols against comparable data.
+pub mod generators {
+    /// Produces `count` VarInt values spread across every encoded width from 1 to 5 bytes.
+    pub fn var_ints(count: usize) -> Vec<i32> {
+        const VALUES: [i32; 5] = [25, 300, 55324, 8877777, i32::MAX];
+        (0..count).map(|i| VALUES[i % VALUES.len()]).collect()
+    }
+
+    /// Produces a string of `len` characters mixing ASCII and multi-byte characters, similar to a
+    /// chat message or a player supplied name.
+    pub fn string(len: usize) -> String {
+        const CHARS: [char; 8] = ['d', 'r', 'a', 'x', ' ', 'é', '✓', '7'];
+        (0..len).map(|i| CHARS[i % CHARS.len()]).collect()
+    }
+
+    /// Produces a compound tag with `entries` nested compounds shaped like a registry entry.
+    #[cfg(feature = "nbt")]
+    pub fn compound(entries: usize) -> crate::nbt::Tag {
+        use crate::nbt::Tag;
+
+        let mut data = Vec::with_capacity(entries);
+        for i in 0..entries {
+            data.push((
+                format!("entry_{i}"),
+                Tag::compound_tag(vec![
+                    ("id", Tag::TagInt(i as i32)),
+                    ("name", Tag::string(format!("name_{i}"))),
+                    ("scale", Tag::TagDouble(i as f64 * 0.5)),
+                    (
+                        "values",
+                        Tag::TagList((3, (0..16).map(Tag::TagInt).collect())),
+                    ),
+                    ("longs", Tag::TagLongArray((0..16).collect())),
+                ]),
+            ));
+        }
+        Tag::compound_tag(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assert_corpus_rejected;
+    use crate::transport::packet::option::Maybe;
+    use crate::transport::packet::primitive::{VarInt, VarLong};
+    use crate::transport::packet::string::LimitedString;
+    use crate::transport::packet::vec::{LimitedVec, VecU8};
+
+    const LENGTH_PREFIXED: &[&str] = &[
+        "empty",
+        "oversized_var_int",
+        "negative_length",
+        "huge_length",
+        "truncated_string",
+    ];
+
+    #[tokio::test]
+    async fn test_var_num_corpus() {
+        assert_corpus_rejected::<_, VarInt>(&mut (), &["empty", "oversized_var_int"]).await;
+        assert_corpus_rejected::<_, VarLong>(&mut (), &["empty"]).await;
+        assert_corpus_rejected::<_, i32>(&mut (), &["empty", "truncated_string"]).await;
+    }
+
+    #[tokio::test]
+    async fn test_string_corpus() {
+        assert_corpus_rejected::<_, String>(&mut (), LENGTH_PREFIXED).await;
+        assert_corpus_rejected::<_, LimitedString<16>>(&mut (), LENGTH_PREFIXED).await;
+        assert_corpus_rejected::<_, Maybe<String>>(&mut (), &["empty", "truncated_string"]).await;
+    }
+
+    #[tokio::test]
+    async fn test_vec_corpus() {
+        assert_corpus_rejected::<_, VecU8>(&mut (), LENGTH_PREFIXED).await;
+        assert_corpus_rejected::<_, Vec<i32>>(&mut (), LENGTH_PREFIXED).await;
+        assert_corpus_rejected::<_, LimitedVec<u8, 16>>(&mut (), LENGTH_PREFIXED).await;
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_json_corpus() {
+        use crate::transport::packet::serde_json::JsonDelegate;
+
+        assert_corpus_rejected::<_, JsonDelegate<serde_json::Value>>(&mut (), LENGTH_PREFIXED)
+            .await;
+    }
+
+    #[cfg(feature = "nbt")]
+    #[tokio::test]
+    async fn test_nbt_corpus() {
+        use crate::nbt::EnsuredCompoundTag;
+
+        assert_corpus_rejected::<_, EnsuredCompoundTag>(
+            &mut (),
+            &[
+                "empty",
+                "truncated_string",
+                "deep_nbt",
+                "nbt_negative_array",
+                "nbt_negative_list",
+                "nbt_huge_list",
+            ],
+        )
+        .await;
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::io::Cursor;
+use std::marker::PhantomData;
+
+pub use arbitrary::{Arbitrary, Error, Result, Unstructured};
+use uuid::Uuid;
+
+use crate::prelude::{PacketComponent, Size};
+use crate::transport::buffer::var_num::size_var_int;
+use crate::transport::connection::DraxConnection;
+use crate::transport::counting::CountingWriter;
+use crate::transport::frame::{FrameReader, FrameWriter};
+use crate::transport::packet::bits::{BitSet, FixedBitSet};
+use crate::transport::packet::option::{IdPlusOneOption, Maybe, SentinelOption, TailOptional};
+use crate::transport::packet::primitive::{StrictVarInt, StrictVarLong, VarInt, VarLong};
+use crate::transport::packet::string::LimitedString;
+use crate::transport::packet::vec::{ByteDrain, LimitedVec, SliceU8, VecU8};
+
+/// Generates values of a component which encode without error, so they can be used to check
+/// that a component decodes everything it encodes. Components defined with the component macros
+/// implement this, along with `Arbitrary`, when they are marked `@arbitrary` and the `arbitrary`
+/// feature is enabled.
+pub trait ArbitraryComponent<C: Send + Sync>: PacketComponent<C> {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType>;
+}
+
+macro_rules! arbitrary_via {
+    ($($delegate:ty),*) => {$(
+        impl<C: Send + Sync> ArbitraryComponent<C> for $delegate {
+            fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+                u.arbitrary()
+            }
+        }
+    )*};
+}
+
+arbitrary_via!(
+    u8,
+    u16,
+    u32,
+    u64,
+    i8,
+    i16,
+    i32,
+    i64,
+    bool,
+    (),
+    VarInt,
+    VarLong,
+    StrictVarInt,
+    StrictVarLong,
+    String,
+    VecU8,
+    ByteDrain
+);
+
+macro_rules! arbitrary_float {
+    ($($float:ty),*) => {$(
+        // NaN never compares equal to itself, so it is left out to keep round trips comparable.
+        impl<C: Send + Sync> ArbitraryComponent<C> for $float {
+            fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+                let value: $float = u.arbitrary()?;
+                Ok(if value.is_nan() { 0.0 } else { value })
+            }
+        }
+    )*};
+}
+
+arbitrary_float!(f32, f64);
+
+impl<C: Send + Sync, T: Send + Sync> ArbitraryComponent<C> for PhantomData<T> {
+    fn arbitrary_component(_: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        Ok(PhantomData)
+    }
+}
+
+impl<C: Send + Sync> ArbitraryComponent<C> for Uuid {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        Ok(Uuid::from_u128(u.arbitrary()?))
+    }
+}
+
+impl<C: Send + Sync, const N: usize> ArbitraryComponent<C> for SliceU8<N> {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        u.arbitrary()
+    }
+}
+
+impl<C: Send + Sync> ArbitraryComponent<C> for BitSet {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        Ok(BitSet::from(u.arbitrary::<Vec<u64>>()?))
+    }
+}
+
+impl<C: Send + Sync, const N: usize> ArbitraryComponent<C> for FixedBitSet<N> {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        let mut set = BitSet::new();
+        for index in 0..N {
+            set.set(index, u.arbitrary()?);
+        }
+        Ok(set)
+    }
+}
+
+impl<C: Send + Sync, const N: usize> ArbitraryComponent<C> for LimitedString<N> {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        let string: String = u.arbitrary()?;
+        let mut units = 0;
+        Ok(string
+            .chars()
+            .take_while(|c| {
+                units += c.len_utf16();
+                units <= N
+            })
+            .collect())
+    }
+}
+
+impl<C: Send + Sync, T: ArbitraryComponent<C>> ArbitraryComponent<C> for Maybe<T> {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        Ok(match u.arbitrary()? {
+            true => Some(T::arbitrary_component(u)?),
+            false => None,
+        })
+    }
+}
+
+impl<C: Send + Sync, T: ArbitraryComponent<C>> ArbitraryComponent<C> for TailOptional<T> {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        Maybe::<T>::arbitrary_component(u)
+    }
+}
+
+impl<C: Send + Sync, T, const SENTINEL: i64> ArbitraryComponent<C> for SentinelOption<T, SENTINEL>
+where
+    T: ArbitraryComponent<C>,
+    T::ComponentType: Copy + TryInto<i64> + TryFrom<i64>,
+{
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        // the sentinel itself can't be encoded as a value, so it generates `None` instead
+        Ok(Maybe::<T>::arbitrary_component(u)?
+            .filter(|value| (*value).try_into().ok() != Some(SENTINEL)))
+    }
+}
+
+impl<C: Send + Sync, T> ArbitraryComponent<C> for IdPlusOneOption<T>
+where
+    T: ArbitraryComponent<C>,
+    T::ComponentType: Copy + TryInto<i64> + TryFrom<i64>,
+{
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        // only ids which can be incremented within the delegate can be encoded
+        Ok(Maybe::<T>::arbitrary_component(u)?.filter(|value| {
+            (*value)
+                .try_into()
+                .ok()
+                .and_then(|value: i64| value.checked_add(1))
+                .is_some_and(|wire| T::ComponentType::try_from(wire).is_ok())
+        }))
+    }
+}
+
+/// Whether another element should be generated, continuing for as long as the data says so and
+/// stopping once it runs out, like the collections of the `arbitrary` crate.
+fn arbitrary_continue(u: &mut Unstructured<'_>) -> Result<bool> {
+    Ok(!u.is_empty() && u.arbitrary()?)
+}
+
+fn arbitrary_vec<C: Send + Sync, T: ArbitraryComponent<C>>(
+    u: &mut Unstructured<'_>,
+    max: usize,
+) -> Result<Vec<T::ComponentType>> {
+    let mut vec = vec![];
+    while vec.len() < max && arbitrary_continue(u)? {
+        vec.push(T::arbitrary_component(u)?);
+    }
+    Ok(vec)
+}
+
+impl<C: Send + Sync, T: ArbitraryComponent<C>> ArbitraryComponent<C> for Vec<T> {
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        arbitrary_vec::<C, T>(u, usize::MAX)
+    }
+}
+
+impl<C: Send + Sync, T: ArbitraryComponent<C>, const N: usize> ArbitraryComponent<C>
+    for LimitedVec<T, N>
+{
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        arbitrary_vec::<C, T>(u, N)
+    }
+}
+
+impl<C: Send + Sync, K, V> ArbitraryComponent<C> for HashMap<K, V>
+where
+    K: ArbitraryComponent<C>,
+    V: ArbitraryComponent<C>,
+    K::ComponentType: Eq + Hash,
+{
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        let mut map = HashMap::new();
+        while arbitrary_continue(u)? {
+            map.insert(K::arbitrary_component(u)?, V::arbitrary_component(u)?);
+        }
+        Ok(map)
+    }
+}
+
+impl<C: Send + Sync, K, V> ArbitraryComponent<C> for BTreeMap<K, V>
+where
+    K: ArbitraryComponent<C>,
+    V: ArbitraryComponent<C>,
+    K::ComponentType: Ord,
+{
+    fn arbitrary_component(u: &mut Unstructured<'_>) -> Result<Self::ComponentType> {
+        let mut map = BTreeMap::new();
+        while arbitrary_continue(u)? {
+            map.insert(K::arbitrary_component(u)?, V::arbitrary_component(u)?);
+        }
+        Ok(map)
+    }
+}
+
+/// Produces `len` bytes of deterministic noise from the seed, for feeding `Unstructured` in soak
+/// tests without depending on a random number generator.
+pub fn seeded_bytes(seed: u64, len: usize) -> Vec<u8> {
+    // xorshift64*, the seed is offset so a zero seed still produces noise.
+    let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+    let mut bytes = Vec::with_capacity(len + 8);
+    while bytes.len() < len {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        bytes.extend_from_slice(&state.wrapping_mul(0x2545_F491_4F6C_DD1D).to_le_bytes());
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+/// Generates values of `P` from the data until it runs out, or until a component stops consuming
+/// it.
+fn arbitrary_values<C: Send + Sync, P: ArbitraryComponent<C>>(
+    data: &[u8],
+) -> Vec<P::ComponentType> {
+    let mut u = Unstructured::new(data);
+    let mut values = vec![];
+    while !u.is_empty() {
+        let remaining = u.len();
+        match P::arbitrary_component(&mut u) {
+            Ok(value) => values.push(value),
+            Err(Error::NotEnoughData) => break,
+            Err(err) => panic!("Failed to generate {}: {err}", std::any::type_name::<P>()),
+        }
+        // Components which consume no data would otherwise generate values forever.
+        if u.len() == remaining {
+            break;
+        }
+    }
+    values
+}
+
+fn frame_size<C: Send + Sync, P: PacketComponent<C>>(
+    context: &mut C,
+    value: &P::ComponentType,
+) -> crate::prelude::Result<usize> {
+    let size = match P::size(value, context)? {
+        Size::Dynamic(x) | Size::Constant(x) => x,
+    };
+    Ok(size + size_var_int(i32::try_from(size)?))
+}
+
+/// Generates values of `P` from a range of seeds and asserts that none of them is sized larger
+/// than `max_size`. Used by the tests the component macros generate for components marked with
+/// both `@max_size` and `@arbitrary`.
+///
+/// # Panics
+/// Panics if any value is larger than `max_size`, or can't be sized.
+pub fn assert_max_size<C: Send + Sync, P: ArbitraryComponent<C>>(context: &mut C, max_size: usize)
+where
+    P::ComponentType: std::fmt::Debug,
+{
+    let name = std::any::type_name::<P>();
+    for seed in 0..16 {
+        for value in arbitrary_values::<C, P>(&seeded_bytes(seed, 4096)) {
+            match P::size(&value, context) {
+                Ok(Size::Dynamic(size) | Size::Constant(size)) => assert!(
+                    size <= max_size,
+                    "{name} of {size} bytes exceeded its declared maximum size of {max_size} \
+                     bytes: {value:?}"
+                ),
+                Err(err) => panic!("Failed to size {name}: {err}"),
+            }
+        }
+    }
+}
+
+/// Generates values of `P` from the data until it runs out, and sends them all as frames between
+/// two `DraxConnection`s over an in-memory duplex pipe. The pipe only buffers a few bytes, so the
+/// sending side is held back by the receiving side and frames arrive in partial reads. Asserts
+/// that each value reports its encoded size accurately and decodes back to the value it was
+/// encoded from. Returns the number of values sent.
+///
+/// # Parameters
+/// * `encode_context` - The context of the sending side.
+/// * `decode_context` - The context of the receiving side.
+/// * `data` - The data to generate values from, such as the output of `seeded_bytes` or a fuzzer.
+///
+/// # Panics
+/// Panics if any value fails to round trip.
+pub async fn assert_round_trips<C: Send + Sync, P: ArbitraryComponent<C>>(
+    encode_context: &mut C,
+    decode_context: &mut C,
+    data: &[u8],
+) -> crate::prelude::Result<usize>
+where
+    P::ComponentType: PartialEq + std::fmt::Debug,
+{
+    let values = arbitrary_values::<C, P>(data);
+
+    let (client, server) = tokio::io::duplex(64);
+    let mut sender = DraxConnection::new(tokio::io::empty(), CountingWriter::new(client))
+        .with_max_frame_size(usize::MAX);
+    let mut receiver =
+        DraxConnection::new(server, tokio::io::sink()).with_max_frame_size(usize::MAX);
+    let send = async {
+        for value in &values {
+            let size = frame_size::<C, P>(encode_context, value)?;
+            let before = sender.get_ref().1.bytes_transferred();
+            sender.write_packet::<C, P>(encode_context, value).await?;
+            assert_eq!(
+                sender.get_ref().1.bytes_transferred() - before,
+                size as u64,
+                "Encoded size did not match the reported size of {value:?}"
+            );
+        }
+        sender.flush().await?;
+        // Dropping the sender ends the stream so the receiver sees a clean EOF.
+        drop(sender);
+        crate::prelude::Result::Ok(())
+    };
+    let receive = async {
+        let mut decoded = Vec::with_capacity(values.len());
+        while let Some(value) = receiver.read_packet::<C, P>(decode_context).await? {
+            decoded.push(value);
+        }
+        crate::prelude::Result::Ok(decoded)
+    };
+    let (sent, decoded) = tokio::join!(send, receive);
+    sent?;
+    assert_eq!(decoded?, values);
+    Ok(values.len())
+}
+
+/// Like `assert_round_trips`, but encodes every value into a buffer before decoding them from it,
+/// without a connection in between.
+///
+/// # Panics
+/// Panics if any value fails to round trip.
+pub async fn assert_round_trips_in_memory<C: Send + Sync, P: ArbitraryComponent<C>>(
+    encode_context: &mut C,
+    decode_context: &mut C,
+    data: &[u8],
+) -> crate::prelude::Result<usize>
+where
+    P::ComponentType: PartialEq + std::fmt::Debug,
+{
+    let values = arbitrary_values::<C, P>(data);
+
+    let mut writer = FrameWriter::new(vec![]).with_max_frame_size(usize::MAX);
+    for value in &values {
+        let size = frame_size::<C, P>(encode_context, value)?;
+        let written = writer.encode_frame::<C, P>(encode_context, value).await?;
+        assert_eq!(
+            written, size,
+            "Encoded size did not match the reported size of {value:?}"
+        );
+    }
+    writer.flush().await?;
+
+    let mut reader =
+        FrameReader::new(Cursor::new(writer.into_inner())).with_max_frame_size(usize::MAX);
+    let mut decoded = Vec::with_capacity(values.len());
+    while let Some(value) = reader.decode_frame::<C, P>(decode_context).await? {
+        decoded.push(value);
+    }
+    assert_eq!(decoded, values);
+    Ok(values.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        assert_round_trips, assert_round_trips_in_memory, seeded_bytes, Arbitrary, Unstructured,
+    };
+    use crate::transport::packet::bits::FixedBitSet;
+    use crate::transport::packet::option::{IdPlusOneOption, Maybe, SentinelOption};
+    use crate::transport::packet::primitive::VarInt;
+    use crate::transport::packet::string::LimitedString;
+    use crate::transport::packet::vec::LimitedVec;
+
+    crate::struct_packet_components! {
+        #[derive(PartialEq)]
+        @arbitrary
+        Sample {
+            id: VarInt,
+            name: LimitedString<8>,
+            tags: LimitedVec<LimitedString<4>, 3>,
+            scale: Maybe<f32>,
+            index: SentinelOption<VarInt, -1>,
+            parent: IdPlusOneOption<u8>,
+            flags: FixedBitSet<12>
+        }
+    }
+
+    crate::enum_packet_components! {
+        #[derive(PartialEq)]
+        @arbitrary
+        SampleEnum {
+            key: VarInt,
+            Empty {},
+            Single {
+                sample: Sample
+            },
+            Many {
+                samples: Vec<Sample>,
+                flag: bool
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_bounds() {
+        let data = seeded_bytes(7, 4096);
+        let mut u = Unstructured::new(&data);
+        while !u.is_empty() {
+            let sample = Sample::arbitrary(&mut u).unwrap();
+            assert!(sample.name.chars().count() <= 8);
+            assert!(sample.tags.len() <= 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_soak() -> crate::prelude::Result<()> {
+        let mut sent = 0;
+        for seed in 0..8 {
+            let data = seeded_bytes(seed, 8192);
+            sent += assert_round_trips::<_, SampleEnum>(&mut (), &mut (), &data).await?;
+        }
+        assert!(sent > 0);
+        let data = seeded_bytes(8, 8192);
+        assert!(assert_round_trips_in_memory::<_, SampleEnum>(&mut (), &mut (), &data).await? > 0);
+        Ok(())
+    }
+}
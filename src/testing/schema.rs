@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+/// The name and delegate type of each field of a component, as in its generated `FIELDS` constant.
+pub type Fields<'a> = &'a [(&'a str, &'a str)];
+
+/// A difference between a macro defined component and a protocol spec.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Mismatch {
+    /// The spec has no packet with the name a variant was matched to.
+    MissingPacket { variant: String, spec_name: String },
+    /// A variant is keyed differently from the id of its packet in the spec.
+    PacketId {
+        variant: String,
+        expected: i64,
+        found: String,
+    },
+    /// A component has a different number of fields from the spec.
+    FieldCount {
+        path: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A field is named differently from the field in the same position in the spec.
+    FieldName {
+        path: String,
+        expected: String,
+        found: String,
+    },
+    /// A field is encoded as a different type from the field in the same position in the spec.
+    FieldType {
+        path: String,
+        expected: String,
+        found: String,
+    },
+    /// The spec isn't shaped the way the validator expects.
+    Malformed { path: String, reason: String },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::MissingPacket { variant, spec_name } => {
+                write!(f, "{variant}: no packet named {spec_name} in the spec")
+            }
+            Mismatch::PacketId {
+                variant,
+                expected,
+                found,
+            } => write!(f, "{variant}: keyed {found}, spec id is {expected:#04x}"),
+            Mismatch::FieldCount {
+                path,
+                expected,
+                found,
+            } => write!(f, "{path}: {found} fields, spec has {expected}"),
+            Mismatch::FieldName {
+                path,
+                expected,
+                found,
+            } => write!(f, "{path}: field {found}, spec has {expected}"),
+            Mismatch::FieldType {
+                path,
+                expected,
+                found,
+            } => write!(f, "{path}: encoded as {found}, spec has {expected}"),
+            Mismatch::Malformed { path, reason } => write!(f, "{path}: {reason}"),
+        }
+    }
+}
+
+/// Cross-checks macro defined components against a protocol spec in the JSON format used by
+/// minecraft-data, so drift between a protocol's definitions and its upstream documentation shows
+/// up in tests.
+///
+/// Fields are compared by position. Names are compared ignoring case and underscores, so `item_id`
+/// matches `itemId`. Types are compared by mapping each delegate to the spec's name for it, such
+/// as `VarInt` to `varint`; fields whose types can't be mapped on either side are only compared by
+/// name. Delegates this crate doesn't know about can be mapped with `with_type`.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaValidator {
+    types: HashMap<String, String>,
+    packet_names: HashMap<String, String>,
+}
+
+impl SchemaValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps a delegate, by the last segment of its path without generics such as `Position`, to
+    /// the name the spec uses for its type.
+    pub fn with_type(mut self, delegate: &str, spec_type: &str) -> Self {
+        self.types
+            .insert(delegate.to_string(), spec_type.to_string());
+        self
+    }
+
+    /// Matches a variant to the packet with the given name in the spec, rather than the snake case
+    /// form of the variant name.
+    pub fn with_packet_name(mut self, variant: &str, spec_name: &str) -> Self {
+        self.packet_names
+            .insert(variant.to_string(), spec_name.to_string());
+        self
+    }
+
+    /// Checks the fields of a component against a spec container such as
+    /// `["container", [{"name": "id", "type": "varint"}]]`.
+    ///
+    /// # Parameters
+    /// * `path` - The name of the component, used to label mismatches.
+    /// * `fields` - The fields of the component, from its generated `FIELDS` constant.
+    /// * `container` - The spec of the component.
+    pub fn validate_fields(
+        &self,
+        path: &str,
+        fields: Fields<'_>,
+        container: &Value,
+    ) -> Vec<Mismatch> {
+        let spec_fields = match container.as_array().map(Vec::as_slice) {
+            Some([kind, Value::Array(spec_fields)]) if kind == "container" => spec_fields,
+            _ => {
+                return vec![Mismatch::Malformed {
+                    path: path.to_string(),
+                    reason: "expected a container".to_string(),
+                }]
+            }
+        };
+
+        let mut mismatches = vec![];
+        if spec_fields.len() != fields.len() {
+            mismatches.push(Mismatch::FieldCount {
+                path: path.to_string(),
+                expected: spec_fields.len(),
+                found: fields.len(),
+            });
+        }
+        for ((name, delegate), spec_field) in fields.iter().zip(spec_fields) {
+            let field_path = format!("{path}.{name}");
+            let spec_name = spec_field["name"].as_str().unwrap_or_default();
+            if normalize_name(name) != normalize_name(spec_name) {
+                mismatches.push(Mismatch::FieldName {
+                    path: field_path.clone(),
+                    expected: spec_name.to_string(),
+                    found: name.to_string(),
+                });
+            }
+            if let (Some(found), Some(expected)) = (
+                self.spec_type(delegate),
+                render_spec_type(&spec_field["type"]),
+            ) {
+                if found != expected {
+                    mismatches.push(Mismatch::FieldType {
+                        path: field_path,
+                        expected,
+                        found,
+                    });
+                }
+            }
+        }
+        mismatches
+    }
+
+    /// Checks the keys and fields of each variant of an enum against the packets of one direction
+    /// of a protocol state in the spec, such as `protocol.status.toClient`. Variants are matched to
+    /// packets by name; packets in the spec with no variant aren't reported.
+    ///
+    /// # Parameters
+    /// * `variants` - The variants of the enum, from its generated `VARIANTS` constant.
+    /// * `spec` - The spec of the state and direction, an object with the packet types under
+    ///   `types`.
+    pub fn validate_packets(
+        &self,
+        variants: &[(&str, &str, Fields<'_>)],
+        spec: &Value,
+    ) -> Vec<Mismatch> {
+        let types = spec.get("types").unwrap_or(spec);
+        let packet = &types["packet"][1];
+        let mappings = packet[0]["type"][1]["mappings"].as_object();
+        let containers = packet[1]["type"][1]["fields"].as_object();
+        let (Some(mappings), Some(containers)) = (mappings, containers) else {
+            return vec![Mismatch::Malformed {
+                path: "packet".to_string(),
+                reason: "expected a container of a mapped id and a switch on it".to_string(),
+            }];
+        };
+
+        let mut mismatches = vec![];
+        for (key, variant, fields) in variants {
+            let spec_name = match self.packet_names.get(*variant) {
+                Some(spec_name) => spec_name.clone(),
+                None => snake_case(variant),
+            };
+            let spec_id = mappings
+                .iter()
+                .find(|(_, name)| name.as_str() == Some(spec_name.as_str()))
+                .and_then(|(id, _)| parse_id(id));
+            let Some(spec_id) = spec_id else {
+                mismatches.push(Mismatch::MissingPacket {
+                    variant: variant.to_string(),
+                    spec_name,
+                });
+                continue;
+            };
+            if parse_id(key) != Some(spec_id) {
+                mismatches.push(Mismatch::PacketId {
+                    variant: variant.to_string(),
+                    expected: spec_id,
+                    found: key.to_string(),
+                });
+            }
+            let container = containers
+                .get(&spec_name)
+                .and_then(Value::as_str)
+                .map(|name| &types[name]);
+            match container {
+                Some(container) => {
+                    mismatches.extend(self.validate_fields(variant, fields, container))
+                }
+                None => mismatches.push(Mismatch::Malformed {
+                    path: variant.to_string(),
+                    reason: format!("no type for packet {spec_name}"),
+                }),
+            }
+        }
+        mismatches
+    }
+
+    /// The spec's name for the type a delegate encodes, if known.
+    pub fn spec_type(&self, delegate: &str) -> Option<String> {
+        let delegate: String = delegate.chars().filter(|c| !c.is_whitespace()).collect();
+        let (path, args) = match delegate.find('<') {
+            Some(start) if delegate.ends_with('>') => (
+                &delegate[..start],
+                split_args(&delegate[start + 1..delegate.len() - 1]),
+            ),
+            _ => (delegate.as_str(), vec![]),
+        };
+        let base = path.rsplit("::").next().unwrap_or(path);
+        if let Some(spec_type) = self.types.get(base) {
+            return Some(spec_type.clone());
+        }
+        let spec_type = match (base, args.as_slice()) {
+            ("VarInt" | "StrictVarInt" | "ProtocolVersion" | "PacketId" | "StateId", _) => {
+                "varint".to_string()
+            }
+            ("VarLong" | "StrictVarLong", _) => "varlong".to_string(),
+            (
+                "bool" | "u8" | "i8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" | "f32"
+                | "f64",
+                _,
+            ) => base.to_string(),
+            ("String" | "LimitedString", _) => "string".to_string(),
+            ("Uuid", _) => "UUID".to_string(),
+            ("VecU8", _) => "buffer<varint>".to_string(),
+            ("ByteDrain", _) => "restBuffer".to_string(),
+            ("Maybe", [inner]) => format!("option<{}>", self.spec_type(inner)?),
+            ("Vec", [inner]) | ("LimitedVec", [inner, _]) => {
+                format!("array<varint, {}>", self.spec_type(inner)?)
+            }
+            _ => return None,
+        };
+        Some(spec_type)
+    }
+}
+
+/// Panics listing every mismatch, if there are any.
+pub fn assert_no_mismatches(mismatches: &[Mismatch]) {
+    if !mismatches.is_empty() {
+        let lines: Vec<String> = mismatches.iter().map(Mismatch::to_string).collect();
+        panic!(
+            "{} mismatches with the protocol spec:\n{}",
+            mismatches.len(),
+            lines.join("\n")
+        );
+    }
+}
+
+/// Renders a spec type in the same form as `SchemaValidator::spec_type`, if it's one the validator
+/// understands.
+fn render_spec_type(spec_type: &Value) -> Option<String> {
+    if let Some(name) = spec_type.as_str() {
+        return Some(name.to_string());
+    }
+    match spec_type.as_array()?.as_slice() {
+        [kind, inner] if kind == "option" => Some(format!("option<{}>", render_spec_type(inner)?)),
+        [kind, options] if kind == "buffer" => {
+            Some(format!("buffer<{}>", options["countType"].as_str()?))
+        }
+        [kind, options] if kind == "array" => Some(format!(
+            "array<{}, {}>",
+            options["countType"].as_str()?,
+            render_spec_type(&options["type"])?
+        )),
+        _ => None,
+    }
+}
+
+fn split_args(args: &str) -> Vec<&str> {
+    let mut split = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(&args[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(&args[start..]);
+    split
+}
+
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.char_indices() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+fn parse_id(id: &str) -> Option<i64> {
+    let id = id.replace('_', "");
+    match id.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => id.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{assert_no_mismatches, Mismatch, SchemaValidator};
+    use crate::transport::packet::option::Maybe;
+    use crate::transport::packet::primitive::VarInt;
+    use crate::transport::packet::string::LimitedString;
+
+    crate::struct_packet_components! {
+        Player {
+            entity_id: VarInt,
+            name: LimitedString<16>,
+            skin: Maybe<String>,
+            scores: Vec<i32>
+        }
+    }
+
+    crate::enum_packet_components! {
+        Clientbound {
+            key: VarInt,
+            0x00 => Disconnect {
+                reason: String
+            },
+            0x02 => KeepAlive {
+                id: i64
+            },
+            0x03 => SetCompression {
+                threshold: VarInt
+            }
+        }
+    }
+
+    fn spec() -> serde_json::Value {
+        json!({
+            "types": {
+                "packet_disconnect": ["container", [{"name": "reason", "type": "string"}]],
+                "packet_keep_alive": ["container", [{"name": "keepAliveId", "type": "i64"}]],
+                "packet_compress": ["container", [{"name": "threshold", "type": "i32"}]],
+                "packet": ["container", [
+                    {"name": "name", "type": ["mapper", {"type": "varint", "mappings": {
+                        "0x00": "disconnect",
+                        "0x01": "keep_alive",
+                        "0x03": "compress"
+                    }}]},
+                    {"name": "params", "type": ["switch", {"compareTo": "name", "fields": {
+                        "disconnect": "packet_disconnect",
+                        "keep_alive": "packet_keep_alive",
+                        "compress": "packet_compress"
+                    }}]}
+                ]]
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_fields() {
+        let validator = SchemaValidator::new();
+        let container = json!(["container", [
+            {"name": "entityId", "type": "varint"},
+            {"name": "name", "type": "string"},
+            {"name": "skin", "type": ["option", "string"]},
+            {"name": "scores", "type": ["array", {"countType": "varint", "type": "i32"}]}
+        ]]);
+        assert_no_mismatches(&validator.validate_fields("Player", Player::FIELDS, &container));
+
+        let container = json!(["container", [
+            {"name": "entityId", "type": "i32"},
+            {"name": "username", "type": "string"}
+        ]]);
+        assert_eq!(
+            validator.validate_fields("Player", Player::FIELDS, &container),
+            vec![
+                Mismatch::FieldCount {
+                    path: "Player".to_string(),
+                    expected: 2,
+                    found: 4
+                },
+                Mismatch::FieldType {
+                    path: "Player.entity_id".to_string(),
+                    expected: "i32".to_string(),
+                    found: "varint".to_string()
+                },
+                Mismatch::FieldName {
+                    path: "Player.name".to_string(),
+                    expected: "username".to_string(),
+                    found: "name".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_packets() {
+        let validator = SchemaValidator::new().with_packet_name("SetCompression", "compress");
+        let mismatches = validator.validate_packets(Clientbound::VARIANTS, &spec());
+        assert_eq!(
+            mismatches,
+            vec![
+                Mismatch::PacketId {
+                    variant: "KeepAlive".to_string(),
+                    expected: 1,
+                    found: "0x02".to_string()
+                },
+                Mismatch::FieldName {
+                    path: "KeepAlive.id".to_string(),
+                    expected: "keepAliveId".to_string(),
+                    found: "id".to_string()
+                },
+                Mismatch::FieldType {
+                    path: "SetCompression.threshold".to_string(),
+                    expected: "i32".to_string(),
+                    found: "varint".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            mismatches[0].to_string(),
+            "KeepAlive: keyed 0x02, spec id is 0x01"
+        );
+
+        let mismatches = SchemaValidator::new().validate_packets(Clientbound::VARIANTS, &spec());
+        assert!(mismatches.contains(&Mismatch::MissingPacket {
+            variant: "SetCompression".to_string(),
+            spec_name: "set_compression".to_string()
+        }));
+    }
+}
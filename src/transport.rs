@@ -1,10 +1,49 @@
+/// Encodes a component once and shares the encoded frame between many writers.
+pub mod broadcast;
 /// Utility for managing the transport layer with `AsyncRead` and `AsyncWrite` types.
 pub mod buffer;
+/// A token for cancelling in-flight reads of frame readers and connections.
+pub mod cancel;
+/// Zlib compression for frame bodies, using a VarInt uncompressed length header and a size
+/// threshold below which bodies are sent uncompressed.
+#[cfg(feature = "compression")]
+pub mod compression;
+/// A connection owning a reader and writer, layering framing, compression and encryption over them.
+pub mod connection;
+/// Reader and writer wrappers counting the bytes transferred through them.
+pub mod counting;
+/// Traces which bytes each field of a macro defined component was decoded from, for inspecting
+/// packets without writing a `Display` for every component.
+#[cfg(feature = "debug-trace")]
+pub mod debug;
 /// Encryption and decryption wrappers over `AsyncRead` and `AsyncWrite` types.
 #[cfg(feature = "encryption")]
 pub mod encryption;
+/// Splits messages larger than the maximum frame size into fragments and reassembles them.
+pub mod fragment;
+/// Reads and writes VarInt length prefixed frames, along with lower level building blocks for users
+/// implementing their own frame formats.
+pub mod frame;
+/// Newtypes for the ids used to identify packets, protocol versions and protocol states.
+pub mod id;
+/// Tracks connection activity and reaps connections which have been idle for too long.
+#[cfg(feature = "idle")]
+pub mod idle;
+/// Schedules keep alives and checks their answers, using the context's clock and random source.
+pub mod keep_alive;
 /// Defines a packet struct protocol for reading and writing packets of a generic structure.
 pub mod packet;
+/// Reads, decodes and handles packets from a connection until it ends, reporting why it ended.
+pub mod read_loop;
+pub use read_loop::run_read_loop;
+/// Dispatches frames to handlers registered by packet id.
+pub mod registry;
+/// Redacted, zeroizing byte components and constant-time comparisons for secrets carried in
+/// packets.
+pub mod secret;
+/// Context traits supplying the time and random values to protocol logic, with deterministic
+/// implementations for tests.
+pub mod source;
 
 /// A result type to capture the transport error type.
 pub type Result<T> = std::result::Result<T, error::TransportError>;
@@ -46,8 +85,55 @@ pub mod error {
                 error_type,
             }
         }
+
+        /// Prefixes the context of the error with the label of the decoding context, see
+        /// `ContextLabel`. Errors already carrying the label, such as those raised by components
+        /// declared with `@label`, are returned as they are.
+        ///
+        /// # Parameters
+        /// * `context` - The context the error was raised while decoding with.
+        pub fn with_label<L: ContextLabel + ?Sized>(mut self, context: &L) -> Self {
+            let Some(label) = context.label() else {
+                return self;
+            };
+            let explanation = match &self.context {
+                TransportErrorContext::Explainable(reason)
+                    if reason.starts_with(&format!("[{label}] ")) =>
+                {
+                    return self
+                }
+                TransportErrorContext::Explainable(reason) => reason.clone(),
+                other => other.to_string(),
+            };
+            self.context = TransportErrorContext::Explainable(context.labelled(explanation));
+            self
+        }
     }
 
+    /// Allows a context to identify itself in errors raised while decoding with it, such as the
+    /// address of the connection a packet was read from. Macro generated components declared with
+    /// `@label` prefix every error they construct with the label, and are only implemented for
+    /// contexts implementing this trait; others don't require it.
+    pub trait ContextLabel {
+        /// The label to include in errors, or `None` if errors should not be labelled.
+        fn label(&self) -> Option<&str> {
+            None
+        }
+
+        /// Prefixes the given explanation with the label of this context, if there is one.
+        ///
+        /// # Parameters
+        /// * `explanation` - The explanation to label.
+        fn labelled(&self, explanation: String) -> String {
+            match self.label() {
+                Some(label) => format!("[{label}] {explanation}"),
+                None => explanation,
+            }
+        }
+    }
+
+    impl ContextLabel for () {}
+
     /// The type of the error.
     #[derive(Debug)]
     pub enum ErrorType {
@@ -71,6 +157,8 @@ pub mod error {
         Cesu8DecodingError(cesu8::Cesu8DecodingError),
         /// The error is caused by an unknown uuid error.
         UuidError(uuid::Error),
+        /// The operation was stopped by a `CancellationToken`.
+        Cancelled,
         /// The error is caused by some anyhow propagator
         #[cfg(feature = "anyhow")]
         AnyhowError(anyhow::Error),
@@ -93,6 +181,7 @@ pub mod error {
                 #[cfg(feature = "nbt")]
                 ErrorType::Cesu8DecodingError(err) => write!(f, "Cesu8DecodingError {err}"),
                 ErrorType::UuidError(err) => write!(f, "UuidError {err}"),
+                ErrorType::Cancelled => write!(f, "Cancelled"),
                 #[cfg(feature = "anyhow")]
                 ErrorType::AnyhowError(err) => write!(f, "AnyhowError {err}"),
             }
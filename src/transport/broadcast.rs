@@ -0,0 +1,174 @@
+use bytes::Bytes;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::prelude::{DraxWriteExt, PacketComponent, TransportError};
+#[cfg(feature = "encryption")]
+use crate::transport::encryption::{AsyncStreamCipher, Cipher};
+
+/// A length prefixed frame which has been encoded once and can be cheaply cloned and written to
+/// any number of writers.
+#[derive(Clone, Debug)]
+pub struct EncodedFrame {
+    bytes: Bytes,
+}
+
+impl EncodedFrame {
+    /// The encoded bytes of the frame, including the length prefix.
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// Writes the shared frame bytes to the given writer.
+    ///
+    /// # Parameters
+    /// * `write` - The writer to send the frame to.
+    pub async fn write_to<W: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        &self,
+        write: &mut W,
+    ) -> crate::prelude::Result<()> {
+        write.write_all(&self.bytes).await?;
+        Ok(())
+    }
+
+    /// Encrypts a copy of the frame with the given cipher and writes it to the given writer. The
+    /// shared frame bytes are left untouched.
+    ///
+    /// # Parameters
+    /// * `write` - The writer to send the frame to.
+    /// * `cipher` - The cipher of the connection being written to.
+    /// * `scratch` - A buffer to encrypt into, reused between writes to avoid reallocating.
+    #[cfg(feature = "encryption")]
+    pub async fn write_encrypted_to<W: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        &self,
+        write: &mut W,
+        cipher: &mut Cipher,
+        scratch: &mut Vec<u8>,
+    ) -> crate::prelude::Result<()> {
+        scratch.clear();
+        scratch.extend_from_slice(&self.bytes);
+        cipher.encrypt(scratch);
+        write.write_all(scratch).await?;
+        Ok(())
+    }
+}
+
+/// Encodes the component a single time into a length prefixed frame which can be shared between
+/// every connection it is broadcast to.
+///
+/// # Parameters
+/// * `context` - The context to encode the component with.
+/// * `value` - The component to encode.
+pub async fn encode_once<D: PacketComponent<C>, C: Send + Sync>(
+    context: &mut C,
+    value: &D::ComponentType,
+) -> crate::prelude::Result<EncodedFrame> {
+    let mut buffer = Vec::new();
+    buffer
+        .write_component_framed::<C, D>(context, value)
+        .await?;
+    Ok(EncodedFrame {
+        bytes: Bytes::from(buffer),
+    })
+}
+
+/// A writer to broadcast a frame to, optionally with the cipher of its connection.
+pub struct Recipient<'a, W: ?Sized> {
+    write: &'a mut W,
+    #[cfg(feature = "encryption")]
+    cipher: Option<&'a mut Cipher>,
+}
+
+impl<'a, W: ?Sized> Recipient<'a, W> {
+    /// Creates a recipient which receives the frame bytes as they are.
+    pub fn new(write: &'a mut W) -> Self {
+        Self {
+            write,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+
+    /// Creates a recipient which receives the frame encrypted with its own cipher.
+    #[cfg(feature = "encryption")]
+    pub fn encrypted(write: &'a mut W, cipher: &'a mut Cipher) -> Self {
+        Self {
+            write,
+            cipher: Some(cipher),
+        }
+    }
+}
+
+/// Writes the frame to every recipient. Encryption is only applied for recipients which have a
+/// cipher attached, sharing a single scratch buffer between them. A failing recipient does not
+/// stop the frame from being sent to the rest.
+///
+/// # Returns
+/// The errors of every recipient which failed, alongside its index in `recipients`.
+pub async fn send_to<'a, W, I>(frame: &EncodedFrame, recipients: I) -> Vec<(usize, TransportError)>
+where
+    W: AsyncWrite + Unpin + Send + Sync + ?Sized + 'a,
+    I: IntoIterator<Item = Recipient<'a, W>>,
+{
+    #[cfg(feature = "encryption")]
+    let mut scratch = Vec::new();
+    let mut failures = Vec::new();
+    for (index, recipient) in recipients.into_iter().enumerate() {
+        #[cfg(feature = "encryption")]
+        let result = match recipient.cipher {
+            Some(cipher) => {
+                frame
+                    .write_encrypted_to(recipient.write, cipher, &mut scratch)
+                    .await
+            }
+            None => frame.write_to(recipient.write).await,
+        };
+        #[cfg(not(feature = "encryption"))]
+        let result = frame.write_to(recipient.write).await;
+
+        if let Err(err) = result {
+            failures.push((index, err));
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{encode_once, send_to, Recipient};
+    use crate::prelude::DraxReadExt;
+    use crate::transport::encryption::{Cipher, NewCipher};
+
+    #[tokio::test]
+    async fn test_broadcast() -> crate::prelude::Result<()> {
+        let frame = encode_once::<String, _>(&mut (), &"drax".to_string()).await?;
+        assert_eq!(frame.bytes().as_ref(), &[5, 4, b'd', b'r', b'a', b'x']);
+
+        let key = [7u8; 16];
+        let mut cipher = Cipher::new_from_slices(&key, &key).unwrap();
+        let mut plain = vec![];
+        let mut encrypted = vec![];
+        let failures = send_to(
+            &frame,
+            [
+                Recipient::new(&mut plain),
+                Recipient::encrypted(&mut encrypted, &mut cipher),
+            ],
+        )
+        .await;
+        assert!(failures.is_empty());
+        assert_eq!(plain, frame.bytes().as_ref());
+        assert_ne!(encrypted, plain);
+
+        let mut cipher = Cipher::new_from_slices(&key, &key).unwrap();
+        let mut cursor = Cursor::new(encrypted);
+        let mut reader = cursor.decrypt(&mut cipher);
+        assert_eq!(reader.read_var_int().await?, 5);
+        assert_eq!(
+            reader.decode_component::<(), String>(&mut ()).await?,
+            "drax"
+        );
+        Ok(())
+    }
+}
@@ -1,15 +1,58 @@
-use tokio::io::{AsyncRead, AsyncWrite};
+use std::io::Cursor;
 
-use crate::prelude::PacketComponent;
-use crate::transport::buffer::var_num::{ReadVarInt, ReadVarLong, WriteVarInt, WriteVarLong};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::prelude::{PacketComponent, Size};
+use crate::transport::buffer::var_num::{
+    ReadVarInt, ReadVarLong, ReadVarNum, VarNumScheme, WriteVarInt, WriteVarLong, WriteVarNum,
+};
+use crate::transport::counting::CountingWriter;
 use crate::transport::encryption::{Cipher, CipherAttachedReader};
-use crate::PinnedLivelyResult;
+use crate::{throw_explain, PinnedLivelyResult};
+
+/// Frames of constant sized components up to this many bytes, including the length prefix, are
+/// encoded on the stack and written with a single write rather than one write per field.
+const INLINE_FRAME_LEN: usize = 64;
+
+/// Runs the future within the span, emitting an event with the given message if it fails. Without
+/// the `tracing` feature the future is returned as is.
+macro_rules! traced {
+    ($span:ident, $message:literal, $future:expr) => {{
+        #[cfg(feature = "tracing")]
+        {
+            use tracing::Instrument;
+
+            Box::pin(
+                async move {
+                    let result = $future.await;
+                    if let Err(err) = &result {
+                        tracing::debug!(error = %err, $message);
+                    }
+                    result
+                }
+                .instrument($span),
+            )
+        }
+        #[cfg(not(feature = "tracing"))]
+        $future
+    }};
+}
 
 pub trait DraxReadExt {
     fn read_var_int(&mut self) -> ReadVarInt<'_, Self>;
 
     fn read_var_long(&mut self) -> ReadVarLong<'_, Self>;
 
+    /// Reads a VarInt, rejecting over-long encodings such as `0x80 0x00` which are never produced
+    /// by a well behaved writer.
+    fn read_var_int_strict(&mut self) -> ReadVarInt<'_, Self>;
+
+    /// Reads a VarLong, rejecting over-long encodings.
+    fn read_var_long_strict(&mut self) -> ReadVarLong<'_, Self>;
+
+    /// Reads a value encoded with the var num scheme `S`.
+    fn read_var_num<S: VarNumScheme>(&mut self) -> ReadVarNum<'_, S, Self>;
+
     fn decode_component<'a, C: Send + Sync, P: PacketComponent<C>>(
         &'a mut self,
         context: &'a mut C,
@@ -34,6 +77,18 @@ where
         var_num::read_var_long(self)
     }
 
+    fn read_var_int_strict(&mut self) -> ReadVarInt<'_, Self> {
+        var_num::read_var_int_strict(self)
+    }
+
+    fn read_var_long_strict(&mut self) -> ReadVarLong<'_, Self> {
+        var_num::read_var_long_strict(self)
+    }
+
+    fn read_var_num<S: VarNumScheme>(&mut self) -> ReadVarNum<'_, S, Self> {
+        var_num::read_var_num::<S, Self>(self)
+    }
+
     fn decode_component<'a, C: Send + Sync, P: PacketComponent<C>>(
         &'a mut self,
         context: &'a mut C,
@@ -41,7 +96,9 @@ where
     where
         P: Sized,
     {
-        P::decode(context, self)
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!("decode_component", component = std::any::type_name::<P>());
+        traced!(span, "Failed to decode component", P::decode(context, self))
     }
 
     fn decrypt<'a>(&'a mut self, cipher: &'a mut Cipher) -> CipherAttachedReader<'a, Self>
@@ -60,11 +117,39 @@ pub trait DraxWriteExt {
 
     fn write_var_long(&mut self, value: i64) -> WriteVarLong<'_, Self>;
 
+    /// Writes the value encoded with the var num scheme `S`.
+    fn write_var_num<S: VarNumScheme>(&mut self, value: S::Value) -> WriteVarNum<'_, Self>;
+
     fn encode_component<'a, C: Send + Sync, P: PacketComponent<C>>(
         &'a mut self,
         context: &'a mut C,
         component: &'a P::ComponentType,
     ) -> PinnedLivelyResult<'a, ()>;
+
+    /// Sizes the component, writes the size as a VarInt length prefix and then encodes the
+    /// component straight to the writer. Returns the total number of bytes written, including the
+    /// length prefix.
+    ///
+    /// Small components with a `Size::Constant` size are written with a single write. Fails if the
+    /// component encodes to a different number of bytes than it was sized as, in which case the
+    /// writer has been left mid frame.
+    fn write_component_framed<'a, C: Send + Sync, P: PacketComponent<C>>(
+        &'a mut self,
+        context: &'a mut C,
+        component: &'a P::ComponentType,
+    ) -> PinnedLivelyResult<'a, usize>;
+
+    /// Like `write_component_framed`, but writes the frame in the compressed frame format, see
+    /// `transport::compression`. The component is encoded into a buffer first, which is
+    /// compressed if it is at least `threshold` bytes. Returns the total number of bytes written,
+    /// including the length prefix.
+    #[cfg(feature = "compression")]
+    fn write_component_framed_compressed<'a, C: Send + Sync, P: PacketComponent<C>>(
+        &'a mut self,
+        context: &'a mut C,
+        component: &'a P::ComponentType,
+        threshold: usize,
+    ) -> PinnedLivelyResult<'a, usize>;
 }
 
 impl<T> DraxWriteExt for T
@@ -79,12 +164,103 @@ where
         var_num::write_var_long(self, value)
     }
 
+    fn write_var_num<S: VarNumScheme>(&mut self, value: S::Value) -> WriteVarNum<'_, Self> {
+        var_num::write_var_num::<S, Self>(self, value)
+    }
+
     fn encode_component<'a, C: Send + Sync, P: PacketComponent<C>>(
         &'a mut self,
         context: &'a mut C,
         component: &'a P::ComponentType,
     ) -> PinnedLivelyResult<'a, ()> {
-        P::encode(component, context, self)
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!("encode_component", component = std::any::type_name::<P>());
+        traced!(
+            span,
+            "Failed to encode component",
+            P::encode(component, context, self)
+        )
+    }
+
+    fn write_component_framed<'a, C: Send + Sync, P: PacketComponent<C>>(
+        &'a mut self,
+        context: &'a mut C,
+        component: &'a P::ComponentType,
+    ) -> PinnedLivelyResult<'a, usize> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "write_frame",
+            component = std::any::type_name::<P>(),
+            len = tracing::field::Empty
+        );
+        traced!(
+            span,
+            "Failed to write frame",
+            Box::pin(async move {
+                let (size, constant) = match P::size(component, context)? {
+                    Size::Dynamic(x) => (x, false),
+                    Size::Constant(x) => (x, true),
+                };
+                #[cfg(feature = "tracing")]
+                tracing::Span::current().record("len", size);
+                let prefix = i32::try_from(size)?;
+                let total = var_num::size_var_int(prefix) + size;
+
+                let written = if constant && total <= INLINE_FRAME_LEN {
+                    let mut buf = [0; INLINE_FRAME_LEN];
+                    let mut cursor = Cursor::new(&mut buf[..]);
+                    cursor.write_var_int(prefix).await?;
+                    P::encode(component, context, &mut cursor).await?;
+                    let written = cursor.position() as usize;
+                    self.write_all(&buf[..written]).await?;
+                    written
+                } else {
+                    let mut writer = CountingWriter::new(&mut *self);
+                    writer.write_var_int(prefix).await?;
+                    P::encode(component, context, &mut writer).await?;
+                    writer.bytes_transferred() as usize
+                };
+                if written != total {
+                    throw_explain!(format!(
+                        "Component was sized as {size} bytes but encoded as {} bytes",
+                        written - var_num::size_var_int(prefix)
+                    ))
+                }
+                Ok(total)
+            })
+        )
+    }
+
+    #[cfg(feature = "compression")]
+    fn write_component_framed_compressed<'a, C: Send + Sync, P: PacketComponent<C>>(
+        &'a mut self,
+        context: &'a mut C,
+        component: &'a P::ComponentType,
+        threshold: usize,
+    ) -> PinnedLivelyResult<'a, usize> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "write_frame",
+            component = std::any::type_name::<P>(),
+            len = tracing::field::Empty
+        );
+        traced!(
+            span,
+            "Failed to write frame",
+            Box::pin(async move {
+                let body =
+                    crate::transport::frame::encode_body_to_bytes::<C, P>(context, component)
+                        .await?;
+                let frame = crate::transport::compression::compress_body(&body, threshold)?;
+                #[cfg(feature = "tracing")]
+                tracing::Span::current().record("len", frame.len());
+                let mut header = Vec::with_capacity(5);
+                var_num::put_var_int(&mut header, i32::try_from(frame.len())?);
+                self.write_all(&header).await?;
+                self.write_all(&frame).await?;
+                Ok(header.len() + frame.len())
+            })
+        )
     }
 }
 
@@ -94,23 +270,121 @@ pub mod var_num {
     use std::pin::Pin;
     use std::task::{ready, Context, Poll};
 
+    use bytes::BufMut;
     use pin_project_lite::pin_project;
     use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
     use crate::{err, err_explain};
 
+    /// A variable length integer encoding, such as signed LEB128 or a prefix varint. Implementing
+    /// it provides `DraxReadExt::read_var_num`, `DraxWriteExt::write_var_num` and the
+    /// `primitive::VarNum` component for the encoding.
+    ///
+    /// Values are decoded a byte at a time so encodings never read past their last byte.
+    pub trait VarNumScheme {
+        /// The integer type values decode to.
+        type Value: Copy + Send + Sync;
+
+        /// The progress of a value being decoded, carried between bytes.
+        type State: Default + Send + Sync;
+
+        /// The most bytes any value can encode to.
+        const MAX_BYTES: usize;
+
+        /// The number of bytes the value encodes to.
+        fn size(value: Self::Value) -> usize;
+
+        /// Appends the encoded value to the buffer, which has room for at least `MAX_BYTES` bytes.
+        fn put(buffer: &mut impl BufMut, value: Self::Value);
+
+        /// Feeds the next byte of an encoded value to the decoder, returning the value once its
+        /// last byte has been read.
+        ///
+        /// # Parameters
+        /// * `state` - The progress of the value, starting from `State::default()`.
+        /// * `byte` - The next byte read.
+        fn decode_byte(
+            state: &mut Self::State,
+            byte: u8,
+        ) -> crate::prelude::Result<Option<Self::Value>>;
+    }
+
+    /// The progress of a value being decoded by one of the builtin schemes. Strict decoding
+    /// rejects encodings which are longer than necessary or which set bits past the width of the
+    /// type.
+    #[derive(Debug, Default)]
+    pub struct VarNumState<T> {
+        value: T,
+        bit_offset: u32,
+        strict: bool,
+    }
+
     macro_rules! declare_var_num_ext {
         (
+        $(#[$doc:meta])*
+        $scheme:ident,
+        $name:literal,
         $typing:ty,
         $sub_typing:ty,
+        $max_bytes:literal,
         $size_fn:ident,
+        $put_fn:ident,
         $read_fn:ident,
+        $read_strict_fn:ident,
         $read_struct:ident,
         $write_fn:ident,
         $write_struct:ident,
         $bit_limit:literal,
-        $and_check:literal
+        $and_check:literal,
+        $last_byte_max:literal
     ) => {
+            $(#[$doc])*
+            #[derive(Debug, Clone, Copy)]
+            pub struct $scheme;
+
+            impl VarNumScheme for $scheme {
+                type Value = $typing;
+                type State = VarNumState<$typing>;
+
+                const MAX_BYTES: usize = $max_bytes;
+
+                fn size(value: $typing) -> usize {
+                    $size_fn(value)
+                }
+
+                fn put(buffer: &mut impl BufMut, value: $typing) {
+                    $put_fn(buffer, value)
+                }
+
+                fn decode_byte(
+                    state: &mut Self::State,
+                    byte: u8,
+                ) -> crate::prelude::Result<Option<$typing>> {
+                    if state.strict {
+                        if state.bit_offset + 7 >= $bit_limit && byte > $last_byte_max {
+                            return Err(err_explain!(concat!($name, " overflowed its type")));
+                        }
+                        if state.bit_offset > 0 && byte == 0 {
+                            return Err(err_explain!(concat!(
+                                $name,
+                                " was not minimally encoded"
+                            )));
+                        }
+                    }
+                    state.value |= <$typing>::from(byte & 0b0111_1111)
+                        .overflowing_shl(state.bit_offset)
+                        .0;
+                    state.bit_offset += 7;
+                    if byte & 0b1000_0000 == 0 {
+                        return Ok(Some(state.value));
+                    }
+                    if state.bit_offset >= $bit_limit {
+                        return Err(err_explain!(concat!($name, " too large")));
+                    }
+                    Ok(None)
+                }
+            }
+
             pub fn $size_fn(var_num: $typing) -> usize {
                 let mut temp: $sub_typing = var_num as $sub_typing;
                 let mut size = 0;
@@ -123,64 +397,37 @@ pub mod var_num {
                 }
             }
 
-            pub(crate) fn $read_fn<A>(reader: &mut A) -> $read_struct<A>
-            where
-                A: AsyncRead + Unpin + ?Sized,
-            {
-                $read_struct {
-                    reader,
-                    value: 0,
-                    bit_offset: 0,
-                    _pin: PhantomPinned,
+            /// Appends the encoded var num to the buffer.
+            pub fn $put_fn(buffer: &mut impl BufMut, var_num: $typing) {
+                let mut temp: $sub_typing = var_num as $sub_typing;
+                loop {
+                    if (temp & $and_check) == 0 {
+                        buffer.put_u8(temp as u8);
+                        return;
+                    }
+                    buffer.put_u8((temp & 0x7F | 0x80) as u8);
+                    temp = temp.overflowing_shr(7).0;
                 }
             }
 
-            pin_project! {
-                #[derive(Debug)]
-                #[must_use = "futures do nothing unless you `.await` or poll them"]
-                pub struct $read_struct<'a, A: ?Sized> {
-                    reader: &'a mut A,
-                    value: $typing,
-                    bit_offset: u32,
-                    // Make this future `!Unpin` for compatibility with async trait methods.
-                    #[pin]
-                    _pin: PhantomPinned,
-                }
-            }
+            pub type $read_struct<'a, A> = ReadVarNum<'a, $scheme, A>;
 
-            impl<A> Future for $read_struct<'_, A>
+            pub(crate) fn $read_fn<A>(reader: &mut A) -> $read_struct<'_, A>
             where
                 A: AsyncRead + Unpin + ?Sized,
             {
-                type Output = crate::transport::Result<$typing>;
-
-                fn poll(
-                    self: Pin<&mut Self>,
-                    cx: &mut Context<'_>,
-                ) -> Poll<crate::transport::Result<$typing>> {
-                    let me = self.project();
+                read_var_num::<$scheme, A>(reader)
+            }
 
-                    loop {
-                        if *me.bit_offset >= $bit_limit {
-                            return Poll::Ready(Err(err_explain!("VarInt too large")));
-                        };
-
-                        let mut inner = [0u8; 1];
-                        let mut buf = ReadBuf::new(inner.as_mut());
-                        ready!(Pin::new(&mut *me.reader).poll_read(cx, &mut buf))?;
-                        if buf.filled().len() == 0 {
-                            return Poll::Ready(Err(err!(crate::prelude::ErrorType::EOF)));
-                        }
-                        let byte = buf.filled()[0];
-                        *me.value |= <$typing>::from(byte & 0b0111_1111)
-                            .overflowing_shl(*me.bit_offset)
-                            .0;
-                        *me.bit_offset += 7;
-                        if byte & 0b1000_0000 == 0 {
-                            return Poll::Ready(Ok(*me.value));
-                        }
-                    }
-                }
+            /// Reads a var num, rejecting encodings which are longer than necessary or which set
+            /// bits past the width of the type.
+            pub(crate) fn $read_strict_fn<A>(reader: &mut A) -> $read_struct<'_, A>
+            where
+                A: AsyncRead + Unpin + ?Sized,
+            {
+                let mut read = read_var_num::<$scheme, A>(reader);
+                read.state.strict = true;
+                read
             }
 
             pub(crate) fn $write_fn<A>(writer: &mut A, value: $typing) -> $write_struct<A>
@@ -235,28 +482,190 @@ pub mod var_num {
     }
 
     declare_var_num_ext!(
+        /// The scheme of `VarInt`, for code generic over schemes.
+        VarIntScheme,
+        "VarInt",
         i32,
         u32,
+        5,
         size_var_int,
+        put_var_int,
         read_var_int,
+        read_var_int_strict,
         ReadVarInt,
         write_var_int,
         WriteVarInt,
         35,
-        0xFFFFFF80u32
+        0xFFFFFF80u32,
+        0x0F
     );
 
     declare_var_num_ext!(
+        /// The scheme of `VarLong`, for code generic over schemes.
+        VarLongScheme,
+        "VarLong",
         i64,
         u64,
+        10,
         size_var_long,
+        put_var_long,
         read_var_long,
+        read_var_long_strict,
         ReadVarLong,
         write_var_long,
         WriteVarLong,
         70,
-        0xFFFFFFFFFFFFFF80u64
+        0xFFFFFFFFFFFFFF80u64,
+        0x01
     );
+
+    /// Decodes a value from the front of the buffer, advancing it past the value.
+    pub(crate) fn get_var_num<S: VarNumScheme>(
+        buf: &mut impl bytes::Buf,
+    ) -> crate::transport::Result<S::Value> {
+        let mut state = S::State::default();
+        for _ in 0..S::MAX_BYTES {
+            if !buf.has_remaining() {
+                return Err(err!(crate::prelude::ErrorType::EOF));
+            }
+            if let Some(value) = S::decode_byte(&mut state, buf.get_u8())? {
+                return Ok(value);
+            }
+        }
+        Err(err_explain!("Var num too large"))
+    }
+
+    pub(crate) fn read_var_num<S, A>(reader: &mut A) -> ReadVarNum<'_, S, A>
+    where
+        S: VarNumScheme,
+        A: AsyncRead + Unpin + ?Sized,
+    {
+        ReadVarNum {
+            reader,
+            state: S::State::default(),
+            read: 0,
+            _pin: PhantomPinned,
+        }
+    }
+
+    pin_project! {
+        #[derive(Debug)]
+        #[must_use = "futures do nothing unless you `.await` or poll them"]
+        pub struct ReadVarNum<'a, S: VarNumScheme, A: ?Sized> {
+            reader: &'a mut A,
+            state: S::State,
+            read: usize,
+            // Make this future `!Unpin` for compatibility with async trait methods.
+            #[pin]
+            _pin: PhantomPinned,
+        }
+    }
+
+    impl<S, A> Future for ReadVarNum<'_, S, A>
+    where
+        S: VarNumScheme,
+        A: AsyncRead + Unpin + ?Sized,
+    {
+        type Output = crate::transport::Result<S::Value>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let me = self.project();
+
+            loop {
+                if *me.read >= S::MAX_BYTES {
+                    return Poll::Ready(Err(err_explain!("Var num too large")));
+                }
+                let mut inner = [0u8; 1];
+                let mut buf = ReadBuf::new(inner.as_mut());
+                ready!(Pin::new(&mut *me.reader).poll_read(cx, &mut buf))?;
+                if buf.filled().is_empty() {
+                    return Poll::Ready(Err(err!(crate::prelude::ErrorType::EOF)));
+                }
+                *me.read += 1;
+                if let Some(value) = S::decode_byte(me.state, buf.filled()[0])? {
+                    return Poll::Ready(Ok(value));
+                }
+            }
+        }
+    }
+
+    pub(crate) fn write_var_num<S, A>(writer: &mut A, value: S::Value) -> WriteVarNum<'_, A>
+    where
+        S: VarNumScheme,
+        A: AsyncWrite + Unpin + ?Sized,
+    {
+        let bytes = if S::MAX_BYTES <= INLINE_VAR_NUM_LEN {
+            let mut inline = [0; INLINE_VAR_NUM_LEN];
+            let mut rest = &mut inline[..];
+            S::put(&mut rest, value);
+            let len = INLINE_VAR_NUM_LEN - rest.len();
+            VarNumBytes::Inline(inline, len)
+        } else {
+            let mut bytes = Vec::with_capacity(S::MAX_BYTES);
+            S::put(&mut bytes, value);
+            VarNumBytes::Heap(bytes)
+        };
+        WriteVarNum {
+            writer,
+            bytes,
+            written: 0,
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// Values of schemes encoding to at most this many bytes are encoded on the stack while they
+    /// are written, rather than into an allocated buffer.
+    const INLINE_VAR_NUM_LEN: usize = 16;
+
+    #[derive(Debug)]
+    enum VarNumBytes {
+        Inline([u8; INLINE_VAR_NUM_LEN], usize),
+        Heap(Vec<u8>),
+    }
+
+    impl VarNumBytes {
+        fn as_slice(&self) -> &[u8] {
+            match self {
+                VarNumBytes::Inline(bytes, len) => &bytes[..*len],
+                VarNumBytes::Heap(bytes) => bytes,
+            }
+        }
+    }
+
+    pin_project! {
+        #[derive(Debug)]
+        #[must_use = "futures do nothing unless you `.await` or poll them"]
+        pub struct WriteVarNum<'a, A: ?Sized> {
+            writer: &'a mut A,
+            bytes: VarNumBytes,
+            written: usize,
+            // Make this future `!Unpin` for compatibility with async trait methods.
+            #[pin]
+            _pin: PhantomPinned,
+        }
+    }
+
+    impl<A> Future for WriteVarNum<'_, A>
+    where
+        A: AsyncWrite + Unpin + ?Sized,
+    {
+        type Output = crate::transport::Result<()>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let me = self.project();
+
+            let bytes = me.bytes.as_slice();
+            while *me.written < bytes.len() {
+                let written =
+                    ready!(Pin::new(&mut *me.writer).poll_write(cx, &bytes[*me.written..]))?;
+                if written == 0 {
+                    return Poll::Ready(Err(err!(crate::prelude::ErrorType::EOF)));
+                }
+                *me.written += written;
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -286,6 +695,44 @@ mod tests {
             let result = cursor.read_var_int().await?;
             assert_eq!(result, attempt.0);
         }
+        let mut cursor = Cursor::new(vec![1]);
+        assert!(format!("{:?}", cursor.read_var_int()).starts_with("ReadVarNum"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_var_num_strict() -> crate::transport::Result<()> {
+        for attempt in var_int_tests!() {
+            let mut cursor = Cursor::new(attempt.1);
+            assert_eq!(cursor.read_var_int_strict().await?, attempt.0);
+        }
+        for value in [0, 1, -1, i64::MAX, i64::MIN] {
+            let mut bytes = vec![];
+            super::var_num::put_var_long(&mut bytes, value);
+            let mut cursor = Cursor::new(bytes);
+            assert_eq!(cursor.read_var_long_strict().await?, value);
+        }
+
+        for bytes in [
+            vec![0x80, 0x00],
+            vec![0x81, 0x80, 0x00],
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0x1F],
+        ] {
+            let mut cursor = Cursor::new(bytes.clone());
+            assert!(cursor.read_var_int_strict().await.is_err());
+            let mut cursor = Cursor::new(bytes);
+            assert!(cursor.read_var_int().await.is_ok());
+        }
+        let mut long = vec![0xFF; 9];
+        long.push(0x03);
+        let mut cursor = Cursor::new(long);
+        let err = cursor.read_var_long_strict().await.unwrap_err();
+        assert!(err.to_string().contains("VarLong overflowed its type"));
+
+        let mut cursor = Cursor::new(vec![0xFF; 11]);
+        let err = cursor.read_var_long().await.unwrap_err();
+        assert!(err.to_string().contains("VarLong too large"));
+        assert_eq!(cursor.position(), 10);
         Ok(())
     }
 
@@ -300,4 +747,190 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_write_component_framed() -> crate::transport::Result<()> {
+        let mut cursor = Cursor::new(vec![]);
+        let value = "drax".to_string();
+        let written = cursor
+            .write_component_framed::<(), String>(&mut (), &value)
+            .await?;
+        assert_eq!(written, 6);
+        assert_eq!(cursor.into_inner(), vec![5, 4, b'd', b'r', b'a', b'x']);
+
+        let mut writer = WriteCounter::default();
+        let written = writer
+            .write_component_framed::<(), i64>(&mut (), &-1)
+            .await?;
+        assert_eq!(written, 9);
+        assert_eq!(writer.writes, 1);
+        assert_eq!(
+            writer.bytes,
+            [8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+
+        let mut writer = WriteCounter::default();
+        let values = vec![1, 2, 3];
+        writer
+            .write_component_framed::<(), Vec<i32>>(&mut (), &values)
+            .await?;
+        assert_eq!(writer.bytes.len(), 14);
+        assert!(writer.writes > 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_write_component_framed_compressed() -> crate::transport::Result<()> {
+        use crate::transport::frame::FrameReader;
+
+        let small = "drax".to_string();
+        let large = "drax".repeat(64);
+        let mut cursor = Cursor::new(vec![]);
+        let written = cursor
+            .write_component_framed_compressed::<(), String>(&mut (), &small, 64)
+            .await?;
+        assert_eq!(written, 7);
+        assert_eq!(cursor.get_ref()[..2], [6, 0]);
+        let written = cursor
+            .write_component_framed_compressed::<(), String>(&mut (), &large, 64)
+            .await?;
+        assert_eq!(cursor.get_ref().len(), 7 + written);
+        assert!(written < large.len());
+
+        let mut reader = FrameReader::new(Cursor::new(cursor.into_inner()));
+        reader.set_compression_threshold(Some(64));
+        assert_eq!(
+            reader.decode_frame::<(), String>(&mut ()).await?,
+            Some(small)
+        );
+        assert_eq!(
+            reader.decode_frame::<(), String>(&mut ()).await?,
+            Some(large)
+        );
+        assert_eq!(reader.decode_frame::<(), String>(&mut ()).await?, None);
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct WriteCounter {
+        bytes: Vec<u8>,
+        writes: usize,
+    }
+
+    impl tokio::io::AsyncWrite for WriteCounter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.writes += 1;
+            self.bytes.extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Signed LEB128, which sign extends the last byte rather than wrapping negative values.
+    struct SignedLeb128;
+
+    impl super::var_num::VarNumScheme for SignedLeb128 {
+        type Value = i64;
+        type State = (i64, u32);
+
+        const MAX_BYTES: usize = 10;
+
+        fn size(value: i64) -> usize {
+            let mut bytes = vec![];
+            Self::put(&mut bytes, value);
+            bytes.len()
+        }
+
+        fn put(buffer: &mut impl bytes::BufMut, mut value: i64) {
+            loop {
+                let byte = (value & 0x7F) as u8;
+                value >>= 7;
+                if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+                    buffer.put_u8(byte);
+                    return;
+                }
+                buffer.put_u8(byte | 0x80);
+            }
+        }
+
+        fn decode_byte(
+            (value, shift): &mut (i64, u32),
+            byte: u8,
+        ) -> crate::prelude::Result<Option<i64>> {
+            *value |= i64::from(byte & 0x7F) << *shift;
+            *shift += 7;
+            if byte & 0x80 != 0 {
+                return Ok(None);
+            }
+            if *shift < 64 && byte & 0x40 != 0 {
+                *value |= -1 << *shift;
+            }
+            Ok(Some(*value))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_var_num_scheme() -> crate::transport::Result<()> {
+        use super::var_num::VarIntScheme;
+        use crate::prelude::PacketComponent;
+        use crate::transport::packet::primitive::VarNum;
+
+        for attempt in var_int_tests!() {
+            let mut cursor = Cursor::new(vec![]);
+            cursor.write_var_num::<VarIntScheme>(attempt.0).await?;
+            assert_eq!(cursor.get_ref(), &attempt.1);
+            let mut cursor = Cursor::new(attempt.1);
+            assert_eq!(cursor.read_var_num::<VarIntScheme>().await?, attempt.0);
+        }
+
+        for (value, bytes) in [
+            (2, vec![0x02]),
+            (-2, vec![0x7E]),
+            (64, vec![0xC0, 0x00]),
+            (-123456, vec![0xC0, 0xBB, 0x78]),
+        ] {
+            let mut cursor = Cursor::new(vec![]);
+            VarNum::<SignedLeb128>::encode(&value, &mut (), &mut cursor).await?;
+            assert_eq!(cursor.get_ref(), &bytes);
+            assert_eq!(
+                VarNum::<SignedLeb128>::size(&value, &mut ())?,
+                crate::prelude::Size::Dynamic(bytes.len())
+            );
+            let mut cursor = Cursor::new(bytes);
+            assert_eq!(
+                VarNum::<SignedLeb128>::decode(&mut (), &mut cursor).await?,
+                value
+            );
+        }
+        for value in [i64::MIN, i64::MAX] {
+            let mut cursor = Cursor::new(vec![]);
+            cursor.write_var_num::<SignedLeb128>(value).await?;
+            cursor.set_position(0);
+            assert_eq!(cursor.read_var_num::<SignedLeb128>().await?, value);
+        }
+
+        let mut cursor = Cursor::new(vec![0x80; 11]);
+        assert!(cursor.read_var_num::<SignedLeb128>().await.is_err());
+        let mut cursor = Cursor::new(vec![0x80]);
+        assert!(cursor.read_var_num::<SignedLeb128>().await.is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,121 @@
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+
+use tokio::sync::Notify;
+
+use crate::prelude::{ErrorType, TransportError};
+
+struct TokenInner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+/// Signals in-flight reads to stop. Clones share the same state, so one clone can be handed to a
+/// reader or connection and another kept to cancel it, such as on shutdown. Reads racing a
+/// cancelled token fail with `ErrorType::Cancelled`.
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(TokenInner {
+                cancelled: AtomicBool::new(false),
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    /// Cancels the token, waking everything waiting on it. Cancelling is permanent.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            // registered before checking, so a cancel between the check and the await isn't lost
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Runs the future until it completes, failing with `ErrorType::Cancelled` instead if the token is
+/// cancelled first. The future is dropped where it stopped, which is at its last yield point for
+/// decodes of always ready readers.
+///
+/// # Parameters
+/// * `token` - The token to race the future against, or `None` to run it to completion.
+/// * `future` - The future to run.
+pub async fn cancellable<T>(
+    token: Option<CancellationToken>,
+    future: impl Future<Output = crate::prelude::Result<T>>,
+) -> crate::prelude::Result<T> {
+    let Some(token) = token else {
+        return future.await;
+    };
+    let mut cancelled = pin!(token.cancelled());
+    let mut future = pin!(future);
+    std::future::poll_fn(|cx| {
+        if cancelled.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Err(TransportError::with_context(
+                "Cancelled while reading".into(),
+                ErrorType::Cancelled,
+            )));
+        }
+        future.as_mut().poll(cx)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cancellable, CancellationToken};
+    use crate::prelude::ErrorType;
+
+    #[tokio::test]
+    async fn test_cancellable() -> crate::prelude::Result<()> {
+        let token = CancellationToken::new();
+        assert_eq!(cancellable(Some(token.clone()), async { Ok(3) }).await?, 3);
+
+        let pending = std::future::pending::<crate::prelude::Result<()>>();
+        let (result, ()) = tokio::join!(cancellable(Some(token.clone()), pending), async {
+            token.cancel()
+        });
+        assert!(matches!(
+            result.map_err(|err| err.error_type),
+            Err(ErrorType::Cancelled)
+        ));
+        assert!(token.is_cancelled());
+        assert_eq!(cancellable(None, async { Ok(4) }).await?, 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::{Cursor, Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::prelude::DraxReadExt;
+use crate::throw_explain;
+use crate::transport::buffer::var_num::put_var_int;
+use crate::transport::packet::yield_now;
+
+/// Compresses a frame body into the compressed frame format. Bodies of at least `threshold` bytes
+/// are prefixed with their uncompressed length as a VarInt and zlib compressed; smaller bodies are
+/// prefixed with a zero length and sent as is.
+///
+/// # Parameters
+/// * `body` - The uncompressed frame body.
+/// * `threshold` - The smallest body, in bytes, which is compressed.
+pub fn compress_body(body: &[u8], threshold: usize) -> crate::prelude::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(body.len() + 5);
+    if body.len() < threshold {
+        put_var_int(&mut out, 0);
+        out.extend_from_slice(body);
+        return Ok(out);
+    }
+
+    put_var_int(&mut out, i32::try_from(body.len())?);
+    let mut encoder = ZlibEncoder::new(out, Compression::default());
+    encoder.write_all(body)?;
+    Ok(encoder.finish()?)
+}
+
+/// The most bytes `compress_body` can add to a body of `len` bytes, which incompressible bodies
+/// grow by: the uncompressed length VarInt plus zlib's worst case expansion over stored blocks.
+pub fn max_compressed_growth(len: usize) -> usize {
+    5 + (len >> 12) + (len >> 14) + (len >> 25) + 13
+}
+
+/// The most bytes inflated between cooperative yields, so a large frame doesn't hold the task's
+/// thread for its whole decompression.
+const INFLATE_CHUNK: u64 = 64 * 1024;
+
+/// Restores the frame body from a frame in the compressed frame format. The declared uncompressed
+/// length is checked against the threshold and `max_size` before anything is inflated, and the
+/// inflated body must match the declared length exactly. Inflating yields to the scheduler every
+/// `INFLATE_CHUNK` bytes.
+///
+/// # Parameters
+/// * `frame` - The frame body as read from the wire.
+/// * `threshold` - The smallest body, in bytes, the writer compresses. Compressed bodies below it
+///   are rejected, as vanilla does.
+/// * `max_size` - The largest uncompressed body, in bytes, which will be inflated.
+pub async fn decompress_body(
+    frame: &[u8],
+    threshold: usize,
+    max_size: usize,
+) -> crate::prelude::Result<Vec<u8>> {
+    let mut cursor = Cursor::new(frame);
+    let data_length = cursor.read_var_int().await?;
+    let data_length = crate::transport::packet::vec::checked_length(data_length)?;
+    let rest = &frame[cursor.position() as usize..];
+    if data_length == 0 {
+        return Ok(rest.to_vec());
+    }
+    if data_length < threshold {
+        throw_explain!(format!(
+            "Compressed frame declared {data_length} bytes, below compression threshold {threshold}"
+        ))
+    }
+    if data_length > max_size {
+        throw_explain!(format!(
+            "Compressed frame declared {data_length} bytes, exceeding maximum frame size {max_size}"
+        ))
+    }
+
+    let mut body = Vec::with_capacity(data_length);
+    // Read one byte past the declared length so an oversized stream is caught without inflating
+    // the rest of it.
+    let mut decoder = ZlibDecoder::new(rest).take(data_length as u64 + 1);
+    while (&mut decoder).take(INFLATE_CHUNK).read_to_end(&mut body)? != 0 {
+        yield_now().await;
+    }
+    if body.len() != data_length {
+        throw_explain!(format!(
+            "Compressed frame declared {data_length} bytes but did not inflate to that length"
+        ))
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_body, decompress_body, max_compressed_growth};
+
+    #[tokio::test]
+    async fn test_compression_threshold() -> crate::prelude::Result<()> {
+        let small = vec![1, 2, 3];
+        let compressed = compress_body(&small, 64)?;
+        assert_eq!(compressed, vec![0, 1, 2, 3]);
+        assert_eq!(decompress_body(&compressed, 64, 64).await?, small);
+
+        let large = vec![7; 256];
+        let compressed = compress_body(&large, 64)?;
+        assert_eq!(&compressed[..2], &[0x80, 0x02]);
+        assert!(compressed.len() < large.len());
+        assert_eq!(decompress_body(&compressed, 64, 256).await?, large);
+        assert!(decompress_body(&compressed, 64, 255).await.is_err());
+        assert!(decompress_body(&compressed, 257, 1024).await.is_err());
+
+        let huge = vec![7; 300_000];
+        let compressed = compress_body(&huge, 64)?;
+        assert_eq!(decompress_body(&compressed, 64, huge.len()).await?, huge);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_growth() -> crate::prelude::Result<()> {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let noise: Vec<u8> = (0..70_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        for len in [0, 1, 100, 4096, 16_384, 70_000] {
+            let compressed = compress_body(&noise[..len], 0)?;
+            assert!(compressed.len() <= len + max_compressed_growth(len));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_decompress_length_mismatch() -> crate::prelude::Result<()> {
+        let mut compressed = compress_body(&[7; 100], 0)?;
+        compressed[0] = 99;
+        assert!(decompress_body(&compressed, 0, 1024).await.is_err());
+        compressed[0] = 101;
+        assert!(decompress_body(&compressed, 0, 1024).await.is_err());
+        Ok(())
+    }
+}
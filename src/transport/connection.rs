@@ -0,0 +1,484 @@
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::prelude::{ContextLabel, PacketComponent};
+use crate::transport::cancel::CancellationToken;
+#[cfg(feature = "encryption")]
+use crate::transport::encryption::{
+    cipher_from_secret, AsyncStreamCipher, Cipher, CipherAttachedReader,
+};
+use crate::transport::frame::{FrameReader, FrameWriter};
+
+/// Logs hex dumps of outbound frames at the debug level for protocol debugging, limited to frames
+/// of at most `max_frame_size` bytes and at most `per_second` dumps each second. Frames are dumped
+/// as written, compressed if compression is enabled but before encryption.
+#[cfg(feature = "tracing")]
+pub struct FrameDump {
+    max_frame_size: usize,
+    per_second: u32,
+    window_start: std::time::Instant,
+    dumped: u32,
+    suppressed: u64,
+}
+
+#[cfg(feature = "tracing")]
+impl FrameDump {
+    /// The environment variable read by `from_env`, holding the largest frame size to dump.
+    pub const ENV_VAR: &'static str = "DRAX_FRAME_DUMP";
+
+    pub fn new(max_frame_size: usize, per_second: u32) -> Self {
+        Self {
+            max_frame_size,
+            per_second,
+            window_start: std::time::Instant::now(),
+            dumped: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Creates a dump of up to 20 frames a second if `DRAX_FRAME_DUMP` is set to the largest
+    /// frame size to dump, `None` if it is unset or not a number. The variable is read once per
+    /// process, by the first call.
+    pub fn from_env() -> Option<Self> {
+        static MAX_FRAME_SIZE: std::sync::OnceLock<Option<usize>> = std::sync::OnceLock::new();
+        let max_frame_size = *MAX_FRAME_SIZE
+            .get_or_init(|| std::env::var(FrameDump::ENV_VAR).ok()?.trim().parse().ok());
+        Some(Self::new(max_frame_size?, 20))
+    }
+
+    fn allow(&mut self, len: usize) -> bool {
+        if len > self.max_frame_size {
+            return false;
+        }
+        if self.window_start.elapsed() >= std::time::Duration::from_secs(1) {
+            if self.suppressed > 0 {
+                tracing::debug!(
+                    suppressed = self.suppressed,
+                    "Suppressed outbound frame dumps"
+                );
+            }
+            self.window_start = std::time::Instant::now();
+            self.dumped = 0;
+            self.suppressed = 0;
+        }
+        if self.dumped == self.per_second {
+            self.suppressed += 1;
+            return false;
+        }
+        self.dumped += 1;
+        true
+    }
+
+    fn dump(&mut self, packet: &str, variant: Option<&str>, frame: &[u8]) {
+        if self.allow(frame.len()) {
+            tracing::debug!(
+                packet,
+                variant,
+                len = frame.len(),
+                hex = %hex(frame),
+                "Outbound frame"
+            );
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+
+    let mut hex = String::with_capacity(bytes.len() * 3);
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            hex.push(' ');
+        }
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+/// Owns both halves of a connection and layers framing, compression and encryption over them, in
+/// the order the protocol expects: frames are compressed, then the whole stream is encrypted.
+///
+/// Compression and encryption start disabled and are enabled part way through the connection, as
+/// negotiated by the protocol; both sides must enable them at the same frame.
+///
+/// Writes are safe to cancel: a frame cut off part way through being written is finished, from
+/// where it stopped, before the next frame or flush.
+pub struct DraxConnection<R, W> {
+    reader: FrameReader<R>,
+    writer: W,
+    frames: FrameWriter<Vec<u8>>,
+    /// The bytes at the front of `frames` which are framed and encrypted, waiting to be sent.
+    ready: usize,
+    /// The bytes of the ready frames which have already been sent.
+    sent: usize,
+    #[cfg(feature = "encryption")]
+    read_cipher: Option<Cipher>,
+    #[cfg(feature = "encryption")]
+    write_cipher: Option<Cipher>,
+    #[cfg(feature = "tracing")]
+    frame_dump: Option<FrameDump>,
+}
+
+impl<R, W> DraxConnection<R, W>
+where
+    R: AsyncRead + Unpin + Send + Sync,
+    W: AsyncWrite + Unpin + Send + Sync,
+{
+    pub fn new(read: R, write: W) -> Self {
+        Self {
+            reader: FrameReader::new(read),
+            writer: write,
+            frames: FrameWriter::new(vec![]),
+            ready: 0,
+            sent: 0,
+            #[cfg(feature = "encryption")]
+            read_cipher: None,
+            #[cfg(feature = "encryption")]
+            write_cipher: None,
+            #[cfg(feature = "tracing")]
+            frame_dump: FrameDump::from_env(),
+        }
+    }
+
+    /// Sets the largest frame body, in bytes, which will be read or written.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.set_max_frame_size(max_frame_size);
+        self
+    }
+
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.reader.set_max_frame_size(max_frame_size);
+        self.frames.set_max_frame_size(max_frame_size);
+    }
+
+    /// Fails reads in flight with `ErrorType::Cancelled` once the token is cancelled, see
+    /// `FrameReader::set_cancellation_token`.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.set_cancellation_token(Some(token));
+        self
+    }
+
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.reader.set_cancellation_token(token);
+    }
+
+    /// Enables the compressed frame format in both directions, see `transport::compression`;
+    /// `None` disables it.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) {
+        self.reader.set_compression_threshold(threshold);
+        self.frames.set_compression_threshold(threshold);
+    }
+
+    /// Replaces the outbound frame dump, which starts as configured by `FrameDump::from_env`;
+    /// `None` disables it.
+    #[cfg(feature = "tracing")]
+    pub fn set_frame_dump(&mut self, frame_dump: Option<FrameDump>) {
+        self.frame_dump = frame_dump;
+    }
+
+    /// Encrypts everything written and decrypts everything read from now on with the shared
+    /// secret, which is used as both the key and the IV.
+    #[cfg(feature = "encryption")]
+    pub fn enable_encryption(&mut self, secret: &[u8]) -> crate::prelude::Result<()> {
+        self.read_cipher = Some(cipher_from_secret(secret)?);
+        self.write_cipher = Some(cipher_from_secret(secret)?);
+        Ok(())
+    }
+
+    /// A frame reader with the same settings as `reader`, reading through the cipher.
+    #[cfg(feature = "encryption")]
+    fn decrypting<'a>(
+        reader: &'a mut FrameReader<R>,
+        cipher: &'a mut Cipher,
+    ) -> FrameReader<CipherAttachedReader<'a, R>> {
+        let max_frame_size = reader.max_frame_size();
+        #[cfg(feature = "compression")]
+        let compression_threshold = reader.compression_threshold();
+        let cancellation = reader.cancellation_token().cloned();
+        let mut decrypting = FrameReader::new(CipherAttachedReader::new(reader.get_mut(), cipher));
+        decrypting.set_cancellation_token(cancellation);
+        #[cfg(feature = "compression")]
+        decrypting.set_compression_threshold(compression_threshold);
+        decrypting.with_max_frame_size(max_frame_size)
+    }
+
+    /// Reads the body of the next frame, see `FrameReader::read_frame`.
+    pub async fn read_frame(&mut self) -> crate::prelude::Result<Option<Vec<u8>>> {
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &mut self.read_cipher {
+            return Self::decrypting(&mut self.reader, cipher)
+                .read_frame()
+                .await;
+        }
+        self.reader.read_frame().await
+    }
+
+    /// Reads the next frame and decodes it as `P`, see `FrameReader::decode_frame`. Returns `None`
+    /// if the connection ends cleanly between frames.
+    pub async fn read_packet<C: Send + Sync, P: PacketComponent<C>>(
+        &mut self,
+        context: &mut C,
+    ) -> crate::prelude::Result<Option<P::ComponentType>> {
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &mut self.read_cipher {
+            return Self::decrypting(&mut self.reader, cipher)
+                .decode_frame::<C, P>(context)
+                .await;
+        }
+        self.reader.decode_frame::<C, P>(context).await
+    }
+
+    /// Like `read_packet`, but labels any error with the context's `ContextLabel`, so failures can
+    /// be attributed to the connection they were read from.
+    pub async fn read_labelled_packet<C, P>(
+        &mut self,
+        context: &mut C,
+    ) -> crate::prelude::Result<Option<P::ComponentType>>
+    where
+        C: Send + Sync + ContextLabel,
+        P: PacketComponent<C>,
+    {
+        let read = self.read_packet::<C, P>(context).await;
+        read.map_err(|err| err.with_label(context))
+    }
+
+    /// Writes the body as a single frame. The frame is written in full but not flushed.
+    pub async fn write_frame(&mut self, body: &[u8]) -> crate::prelude::Result<()> {
+        self.frames.write_frame(body).await?;
+        self.send_buffered("raw", None).await
+    }
+
+    /// Encodes the packet as a single frame and writes it in full, without flushing.
+    pub async fn write_packet<C: Send + Sync, P: PacketComponent<C>>(
+        &mut self,
+        context: &mut C,
+        packet: &P::ComponentType,
+    ) -> crate::prelude::Result<()> {
+        self.frames.encode_frame::<C, P>(context, packet).await?;
+        self.send_buffered(std::any::type_name::<P>(), P::variant_name(packet))
+            .await
+    }
+
+    /// Encrypts the frame just buffered behind any ready frames, then sends them all. The packet
+    /// type and variant name the frame in the frame dump.
+    async fn send_buffered(
+        &mut self,
+        _packet: &str,
+        _variant: Option<&str>,
+    ) -> crate::prelude::Result<()> {
+        let frame = &mut self.frames.get_mut()[self.ready..];
+        #[cfg(feature = "tracing")]
+        if let Some(frame_dump) = &mut self.frame_dump {
+            frame_dump.dump(_packet, _variant, frame);
+        }
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &mut self.write_cipher {
+            cipher.encrypt(frame);
+        }
+        self.ready = self.frames.get_ref().len();
+        self.send_ready().await
+    }
+
+    /// Sends the ready frames, recording progress as it goes so a cancelled send resumes where it
+    /// stopped rather than resending or re-encrypting anything. The frames are dropped if sending
+    /// them fails.
+    async fn send_ready(&mut self) -> crate::prelude::Result<()> {
+        while self.sent < self.ready {
+            let written = match self
+                .writer
+                .write(&self.frames.get_ref()[self.sent..self.ready])
+                .await
+            {
+                Ok(0) => Err(std::io::Error::from(std::io::ErrorKind::WriteZero)),
+                written => written,
+            };
+            match written {
+                Ok(written) => self.sent += written,
+                Err(err) => {
+                    self.clear_ready();
+                    return Err(err.into());
+                }
+            }
+        }
+        self.clear_ready();
+        Ok(())
+    }
+
+    fn clear_ready(&mut self) {
+        self.frames.get_mut().clear();
+        self.ready = 0;
+        self.sent = 0;
+    }
+
+    pub async fn flush(&mut self) -> crate::prelude::Result<()> {
+        self.send_ready().await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> (&R, &W) {
+        (self.reader.get_ref(), &self.writer)
+    }
+
+    /// Splits the connection back into its reader and writer. Any cipher state is lost, so this
+    /// should only be done before encryption is enabled or once the connection is finished.
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader.into_inner(), self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DraxConnection;
+    use crate::transport::packet::primitive::VarInt;
+
+    #[tokio::test]
+    async fn test_connection() -> crate::prelude::Result<()> {
+        let (client_read, server_write) = tokio::io::duplex(8192);
+        let (server_read, client_write) = tokio::io::duplex(8192);
+        let mut client = DraxConnection::new(client_read, client_write);
+        let mut server = DraxConnection::new(server_read, server_write);
+
+        client.write_packet::<_, VarInt>(&mut (), &300).await?;
+        client.flush().await?;
+        assert_eq!(server.read_packet::<_, VarInt>(&mut ()).await?, Some(300));
+
+        let large = "a".repeat(512);
+        #[cfg(feature = "encryption")]
+        {
+            client.enable_encryption(&[3; 16])?;
+            server.enable_encryption(&[3; 16])?;
+        }
+        #[cfg(feature = "compression")]
+        {
+            client.set_compression_threshold(Some(64));
+            server.set_compression_threshold(Some(64));
+        }
+        for _ in 0..2 {
+            client.write_packet::<_, String>(&mut (), &large).await?;
+            client.write_frame(&[1, 2, 3]).await?;
+        }
+        client.flush().await?;
+        for _ in 0..2 {
+            assert_eq!(
+                server.read_packet::<_, String>(&mut ()).await?,
+                Some(large.clone())
+            );
+            assert_eq!(server.read_frame().await?, Some(vec![1, 2, 3]));
+        }
+
+        server.write_packet::<_, VarInt>(&mut (), &7).await?;
+        drop(server);
+        assert_eq!(client.read_packet::<_, VarInt>(&mut ()).await?, Some(7));
+        assert_eq!(client.read_frame().await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_write() -> crate::prelude::Result<()> {
+        use std::future::Future;
+        use std::task::{Context, Waker};
+
+        let (client_read, server_write) = tokio::io::duplex(64);
+        let (server_read, client_write) = tokio::io::duplex(64);
+        let mut client = DraxConnection::new(client_read, client_write);
+        let mut server = DraxConnection::new(server_read, server_write);
+        #[cfg(feature = "encryption")]
+        {
+            client.enable_encryption(&[5; 16])?;
+            server.enable_encryption(&[5; 16])?;
+        }
+
+        // the pipe only holds part of the frame, so the write is dropped part way through
+        let large = vec![9; 256];
+        {
+            let write = std::pin::pin!(client.write_frame(&large));
+            assert!(write
+                .poll(&mut Context::from_waker(Waker::noop()))
+                .is_pending());
+        }
+        let (written, read) = tokio::join!(
+            async {
+                client.write_frame(&[1, 2, 3]).await?;
+                client.flush().await
+            },
+            server.read_frame(),
+        );
+        written?;
+        assert_eq!(read?, Some(large));
+        assert_eq!(server.read_frame().await?, Some(vec![1, 2, 3]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_labelled_read() -> crate::prelude::Result<()> {
+        use crate::prelude::ContextLabel;
+        use crate::transport::packet::string::LimitedString;
+
+        struct Peer;
+
+        impl ContextLabel for Peer {
+            fn label(&self) -> Option<&str> {
+                Some("127.0.0.1:25565")
+            }
+        }
+
+        let (client_read, server_write) = tokio::io::duplex(64);
+        let (server_read, client_write) = tokio::io::duplex(64);
+        let mut client = DraxConnection::new(client_read, client_write);
+        let mut server = DraxConnection::new(server_read, server_write);
+        client
+            .write_packet::<_, String>(&mut (), &"drax drax".to_string())
+            .await?;
+        client.flush().await?;
+        let err = server
+            .read_labelled_packet::<_, LimitedString<4>>(&mut Peer)
+            .await
+            .expect_err("An over-long string should fail to decode");
+        assert!(err.context.to_string().starts_with("`[127.0.0.1:25565] "));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_read() -> crate::prelude::Result<()> {
+        use crate::prelude::ErrorType;
+        use crate::transport::cancel::CancellationToken;
+
+        let (client_read, server_write) = tokio::io::duplex(64);
+        let (server_read, client_write) = tokio::io::duplex(64);
+        let mut client = DraxConnection::new(client_read, client_write);
+        let token = CancellationToken::new();
+        let mut server =
+            DraxConnection::new(server_read, server_write).with_cancellation_token(token.clone());
+        #[cfg(feature = "encryption")]
+        {
+            client.enable_encryption(&[7; 16])?;
+            server.enable_encryption(&[7; 16])?;
+        }
+
+        client.write_packet::<_, VarInt>(&mut (), &25).await?;
+        client.flush().await?;
+        assert_eq!(server.read_packet::<_, VarInt>(&mut ()).await?, Some(25));
+
+        // nothing more is sent, so the read only ends through the token
+        let (read, ()) = tokio::join!(server.read_frame(), async { token.cancel() });
+        assert!(matches!(
+            read.map_err(|err| err.error_type),
+            Err(ErrorType::Cancelled)
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_frame_dump_limits() {
+        let mut dump = super::FrameDump::new(4, 2);
+        assert!(!dump.allow(5));
+        assert!(dump.allow(4));
+        assert!(dump.allow(1));
+        assert!(!dump.allow(1));
+        assert_eq!(dump.suppressed, 1);
+        assert_eq!(super::hex(&[0x00, 0xAB, 0x10]), "00 ab 10");
+    }
+}
@@ -0,0 +1,135 @@
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+pin_project! {
+    /// Wraps a reader and counts the bytes read through it.
+    pub struct CountingReader<R> {
+        #[pin]
+        inner: R,
+        transferred: u64,
+    }
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            transferred: 0,
+        }
+    }
+
+    /// The number of bytes read through this wrapper so far.
+    pub fn bytes_transferred(&self) -> u64 {
+        self.transferred
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let me = self.project();
+        let before = buf.filled().len();
+        ready!(me.inner.poll_read(cx, buf))?;
+        *me.transferred += (buf.filled().len() - before) as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+pin_project! {
+    /// Wraps a writer and counts the bytes written through it.
+    pub struct CountingWriter<W> {
+        #[pin]
+        inner: W,
+        transferred: u64,
+    }
+}
+
+impl<W> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            transferred: 0,
+        }
+    }
+
+    /// The number of bytes written through this wrapper so far.
+    pub fn bytes_transferred(&self) -> u64 {
+        self.transferred
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for CountingWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let me = self.project();
+        let written = ready!(me.inner.poll_write(cx, buf))?;
+        *me.transferred += written as u64;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{CountingReader, CountingWriter};
+    use crate::prelude::{DraxReadExt, DraxWriteExt};
+
+    #[tokio::test]
+    async fn test_counting() -> crate::prelude::Result<()> {
+        let mut writer = CountingWriter::new(vec![]);
+        writer.write_var_int(300).await?;
+        writer.write_all(&[1, 2, 3]).await?;
+        assert_eq!(writer.bytes_transferred(), 5);
+
+        let mut reader = CountingReader::new(&writer.get_ref()[..]);
+        assert_eq!(reader.read_var_int().await?, 300);
+        assert_eq!(reader.bytes_transferred(), 2);
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).await?;
+        assert_eq!(rest, vec![1, 2, 3]);
+        assert_eq!(reader.bytes_transferred(), 5);
+        Ok(())
+    }
+}
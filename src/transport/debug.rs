@@ -0,0 +1,252 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::prelude::PacketComponent;
+
+/// The number of traces being decoded on any thread, so fields decoded while nothing is traced
+/// skip the thread local entirely.
+static ACTIVE_TRACES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static ACTIVE: RefCell<Option<TraceState>> = const { RefCell::new(None) };
+}
+
+/// A field of a macro defined component decoded during a trace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceEntry {
+    /// The dot separated names of the field and the fields enclosing it, such as `"header.id"`.
+    pub path: String,
+    /// The bytes of the traced reader the field was decoded from.
+    pub range: Range<u64>,
+    /// The `Debug` output of the decoded value, or `None` for fields whose own fields were traced,
+    /// which are described by their nested entries instead of formatting the subtree again.
+    pub value: Option<String>,
+}
+
+/// The fields decoded during a trace, in the order they finished decoding, so nested fields come
+/// before the fields enclosing them.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Trace {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl Display for Trace {
+    /// One field per line, indented by depth, in the order the fields started decoding.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut entries: Vec<&TraceEntry> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| (entry.range.start, std::cmp::Reverse(entry.range.end)));
+        for entry in entries {
+            let depth = entry.path.matches('.').count();
+            write!(
+                f,
+                "{:indent$}{} [{}..{}]",
+                "",
+                entry.path,
+                entry.range.start,
+                entry.range.end,
+                indent = depth * 2
+            )?;
+            match &entry.value {
+                Some(value) => writeln!(f, " = {value}")?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct TraceState {
+    position: u64,
+    /// The name, start position and number of entries recorded before each open field.
+    open: Vec<(&'static str, u64, usize)>,
+    entries: Vec<TraceEntry>,
+}
+
+/// Marks the start of a field decoded by the component macros.
+#[doc(hidden)]
+#[inline]
+pub fn enter_field(name: &'static str) {
+    if ACTIVE_TRACES.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    ACTIVE.with(|active| {
+        if let Some(state) = active.borrow_mut().as_mut() {
+            let position = state.position;
+            let recorded = state.entries.len();
+            state.open.push((name, position, recorded));
+        }
+    });
+}
+
+/// Marks the end of the field most recently entered, recording its value unless any of its own
+/// fields were recorded.
+#[doc(hidden)]
+#[inline]
+pub fn exit_field<T: Debug>(value: &T) {
+    if ACTIVE_TRACES.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    ACTIVE.with(|active| {
+        if let Some(state) = active.borrow_mut().as_mut() {
+            let mut path = String::new();
+            for (name, _, _) in &state.open {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(name);
+            }
+            if let Some((_, start, recorded)) = state.open.pop() {
+                let value = (state.entries.len() == recorded).then(|| format!("{value:?}"));
+                state.entries.push(TraceEntry {
+                    path,
+                    range: start..state.position,
+                    value,
+                });
+            }
+        }
+    });
+}
+
+/// Counts the bytes read through it into the trace active on the current thread, if any.
+pub struct TracingReader<R> {
+    inner: R,
+}
+
+impl<R> TracingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TracingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = (buf.filled().len() - filled) as u64;
+        ACTIVE.with(|active| {
+            if let Some(state) = active.borrow_mut().as_mut() {
+                state.position += read;
+            }
+        });
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Installs its trace on the thread polling it for the duration of each poll, so the trace
+/// follows the decode across threads and nested traces don't see each other's fields.
+struct Traced<F> {
+    future: F,
+    state: Option<TraceState>,
+}
+
+impl<F: Future + Unpin> Future for Traced<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let state = self.state.take();
+        let outer = ACTIVE.with(|active| active.replace(state));
+        let poll = Pin::new(&mut self.future).poll(cx);
+        self.state = ACTIVE.with(|active| active.replace(outer));
+        poll
+    }
+}
+
+/// Decodes a component from the reader while recording the byte range and value of every field of
+/// a macro defined component decoded along the way. Returns the trace even if decoding fails, in
+/// which case it holds the fields decoded before the failure.
+///
+/// # Parameters
+/// * `context` - The context to decode the component with.
+/// * `read` - The reader to decode the component from; byte ranges start from its position.
+pub async fn trace_decode<C, P, R>(
+    context: &mut C,
+    read: &mut R,
+) -> (crate::prelude::Result<P::ComponentType>, Trace)
+where
+    C: Send + Sync,
+    P: PacketComponent<C>,
+    R: AsyncRead + Unpin + Send + Sync + ?Sized,
+{
+    let mut reader = TracingReader::new(read);
+    ACTIVE_TRACES.fetch_add(1, Ordering::Relaxed);
+    let mut traced = Traced {
+        future: P::decode(context, &mut reader),
+        state: Some(TraceState::default()),
+    };
+    let result = (&mut traced).await;
+    ACTIVE_TRACES.fetch_sub(1, Ordering::Relaxed);
+    let entries = traced.state.take().map(|state| state.entries);
+    (
+        result,
+        Trace {
+            entries: entries.unwrap_or_default(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::trace_decode;
+    use crate::transport::packet::primitive::VarInt;
+
+    crate::struct_packet_components! {
+        Header {
+            id: VarInt,
+            flag: bool
+        }
+        Traced {
+            header: Header,
+            name: String
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trace_decode() {
+        let bytes = vec![0xAC, 0x02, 1, 4, b'd', b'r', b'a', b'x'];
+        let (result, trace) =
+            trace_decode::<_, Traced, _>(&mut (), &mut Cursor::new(bytes.clone())).await;
+        assert_eq!(result.unwrap().name, "drax");
+        let summary: Vec<_> = trace
+            .entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.range.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("header.id", 0..2),
+                ("header.flag", 2..3),
+                ("header", 0..3),
+                ("name", 3..8)
+            ]
+        );
+        assert_eq!(trace.entries[0].value.as_deref(), Some("300"));
+        assert_eq!(trace.entries[2].value, None);
+        assert!(trace
+            .to_string()
+            .starts_with("header [0..3]\n  header.id [0..2] = 300\n"));
+
+        let (result, trace) =
+            trace_decode::<_, Traced, _>(&mut (), &mut Cursor::new(bytes[..5].to_vec())).await;
+        assert!(result.is_err());
+        assert_eq!(trace.entries.len(), 3);
+    }
+}
@@ -4,11 +4,27 @@ use std::task::{ready, Context, Poll};
 pub use aes::cipher::AsyncStreamCipher;
 pub use aes::cipher::NewCipher;
 use pin_project_lite::pin_project;
-use tokio::io::{AsyncRead, ReadBuf};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// The RSA exchange of the shared secret which keys the ciphers, as done during login.
+#[cfg(feature = "handshake")]
+pub mod handshake;
 
 /// Encryption type alias for `cfb8::Encryptor<Aes128>`
 pub type Cipher = cfb8::Cfb8<aes::Aes128>;
 
+/// Creates a cipher from a shared secret used as both the key and the IV, as negotiated by the
+/// protocol. Fails rather than panicking when the secret is not 16 bytes long.
+pub fn cipher_from_secret(secret: &[u8]) -> crate::prelude::Result<Cipher> {
+    match Cipher::new_from_slices(secret, secret) {
+        Ok(cipher) if secret.len() == 16 => Ok(cipher),
+        _ => crate::throw_explain!(format!(
+            "Invalid shared secret of {} bytes, expected 16 bytes",
+            secret.len()
+        )),
+    }
+}
+
 pin_project! {
     pub struct CipherAttachedReader<'a, R> {
         pub(crate) inner: &'a mut R,
@@ -16,6 +32,14 @@ pin_project! {
     }
 }
 
+impl<'a, R> CipherAttachedReader<'a, R> {
+    /// Attaches an already constructed cipher to the reader, so key material never has to pass
+    /// through Drax.
+    pub fn new(inner: &'a mut R, cipher: &'a mut Cipher) -> Self {
+        Self { inner, cipher }
+    }
+}
+
 impl<'a, R: AsyncRead + Unpin> AsyncRead for CipherAttachedReader<'a, R> {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -23,8 +47,416 @@ impl<'a, R: AsyncRead + Unpin> AsyncRead for CipherAttachedReader<'a, R> {
         buf: &mut ReadBuf<'_>,
     ) -> Poll<std::io::Result<()>> {
         let me = self.project();
+        let filled = buf.filled().len();
         ready!(Pin::new(me.inner).poll_read(cx, buf))?;
-        me.cipher.decrypt(buf.filled_mut());
+        me.cipher.decrypt(&mut buf.filled_mut()[filled..]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Bytes encrypted by a writer and accepted from the caller but not yet taken by the inner writer,
+/// so a write which is cancelled or only partially accepted never encrypts the same bytes twice.
+#[derive(Default)]
+struct PendingWrite {
+    pending: Vec<u8>,
+    written: usize,
+}
+
+impl PendingWrite {
+    fn poll_send<W: AsyncWrite + Unpin + ?Sized>(
+        &mut self,
+        inner: &mut W,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        while self.written < self.pending.len() {
+            let written =
+                ready!(Pin::new(&mut *inner).poll_write(cx, &self.pending[self.written..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+            }
+            self.written += written;
+        }
         Poll::Ready(Ok(()))
     }
+
+    /// Sends what is pending, then encrypts the buffer as the next pending bytes and starts
+    /// sending them. Returns the number of bytes accepted, which is all of them once encrypted.
+    fn poll_write<W: AsyncWrite + Unpin + ?Sized>(
+        &mut self,
+        inner: &mut W,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        encrypt: impl FnOnce(&mut [u8]),
+    ) -> Poll<std::io::Result<usize>> {
+        ready!(self.poll_send(inner, cx))?;
+        self.pending.clear();
+        self.pending.extend_from_slice(buf);
+        self.written = 0;
+        encrypt(&mut self.pending);
+        // the bytes are accepted once encrypted, the rest is sent by the next write or flush
+        if let Poll::Ready(Err(err)) = self.poll_send(inner, cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush<W: AsyncWrite + Unpin + ?Sized>(
+        &mut self,
+        inner: &mut W,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        ready!(self.poll_send(inner, cx))?;
+        Pin::new(inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown<W: AsyncWrite + Unpin + ?Sized>(
+        &mut self,
+        inner: &mut W,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        ready!(self.poll_send(inner, cx))?;
+        Pin::new(inner).poll_shutdown(cx)
+    }
+}
+
+/// Encrypts everything written through it with an attached cipher, the writing counterpart of
+/// `CipherAttachedReader`. Writes are safe to cancel, see `RekeyingCipherWriter`.
+pub struct CipherAttachedWriter<'a, W> {
+    inner: &'a mut W,
+    cipher: &'a mut Cipher,
+    pending: PendingWrite,
+}
+
+impl<'a, W> CipherAttachedWriter<'a, W> {
+    pub fn new(inner: &'a mut W, cipher: &'a mut Cipher) -> Self {
+        Self {
+            inner,
+            cipher,
+            pending: PendingWrite::default(),
+        }
+    }
+}
+
+impl<'a, W: AsyncWrite + Unpin> AsyncWrite for CipherAttachedWriter<'a, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let me = self.get_mut();
+        let cipher = &mut *me.cipher;
+        me.pending
+            .poll_write(me.inner, cx, buf, |data| cipher.encrypt(data))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let me = self.get_mut();
+        me.pending.poll_flush(me.inner, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let me = self.get_mut();
+        me.pending.poll_shutdown(me.inner, cx)
+    }
+}
+
+/// Derives the cipher for the given key generation. The first rekey requests generation `1`.
+pub type KeyDerivation = Box<dyn FnMut(u64) -> Cipher + Send + Sync>;
+
+/// When a `RekeyingCipher` replaces its key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RekeyAfter {
+    /// After the given number of bytes have passed through the cipher.
+    Bytes(u64),
+    /// After the given number of frames have been ended with `RekeyingCipher::end_frame`. The
+    /// reading side must not read past the end of a frame before ending it, since bytes read ahead
+    /// would be decrypted with the old key.
+    Frames(u64),
+}
+
+/// A cipher which replaces its key after a fixed number of bytes or frames have passed through it.
+/// Both sides of a connection must use the same threshold and derivation so that they rekey at the
+/// same point in the stream.
+pub struct RekeyingCipher {
+    cipher: Cipher,
+    after: RekeyAfter,
+    processed: u64,
+    generation: u64,
+    derive: KeyDerivation,
+}
+
+impl RekeyingCipher {
+    /// Creates a new rekeying cipher, failing if the threshold is zero.
+    ///
+    /// # Parameters
+    /// * `cipher` - The cipher to start with, generation `0`.
+    /// * `after` - The number of bytes or frames to process with each key.
+    /// * `derive` - The key derivation called with each new generation.
+    pub fn new(
+        cipher: Cipher,
+        after: RekeyAfter,
+        derive: KeyDerivation,
+    ) -> crate::prelude::Result<Self> {
+        if let RekeyAfter::Bytes(0) | RekeyAfter::Frames(0) = after {
+            crate::throw_explain!(format!("Rekey threshold {after:?} must be non-zero"))
+        }
+        Ok(Self {
+            cipher,
+            after,
+            processed: 0,
+            generation: 0,
+            derive,
+        })
+    }
+
+    /// The generation of the key currently in use.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Immediately replaces the key with the next generation, for rekeys signalled by the
+    /// protocol rather than the threshold.
+    pub fn rekey(&mut self) {
+        self.generation += 1;
+        self.processed = 0;
+        self.cipher = (self.derive)(self.generation);
+    }
+
+    /// Marks the end of a frame, rekeying once the frame threshold is reached so the next frame
+    /// starts with the new key. Does nothing for byte thresholds.
+    pub fn end_frame(&mut self) {
+        if let RekeyAfter::Frames(frames) = self.after {
+            self.processed += 1;
+            if self.processed == frames {
+                self.rekey();
+            }
+        }
+    }
+
+    fn apply(&mut self, mut data: &mut [u8], decrypt: bool) {
+        let RekeyAfter::Bytes(rekey_after) = self.after else {
+            if decrypt {
+                self.cipher.decrypt(data);
+            } else {
+                self.cipher.encrypt(data);
+            }
+            return;
+        };
+        while !data.is_empty() {
+            if self.processed == rekey_after {
+                self.rekey();
+            }
+            let available = rekey_after - self.processed;
+            let split = data
+                .len()
+                .min(usize::try_from(available).unwrap_or(usize::MAX));
+            let (current, rest) = data.split_at_mut(split);
+            if decrypt {
+                self.cipher.decrypt(current);
+            } else {
+                self.cipher.encrypt(current);
+            }
+            self.processed += split as u64;
+            data = rest;
+        }
+    }
+
+    /// Encrypts the data in place, rekeying whenever the byte threshold is crossed.
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        self.apply(data, false)
+    }
+
+    /// Decrypts the data in place, rekeying whenever the byte threshold is crossed.
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        self.apply(data, true)
+    }
+}
+
+pin_project! {
+    pub struct RekeyingCipherReader<'a, R> {
+        pub(crate) inner: &'a mut R,
+        pub(crate) cipher: &'a mut RekeyingCipher,
+    }
+}
+
+impl<'a, R> RekeyingCipherReader<'a, R> {
+    pub fn new(inner: &'a mut R, cipher: &'a mut RekeyingCipher) -> Self {
+        Self { inner, cipher }
+    }
+
+    /// Marks the end of a frame read through the reader, see `RekeyingCipher::end_frame`.
+    pub fn end_frame(&mut self) {
+        self.cipher.end_frame()
+    }
+}
+
+impl<'a, R: AsyncRead + Unpin> AsyncRead for RekeyingCipherReader<'a, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let me = self.project();
+        let filled = buf.filled().len();
+        ready!(Pin::new(me.inner).poll_read(cx, buf))?;
+        me.cipher.decrypt(&mut buf.filled_mut()[filled..]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Encrypts everything written through it with a `RekeyingCipher`. Written bytes are encrypted as
+/// soon as they are accepted and held until the inner writer takes them, so a write which is
+/// cancelled or only partially accepted never encrypts the same bytes twice.
+pub struct RekeyingCipherWriter<'a, W> {
+    inner: &'a mut W,
+    cipher: &'a mut RekeyingCipher,
+    pending: PendingWrite,
+}
+
+impl<'a, W> RekeyingCipherWriter<'a, W> {
+    pub fn new(inner: &'a mut W, cipher: &'a mut RekeyingCipher) -> Self {
+        Self {
+            inner,
+            cipher,
+            pending: PendingWrite::default(),
+        }
+    }
+
+    /// Marks the end of a frame written through the writer, see `RekeyingCipher::end_frame`.
+    /// Written bytes are encrypted as soon as they're accepted, so this can be called before they
+    /// are flushed.
+    pub fn end_frame(&mut self) {
+        self.cipher.end_frame()
+    }
+}
+
+impl<'a, W: AsyncWrite + Unpin> AsyncWrite for RekeyingCipherWriter<'a, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let me = self.get_mut();
+        let cipher = &mut *me.cipher;
+        me.pending
+            .poll_write(me.inner, cx, buf, |data| cipher.encrypt(data))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let me = self.get_mut();
+        me.pending.poll_flush(me.inner, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let me = self.get_mut();
+        me.pending.poll_shutdown(me.inner, cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{
+        cipher_from_secret, AsyncStreamCipher, Cipher, CipherAttachedReader, NewCipher, RekeyAfter,
+        RekeyingCipher, RekeyingCipherReader, RekeyingCipherWriter,
+    };
+
+    fn derive(generation: u64) -> Cipher {
+        let key = [generation as u8 + 1; 16];
+        Cipher::new_from_slices(&key, &key).unwrap()
+    }
+
+    fn rekeying(after: RekeyAfter) -> RekeyingCipher {
+        RekeyingCipher::new(derive(0), after, Box::new(derive)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rekeying_round_trip() -> crate::prelude::Result<()> {
+        let plain: Vec<u8> = (0..100).collect();
+        let mut encrypted = plain.clone();
+        let mut encryptor = rekeying(RekeyAfter::Bytes(16));
+        // encrypt in uneven chunks to cross the threshold mid buffer
+        for chunk in encrypted.chunks_mut(7) {
+            encryptor.encrypt(chunk);
+        }
+        assert_eq!(encryptor.generation(), 6);
+
+        let mut single_key = plain.clone();
+        derive(0).encrypt(&mut single_key);
+        assert_eq!(encrypted[..16], single_key[..16]);
+        assert_ne!(encrypted[16..], single_key[16..]);
+
+        let mut decryptor = rekeying(RekeyAfter::Bytes(16));
+        let mut cursor = Cursor::new(encrypted);
+        let mut reader = RekeyingCipherReader::new(&mut cursor, &mut decryptor);
+        let mut decrypted = vec![];
+        reader.read_to_end(&mut decrypted).await?;
+        assert_eq!(decrypted, plain);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cipher_from_secret() -> crate::prelude::Result<()> {
+        assert!(cipher_from_secret(&[1; 15]).is_err());
+        assert!(cipher_from_secret(&[1; 32]).is_err());
+
+        let mut encrypted = vec![1, 2, 3, 4];
+        cipher_from_secret(&[7; 16])?.encrypt(&mut encrypted);
+        let mut cipher = Cipher::new_from_slices(&[7; 16], &[7; 16]).unwrap();
+        let mut cursor = Cursor::new(encrypted);
+        let mut reader = CipherAttachedReader::new(&mut cursor, &mut cipher);
+        let mut decrypted = vec![];
+        reader.read_to_end(&mut decrypted).await?;
+        assert_eq!(decrypted, vec![1, 2, 3, 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_manual_rekey() {
+        let mut encryptor = rekeying(RekeyAfter::Bytes(1024));
+        let mut decryptor = rekeying(RekeyAfter::Bytes(1024));
+        let mut data = vec![1, 2, 3];
+        encryptor.encrypt(&mut data);
+        encryptor.rekey();
+        decryptor.decrypt(&mut data);
+        decryptor.rekey();
+        assert_eq!(data, vec![1, 2, 3]);
+
+        encryptor.encrypt(&mut data);
+        decryptor.decrypt(&mut data);
+        assert_eq!(data, vec![1, 2, 3]);
+        assert_eq!(encryptor.generation(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rekey_after_frames() -> crate::prelude::Result<()> {
+        assert!(RekeyingCipher::new(derive(0), RekeyAfter::Frames(0), Box::new(derive)).is_err());
+        assert!(RekeyingCipher::new(derive(0), RekeyAfter::Bytes(0), Box::new(derive)).is_err());
+
+        let frames: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 10 + i as usize]).collect();
+        let mut encryptor = rekeying(RekeyAfter::Frames(2));
+        let mut encrypted = vec![];
+        let mut writer = RekeyingCipherWriter::new(&mut encrypted, &mut encryptor);
+        for frame in &frames {
+            writer.write_all(frame).await?;
+            writer.end_frame();
+        }
+        writer.flush().await?;
+        assert_eq!(encryptor.generation(), 2);
+
+        let mut decryptor = rekeying(RekeyAfter::Frames(2));
+        let mut cursor = Cursor::new(encrypted);
+        let mut reader = RekeyingCipherReader::new(&mut cursor, &mut decryptor);
+        for frame in &frames {
+            let mut decrypted = vec![0; frame.len()];
+            reader.read_exact(&mut decrypted).await?;
+            assert_eq!(&decrypted, frame);
+            reader.end_frame();
+        }
+        assert_eq!(decryptor.generation(), 2);
+        Ok(())
+    }
 }
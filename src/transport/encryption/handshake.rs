@@ -0,0 +1,211 @@
+use rsa::pkcs8::{DecodePublicKey, EncodePublicKey};
+use rsa::rand_core::{OsRng, RngCore};
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
+
+use crate::transport::encryption::{
+    cipher_from_secret, Cipher, CipherAttachedReader, CipherAttachedWriter,
+};
+use crate::transport::secret::SecretBytes;
+use crate::{err_explain, throw_explain};
+
+/// The length of the shared secret, which keys AES-128.
+pub const SHARED_SECRET_LEN: usize = 16;
+/// The length of the verify token the server sends alongside its public key.
+pub const VERIFY_TOKEN_LEN: usize = 4;
+/// The size of the key pair generated by the vanilla server.
+pub const DEFAULT_KEY_BITS: usize = 1024;
+
+/// Generates a random shared secret, on the client.
+pub fn generate_shared_secret() -> SecretBytes {
+    let mut secret = vec![0; SHARED_SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    SecretBytes::new(secret)
+}
+
+/// Generates a random verify token, on the server.
+pub fn generate_verify_token() -> [u8; VERIFY_TOKEN_LEN] {
+    let mut token = [0; VERIFY_TOKEN_LEN];
+    OsRng.fill_bytes(&mut token);
+    token
+}
+
+/// Encrypts the shared secret or verify token with the server's public key, on the client.
+///
+/// # Parameters
+/// * `public_key_der` - The server's public key, a DER encoded `SubjectPublicKeyInfo` as sent in
+///   the encryption request.
+/// * `data` - The bytes to encrypt.
+pub fn encrypt_with_public_key(
+    public_key_der: &[u8],
+    data: &[u8],
+) -> crate::prelude::Result<Vec<u8>> {
+    let key = RsaPublicKey::from_public_key_der(public_key_der)
+        .map_err(|err| err_explain!(format!("Invalid server public key: {err}")))?;
+    key.encrypt(&mut OsRng, Pkcs1v15Encrypt, data)
+        .map_err(|err| err_explain!(format!("Failed to encrypt with server public key: {err}")))
+}
+
+/// The key pair a server decrypts the client's shared secret with.
+pub struct ServerKey {
+    private_key: RsaPrivateKey,
+    public_key_der: Vec<u8>,
+}
+
+impl ServerKey {
+    /// Generates a key pair of the given size, usually `DEFAULT_KEY_BITS`.
+    pub fn generate(bits: usize) -> crate::prelude::Result<Self> {
+        let private_key = RsaPrivateKey::new(&mut OsRng, bits)
+            .map_err(|err| err_explain!(format!("Failed to generate server key: {err}")))?;
+        Self::from_private_key(private_key)
+    }
+
+    pub fn from_private_key(private_key: RsaPrivateKey) -> crate::prelude::Result<Self> {
+        let public_key_der = private_key
+            .to_public_key()
+            .to_public_key_der()
+            .map_err(|err| err_explain!(format!("Failed to encode server public key: {err}")))?
+            .into_vec();
+        Ok(Self {
+            private_key,
+            public_key_der,
+        })
+    }
+
+    /// The public key as sent in the encryption request, a DER encoded `SubjectPublicKeyInfo`.
+    pub fn public_key_der(&self) -> &[u8] {
+        &self.public_key_der
+    }
+
+    /// Decrypts bytes the client encrypted with the public key. The decryption is blinded, but
+    /// PKCS#1 v1.5 decryption in `rsa` 0.9 still leaks timing through its padding checks, see
+    /// RUSTSEC-2023-0071 (the Marvin attack), so a server should not reveal why a response failed
+    /// or how long rejecting it took.
+    pub fn decrypt(&self, data: &[u8]) -> crate::prelude::Result<SecretBytes> {
+        self.private_key
+            .decrypt_blinded(&mut OsRng, Pkcs1v15Encrypt, data)
+            .map(SecretBytes::new)
+            .map_err(|_| err_explain!("Failed to decrypt with server private key"))
+    }
+
+    /// Decrypts the shared secret and verify token from the client's encryption response, failing
+    /// if the token doesn't match the one sent in the request.
+    pub fn decrypt_response(
+        &self,
+        encrypted_secret: &[u8],
+        encrypted_token: &[u8],
+        verify_token: &[u8],
+    ) -> crate::prelude::Result<SecretBytes> {
+        let token = self.decrypt(encrypted_token)?;
+        #[cfg(feature = "subtle")]
+        let matches = crate::transport::secret::ct_eq(token.expose(), verify_token);
+        #[cfg(not(feature = "subtle"))]
+        let matches = token.expose() == verify_token;
+        if !matches {
+            throw_explain!("Verify token did not match")
+        }
+        let secret = self.decrypt(encrypted_secret)?;
+        if secret.len() != SHARED_SECRET_LEN {
+            throw_explain!(format!(
+                "Invalid shared secret of {} bytes, expected {SHARED_SECRET_LEN} bytes",
+                secret.len()
+            ))
+        }
+        Ok(secret)
+    }
+}
+
+/// The ciphers a connection encrypts what it writes and decrypts what it reads with, both keyed
+/// by the shared secret.
+pub struct CipherPair {
+    pub encrypt: Cipher,
+    pub decrypt: Cipher,
+}
+
+impl CipherPair {
+    pub fn from_secret(secret: &SecretBytes) -> crate::prelude::Result<Self> {
+        Ok(Self {
+            encrypt: cipher_from_secret(secret.expose())?,
+            decrypt: cipher_from_secret(secret.expose())?,
+        })
+    }
+
+    /// Attaches the ciphers to both halves of a connection, so everything written through the
+    /// returned writer is encrypted and everything read through the returned reader decrypted.
+    pub fn attach<'a, R, W>(
+        &'a mut self,
+        read: &'a mut R,
+        write: &'a mut W,
+    ) -> (CipherAttachedReader<'a, R>, CipherAttachedWriter<'a, W>) {
+        (
+            CipherAttachedReader::new(read, &mut self.decrypt),
+            CipherAttachedWriter::new(write, &mut self.encrypt),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encrypt_with_public_key, generate_shared_secret, generate_verify_token, CipherPair,
+        ServerKey,
+    };
+    use crate::transport::encryption::AsyncStreamCipher;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_handshake() -> crate::prelude::Result<()> {
+        // small so the test stays fast, vanilla uses `DEFAULT_KEY_BITS`
+        let server = ServerKey::generate(512)?;
+        let token = generate_verify_token();
+
+        let secret = generate_shared_secret();
+        let encrypted_secret = encrypt_with_public_key(server.public_key_der(), secret.expose())?;
+        let encrypted_token = encrypt_with_public_key(server.public_key_der(), &token)?;
+        assert!(encrypt_with_public_key(&[0, 1, 2], &token).is_err());
+
+        let decrypted = server.decrypt_response(&encrypted_secret, &encrypted_token, &token)?;
+        assert_eq!(decrypted.expose(), secret.expose());
+        assert!(server
+            .decrypt_response(&encrypted_secret, &encrypted_token, &[0; 4])
+            .is_err());
+        assert!(server
+            .decrypt_response(&encrypted_token, &encrypted_token, &token)
+            .is_err());
+
+        let mut client = CipherPair::from_secret(&secret)?;
+        let mut server = CipherPair::from_secret(&decrypted)?;
+        let mut data = *b"drax";
+        client.encrypt.encrypt(&mut data);
+        server.decrypt.decrypt(&mut data);
+        assert_eq!(&data, b"drax");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_attached_pair() -> crate::prelude::Result<()> {
+        let secret = generate_shared_secret();
+        let mut client = CipherPair::from_secret(&secret)?;
+        let mut server = CipherPair::from_secret(&secret)?;
+
+        let (mut client_end, mut server_end) = tokio::io::duplex(64);
+        let mut none = tokio::io::empty();
+        let (_, mut writer) = client.attach(&mut none, &mut client_end);
+        writer.write_all(b"drax").await?;
+        writer.flush().await?;
+
+        let mut raw = [0; 4];
+        server_end.read_exact(&mut raw).await?;
+        assert_ne!(&raw, b"drax");
+        server.decrypt.decrypt(&mut raw);
+        assert_eq!(&raw, b"drax");
+
+        client.encrypt.encrypt(&mut raw);
+        let mut encrypted = &raw[..];
+        let mut sink = tokio::io::sink();
+        let (mut reader, _) = server.attach(&mut encrypted, &mut sink);
+        let mut read = [0; 4];
+        reader.read_exact(&mut read).await?;
+        assert_eq!(&read, b"drax");
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::prelude::PacketComponent;
+use crate::throw_explain;
+use crate::transport::frame::{decode_whole, encode_body_to_bytes, FrameReader, FrameWriter};
+use crate::transport::packet::yield_point;
+
+/// The header of the final fragment of a message, including messages sent as a single fragment.
+pub const FRAGMENT_LAST: u8 = 0;
+/// The header of a fragment which is followed by at least one more fragment of the same message.
+pub const FRAGMENT_MORE: u8 = 1;
+
+/// The default limit on the total size of a reassembled message, 16 MiB.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+/// The default limit on the number of fragments a message may be split into.
+pub const DEFAULT_MAX_FRAGMENTS: usize = 64;
+
+/// Writes messages which may be larger than the maximum frame size by splitting them into as
+/// many frames as needed. Each frame body starts with a one byte header, `FRAGMENT_MORE` if the
+/// message continues in the next frame or `FRAGMENT_LAST` if it ends in this one.
+pub struct FragmentWriter<W> {
+    frames: FrameWriter<W>,
+}
+
+impl<W: AsyncWrite + Unpin + Send + Sync> FragmentWriter<W> {
+    /// Fragments are sized to fill frames up to the writer's maximum frame size, which must match
+    /// the maximum negotiated with the other end. Fails if the maximum frame size leaves no room
+    /// for a payload after the one byte header.
+    pub fn new(frames: FrameWriter<W>) -> crate::prelude::Result<Self> {
+        let writer = Self { frames };
+        writer.payload_size()?;
+        Ok(writer)
+    }
+
+    /// The largest fragment payload whose frame fits the maximum frame size. With compression on,
+    /// room is left for incompressible fragments growing when compressed.
+    fn payload_size(&self) -> crate::prelude::Result<usize> {
+        let max_frame_size = self.frames.max_frame_size();
+        #[cfg(feature = "compression")]
+        let max_frame_size = match self.frames.compression_threshold() {
+            Some(_) => max_frame_size.saturating_sub(
+                crate::transport::compression::max_compressed_growth(max_frame_size),
+            ),
+            None => max_frame_size,
+        };
+        match max_frame_size.checked_sub(1) {
+            Some(payload) if payload > 0 => Ok(payload),
+            _ => throw_explain!(format!(
+                "Maximum frame size {} leaves no room for a fragment payload",
+                self.frames.max_frame_size()
+            )),
+        }
+    }
+
+    pub fn get_ref(&self) -> &FrameWriter<W> {
+        &self.frames
+    }
+
+    pub fn get_mut(&mut self) -> &mut FrameWriter<W> {
+        &mut self.frames
+    }
+
+    pub fn into_inner(self) -> FrameWriter<W> {
+        self.frames
+    }
+
+    /// Writes the message as one or more fragments, returning the number of fragments written.
+    pub async fn write_message(&mut self, message: &[u8]) -> crate::prelude::Result<usize> {
+        let payload = self.payload_size()?;
+        let mut fragment = Vec::with_capacity(payload.min(message.len()) + 1);
+        let mut chunks = message.chunks(payload).peekable();
+        let mut written = 0;
+        loop {
+            let chunk = chunks.next().unwrap_or_default();
+            let header = match chunks.peek() {
+                Some(_) => FRAGMENT_MORE,
+                None => FRAGMENT_LAST,
+            };
+            fragment.clear();
+            fragment.push(header);
+            fragment.extend_from_slice(chunk);
+            self.frames.write_frame(&fragment).await?;
+            written += 1;
+            if header == FRAGMENT_LAST {
+                return Ok(written);
+            }
+        }
+    }
+
+    /// Encodes the component and writes it as one or more fragments, returning the number of
+    /// fragments written.
+    pub async fn encode_message<C: Send + Sync, P: PacketComponent<C>>(
+        &mut self,
+        context: &mut C,
+        component: &P::ComponentType,
+    ) -> crate::prelude::Result<usize> {
+        let message = encode_body_to_bytes::<C, P>(context, component).await?;
+        self.write_message(&message).await
+    }
+
+    pub async fn flush(&mut self) -> crate::prelude::Result<()> {
+        self.frames.flush().await
+    }
+}
+
+/// Reads messages written by a `FragmentWriter`, reassembling their fragments. Messages larger
+/// than the maximum message size or split into more than the maximum number of fragments are
+/// rejected as soon as the fragment which crosses the limit is read.
+pub struct FragmentReader<R> {
+    frames: FrameReader<R>,
+    max_message_size: usize,
+    max_fragments: usize,
+}
+
+impl<R: AsyncRead + Unpin + Send + Sync> FragmentReader<R> {
+    pub fn new(frames: FrameReader<R>) -> Self {
+        Self {
+            frames,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_fragments: DEFAULT_MAX_FRAGMENTS,
+        }
+    }
+
+    /// Sets the largest reassembled message, in bytes, which will be read.
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Sets the most fragments a single message may be split into.
+    pub fn with_max_fragments(mut self, max_fragments: usize) -> Self {
+        self.max_fragments = max_fragments;
+        self
+    }
+
+    pub fn get_ref(&self) -> &FrameReader<R> {
+        &self.frames
+    }
+
+    pub fn get_mut(&mut self) -> &mut FrameReader<R> {
+        &mut self.frames
+    }
+
+    pub fn into_inner(self) -> FrameReader<R> {
+        self.frames
+    }
+
+    /// Reads and reassembles the next message. Returns `None` if the reader ends cleanly between
+    /// messages; ending part way through a message is an error.
+    pub async fn read_message(&mut self) -> crate::prelude::Result<Option<Vec<u8>>> {
+        let mut message = vec![];
+        let mut fragments = 0;
+        loop {
+            let fragment = match self.frames.read_frame().await? {
+                Some(fragment) => fragment,
+                None if fragments == 0 => return Ok(None),
+                None => throw_explain!(format!(
+                    "Reader ended after {fragments} fragments of an unfinished message"
+                )),
+            };
+            yield_point(fragments).await;
+            fragments += 1;
+            if fragments > self.max_fragments {
+                throw_explain!(format!(
+                    "Message exceeded maximum fragment count {}",
+                    self.max_fragments
+                ))
+            }
+            let (header, payload) = match fragment.split_first() {
+                Some(split) => split,
+                None => throw_explain!("Received fragment without a header"),
+            };
+            if message.len() + payload.len() > self.max_message_size {
+                throw_explain!(format!(
+                    "Message exceeded maximum message size {}",
+                    self.max_message_size
+                ))
+            }
+            message.extend_from_slice(payload);
+            match *header {
+                FRAGMENT_LAST => return Ok(Some(message)),
+                FRAGMENT_MORE => {}
+                header => throw_explain!(format!("Received invalid fragment header {header}")),
+            }
+        }
+    }
+
+    /// Reads the next message and decodes it with `P`, which must consume the entire message.
+    /// Returns `None` if the reader ends cleanly between messages.
+    pub async fn decode_message<C: Send + Sync, P: PacketComponent<C>>(
+        &mut self,
+        context: &mut C,
+    ) -> crate::prelude::Result<Option<P::ComponentType>> {
+        match self.read_message().await? {
+            Some(message) => Ok(Some(
+                decode_whole::<C, P>("Message", context, &message).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FragmentReader, FragmentWriter};
+    use crate::transport::frame::{FrameReader, FrameWriter};
+
+    #[tokio::test]
+    async fn test_fragments() -> crate::prelude::Result<()> {
+        assert!(FragmentWriter::new(FrameWriter::new(vec![]).with_max_frame_size(1)).is_err());
+        let mut writer = FragmentWriter::new(FrameWriter::new(vec![]).with_max_frame_size(5))?;
+        assert_eq!(writer.write_message(&[1, 2, 3]).await?, 1);
+        assert_eq!(writer.write_message(&[]).await?, 1);
+        let large = "a".repeat(10);
+        assert_eq!(
+            writer.encode_message::<_, String>(&mut (), &large).await?,
+            3
+        );
+        let bytes = writer.into_inner().into_inner();
+        assert_eq!(&bytes[..7], &[4, 0, 1, 2, 3, 1, 0]);
+        assert_eq!(&bytes[7..12], &[5, 1, 10, b'a', b'a']);
+
+        let mut reader = FragmentReader::new(FrameReader::new(std::io::Cursor::new(bytes.clone())));
+        assert_eq!(reader.read_message().await?, Some(vec![1, 2, 3]));
+        assert_eq!(reader.read_message().await?, Some(vec![]));
+        assert_eq!(
+            reader.decode_message::<_, String>(&mut ()).await?,
+            Some(large)
+        );
+        assert_eq!(reader.read_message().await?, None);
+
+        let mut reader = FragmentReader::new(FrameReader::new(std::io::Cursor::new(bytes.clone())))
+            .with_max_fragments(2);
+        reader.read_message().await?;
+        reader.read_message().await?;
+        assert!(reader.read_message().await.is_err());
+
+        let mut reader = FragmentReader::new(FrameReader::new(std::io::Cursor::new(bytes.clone())))
+            .with_max_message_size(8);
+        reader.read_message().await?;
+        reader.read_message().await?;
+        assert!(reader.read_message().await.is_err());
+
+        // ends part way through the fragmented message
+        let mut reader =
+            FragmentReader::new(FrameReader::new(std::io::Cursor::new(bytes[..12].to_vec())));
+        reader.read_message().await?;
+        reader.read_message().await?;
+        assert!(reader.read_message().await.is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_compressed_fragments() -> crate::prelude::Result<()> {
+        let mut frames = FrameWriter::new(vec![]).with_max_frame_size(64);
+        frames.set_compression_threshold(Some(16));
+        let mut writer = FragmentWriter::new(frames)?;
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let noise: Vec<u8> = (0..1000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        writer.write_message(&noise).await?;
+
+        let mut frames = FrameReader::new(std::io::Cursor::new(writer.into_inner().into_inner()))
+            .with_max_frame_size(64);
+        frames.set_compression_threshold(Some(16));
+        let mut reader = FragmentReader::new(frames);
+        assert_eq!(reader.read_message().await?, Some(noise));
+        Ok(())
+    }
+}
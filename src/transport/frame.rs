@@ -0,0 +1,1258 @@
+use std::future::Future;
+use std::io::{Cursor, IoSlice};
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::prelude::{ContextLabel, DraxReadExt, DraxWriteExt, PacketComponent, Size};
+use crate::throw_explain;
+use crate::transport::buffer::var_num::{put_var_int, size_var_int, VarIntScheme, VarNumScheme};
+use crate::transport::cancel::{cancellable, CancellationToken};
+use crate::transport::counting::{CountingReader, CountingWriter};
+use crate::transport::packet::yield_point;
+
+/// Encodes the component on its own, without a length prefix or any other framing, so it can be
+/// wrapped in a custom frame format.
+///
+/// # Parameters
+/// * `context` - The context to encode the component with.
+/// * `component` - The component to encode.
+pub async fn encode_body_to_bytes<C: Send + Sync, P: PacketComponent<C>>(
+    context: &mut C,
+    component: &P::ComponentType,
+) -> crate::prelude::Result<Vec<u8>> {
+    let size = match P::size(component, context)? {
+        Size::Dynamic(x) | Size::Constant(x) => x,
+    };
+    let mut body = Vec::with_capacity(size);
+    P::encode(component, context, &mut body).await?;
+    Ok(body)
+}
+
+/// Prefixes the body with a header built from it.
+///
+/// # Parameters
+/// * `body` - The encoded body to wrap.
+/// * `header_fn` - Writes the header for the given body into the given buffer.
+pub fn wrap_with_header<F>(body: &[u8], header_fn: F) -> crate::prelude::Result<Vec<u8>>
+where
+    F: FnOnce(&[u8], &mut Vec<u8>) -> crate::prelude::Result<()>,
+{
+    let mut frame = Vec::with_capacity(body.len() + 5);
+    header_fn(body, &mut frame)?;
+    frame.extend_from_slice(body);
+    Ok(frame)
+}
+
+/// The header used by Drax's own framing, the length of the body as a VarInt.
+pub fn var_int_length_header(body: &[u8], header: &mut Vec<u8>) -> crate::prelude::Result<()> {
+    put_var_int(header, i32::try_from(body.len())?);
+    Ok(())
+}
+
+/// The outcome of encoding a frame into a caller provided buffer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SliceWrite {
+    /// The frame was written to the start of the buffer and took this many bytes.
+    Written(usize),
+    /// Nothing was written; the frame needs a buffer of at least this many bytes.
+    NeedsMoreSpace(usize),
+}
+
+/// Encodes a VarInt length prefixed frame directly into the start of a buffer owned by the caller,
+/// such as a region of a ring buffer registered with the kernel or shared with another process.
+/// The frame is sized up front so nothing is allocated or written when it doesn't fit.
+///
+/// # Parameters
+/// * `context` - The context to encode the component with.
+/// * `component` - The component to encode.
+/// * `buf` - The buffer to encode the frame into.
+pub async fn encode_framed_into<C: Send + Sync, P: PacketComponent<C>>(
+    context: &mut C,
+    component: &P::ComponentType,
+    buf: &mut [u8],
+) -> crate::prelude::Result<SliceWrite> {
+    let size = match P::size(component, context)? {
+        Size::Dynamic(x) | Size::Constant(x) => x,
+    };
+    let len = i32::try_from(size)?;
+    let total = size_var_int(len) + size;
+    if total > buf.len() {
+        return Ok(SliceWrite::NeedsMoreSpace(total));
+    }
+
+    let mut cursor = Cursor::new(&mut buf[..total]);
+    cursor.write_var_int(len).await?;
+    P::encode(component, context, &mut cursor).await?;
+    if cursor.position() as usize != total {
+        throw_explain!(format!(
+            "Component was sized as {size} bytes but encoded as {} bytes",
+            cursor.position() as usize - size_var_int(len)
+        ))
+    }
+    Ok(SliceWrite::Written(total))
+}
+
+/// Fails if a component decoded from a body of `len` bytes left any of them unread.
+///
+/// # Parameters
+/// * `what` - What the body was, such as `"Frame"`, for the error.
+/// * `len` - The length of the body.
+/// * `consumed` - The bytes of the body the component consumed.
+pub(crate) fn check_consumed(
+    what: &str,
+    len: usize,
+    consumed: usize,
+) -> crate::prelude::Result<()> {
+    if consumed != len {
+        throw_explain!(format!(
+            "{what} of {len} bytes had {} trailing bytes after decoding",
+            len - consumed
+        ))
+    }
+    Ok(())
+}
+
+/// Decodes a component from a body which it must consume entirely, see `check_consumed`.
+pub(crate) async fn decode_whole<C: Send + Sync, P: PacketComponent<C>>(
+    what: &str,
+    context: &mut C,
+    body: &[u8],
+) -> crate::prelude::Result<P::ComponentType> {
+    let mut reader = CountingReader::new(body);
+    let component = P::decode(context, &mut reader).await?;
+    check_consumed(what, body.len(), reader.bytes_transferred() as usize)?;
+    Ok(component)
+}
+
+/// The most polls in a row `complete_in_memory` makes of a future which returned pending without
+/// waking itself before giving up on it.
+const MAX_UNWOKEN_POLLS: usize = 64;
+
+/// Records whether the future being completed in memory woke itself while it was polled.
+struct WakeFlag(AtomicBool);
+
+impl Wake for WakeFlag {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Drives a future over in-memory data to completion on the current thread. Readers and writers
+/// over buffers are always ready, so the only pending polls come from cooperative yields which
+/// have already woken themselves, and polling again is enough to make progress. A future which
+/// keeps returning pending without being woken is waiting on something else, such as a lock or a
+/// timer, and fails rather than being spun on forever.
+fn complete_in_memory<T, F: Future<Output = crate::prelude::Result<T>>>(
+    future: F,
+) -> crate::prelude::Result<T> {
+    let mut future = pin!(future);
+    let flag = Arc::new(WakeFlag(AtomicBool::new(false)));
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut unwoken = 0;
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        if flag.0.swap(false, Ordering::Acquire) {
+            unwoken = 0;
+        } else {
+            unwoken += 1;
+            if unwoken == MAX_UNWOKEN_POLLS {
+                throw_explain!(
+                    "Component waited on something other than its in-memory reader or writer"
+                )
+            }
+        }
+    }
+}
+
+/// Decodes a component from the start of a byte slice without an async runtime, returning it along
+/// with the number of bytes it consumed so callers can check for, or continue from, any bytes left
+/// after it. Components which wait on anything other than the reader fail to decode this way.
+///
+/// # Parameters
+/// * `context` - The context to decode the component with.
+/// * `bytes` - The bytes to decode the component from.
+pub fn decode_from_slice<C: Send + Sync, P: PacketComponent<C>>(
+    context: &mut C,
+    bytes: &[u8],
+) -> crate::prelude::Result<(P::ComponentType, usize)> {
+    let mut reader = CountingReader::new(bytes);
+    let component = complete_in_memory(P::decode(context, &mut reader))?;
+    Ok((component, reader.bytes_transferred() as usize))
+}
+
+/// Encodes a component, without any framing, into the start of a buffer without an async runtime.
+/// The component is sized up front so nothing is written when it doesn't fit, and must encode to
+/// exactly the size it reports.
+///
+/// # Parameters
+/// * `context` - The context to encode the component with.
+/// * `component` - The component to encode.
+/// * `buf` - The buffer to encode the component into.
+pub fn encode_to_slice<C: Send + Sync, P: PacketComponent<C>>(
+    context: &mut C,
+    component: &P::ComponentType,
+    buf: &mut [u8],
+) -> crate::prelude::Result<SliceWrite> {
+    let size = match P::size(component, context)? {
+        Size::Dynamic(x) | Size::Constant(x) => x,
+    };
+    if size > buf.len() {
+        return Ok(SliceWrite::NeedsMoreSpace(size));
+    }
+
+    let mut cursor = Cursor::new(&mut buf[..size]);
+    complete_in_memory(P::encode(component, context, &mut cursor))?;
+    if cursor.position() as usize != size {
+        throw_explain!(format!(
+            "Component was sized as {size} bytes but encoded as {} bytes",
+            cursor.position()
+        ))
+    }
+    Ok(SliceWrite::Written(size))
+}
+
+/// Adapts a blocking `std::io` reader or writer to the async traits components are written
+/// against. Every poll completes synchronously, blocking the thread if the inner reader or writer
+/// blocks.
+struct Blocking<T>(T);
+
+impl<R: std::io::Read + Unpin> AsyncRead for Blocking<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        loop {
+            match self.0.read(buf.initialize_unfilled()) {
+                Ok(read) => {
+                    buf.advance(read);
+                    return Poll::Ready(Ok(()));
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+    }
+}
+
+impl<W: std::io::Write + Unpin> AsyncWrite for Blocking<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        loop {
+            match self.0.write(buf) {
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                written => return Poll::Ready(written),
+            }
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.0.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Decodes a component, without any framing, from a blocking `std::io` reader without an async
+/// runtime. Only the bytes of the component are read, so the reader can be decoded from again.
+/// Components which wait on anything other than the reader fail to decode this way.
+///
+/// # Parameters
+/// * `context` - The context to decode the component with.
+/// * `read` - The reader to decode the component from.
+pub fn decode_sync<C, P, R>(
+    context: &mut C,
+    read: &mut R,
+) -> crate::prelude::Result<P::ComponentType>
+where
+    C: Send + Sync,
+    P: PacketComponent<C>,
+    R: std::io::Read + Send + Sync,
+{
+    complete_in_memory(P::decode(context, &mut Blocking(read)))
+}
+
+/// Encodes a component, without any framing, to a blocking `std::io` writer without an async
+/// runtime. Components are written in many small writes, so unbuffered writers such as files and
+/// sockets should be wrapped in a `std::io::BufWriter`. The writer is not flushed.
+///
+/// # Parameters
+/// * `context` - The context to encode the component with.
+/// * `component` - The component to encode.
+/// * `write` - The writer to encode the component to.
+pub fn encode_sync<C, P, W>(
+    context: &mut C,
+    component: &P::ComponentType,
+    write: &mut W,
+) -> crate::prelude::Result<()>
+where
+    C: Send + Sync,
+    P: PacketComponent<C>,
+    W: std::io::Write + Send + Sync,
+{
+    complete_in_memory(P::encode(component, context, &mut Blocking(write)))
+}
+
+/// The largest frame accepted by default, the most a 3 byte VarInt length prefix can describe.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 2_097_151;
+
+fn check_frame_size(len: usize, max_frame_size: usize) -> crate::prelude::Result<()> {
+    if len > max_frame_size {
+        throw_explain!(format!(
+            "Frame of {len} bytes exceeded maximum frame size {max_frame_size}"
+        ))
+    }
+    Ok(())
+}
+
+/// Reads VarInt length prefixed frames from the inner reader, rejecting frames longer than the
+/// maximum frame size before reading their bodies.
+pub struct FrameReader<R> {
+    inner: R,
+    max_frame_size: usize,
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<R: AsyncRead + Unpin + Send + Sync> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+            cancellation: None,
+        }
+    }
+
+    /// Sets the largest frame body, in bytes, which will be read.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Enables the compressed frame format once set, see `transport::compression`. The threshold
+    /// must match the one used by the writer on the other end; `None` disables compression.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold
+    }
+
+    /// Races every read against the token once set, failing reads with `ErrorType::Cancelled` once
+    /// it is cancelled; `None` lets reads run to completion.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation = token;
+    }
+
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads the body of the next frame. Returns `None` if the reader ends cleanly between
+    /// frames; ending part way through a frame is an error.
+    pub async fn read_frame(&mut self) -> crate::prelude::Result<Option<Vec<u8>>> {
+        cancellable(self.cancellation.clone(), self.read_body()).await
+    }
+
+    async fn read_body(&mut self) -> crate::prelude::Result<Option<Vec<u8>>> {
+        let mut first = [0u8; 1];
+        if self.inner.read(&mut first).await? == 0 {
+            return Ok(None);
+        }
+        let len = (&first[..]).chain(&mut self.inner).read_var_int().await?;
+        let len = crate::transport::packet::vec::checked_length(len)?;
+        check_frame_size(len, self.max_frame_size)?;
+
+        let mut body = vec![0; len];
+        self.inner.read_exact(&mut body).await?;
+        #[cfg(feature = "compression")]
+        if let Some(threshold) = self.compression_threshold {
+            let body = crate::transport::compression::decompress_body(
+                &body,
+                threshold,
+                self.max_frame_size,
+            )
+            .await?;
+            return Ok(Some(body));
+        }
+        Ok(Some(body))
+    }
+
+    /// Reads the next frame and decodes its body with `P`, which must consume the entire body.
+    /// Returns `None` if the reader ends cleanly between frames.
+    pub async fn decode_frame<C: Send + Sync, P: PacketComponent<C>>(
+        &mut self,
+        context: &mut C,
+    ) -> crate::prelude::Result<Option<P::ComponentType>> {
+        cancellable(self.cancellation.clone(), async {
+            match self.read_body().await? {
+                Some(body) => Ok(Some(decode_whole::<C, P>("Frame", context, &body).await?)),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    /// Like `decode_frame`, but labels any error with the context's `ContextLabel`, so failures
+    /// can be attributed to the connection they were read from.
+    pub async fn decode_labelled_frame<C, P>(
+        &mut self,
+        context: &mut C,
+    ) -> crate::prelude::Result<Option<P::ComponentType>>
+    where
+        C: Send + Sync + ContextLabel,
+        P: PacketComponent<C>,
+    {
+        let decoded = self.decode_frame::<C, P>(context).await;
+        decoded.map_err(|err| err.with_label(context))
+    }
+}
+
+/// The number of bytes a `PacketReader` asks its reader for at once when it doesn't yet know how
+/// long the next frame is.
+const READ_CAPACITY: usize = 8 * 1024;
+
+/// Parses a VarInt from the front of the buffer, returning it with the number of bytes it took, or
+/// `None` if the buffer ends before the VarInt does.
+fn peek_var_int(buf: &[u8]) -> crate::prelude::Result<Option<(i32, usize)>> {
+    let mut state = Default::default();
+    for (i, byte) in buf.iter().enumerate() {
+        if let Some(value) = VarIntScheme::decode_byte(&mut state, *byte)? {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads VarInt length prefixed frames through a buffer, asking the inner reader for as many bytes
+/// as it has available rather than reading each header and body separately. Frames split across
+/// reads are accumulated until complete and frames arriving together are split out of a single
+/// read, each body being handed out as `Bytes` sharing the buffer's memory rather than copied.
+///
+/// Decryption happens below the buffer, by reading through a `CipherAttachedReader`. Since frames
+/// are read ahead, bytes following the frame which enabled encryption may already be buffered
+/// undecrypted when the cipher is attached; `decrypt_buffered` catches those up.
+pub struct PacketReader<R> {
+    inner: R,
+    buffer: BytesMut,
+    max_frame_size: usize,
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<R: AsyncRead + Unpin + Send + Sync> PacketReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buffer: BytesMut::new(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+            cancellation: None,
+        }
+    }
+
+    /// Sets the largest frame body, in bytes, which will be buffered.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Enables the compressed frame format once set, see `transport::compression`. The threshold
+    /// must match the one used by the writer on the other end; `None` disables compression.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold
+    }
+
+    /// The bytes read from the inner reader which haven't been handed out as a frame yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Decrypts the bytes already buffered with the cipher just attached to the inner reader, for
+    /// the bytes read ahead of the frame after which encryption began.
+    #[cfg(feature = "encryption")]
+    pub fn decrypt_buffered(&mut self, cipher: &mut crate::transport::encryption::Cipher) {
+        use crate::transport::encryption::AsyncStreamCipher;
+        cipher.decrypt(&mut self.buffer);
+    }
+
+    /// Races every read against the token once set, failing reads with `ErrorType::Cancelled` once
+    /// it is cancelled; `None` lets reads run to completion.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation = token;
+    }
+
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the inner reader, dropping any buffered bytes.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Splits the next frame body off the buffer if it's complete, otherwise returns the number of
+    /// bytes the buffer needs to hold before it could be.
+    fn split_frame(&mut self) -> crate::prelude::Result<Result<Bytes, usize>> {
+        let (len, header_len) = match peek_var_int(&self.buffer)? {
+            Some(header) => header,
+            None => return Ok(Err(self.buffer.len() + 1)),
+        };
+        let len = crate::transport::packet::vec::checked_length(len)?;
+        check_frame_size(len, self.max_frame_size)?;
+        if self.buffer.len() < header_len + len {
+            return Ok(Err(header_len + len));
+        }
+        bytes::Buf::advance(&mut self.buffer, header_len);
+        Ok(Ok(self.buffer.split_to(len).freeze()))
+    }
+
+    /// Reads the body of the next frame, reading from the inner reader only if the buffer doesn't
+    /// already hold it. Returns `None` if the reader ends cleanly between frames; ending part way
+    /// through a frame is an error.
+    pub async fn read_frame(&mut self) -> crate::prelude::Result<Option<Bytes>> {
+        cancellable(self.cancellation.clone(), self.read_body()).await
+    }
+
+    async fn read_body(&mut self) -> crate::prelude::Result<Option<Bytes>> {
+        let mut reads = 0;
+        loop {
+            yield_point(reads).await;
+            reads += 1;
+            let needed = match self.split_frame()? {
+                Ok(body) => return self.decompress(body).await.map(Some),
+                Err(needed) => needed,
+            };
+            self.buffer
+                .reserve((needed - self.buffer.len()).max(READ_CAPACITY));
+            if self.inner.read_buf(&mut self.buffer).await? == 0 {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                throw_explain!(format!(
+                    "Reader ended part way through a frame, with {} bytes buffered",
+                    self.buffer.len()
+                ))
+            }
+        }
+    }
+
+    /// Restores a body in the compressed frame format if compression is enabled. Bodies sent
+    /// uncompressed are handed out without being copied.
+    #[cfg(feature = "compression")]
+    async fn decompress(&self, body: Bytes) -> crate::prelude::Result<Bytes> {
+        let Some(threshold) = self.compression_threshold else {
+            return Ok(body);
+        };
+        let mut rest = body.clone();
+        if crate::transport::buffer::var_num::get_var_num::<VarIntScheme>(&mut rest)? == 0 {
+            return Ok(rest);
+        }
+        let body =
+            crate::transport::compression::decompress_body(&body, threshold, self.max_frame_size)
+                .await?;
+        Ok(Bytes::from(body))
+    }
+
+    #[cfg(not(feature = "compression"))]
+    async fn decompress(&self, body: Bytes) -> crate::prelude::Result<Bytes> {
+        Ok(body)
+    }
+
+    /// Reads the next frame and decodes its body with `P`, which must consume the entire body.
+    /// The body is decoded with `decode_from_buf`, so components holding byte data share the
+    /// buffer's memory. Returns `None` if the reader ends cleanly between frames.
+    pub async fn decode_frame<C: Send + Sync, P: PacketComponent<C>>(
+        &mut self,
+        context: &mut C,
+    ) -> crate::prelude::Result<Option<P::ComponentType>> {
+        let mut body = match self.read_frame().await? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let len = body.len();
+        let component = P::decode_from_buf(context, &mut body)?;
+        check_consumed("Frame", len, len - body.len())?;
+        Ok(Some(component))
+    }
+
+    /// Like `decode_frame`, but labels any error with the context's `ContextLabel`, so failures
+    /// can be attributed to the connection they were read from.
+    pub async fn decode_labelled_frame<C, P>(
+        &mut self,
+        context: &mut C,
+    ) -> crate::prelude::Result<Option<P::ComponentType>>
+    where
+        C: Send + Sync + ContextLabel,
+        P: PacketComponent<C>,
+    {
+        let decoded = self.decode_frame::<C, P>(context).await;
+        decoded.map_err(|err| err.with_label(context))
+    }
+}
+
+/// The header written in front of a frame body, with the compressed body if compressing replaced
+/// it. Bodies below the compression threshold only gain a zero length in the header, so they are
+/// never copied.
+struct FrameHeader {
+    header: Vec<u8>,
+    compressed: Option<Vec<u8>>,
+    frame_len: usize,
+}
+
+fn frame_header(
+    body: &[u8],
+    compression_threshold: Option<usize>,
+) -> crate::prelude::Result<FrameHeader> {
+    let mut header = Vec::with_capacity(6);
+    match compression_threshold {
+        #[cfg(feature = "compression")]
+        Some(threshold) if body.len() >= threshold => {
+            let compressed = crate::transport::compression::compress_body(body, threshold)?;
+            var_int_length_header(&compressed, &mut header)?;
+            Ok(FrameHeader {
+                header,
+                frame_len: compressed.len(),
+                compressed: Some(compressed),
+            })
+        }
+        #[cfg(feature = "compression")]
+        Some(_) => {
+            put_var_int(&mut header, i32::try_from(body.len() + 1)?);
+            put_var_int(&mut header, 0);
+            Ok(FrameHeader {
+                header,
+                compressed: None,
+                frame_len: body.len() + 1,
+            })
+        }
+        _ => {
+            var_int_length_header(body, &mut header)?;
+            Ok(FrameHeader {
+                header,
+                compressed: None,
+                frame_len: body.len(),
+            })
+        }
+    }
+}
+
+/// Writes every byte of both segments, handing them to the writer together so writers supporting
+/// vectored writes send them without joining them first.
+async fn write_all_vectored<W: AsyncWrite + Unpin + ?Sized>(
+    write: &mut W,
+    mut segments: [&[u8]; 2],
+) -> std::io::Result<()> {
+    while segments.iter().any(|segment| !segment.is_empty()) {
+        let slices = [IoSlice::new(segments[0]), IoSlice::new(segments[1])];
+        let mut written = write.write_vectored(&slices).await?;
+        if written == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+        for segment in &mut segments {
+            let advance = written.min(segment.len());
+            *segment = &segment[advance..];
+            written -= advance;
+        }
+    }
+    Ok(())
+}
+
+/// An encoded frame held as its header and body segments rather than one buffer, so the body is
+/// never copied behind the header. Written with `FrameWriter::write_segments`, which hands both
+/// segments to the writer in a single vectored write.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FrameSegments {
+    header: Bytes,
+    body: Bytes,
+    frame_len: usize,
+}
+
+impl FrameSegments {
+    /// Frames the body with a VarInt length header, first compressing it in the compressed frame
+    /// format if a compression threshold is given, see `transport::compression`. Bodies which
+    /// aren't compressed are shared rather than copied.
+    ///
+    /// # Parameters
+    /// * `body` - The encoded frame body.
+    /// * `compression_threshold` - The compressed frame format threshold, `None` for plain frames.
+    pub fn new(body: Bytes, compression_threshold: Option<usize>) -> crate::prelude::Result<Self> {
+        let FrameHeader {
+            header,
+            compressed,
+            frame_len,
+        } = frame_header(&body, compression_threshold)?;
+        Ok(Self {
+            header: Bytes::from(header),
+            body: compressed.map(Bytes::from).unwrap_or(body),
+            frame_len,
+        })
+    }
+
+    pub fn header(&self) -> &Bytes {
+        &self.header
+    }
+
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// The length of the frame, as declared in its length prefix.
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    /// The number of bytes written for the frame, including its length prefix.
+    pub fn encoded_len(&self) -> usize {
+        self.header.len() + self.body.len()
+    }
+
+    /// Joins the segments into a single buffer, copying them.
+    pub fn to_vec(&self) -> Vec<u8> {
+        [&self.header[..], &self.body[..]].concat()
+    }
+}
+
+/// Writes VarInt length prefixed frames to the inner writer, rejecting frames longer than the
+/// maximum frame size before writing anything.
+pub struct FrameWriter<W> {
+    inner: W,
+    max_frame_size: usize,
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
+}
+
+impl<W: AsyncWrite + Unpin + Send + Sync> FrameWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+        }
+    }
+
+    /// Sets the largest frame body, in bytes, which will be written.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Compresses frame bodies of at least `threshold` bytes once set, see
+    /// `transport::compression`; `None` disables compression.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    #[cfg(feature = "compression")]
+    fn active_compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn active_compression_threshold(&self) -> Option<usize> {
+        None
+    }
+
+    /// Writes the body as a single frame.
+    pub async fn write_frame(&mut self, body: &[u8]) -> crate::prelude::Result<()> {
+        self.write_body(body).await?;
+        Ok(())
+    }
+
+    async fn write_body(&mut self, body: &[u8]) -> crate::prelude::Result<usize> {
+        check_frame_size(body.len(), self.max_frame_size)?;
+        let FrameHeader {
+            header,
+            compressed,
+            frame_len,
+        } = frame_header(body, self.active_compression_threshold())?;
+        check_frame_size(frame_len, self.max_frame_size)?;
+        let body = compressed.as_deref().unwrap_or(body);
+        write_all_vectored(&mut self.inner, [&header, body]).await?;
+        Ok(header.len() + body.len())
+    }
+
+    /// Writes a frame framed ahead of time, which must have been built with the same compression
+    /// threshold as this writer's.
+    pub async fn write_segments(&mut self, frame: &FrameSegments) -> crate::prelude::Result<()> {
+        check_frame_size(frame.frame_len, self.max_frame_size)?;
+        write_all_vectored(&mut self.inner, [&frame.header, &frame.body]).await?;
+        Ok(())
+    }
+
+    /// Encodes the component as the body of a single frame, returning the number of bytes
+    /// written including the length prefix.
+    pub async fn encode_frame<C: Send + Sync, P: PacketComponent<C>>(
+        &mut self,
+        context: &mut C,
+        component: &P::ComponentType,
+    ) -> crate::prelude::Result<usize> {
+        let size = match P::size(component, context)? {
+            Size::Dynamic(x) | Size::Constant(x) => x,
+        };
+        check_frame_size(size, self.max_frame_size)?;
+        if self.active_compression_threshold().is_some() {
+            let body = encode_body_to_bytes::<C, P>(context, component).await?;
+            return self.write_body(&body).await;
+        }
+        let mut frame = Vec::with_capacity(size + 5);
+        put_var_int(&mut frame, i32::try_from(size)?);
+        let mut writer = CountingWriter::new(&mut frame);
+        P::encode(component, context, &mut writer).await?;
+        let written = writer.bytes_transferred() as usize;
+        if written != size {
+            throw_explain!(format!(
+                "Component was sized at {size} bytes but encoded {written} bytes"
+            ))
+        }
+        self.inner.write_all(&frame).await?;
+        Ok(frame.len())
+    }
+
+    pub async fn flush(&mut self) -> crate::prelude::Result<()> {
+        self.inner.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_from_slice, decode_sync, encode_body_to_bytes, encode_framed_into, encode_sync,
+        encode_to_slice, var_int_length_header, wrap_with_header, FrameReader, FrameSegments,
+        FrameWriter, PacketReader, SliceWrite,
+    };
+    use crate::prelude::{DraxReadExt, DraxWriteExt, PacketComponent};
+    use crate::transport::packet::primitive::VarInt;
+
+    #[tokio::test]
+    async fn test_custom_header() -> crate::prelude::Result<()> {
+        let body = encode_body_to_bytes::<_, String>(&mut (), &"drax".to_string()).await?;
+        assert_eq!(body, vec![4, b'd', b'r', b'a', b'x']);
+
+        // a fixed width length followed by a packet type
+        let frame = wrap_with_header(&body, |body, header| {
+            header.extend_from_slice(&u16::try_from(body.len())?.to_be_bytes());
+            header.push(0x7);
+            Ok(())
+        })?;
+        assert_eq!(frame, vec![0, 5, 7, 4, b'd', b'r', b'a', b'x']);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_var_int_header_matches_framed() -> crate::prelude::Result<()> {
+        let value = "a".repeat(300);
+        let body = encode_body_to_bytes::<_, String>(&mut (), &value).await?;
+        let frame = wrap_with_header(&body, var_int_length_header)?;
+
+        let mut framed = vec![];
+        framed
+            .write_component_framed::<_, String>(&mut (), &value)
+            .await?;
+        assert_eq!(frame, framed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encode_into_slice() -> crate::prelude::Result<()> {
+        let value = "a".repeat(200);
+        let mut framed = vec![];
+        framed
+            .write_component_framed::<_, String>(&mut (), &value)
+            .await?;
+
+        let mut small = [0u8; 16];
+        assert_eq!(
+            encode_framed_into::<_, String>(&mut (), &value, &mut small).await?,
+            SliceWrite::NeedsMoreSpace(framed.len())
+        );
+        assert_eq!(small, [0u8; 16]);
+
+        let mut region = [0xFFu8; 256];
+        assert_eq!(
+            encode_framed_into::<_, String>(&mut (), &value, &mut region).await?,
+            SliceWrite::Written(framed.len())
+        );
+        assert_eq!(&region[..framed.len()], &framed[..]);
+        assert_eq!(region[framed.len()], 0xFF);
+
+        let mut exact = vec![0u8; framed.len()];
+        assert_eq!(
+            encode_framed_into::<_, String>(&mut (), &value, &mut exact).await?,
+            SliceWrite::Written(framed.len())
+        );
+        assert_eq!(exact, framed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_slice_helpers() -> crate::prelude::Result<()> {
+        let mut small = [0u8; 4];
+        assert_eq!(
+            encode_to_slice::<_, String>(&mut (), &"drax".to_string(), &mut small)?,
+            SliceWrite::NeedsMoreSpace(5)
+        );
+        let mut buf = [0xFFu8; 8];
+        assert_eq!(
+            encode_to_slice::<_, String>(&mut (), &"drax".to_string(), &mut buf)?,
+            SliceWrite::Written(5)
+        );
+        assert_eq!(&buf[..6], &[4, b'd', b'r', b'a', b'x', 0xFF]);
+
+        assert_eq!(
+            decode_from_slice::<_, String>(&mut (), &buf)?,
+            ("drax".to_string(), 5)
+        );
+        assert!(decode_from_slice::<_, String>(&mut (), &buf[..3]).is_err());
+
+        // long enough to pass through cooperative yields while decoding
+        let values: Vec<i32> = (0..3000).collect();
+        let mut buf = vec![0u8; 8192];
+        let written = match encode_to_slice::<_, Vec<VarInt>>(&mut (), &values, &mut buf)? {
+            SliceWrite::Written(written) => written,
+            SliceWrite::NeedsMoreSpace(needed) => panic!("Needed {needed} bytes"),
+        };
+        assert_eq!(
+            decode_from_slice::<_, Vec<VarInt>>(&mut (), &buf)?,
+            (values, written)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_codec() -> crate::prelude::Result<()> {
+        let values: Vec<i32> = (0..3000).collect();
+        let mut bytes = vec![];
+        encode_sync::<_, String, _>(&mut (), &"drax".to_string(), &mut bytes)?;
+        encode_sync::<_, Vec<VarInt>, _>(&mut (), &values, &mut bytes)?;
+        assert_eq!(&bytes[..5], &[4, b'd', b'r', b'a', b'x']);
+
+        let mut reader = std::io::Cursor::new(bytes);
+        assert_eq!(
+            decode_sync::<_, String, _>(&mut (), &mut reader)?,
+            "drax".to_string()
+        );
+        assert_eq!(
+            decode_sync::<_, Vec<VarInt>, _>(&mut (), &mut reader)?,
+            values
+        );
+        assert!(decode_sync::<_, VarInt, _>(&mut (), &mut reader).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_codec_never_ready() {
+        struct Stuck;
+
+        impl PacketComponent<()> for Stuck {
+            type ComponentType = ();
+
+            fn decode<'a, A: tokio::io::AsyncRead + Unpin + Send + Sync + ?Sized>(
+                _: &'a mut (),
+                _: &'a mut A,
+            ) -> crate::PinnedLivelyResult<'a, ()> {
+                Box::pin(std::future::pending())
+            }
+
+            fn encode<'a, A: tokio::io::AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                _: &'a (),
+                _: &'a mut (),
+                _: &'a mut A,
+            ) -> crate::PinnedLivelyResult<'a, ()> {
+                Box::pin(std::future::pending())
+            }
+
+            fn size(_: &(), _: &mut ()) -> crate::prelude::Result<crate::prelude::Size> {
+                Ok(crate::prelude::Size::Constant(0))
+            }
+        }
+
+        assert!(decode_from_slice::<_, Stuck>(&mut (), &[]).is_err());
+        assert!(encode_sync::<_, Stuck, _>(&mut (), &(), &mut vec![]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_header_fields() -> crate::prelude::Result<()> {
+        type Frame = ((i64, VarInt), String);
+
+        let frame = ((1_700_000_000_i64, 42), "drax".to_string());
+        let mut framed = vec![];
+        framed
+            .write_component_framed::<_, Frame>(&mut (), &frame)
+            .await?;
+        assert_eq!(framed[0] as usize, framed.len() - 1);
+        assert_eq!(&framed[1..9], &1_700_000_000_i64.to_be_bytes());
+        assert_eq!(framed[9], 42);
+
+        let mut cursor = std::io::Cursor::new(framed);
+        cursor.read_var_int().await?;
+        assert_eq!(cursor.decode_component::<_, Frame>(&mut ()).await?, frame);
+        assert_eq!(<Frame as PacketComponent<()>>::MAX_SIZE, None);
+        assert_eq!(<(i64, VarInt) as PacketComponent<()>>::MAX_SIZE, Some(13));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frame_codec() -> crate::prelude::Result<()> {
+        let mut writer = FrameWriter::new(vec![]).with_max_frame_size(16);
+        writer
+            .encode_frame::<_, String>(&mut (), &"drax".to_string())
+            .await?;
+        writer.write_frame(&[1, 2, 3]).await?;
+        writer.write_frame(&[]).await?;
+        assert!(writer.write_frame(&[0; 17]).await.is_err());
+        assert!(writer
+            .encode_frame::<_, String>(&mut (), &"a".repeat(16))
+            .await
+            .is_err());
+        let bytes = writer.into_inner();
+        assert_eq!(bytes, vec![5, 4, b'd', b'r', b'a', b'x', 3, 1, 2, 3, 0]);
+
+        let mut reader = FrameReader::new(std::io::Cursor::new(bytes));
+        assert_eq!(
+            reader.decode_frame::<_, String>(&mut ()).await?,
+            Some("drax".to_string())
+        );
+        assert!(reader.decode_frame::<_, u8>(&mut ()).await.is_err());
+        assert_eq!(reader.read_frame().await?, Some(vec![]));
+        assert_eq!(reader.read_frame().await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frame_reader_limits() {
+        let mut reader =
+            FrameReader::new(std::io::Cursor::new(vec![17, 0, 0])).with_max_frame_size(16);
+        assert!(reader.read_frame().await.is_err());
+
+        let mut reader = FrameReader::new(std::io::Cursor::new(vec![4, 0, 0]));
+        assert!(reader.read_frame().await.is_err());
+
+        let mut reader = FrameReader::new(std::io::Cursor::new(vec![0x80]));
+        assert!(reader.read_frame().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_packet_reader() -> crate::prelude::Result<()> {
+        let mut large = vec![0xAC, 0x02];
+        large.extend((0..300).map(|i| i as u8));
+        // frames split across reads, several frames in one read and a header split across reads
+        let source = tokio_test::io::Builder::new()
+            .read(&[5, 4, b'd'])
+            .read(&[b'r', b'a', b'x', 3, 1])
+            .read(&[2, 3, 0, large[0]])
+            .read(&large[1..])
+            .build();
+        let mut reader = PacketReader::new(source);
+        assert_eq!(
+            reader.decode_frame::<_, String>(&mut ()).await?,
+            Some("drax".to_string())
+        );
+        assert_eq!(reader.read_frame().await?.as_deref(), Some(&[1, 2, 3][..]));
+        assert_eq!(reader.buffered(), &[0, 0xAC]);
+        assert_eq!(reader.read_frame().await?.as_deref(), Some(&[][..]));
+        assert_eq!(reader.read_frame().await?.as_deref(), Some(&large[2..]));
+        assert_eq!(reader.read_frame().await?, None);
+
+        let mut reader = PacketReader::new(&[3, 1, 2, 3][..]);
+        assert!(reader.decode_frame::<_, u8>(&mut ()).await.is_err());
+        let mut reader = PacketReader::new(&[17, 0, 0][..]).with_max_frame_size(16);
+        assert!(reader.read_frame().await.is_err());
+        let mut reader = PacketReader::new(&[4, 0, 0][..]);
+        assert!(reader.read_frame().await.is_err());
+        let mut reader = PacketReader::new(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x01][..]);
+        assert!(reader.read_frame().await.is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_compressed_frames() -> crate::prelude::Result<()> {
+        let mut writer = FrameWriter::new(vec![]);
+        writer.set_compression_threshold(Some(64));
+        writer.write_frame(&[1, 2, 3]).await?;
+        let large = "a".repeat(512);
+        writer.encode_frame::<_, String>(&mut (), &large).await?;
+        let bytes = writer.into_inner();
+        assert_eq!(&bytes[..5], &[4, 0, 1, 2, 3]);
+        assert!(bytes.len() < 5 + large.len());
+
+        let mut reader = FrameReader::new(std::io::Cursor::new(bytes.clone()));
+        reader.set_compression_threshold(Some(64));
+        assert_eq!(reader.read_frame().await?, Some(vec![1, 2, 3]));
+        assert_eq!(
+            reader.decode_frame::<_, String>(&mut ()).await?,
+            Some(large)
+        );
+        assert_eq!(reader.read_frame().await?, None);
+
+        let mut reader =
+            FrameReader::new(std::io::Cursor::new(bytes.clone())).with_max_frame_size(256);
+        reader.set_compression_threshold(Some(64));
+        reader.read_frame().await?;
+        assert!(reader.read_frame().await.is_err());
+
+        let mut reader = PacketReader::new(&bytes[..]);
+        reader.set_compression_threshold(Some(64));
+        assert_eq!(reader.read_frame().await?.as_deref(), Some(&[1, 2, 3][..]));
+        assert_eq!(
+            reader.decode_frame::<_, String>(&mut ()).await?,
+            Some("a".repeat(512))
+        );
+        assert_eq!(reader.read_frame().await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frame_segments() -> crate::prelude::Result<()> {
+        let body = bytes::Bytes::from(vec![7; 200]);
+        let segments = FrameSegments::new(body.clone(), None)?;
+        // the body is shared rather than copied behind the header
+        assert_eq!(segments.body().as_ptr(), body.as_ptr());
+        assert_eq!(&segments.header()[..], &[200, 1]);
+        assert_eq!(segments.encoded_len(), 202);
+
+        let mut plain = FrameWriter::new(vec![]);
+        plain.write_frame(&body).await?;
+        assert_eq!(plain.get_ref(), &segments.to_vec());
+
+        // a pipe smaller than the frame only takes part of a segment per write
+        let (client, mut server) = tokio::io::duplex(16);
+        let mut writer = FrameWriter::new(client);
+        let (written, read) = tokio::join!(
+            async move {
+                writer.write_segments(&segments).await?;
+                writer.write_frame(&[1, 2, 3]).await?;
+                drop(writer);
+                crate::prelude::Result::Ok(())
+            },
+            async {
+                let mut bytes = vec![];
+                tokio::io::AsyncReadExt::read_to_end(&mut server, &mut bytes)
+                    .await
+                    .map(|_| bytes)
+            }
+        );
+        written?;
+        let mut reader = FrameReader::new(std::io::Cursor::new(read?));
+        assert_eq!(reader.read_frame().await?, Some(body.to_vec()));
+        assert_eq!(reader.read_frame().await?, Some(vec![1, 2, 3]));
+
+        let mut writer = FrameWriter::new(vec![]).with_max_frame_size(100);
+        let segments = FrameSegments::new(body.clone(), None)?;
+        assert!(writer.write_segments(&segments).await.is_err());
+        assert!(writer.get_ref().is_empty());
+
+        #[cfg(feature = "compression")]
+        {
+            let small = FrameSegments::new(bytes::Bytes::from_static(&[1, 2, 3]), Some(64))?;
+            assert_eq!(small.to_vec(), vec![4, 0, 1, 2, 3]);
+            let large = FrameSegments::new(body.clone(), Some(64))?;
+            assert!(large.encoded_len() < body.len());
+            let mut writer = FrameWriter::new(vec![]);
+            writer.set_compression_threshold(Some(64));
+            writer.write_segments(&small).await?;
+            writer.write_segments(&large).await?;
+            let mut reader = FrameReader::new(std::io::Cursor::new(writer.into_inner()));
+            reader.set_compression_threshold(Some(64));
+            assert_eq!(reader.read_frame().await?, Some(vec![1, 2, 3]));
+            assert_eq!(reader.read_frame().await?, Some(body.to_vec()));
+        }
+        Ok(())
+    }
+}
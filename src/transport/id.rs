@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::transport::buffer::var_num::{get_var_num, size_var_int, VarIntScheme};
+use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
+use crate::transport::packet::{max_size_max, PacketComponent, Size};
+use crate::PinnedLivelyResult;
+
+macro_rules! define_ids {
+    ($($(#[$($doc_tt:tt)*])* $id:ident),*) => {$(
+        $(#[$($doc_tt)*])*
+        ///
+        /// Encoded on the wire as a VarInt.
+        #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+        pub struct $id(pub i32);
+
+        impl Display for $id {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl<C: Send + Sync> PacketComponent<C> for $id {
+            type ComponentType = $id;
+
+            const MAX_SIZE: Option<usize> = Some(5);
+
+            fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                _: &'a mut C,
+                read: &'a mut A,
+            ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+                Box::pin(async move { Ok($id(read.read_var_int().await?)) })
+            }
+
+            fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                component_ref: &'a Self::ComponentType,
+                _: &'a mut C,
+                write: &'a mut A,
+            ) -> PinnedLivelyResult<'a, ()> {
+                Box::pin(async move { write.write_var_int(component_ref.0).await })
+            }
+
+            fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+                Ok(Size::Dynamic(size_var_int(input.0)))
+            }
+
+            fn decode_from_buf(
+                _: &mut C,
+                buf: &mut Bytes,
+            ) -> crate::prelude::Result<Self::ComponentType> {
+                Ok($id(get_var_num::<VarIntScheme>(buf)?))
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $id {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_i32(self.0)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $id {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                i32::deserialize(deserializer).map($id)
+            }
+        }
+    )*};
+}
+
+define_ids!(
+    /// The id of a packet within a protocol state.
+    PacketId,
+    /// The version of the protocol spoken over a connection.
+    ProtocolVersion,
+    /// The id of a protocol state, such as handshaking or play.
+    StateId
+);
+
+/// Maps the keys enums are declared with to the ids used on the wire by the protocol version a
+/// context speaks, so one enum definition can serve several versions. Enums declared with
+/// `@remap(table)` consult their context, which must implement this trait, whenever a key is read,
+/// written or sized.
+pub trait IdRemapper {
+    /// Maps a declared key to the id written on the wire, `None` if the key does not exist in the
+    /// negotiated version.
+    fn wire_id(&self, table: &str, key: i32) -> Option<i32>;
+
+    /// Maps an id read from the wire to its declared key, `None` if the id is unknown.
+    fn declared_key(&self, table: &str, id: i32) -> Option<i32>;
+}
+
+/// A table of declared keys and the ids they are written with, for implementing `IdRemapper`.
+/// Keys missing from the table are written as is.
+#[derive(Clone, Debug, Default)]
+pub struct RemapTable {
+    to_wire: HashMap<i32, i32>,
+    from_wire: HashMap<i32, i32>,
+}
+
+impl RemapTable {
+    /// Creates a table from pairs of declared keys and wire ids.
+    pub fn new<I: IntoIterator<Item = (i32, i32)>>(pairs: I) -> Self {
+        let mut table = Self::default();
+        for (key, id) in pairs {
+            table.insert(key, id);
+        }
+        table
+    }
+
+    /// Maps the declared key to the wire id, replacing any previous mapping of either.
+    pub fn insert(&mut self, key: i32, id: i32) {
+        if let Some(previous) = self.to_wire.insert(key, id) {
+            self.from_wire.remove(&previous);
+        }
+        if let Some(previous) = self.from_wire.insert(id, key) {
+            if previous != key {
+                self.to_wire.remove(&previous);
+            }
+        }
+    }
+
+    /// Maps the declared key to its wire id.
+    pub fn wire_id(&self, key: i32) -> i32 {
+        self.to_wire.get(&key).copied().unwrap_or(key)
+    }
+
+    /// Maps the wire id to its declared key. An unmapped id is its own key unless that key is
+    /// written as another id, in which case `None` is returned.
+    pub fn declared_key(&self, id: i32) -> Option<i32> {
+        match self.from_wire.get(&id) {
+            Some(key) => Some(*key),
+            None if self.to_wire.contains_key(&id) => None,
+            None => Some(id),
+        }
+    }
+}
+
+/// A context which knows the protocol version negotiated for its connection, letting components
+/// such as `Versioned` change their encoding between versions.
+pub trait ProtocolVersionCtx {
+    fn protocol_version(&self) -> ProtocolVersion;
+}
+
+impl ProtocolVersionCtx for ProtocolVersion {
+    fn protocol_version(&self) -> ProtocolVersion {
+        *self
+    }
+}
+
+/// Encodes and decodes with `New` when the context's protocol version is at least `SINCE`, and
+/// with `Old` before it. Nesting covers any number of versions, newest first, for a field whose
+/// encoding changed more than once:
+///
+/// ```
+/// use drax::delegates::{VarInt, Versioned};
+/// use drax::prelude::PacketComponent;
+/// use drax::transport::id::ProtocolVersion;
+///
+/// // A VarInt since 764, a fixed width i32 from 760 until then and a VarInt before 760.
+/// type Field = Versioned<764, VarInt, Versioned<760, i32, VarInt>>;
+///
+/// assert_eq!(<Field as PacketComponent<ProtocolVersion>>::MAX_SIZE, Some(5));
+/// ```
+pub struct Versioned<const SINCE: i32, New, Old>(PhantomData<(New, Old)>);
+
+impl<const SINCE: i32, New, Old> Versioned<SINCE, New, Old> {
+    fn is_new<C: ProtocolVersionCtx>(context: &C) -> bool {
+        context.protocol_version() >= ProtocolVersion(SINCE)
+    }
+}
+
+impl<C, const SINCE: i32, New, Old> PacketComponent<C> for Versioned<SINCE, New, Old>
+where
+    C: ProtocolVersionCtx + Send + Sync,
+    Old: PacketComponent<C>,
+    New: PacketComponent<C, ComponentType = Old::ComponentType>,
+{
+    type ComponentType = Old::ComponentType;
+
+    const MAX_SIZE: Option<usize> = max_size_max(&[New::MAX_SIZE, Old::MAX_SIZE]);
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        if Self::is_new(context) {
+            New::decode(context, read)
+        } else {
+            Old::decode(context, read)
+        }
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        if Self::is_new(context) {
+            New::encode(component_ref, context, write)
+        } else {
+            Old::encode(component_ref, context, write)
+        }
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        if Self::is_new(context) {
+            New::size(input, context)
+        } else {
+            Old::size(input, context)
+        }
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        if Self::is_new(context) {
+            New::decode_from_buf(context, buf)
+        } else {
+            Old::decode_from_buf(context, buf)
+        }
+    }
+
+    /// Sizing the heap has no context to pick a version with, so the larger of the two is used.
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        New::heap_size(input).max(Old::heap_size(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{PacketId, ProtocolVersion, Versioned};
+    use crate::prelude::PacketComponent;
+    use crate::transport::packet::primitive::VarInt;
+
+    #[tokio::test]
+    async fn test_ids() -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![]);
+        PacketId::encode(&PacketId(300), &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![0xAC, 0x02]);
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(PacketId::decode(&mut (), &mut cursor).await?, PacketId(300));
+        assert_eq!(ProtocolVersion(761).to_string(), "761");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_versioned() -> crate::prelude::Result<()> {
+        type Field = Versioned<764, VarInt, Versioned<760, i32, VarInt>>;
+
+        for (version, expected) in [(759, vec![7]), (760, vec![0, 0, 0, 7]), (765, vec![7])] {
+            let mut context = ProtocolVersion(version);
+            let mut cursor = Cursor::new(vec![]);
+            Field::encode(&7, &mut context, &mut cursor).await?;
+            assert_eq!(cursor.get_ref(), &expected);
+            cursor.set_position(0);
+            assert_eq!(Field::decode(&mut context, &mut cursor).await?, 7);
+        }
+        assert_eq!(
+            <Field as PacketComponent<ProtocolVersion>>::MAX_SIZE,
+            Some(5)
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_ids_serde() -> crate::prelude::Result<()> {
+        assert_eq!(serde_json::to_string(&ProtocolVersion(761))?, "761");
+        assert_eq!(serde_json::from_str::<PacketId>("12")?, PacketId(12));
+        Ok(())
+    }
+}
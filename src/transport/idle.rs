@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+struct TrackerInner {
+    origin: Instant,
+    // Microseconds since `origin` of the last activity.
+    last_active: AtomicU64,
+}
+
+/// Records when a connection last sent or received data. Clones share the same timestamp, so one
+/// clone can be handed to the connection and another to a `Reaper`.
+#[derive(Clone)]
+pub struct IdleTracker {
+    inner: Arc<TrackerInner>,
+}
+
+impl IdleTracker {
+    /// Creates a tracker which is considered active as of now.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(TrackerInner {
+                origin: Instant::now(),
+                last_active: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Marks the connection as active now. Connections should call this for every frame read or
+    /// written, or wrap their stream in `IdleTracked` to do so automatically.
+    pub fn touch(&self) {
+        let elapsed = self.inner.origin.elapsed().as_micros();
+        self.inner.last_active.fetch_max(
+            u64::try_from(elapsed).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// The time elapsed since the connection was last active.
+    pub fn idle_for(&self) -> Duration {
+        let last = Duration::from_micros(self.inner.last_active.load(Ordering::Relaxed));
+        self.inner.origin.elapsed().saturating_sub(last)
+    }
+}
+
+impl Default for IdleTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pin_project! {
+    /// Wraps a stream and touches the tracker whenever bytes are read from or written to it.
+    pub struct IdleTracked<T> {
+        #[pin]
+        inner: T,
+        tracker: IdleTracker,
+    }
+}
+
+impl<T> IdleTracked<T> {
+    pub fn new(inner: T, tracker: IdleTracker) -> Self {
+        Self { inner, tracker }
+    }
+
+    pub fn tracker(&self) -> &IdleTracker {
+        &self.tracker
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for IdleTracked<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let me = self.project();
+        let before = buf.filled().len();
+        ready!(me.inner.poll_read(cx, buf))?;
+        if buf.filled().len() > before {
+            me.tracker.touch();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite> AsyncWrite for IdleTracked<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let me = self.project();
+        let written = ready!(me.inner.poll_write(cx, buf))?;
+        if written > 0 {
+            me.tracker.touch();
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
+type CloseFn = Box<dyn FnOnce() + Send>;
+type ReapedFn<K> = Box<dyn FnMut(&K, Duration) + Send>;
+type Connections<K> = Arc<Mutex<HashMap<K, (IdleTracker, CloseFn)>>>;
+
+fn lock<K>(connections: &Connections<K>) -> MutexGuard<'_, HashMap<K, (IdleTracker, CloseFn)>> {
+    connections.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Configures and spawns a `Reaper`.
+pub struct ReaperBuilder<K> {
+    timeout: Duration,
+    interval: Option<Duration>,
+    on_reaped: Option<ReapedFn<K>>,
+}
+
+impl<K: Eq + Hash + Clone + Send + 'static> ReaperBuilder<K> {
+    /// Creates a builder for a reaper closing connections idle for at least `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            interval: None,
+            on_reaped: None,
+        }
+    }
+
+    /// How often connections are checked, defaults to a quarter of the timeout.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Called with the key and idle time of every connection after it has been closed.
+    pub fn on_reaped<F: FnMut(&K, Duration) + Send + 'static>(mut self, on_reaped: F) -> Self {
+        self.on_reaped = Some(Box::new(on_reaped));
+        self
+    }
+
+    /// Spawns the reaper task onto the current tokio runtime.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    pub fn spawn(self) -> Reaper<K> {
+        let connections: Connections<K> = Arc::new(Mutex::new(HashMap::new()));
+        let period = self
+            .interval
+            .unwrap_or(self.timeout / 4)
+            .max(Duration::from_millis(1));
+        let timeout = self.timeout;
+        let mut on_reaped = self.on_reaped;
+        let task_connections = connections.clone();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let reaped: Vec<(K, Duration, CloseFn)> = {
+                    let mut connections = lock(&task_connections);
+                    let idle: Vec<K> = connections
+                        .iter()
+                        .filter(|(_, (tracker, _))| tracker.idle_for() >= timeout)
+                        .map(|(key, _)| key)
+                        .cloned()
+                        .collect();
+                    idle.into_iter()
+                        .filter_map(|key| {
+                            let (tracker, close) = connections.remove(&key)?;
+                            Some((key, tracker.idle_for(), close))
+                        })
+                        .collect()
+                };
+                for (key, idle_for, close) in reaped {
+                    close();
+                    if let Some(on_reaped) = on_reaped.as_mut() {
+                        on_reaped(&key, idle_for);
+                    }
+                }
+            }
+        });
+        Reaper {
+            connections,
+            handle,
+        }
+    }
+}
+
+/// A background task closing registered connections which have been idle past a timeout. Each
+/// connection is closed at most once and is deregistered before its close callback runs. The task
+/// is stopped when the reaper is dropped.
+pub struct Reaper<K> {
+    connections: Connections<K>,
+    handle: JoinHandle<()>,
+}
+
+impl<K: Eq + Hash> Reaper<K> {
+    /// Registers a connection, replacing any connection already registered with the same key.
+    ///
+    /// # Parameters
+    /// * `key` - The key identifying the connection, passed to the reaped event.
+    /// * `tracker` - The tracker updated by the connection.
+    /// * `close` - Closes the connection once it is considered idle.
+    pub fn register<F: FnOnce() + Send + 'static>(&self, key: K, tracker: IdleTracker, close: F) {
+        lock(&self.connections).insert(key, (tracker, Box::new(close)));
+    }
+
+    /// Deregisters a connection without closing it, returning whether it was registered.
+    pub fn deregister(&self, key: &K) -> bool {
+        lock(&self.connections).remove(key).is_some()
+    }
+
+    /// The number of connections currently registered.
+    pub fn len(&self) -> usize {
+        lock(&self.connections).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K> Drop for Reaper<K> {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{IdleTracked, IdleTracker, ReaperBuilder};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tracked_stream_touches() -> std::io::Result<()> {
+        let tracker = IdleTracker::new();
+        tokio::time::advance(Duration::from_millis(20)).await;
+        assert!(tracker.idle_for() >= Duration::from_millis(20));
+
+        let mut stream = IdleTracked::new(Cursor::new(vec![1, 2, 3]), tracker.clone());
+        let mut out = [0u8; 3];
+        stream.read_exact(&mut out).await?;
+        assert!(tracker.idle_for() < Duration::from_millis(20));
+
+        tokio::time::advance(Duration::from_millis(20)).await;
+        stream.write_all(&[4]).await?;
+        assert!(tracker.idle_for() < Duration::from_millis(20));
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reaper_closes_idle() {
+        let reaped = Arc::new(Mutex::new(vec![]));
+        let events = reaped.clone();
+        let reaper = ReaperBuilder::new(Duration::from_millis(50))
+            .interval(Duration::from_millis(5))
+            .on_reaped(move |key: &u32, idle_for| {
+                assert!(idle_for >= Duration::from_millis(50));
+                events.lock().unwrap().push(*key);
+            })
+            .spawn();
+
+        let active = IdleTracker::new();
+        let idle = IdleTracker::new();
+        let active_closed = Arc::new(AtomicBool::new(false));
+        let idle_closed = Arc::new(AtomicBool::new(false));
+        let closed = active_closed.clone();
+        reaper.register(1, active.clone(), move || {
+            closed.store(true, Ordering::SeqCst)
+        });
+        let closed = idle_closed.clone();
+        reaper.register(2, idle, move || closed.store(true, Ordering::SeqCst));
+        reaper.register(3, IdleTracker::new(), || {});
+        assert!(reaper.deregister(&3));
+
+        for _ in 0..30 {
+            active.touch();
+            tokio::time::advance(Duration::from_millis(5)).await;
+        }
+
+        assert!(idle_closed.load(Ordering::SeqCst));
+        assert!(!active_closed.load(Ordering::SeqCst));
+        assert_eq!(*reaped.lock().unwrap(), vec![2]);
+        assert_eq!(reaper.len(), 1);
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+use crate::throw_explain;
+use crate::transport::source::{ClockSource, RngSource};
+
+/// Decides when a connection should send a keep alive and checks the answers to them, taking ids
+/// and times from the context so it behaves the same on every run under test. Only one keep alive
+/// is outstanding at a time.
+#[derive(Clone, Debug)]
+pub struct KeepAlive {
+    interval: Duration,
+    timeout: Duration,
+    last_sent: Option<Instant>,
+    outstanding: Option<(i64, Instant)>,
+    latency: Option<Duration>,
+}
+
+impl KeepAlive {
+    /// # Parameters
+    /// * `interval` - How long to wait after sending a keep alive before sending the next.
+    /// * `timeout` - How long a keep alive can go unanswered before the connection is considered
+    ///   dead.
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self {
+            interval,
+            timeout,
+            last_sent: None,
+            outstanding: None,
+            latency: None,
+        }
+    }
+
+    /// Returns the id of a keep alive to send if one is due, recording it as outstanding. One is
+    /// due once the interval has passed since the last was sent and it has been answered.
+    pub fn poll_send<C: ClockSource + RngSource>(&mut self, context: &mut C) -> Option<i64> {
+        let now = context.now();
+        if self.outstanding.is_some() {
+            return None;
+        }
+        if let Some(last_sent) = self.last_sent {
+            if now.saturating_duration_since(last_sent) < self.interval {
+                return None;
+            }
+        }
+        let id = context.next_u64() as i64;
+        self.last_sent = Some(now);
+        self.outstanding = Some((id, now));
+        Some(id)
+    }
+
+    /// Records the answer to the outstanding keep alive, returning the round trip time. Fails if
+    /// no keep alive is outstanding or the id doesn't match it.
+    pub fn acknowledge<C: ClockSource>(
+        &mut self,
+        context: &C,
+        id: i64,
+    ) -> crate::prelude::Result<Duration> {
+        match self.outstanding {
+            Some((expected, sent)) if expected == id => {
+                let latency = context.now().saturating_duration_since(sent);
+                self.outstanding = None;
+                self.latency = Some(latency);
+                Ok(latency)
+            }
+            Some(_) => throw_explain!(format!("Keep alive {id} did not match the one sent")),
+            None => throw_explain!(format!("Keep alive {id} was not expected")),
+        }
+    }
+
+    /// Whether the outstanding keep alive has gone unanswered for longer than the timeout.
+    pub fn timed_out<C: ClockSource>(&self, context: &C) -> bool {
+        match self.outstanding {
+            Some((_, sent)) => context.now().saturating_duration_since(sent) > self.timeout,
+            None => false,
+        }
+    }
+
+    /// The round trip time of the most recently answered keep alive.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::KeepAlive;
+    use crate::transport::source::{ClockSource, ManualClock, RngSource, SeededRng};
+
+    struct TestContext {
+        clock: ManualClock,
+        rng: SeededRng,
+    }
+
+    impl ClockSource for TestContext {
+        fn now(&self) -> std::time::Instant {
+            self.clock.now()
+        }
+    }
+
+    impl RngSource for TestContext {
+        fn next_u64(&mut self) -> u64 {
+            self.rng.next_u64()
+        }
+    }
+
+    #[test]
+    fn test_keep_alive() -> crate::prelude::Result<()> {
+        let mut context = TestContext {
+            clock: ManualClock::new(Duration::ZERO),
+            rng: SeededRng::new(1),
+        };
+        let mut keep_alive = KeepAlive::new(Duration::from_secs(15), Duration::from_secs(30));
+
+        let id = keep_alive
+            .poll_send(&mut context)
+            .expect("first keep alive");
+        assert_eq!(id, SeededRng::new(1).next_u64() as i64);
+        assert_eq!(keep_alive.poll_send(&mut context), None);
+        assert!(keep_alive.acknowledge(&context, id + 1).is_err());
+
+        context.clock.advance(Duration::from_millis(40));
+        assert_eq!(
+            keep_alive.acknowledge(&context, id)?,
+            Duration::from_millis(40)
+        );
+        assert!(keep_alive.acknowledge(&context, id).is_err());
+        assert_eq!(keep_alive.poll_send(&mut context), None);
+
+        context.clock.advance(Duration::from_secs(15));
+        let id = keep_alive
+            .poll_send(&mut context)
+            .expect("second keep alive");
+        context.clock.advance(Duration::from_secs(30));
+        assert!(!keep_alive.timed_out(&context));
+        context.clock.advance(Duration::from_millis(1));
+        assert!(keep_alive.timed_out(&context));
+        assert_eq!(keep_alive.latency(), Some(Duration::from_millis(40)));
+        keep_alive.acknowledge(&context, id)?;
+        Ok(())
+    }
+}
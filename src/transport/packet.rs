@@ -1,7 +1,13 @@
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 
+use bytes::Buf;
+pub use bytes::Bytes;
 use tokio::io::{AsyncRead, AsyncWrite};
 
+use crate::transport::frame::decode_from_slice;
 use crate::PinnedLivelyResult;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -33,16 +39,178 @@ impl std::ops::Add<usize> for Size {
     }
 }
 
+/// Sums the maximum sizes of components encoded one after another, `None` if any is unbounded.
+pub const fn max_size_sum(sizes: &[Option<usize>]) -> Option<usize> {
+    let mut total = 0usize;
+    let mut i = 0;
+    while i < sizes.len() {
+        match sizes[i] {
+            Some(size) => match total.checked_add(size) {
+                Some(sum) => total = sum,
+                None => return None,
+            },
+            None => return None,
+        }
+        i += 1;
+    }
+    Some(total)
+}
+
+/// The largest of the given maximum sizes, `None` if any is unbounded.
+pub const fn max_size_max(sizes: &[Option<usize>]) -> Option<usize> {
+    let mut max = 0usize;
+    let mut i = 0;
+    while i < sizes.len() {
+        match sizes[i] {
+            Some(size) if size > max => max = size,
+            Some(_) => {}
+            None => return None,
+        }
+        i += 1;
+    }
+    Some(max)
+}
+
+/// Whether the keys cover every value from `start` up to `start + keys.len()` exactly once.
+pub const fn keys_dense(keys: &[i64], start: i64) -> bool {
+    let mut expected = 0;
+    while expected < keys.len() {
+        let mut found = false;
+        let mut i = 0;
+        while i < keys.len() {
+            if keys[i] == start + expected as i64 {
+                found = true;
+            }
+            i += 1;
+        }
+        if !found {
+            return false;
+        }
+        expected += 1;
+    }
+    true
+}
+
+/// Views a decoded enum key as the type its cases are matched against, so that keys decoded as a
+/// `String` can be matched against the string literal cases of `enum_packet_components!`.
+///
+/// Integer, `bool` and `char` keys match as themselves, and `String`, `Box<str>`, `Arc<str>` and
+/// `buf::BytesStr` keys match as `str`. Enums keyed by any other type need an implementation of
+/// this and of `FromKeyCase` for the type.
+pub trait KeyCase {
+    type Case: ?Sized;
+
+    fn as_case(&self) -> &Self::Case;
+}
+
+/// Builds the key written for an enum case from the literal it was declared with.
+pub trait FromKeyCase<Case> {
+    fn from_key_case(case: Case) -> Self;
+}
+
+impl<T> FromKeyCase<T> for T {
+    fn from_key_case(case: T) -> Self {
+        case
+    }
+}
+
+macro_rules! key_case_str {
+    ($($ty:ty),*) => {$(
+        impl KeyCase for $ty {
+            type Case = str;
+
+            fn as_case(&self) -> &str {
+                self
+            }
+        }
+
+        impl FromKeyCase<&'static str> for $ty {
+            fn from_key_case(case: &'static str) -> Self {
+                case.into()
+            }
+        }
+    )*};
+}
+
+key_case_str!(String, Box<str>, Arc<str>, buf::BytesStr);
+
+macro_rules! key_case_self {
+    ($($ty:ty),*) => {$(
+        impl KeyCase for $ty {
+            type Case = $ty;
+
+            fn as_case(&self) -> &$ty {
+                self
+            }
+        }
+    )*};
+}
+
+key_case_self!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, bool, char);
+
+/// The number of elements decoded between cooperative yields in loops over wire supplied lengths.
+pub(crate) const YIELD_INTERVAL: usize = 1024;
+
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Yields to the scheduler once every `YIELD_INTERVAL` elements. Readers which are always ready,
+/// such as buffers holding a whole frame, never return to the scheduler on their own, so without
+/// this a huge collection holds its task's thread until it is decoded and a decode raced against
+/// a timeout or shutdown signal can't be dropped part way through.
+pub(crate) async fn yield_point(index: usize) {
+    if index % YIELD_INTERVAL == YIELD_INTERVAL - 1 {
+        yield_now().await
+    }
+}
+
+/// Yields to the scheduler once, for work which isn't counted in elements.
+pub(crate) async fn yield_now() {
+    YieldNow(false).await
+}
+
 /// Defines a structure that can be encoded and decoded.
 pub trait PacketComponent<C: Send + Sync> {
     type ComponentType: Sized + Send + Sync;
 
+    /// The most bytes any value of the component can encode to, or `None` if it is unbounded.
+    /// Allows buffers to be sized at compile time for components which are bounded.
+    const MAX_SIZE: Option<usize> = None;
+
     /// Decodes the packet component from the given reader.
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
         context: &'a mut C,
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType>;
 
+    /// Decodes the packet component without boxing the future, so it can be inlined into the
+    /// future decoding an enclosing component. The component macros decode every field through
+    /// this, and implement it for the components they define, so a tree of macro defined
+    /// components over primitive fields decodes with a single boxed future rather than one per
+    /// field. Defaults to `decode`; components whose decode isn't recursive can override it and
+    /// implement `decode` by boxing it.
+    fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> impl Future<Output = crate::prelude::Result<Self::ComponentType>> + Send + Sync + 'a
+    where
+        Self::ComponentType: 'a,
+    {
+        Self::decode(context, read)
+    }
+
     /// Encodes the packet component to the given writer.
     fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
         component_ref: &'a Self::ComponentType,
@@ -51,12 +219,56 @@ pub trait PacketComponent<C: Send + Sync> {
     ) -> PinnedLivelyResult<'a, ()>;
 
     fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size>;
+
+    /// Decodes the packet component from the front of the buffer without an async runtime,
+    /// advancing the buffer past it. Defaults to decoding from the buffer as a slice, see
+    /// `decode_from_slice`; components holding byte data, such as `buf::ByteBuf` and
+    /// `buf::BytesStr`, override it to share the buffer's memory instead of copying out of it.
+    /// Components made by the macros and those wrapping other components forward it to their
+    /// fields, so byte data nested in them is shared too.
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self: Sized,
+    {
+        let (component, consumed) = decode_from_slice::<C, Self>(context, buf)?;
+        buf.advance(consumed);
+        Ok(component)
+    }
+
+    /// An estimate of the heap memory retained by a decoded value, not counting the value itself.
+    /// Allows the memory held by queued packets to be metered. Defaults to `0`, which is exact for
+    /// components which own no heap memory.
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        let _ = input;
+        0
+    }
+
+    /// The name of the variant a value is, for logging which packet of an enum was sent. The
+    /// enums made by the component macros return their variant names; other components default to
+    /// `None`.
+    fn variant_name(input: &Self::ComponentType) -> Option<&'static str> {
+        let _ = input;
+        None
+    }
+}
+
+/// The heap memory retained by a vec of components decoded by `T`, including its spare capacity.
+#[allow(clippy::ptr_arg)]
+pub fn vec_heap_size<C: Send + Sync, T: PacketComponent<C>>(vec: &Vec<T::ComponentType>) -> usize {
+    vec.capacity() * std::mem::size_of::<T::ComponentType>()
+        + vec.iter().map(T::heap_size).sum::<usize>()
 }
 
 macro_rules! impl_deref_component {
     ($impl_ty:ty, $c_ty:ty, $t_ty:ty) => {
         type ComponentType = $impl_ty;
 
+        const MAX_SIZE: Option<usize> =
+            <$t_ty as $crate::prelude::PacketComponent<$c_ty>>::MAX_SIZE;
+
         fn decode<'a, A: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(
             context: &'a mut $c_ty,
             read: &'a mut A,
@@ -82,6 +294,24 @@ macro_rules! impl_deref_component {
         fn size(input: &Self::ComponentType, context: &mut $c_ty) -> crate::prelude::Result<Size> {
             <$t_ty as $crate::prelude::PacketComponent<$c_ty>>::size(input.as_ref(), context)
         }
+
+        fn decode_from_buf(
+            context: &mut $c_ty,
+            buf: &mut Bytes,
+        ) -> crate::prelude::Result<Self::ComponentType> {
+            let component =
+                <$t_ty as $crate::prelude::PacketComponent<$c_ty>>::decode_from_buf(context, buf)?;
+            Ok(<$impl_ty>::new(component))
+        }
+
+        fn heap_size(input: &Self::ComponentType) -> usize {
+            std::mem::size_of::<<$t_ty as $crate::prelude::PacketComponent<$c_ty>>::ComponentType>()
+                + <$t_ty as $crate::prelude::PacketComponent<$c_ty>>::heap_size(input.as_ref())
+        }
+
+        fn variant_name(input: &Self::ComponentType) -> Option<&'static str> {
+            <$t_ty as $crate::prelude::PacketComponent<$c_ty>>::variant_name(input.as_ref())
+        }
     };
 }
 
@@ -99,21 +329,31 @@ where
     impl_deref_component!(Arc<T::ComponentType>, C, T);
 }
 
+pub mod bits;
+pub mod buf;
+pub mod limit;
+pub mod map;
 pub mod option;
+pub mod prefixed;
 pub mod primitive;
 #[cfg(feature = "serde")]
 pub mod serde_json;
+pub mod stream;
 pub mod string;
+pub mod tuple;
 pub mod vec;
 
 #[cfg(feature = "macros")]
 pub mod macros {
     #[macro_export]
     macro_rules! component_internal {
-        ($(#[$($tt:tt)*])* enum $enum_name:ident {
+        ($(#[$($tt:tt)*])* $(@derives($($derive:path),+))? $(@max_size($max_size:expr))? $(@round_trip($test_name:ident $(, $sample:expr)?))? $(@label $($__label:lifetime)?)? $(@arbitrary $($__arbitrary:lifetime)?)? enum $enum_name:ident {
             $key_name:ident: $key_delegate_type:ty,
             $(@ser_delegate $static_product_delegate_type:ty,)?
             $(@match $key_matcher:expr,)?
+            $(@dense($dense_start:literal),)?
+            $(@remap($remap_table:expr),)?
+            $(@default($default_variant:ident $(, $remaining:ident)?),)?
             $(
                 $(#[$($vtt:tt)*])*
                 $($key_matcher_case:literal =>)? $variant_name:ident {
@@ -128,10 +368,18 @@ pub mod macros {
         }) => {
             $crate::enum_packet_components! {
                 $(#[$($tt)*])*
+                $(@derives($($derive),+))?
+                $(@max_size($max_size))?
+                $(@round_trip($test_name $(, $sample)?))?
+                $(@label $($__label)?)?
+                $(@arbitrary $($__arbitrary)?)?
                 $enum_name {
                     $key_name: $key_delegate_type,
                     $(@ser_delegate $static_product_delegate_type,)?
                     $(@match $key_matcher,)?
+                    $(@dense($dense_start),)?
+                    $(@remap($remap_table),)?
+                    $(@default($default_variant $(, $remaining)?),)?
                     $(
                         $(#[$($vtt)*])*
                         $($key_matcher_case =>)? $variant_name {
@@ -146,7 +394,7 @@ pub mod macros {
                 }
             }
         };
-        ($(#[$($tt:tt)*])* struct $struct_name:ident {
+        ($(#[$($tt:tt)*])* $(@derives($($derive:path),+))? $(@max_size($max_size:expr))? $(@round_trip($test_name:ident $(, $sample:expr)?))? $(@label $($__label:lifetime)?)? $(@arbitrary $($__arbitrary:lifetime)?)? struct $struct_name:ident {
             $(
                 $(
                     $(#[$($doc_tt:tt)*])*
@@ -156,6 +404,11 @@ pub mod macros {
         }) => {
             $crate::struct_packet_components! {
                 $(#[$($tt)*])*
+                $(@derives($($derive),+))?
+                $(@max_size($max_size))?
+                $(@round_trip($test_name $(, $sample)?))?
+                $(@label $($__label)?)?
+                $(@arbitrary $($__arbitrary)?)?
                 $struct_name {
                     $(
                         $(
@@ -172,9 +425,17 @@ pub mod macros {
     macro_rules! components {
         ($(
             $(#[$($tt2:tt)*])* // any extra attributes for the struct
+            $(@derives($($c_derive:path),+ $(,)?))? // any extra derives for the struct
+            $(@max_size($c_max_size:expr))? // the declared maximum encoded size
+            $(@round_trip($c_test_name:ident $(, $c_sample:expr)?))? // an opt-in round trip test
+            $(@label $($c_label:lifetime)?)? // labels errors with the context's ContextLabel
+            $(@arbitrary $($c_arbitrary:lifetime)?)? // implements Arbitrary with the `arbitrary` feature
             $(enum $component_enum_name:ident<$(C: $c_e_ctx_ty:ty,)? $c_key_name:ident: $c_key_delegate_type:ty> {
                 $(@ser_delegate $c_static_product_delegate_type:ty,)?
                 $(@match $c_key_matcher:expr,)?
+                $(@dense($c_dense_start:literal),)?
+                $(@remap($c_remap_table:expr),)?
+                $(@default($c_default_variant:ident $(, $c_remaining:ident)?),)?
                 $( // enum field delegations
                     $(#[$($cvtt:tt)*])*
                     $c_variant_name:ident {
@@ -199,10 +460,18 @@ pub mod macros {
         ),*) => {
             $($crate::component_internal! {
                 $(#[$($tt2)*])*
+                $(@derives($($c_derive),+))?
+                $(@max_size($c_max_size))?
+                $(@round_trip($c_test_name $(, $c_sample)?))?
+                $(@label $($c_label)?)?
+                $(@arbitrary $($c_arbitrary)?)?
                 $(enum $component_enum_name$(<$c_e_ctx_ty>)? {
                     $c_key_name: $c_key_delegate_type,
                     $(@ser_delegate $c_static_product_delegate_type,)?
                     $(@match $c_key_matcher,)?
+                    $(@dense($c_dense_start),)?
+                    $(@remap($c_remap_table),)?
+                    $(@default($c_default_variant $(, $c_remaining)?),)?
                     $( // enum field delegations
                         $(#[$($cvtt)*])*
                         $($c_key_matcher_case =>)? $c_variant_name {
@@ -230,7 +499,7 @@ pub mod macros {
     #[macro_export]
     macro_rules! enum_packet_components {
         (@internal @match $key_ident:ident) => {
-            $key_ident
+            $crate::transport::packet::KeyCase::as_case(&$key_ident)
         };
         (@internal @match $__:ident @alt $matcher:expr) => {
             $matcher
@@ -247,6 +516,34 @@ pub mod macros {
         (@internal @vdoc $__:literal @alt $value:literal) => {
             stringify!($value)
         };
+        (@internal @labelled [@label] $context:ident, $explanation:expr) => {
+            $crate::transport::error::ContextLabel::labelled(&*$context, $explanation)
+        };
+        (@internal @labelled [] $context:ident, $explanation:expr) => {
+            $explanation
+        };
+        (@internal @remap_out [$($label:tt)*] $context:ident, $key:ident, $enum_name:ident) => {};
+        (@internal @remap_out [$($label:tt)*] $context:ident, $key:ident, $enum_name:ident, $table:expr) => {
+            let $key = match $crate::transport::id::IdRemapper::wire_id(&*$context, $table, $key) {
+                Some(id) => id,
+                None => $crate::throw_explain!($crate::enum_packet_components!(
+                    @internal @labelled [$($label)*] $context,
+                    format!("Key {} of type {} has no id in table {}", $key, stringify!($enum_name), $table)
+                )),
+            };
+        };
+        (@internal @default_max $key_max:expr) => {
+            $key_max
+        };
+        (@internal @default_max $key_max:expr, $remaining:ident) => {
+            None
+        };
+        (@internal @key_max $ctx_ty:ty : $ty:ty) => {
+            <$ty as $crate::transport::packet::PacketComponent<$ctx_ty>>::MAX_SIZE
+        };
+        (@internal @key_max $ctx_ty:ty : $__:ty : $dty:ty) => {
+            <$dty as $crate::transport::packet::PacketComponent<$ctx_ty>>::MAX_SIZE
+        };
         ($context:ident: $ctx_ty:ty, $w_ident:ident, $field_name:ident @ser : $ty:ty) => {
             $crate::expand_field!(@internal @ser_bind $context: $ctx_ty, $w_ident, $field_name, $ty)
         };
@@ -259,10 +556,19 @@ pub mod macros {
         ($context:ident: $ctx_ty:ty, $c_counter:ident, $d_counter:ident, $field_name:ident @size : $__:ty : $dty:ty) => {
             $crate::expand_field!(@internal @size_bind $context: $ctx_ty, $c_counter, $d_counter, $field_name, $dty)
         };
-        ($($(#[$($tt:tt)*])* $enum_name:ident$(<$ctx_ty:ty>)? {
+        ($(
+            $(#[$($tt:tt)*])*
+            $(@derives($($derive:path),+ $(,)?))?
+            $(@max_size($max_size:expr))?
+            $(@round_trip($test_name:ident $(, $sample:expr)?))?
+            $(@label $($__label:lifetime)?)? $(@arbitrary $($__arbitrary:lifetime)?)?
+            $enum_name:ident$(<$ctx_ty:ty>)? {
             $key_name:ident: $key_delegate_type:ty,
                 $(@ser_delegate $static_product_delegate_type:ty,)?
                 $(@match $key_matcher:expr,)?
+                $(@dense($dense_start:literal),)?
+                $(@remap($remap_table:expr),)?
+                $(@default($default_variant:ident $(, $remaining:ident)?),)?
             $(
                 $(#[$($variant_tt:tt)*])*
                 $($key_matcher_case:literal =>)? $variant_name:ident {
@@ -281,6 +587,82 @@ pub mod macros {
                 };
             }
 
+            #[allow(unused_macros)]
+            macro_rules! labelled {
+                ($$context:ident, $$explanation:expr) => {
+                    $crate::enum_packet_components!(@internal @labelled [$(@label $($__label)?)?] $$context, $$explanation)
+                };
+            }
+
+            /// Decodes the default variant for a key no other variant matched, or fails with the
+            /// explanation when the enum has no default variant.
+            #[allow(unused_macros)]
+            macro_rules! default_variant {
+                ($$bind:ident, $$context:ident, $$source:ident, $$key:ident, $$explanation:expr) => {
+                    default_variant!(@[$($default_variant)?] $$bind, $$context, $$source, $$key, $$explanation)
+                };
+                (@[] $$bind:ident, $$context:ident, $$source:ident, $$key:ident, $$explanation:expr) => {
+                    $crate::throw_explain!(labelled!($$context, $$explanation))
+                };
+                (@[$$__:ident] $$bind:ident, $$context:ident, $$source:ident, $$key:ident, $$explanation:expr) => {{
+                    $(
+                    $(
+                    $crate::expand_field!(@internal @$$bind $$context: ctx_type!(C), $$source, $remaining, $crate::transport::packet::vec::ByteDrain);
+                    )?
+                    return Ok(Self::$default_variant {
+                        $key_name: $$key,
+                        $($remaining,)?
+                    });
+                    )?
+                }};
+            }
+
+            /// Decodes the key and then the fields of the variant it selects, from a reader with
+            /// `de_bind` or from a buffer with `buf_bind`. Keys no variant matches, including
+            /// remapped ids the context doesn't know, fall back to the default variant with the key
+            /// as read from the wire.
+            macro_rules! decode_variant {
+                ($$bind:ident, $$context:ident, $$source:ident) => {{
+                    $crate::expand_field!(@internal @$$bind $$context: ctx_type!(C), $$source, $key_name, $key_delegate_type);
+                    #[allow(unused_variables)]
+                    let __wire_key = $key_name.clone();
+                    $(
+                    let $key_name = match $crate::transport::id::IdRemapper::declared_key(&*$$context, $remap_table, $key_name) {
+                        Some(key) => key,
+                        None => default_variant!(
+                            $$bind,
+                            $$context,
+                            $$source,
+                            __wire_key,
+                            format!("Failed to remap id {} for type {} in table {}", __wire_key, stringify!($enum_name), $remap_table)
+                        ),
+                    };
+                    )?
+
+                    #[deny(unreachable_patterns)]
+                    match $crate::enum_packet_components!(@internal @match $key_name $(@alt $key_matcher)?) {
+                        $(
+                        $crate::enum_packet_components!(@internal @case ${index(0)} $(@alt $key_matcher_case)?) => {
+                            $($(
+                                $crate::expand_field!(@internal @$$bind $$context: ctx_type!(C), $$source, $field_name, $delegate_type);
+                            )+)?
+                            Ok(Self::$variant_name $({
+                                $($field_name,)*
+                            })?)
+                        }
+                        )*
+                        #[allow(unreachable_patterns)]
+                        _ => default_variant!(
+                            $$bind,
+                            $$context,
+                            $$source,
+                            __wire_key,
+                            format!("Failed to decode key {} for type {}", __wire_key, stringify!($enum_name))
+                        ),
+                    }
+                }};
+            }
+
             $(#[$($tt)*])*
             ///
             /// Component Variant Breakdown
@@ -312,7 +694,7 @@ pub mod macros {
                 )*
             /// </tbody>
             /// </table>
-            #[derive(Debug)]
+            #[derive(Debug $($(, $derive)+)?)]
             pub enum $enum_name {
                 $(
                     $(#[$($variant_tt)*])*
@@ -348,11 +730,81 @@ pub mod macros {
                         )+
                     })?,
                 )*
+                $(
+                    /// Any key not matched by another variant, as read from the wire before any
+                    /// remapping, along with the bytes left in the reader after it if captured.
+                    $default_variant {
+                        $key_name: <$key_delegate_type as $crate::transport::packet::PacketComponent<ctx_type!(())>>::ComponentType,
+                        $($remaining: Vec<u8>,)?
+                    },
+                )?
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! dense_keys {
+                () => {
+                    [$($crate::enum_packet_components!(@internal @case ${index(0)} $(@alt $key_matcher_case)?) as i64),*]
+                };
+            }
+
+            $(
+            const _: () = assert!(
+                $crate::transport::packet::keys_dense(&dense_keys!(), $dense_start),
+                concat!("Keys of ", stringify!($enum_name), " are not dense")
+            );
+            )?
+
+            impl $enum_name {
+                /// The maximum encoded size of the variant key.
+                pub const KEY_MAX_SIZE: Option<usize> =
+                    $crate::enum_packet_components!(@internal @key_max ctx_type!(()) : $key_delegate_type $(: $static_product_delegate_type)?);
+
+                /// The maximum encoded size of each variant including its key, in declaration
+                /// order followed by the default variant. `None` for variants which are unbounded.
+                pub const VARIANT_MAX_SIZES: &'static [Option<usize>] = &[$(
+                    $crate::transport::packet::max_size_sum(&[
+                        Self::KEY_MAX_SIZE,
+                        $($(
+                        <$delegate_type as $crate::transport::packet::PacketComponent<ctx_type!(())>>::MAX_SIZE,
+                        )+)?
+                    ]),
+                )* $(
+                    $crate::enum_packet_components!(@internal @default_max Self::KEY_MAX_SIZE $(, $remaining)?),
+                )?];
+
+                /// The maximum encoded size of the largest variant, or `None` if any variant is
+                /// unbounded.
+                pub const MAX_VARIANT_SIZE: Option<usize> =
+                    $crate::transport::packet::max_size_max(Self::VARIANT_MAX_SIZES);
+
+                /// The key, name, and the name and delegate type of each field of every variant, in
+                /// declaration order. Keys are as written in the declaration, such as `"0x01"`, and
+                /// the default variant isn't included.
+                #[allow(dead_code)]
+                pub const VARIANTS: &'static [(&'static str, &'static str, &'static [(&'static str, &'static str)])] = &[$(
+                    (
+                        $crate::enum_packet_components!(@internal @vdoc ${index(0)} $(@alt $key_matcher_case)?),
+                        stringify!($variant_name),
+                        &[$($(
+                            (stringify!($field_name), stringify!($delegate_type)),
+                        )+)?],
+                    ),
+                )*];
             }
 
-            $crate::expand_field!(@internal @impl_bind $enum_name, C $(@alt $ctx_ty)? {
+            $crate::__arbitrary_component!([$(@arbitrary $($__arbitrary)?)?] @enum [$(@label $($__label)?)?] $enum_name, ctx_type!(()), C $(@alt $ctx_ty)? {
+                $($variant_name $({ $($field_name: $delegate_type),+ })?),*
+            });
+
+            $($crate::__round_trip_test!($test_name, ctx_type!(()), $enum_name $(, $sample)?);)?
+
+            $crate::__max_size_test!([$(@arbitrary $($__arbitrary)?)?] [$($max_size)?] $enum_name, ctx_type!(()));
+
+            $crate::expand_field!(@internal @impl_bind [$(@label $($__label)?)?] $enum_name, C $(@alt $ctx_ty)? {
                 type ComponentType = Self;
 
+                const MAX_SIZE: Option<usize> = Self::MAX_VARIANT_SIZE;
+
                 fn decode<'a, A: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(
                     __context: &'a mut ctx_type!(C),
                     __read: &'a mut A,
@@ -360,23 +812,24 @@ pub mod macros {
                 where
                     Self: Sized
                 {
-                    Box::pin(async move {
-                        $crate::expand_field!(@internal @de_bind __context: ctx_type!(C), __read, $key_name, $key_delegate_type);
+                    Box::pin(<Self as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::decode_unboxed(__context, __read))
+                }
 
-                        match $crate::enum_packet_components!(@internal @match $key_name $(@alt $key_matcher)?) {
-                            $(
-                            $crate::enum_packet_components!(@internal @case ${index(0)} $(@alt $key_matcher_case)?) => {
-                                $($(
-                                    $crate::expand_field!(@internal @de_bind __context: ctx_type!(C), __read, $field_name, $delegate_type);
-                                )+)?
-                                Ok(Self::$variant_name $({
-                                    $($field_name,)*
-                                })?)
-                            }
-                            )*
-                            _ => $crate::throw_explain!(format!("Failed to decode key {} for type {}", $key_name, stringify!($enum_name))),
-                        }
-                    })
+                fn decode_unboxed<'a, A: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(
+                    __context: &'a mut ctx_type!(C),
+                    __read: &'a mut A,
+                ) -> impl ::std::future::Future<Output = $crate::transport::Result<Self::ComponentType>> + Send + Sync + 'a
+                where
+                    Self::ComponentType: 'a,
+                {
+                    async move { decode_variant!(de_bind, __context, __read) }
+                }
+
+                fn decode_from_buf(
+                    __context: &mut ctx_type!(C),
+                    __buf: &mut $crate::transport::packet::Bytes,
+                ) -> $crate::transport::Result<Self::ComponentType> {
+                    decode_variant!(buf_bind, __context, __buf)
                 }
 
                 fn encode<'a, A: $crate::prelude::AsyncWrite + Unpin + Send + Sync + ?Sized>(
@@ -386,6 +839,12 @@ pub mod macros {
                 ) -> $crate::PinnedLivelyResult<'a, ()>
                 {
                     Box::pin(async move {
+                        $crate::expand_field!(@internal @max_size_check [$(@label $($__label)?)?] [$($max_size)?] $enum_name, component_ref, __context: ctx_type!(C));
+                        macro_rules! remap_key {
+                            ($$key:ident) => {
+                                $crate::enum_packet_components!(@internal @remap_out [$(@label $($__label)?)?] __context, $$key, $enum_name $(, $remap_table)?);
+                            };
+                        }
                         macro_rules! expand_key_types {
                             (
                                 $$write_ref:ident,
@@ -406,7 +865,10 @@ pub mod macros {
                                     $field_name,
                                 )+})? => {
                                     {
-                                        let key = $crate::enum_packet_components!(@internal @case ${index(0)} $(@alt $key_matcher_case)?);
+                                        let key = $crate::transport::packet::FromKeyCase::from_key_case(
+                                            $crate::enum_packet_components!(@internal @case ${index(0)} $(@alt $key_matcher_case)?)
+                                        );
+                                        remap_key!(key);
                                         let key_ref = &key;
                                         expand_key_types!(__write, key_ref, __context);
                                     }
@@ -416,12 +878,29 @@ pub mod macros {
                                     Ok(())
                                 }
                             )*
+                            $(
+                                Self::$default_variant { $key_name, $($remaining,)? } => {
+                                    {
+                                        let key_ref = $key_name;
+                                        expand_key_types!(__write, key_ref, __context);
+                                    }
+                                    $(
+                                        $crate::expand_field!(@internal @ser_bind __context: ctx_type!(C), __write, $remaining, $crate::transport::packet::vec::ByteDrain);
+                                    )?
+                                    Ok(())
+                                }
+                            )?
                         }
                     })
                 }
 
                 fn size(component_ref: &Self, __context: &mut ctx_type!(C)) -> $crate::prelude::Result<$crate::prelude::Size>
                 {
+                    macro_rules! remap_key {
+                        ($$key:ident) => {
+                            $crate::enum_packet_components!(@internal @remap_out [$(@label $($__label)?)?] __context, $$key, $enum_name $(, $remap_table)?);
+                        };
+                    }
                     macro_rules! expand_key_types {
                         (
                             $$constant_counter:ident,
@@ -445,7 +924,10 @@ pub mod macros {
                         $field_name,
                         )+})? => {
                             {
-                                let key = $crate::enum_packet_components!(@internal @case ${index(0)} $(@alt $key_matcher_case)?);
+                                let key = $crate::transport::packet::FromKeyCase::from_key_case(
+                                    $crate::enum_packet_components!(@internal @case ${index(0)} $(@alt $key_matcher_case)?)
+                                );
+                                remap_key!(key);
                                 let key_ref = &key;
                                 expand_key_types!(constant_counter, dynamic_counter, key_ref, __context);
                             }
@@ -454,6 +936,17 @@ pub mod macros {
                             )+)?
                         }
                         )*
+                        $(
+                        Self::$default_variant { $key_name, $($remaining,)? } => {
+                            {
+                                let key_ref = $key_name;
+                                expand_key_types!(constant_counter, dynamic_counter, key_ref, __context);
+                            }
+                            $(
+                            $crate::expand_field!(@internal @size_bind __context: ctx_type!(C), constant_counter, dynamic_counter, $remaining, $crate::transport::packet::vec::ByteDrain);
+                            )?
+                        }
+                        )?
                     }
 
                     if constant_counter == dynamic_counter {
@@ -462,22 +955,74 @@ pub mod macros {
                         Ok($crate::transport::packet::Size::Dynamic(dynamic_counter))
                     }
                 }
+
+                fn heap_size(component_ref: &Self) -> usize {
+                    match component_ref {
+                        $(
+                        Self::$variant_name $({$(
+                        $field_name,
+                        )+})? => {
+                            0 $($(
+                            + <$delegate_type as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::heap_size($field_name)
+                            )+)?
+                        }
+                        )*
+                        $(
+                        Self::$default_variant { $key_name, $($remaining,)? } => {
+                            <$key_delegate_type as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::heap_size($key_name)
+                            $(+ <$crate::transport::packet::vec::ByteDrain as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::heap_size($remaining))?
+                        }
+                        )?
+                    }
+                }
+
+                fn variant_name(component_ref: &Self) -> Option<&'static str> {
+                    Some(match component_ref {
+                        $(Self::$variant_name { .. } => stringify!($variant_name),)*
+                        $(Self::$default_variant { .. } => stringify!($default_variant),)?
+                    })
+                }
             });
         )*};
     }
 
     #[macro_export]
     macro_rules! expand_field {
-        (@internal @impl_bind $struct_name:ident, $field_name:ident { $($impl_tokens:tt)* }) => {
+        (@internal @impl_bind [@label] $struct_name:ident, $field_name:ident { $($impl_tokens:tt)* }) => {
+            impl<$field_name: Send + Sync + $crate::transport::error::ContextLabel> $crate::transport::packet::PacketComponent<$field_name> for $struct_name {
+                $($impl_tokens)*
+            }
+        };
+        (@internal @impl_bind [] $struct_name:ident, $field_name:ident { $($impl_tokens:tt)* }) => {
             impl<$field_name: Send + Sync> $crate::transport::packet::PacketComponent<$field_name> for $struct_name {
                 $($impl_tokens)*
             }
         };
-        (@internal @impl_bind $struct_name:ident, $__:ident @alt $ctx_ty:ty { $($impl_tokens:tt)* }) => {
+        (@internal @impl_bind [$($label:tt)*] $struct_name:ident, $__:ident @alt $ctx_ty:ty { $($impl_tokens:tt)* }) => {
             impl $crate::transport::packet::PacketComponent<$ctx_ty> for $struct_name {
                 $($impl_tokens)*
             }
         };
+        (@internal @max_size_check [$($label:tt)*] [] $name:ident, $component:ident, $context:ident: $ctx_ty:ty) => {};
+        (@internal @max_size_check [$($label:tt)*] [$max_size:expr] $name:ident, $component:ident, $context:ident: $ctx_ty:ty) => {
+            #[cfg(debug_assertions)]
+            {
+                let size = match <$name as $crate::transport::packet::PacketComponent<$ctx_ty>>::size($component, $context)? {
+                    $crate::transport::packet::Size::Dynamic(x) | $crate::transport::packet::Size::Constant(x) => x,
+                };
+                if size > $max_size {
+                    $crate::throw_explain!($crate::enum_packet_components!(
+                        @internal @labelled [$($label)*] $context,
+                        format!(
+                            "{} of {} bytes exceeded its declared maximum size of {} bytes",
+                            stringify!($name),
+                            size,
+                            $max_size
+                        )
+                    ))
+                }
+            }
+        };
         (@internal @ty_bind $typing:ty;) => {
             $typing
         };
@@ -488,7 +1033,10 @@ pub mod macros {
             <$delegate_type as $crate::transport::packet::PacketComponent<$ctx_ty>>::encode($field_name, $context, $w_ident).await?
         };
         (@internal @de_bind $context:ident: $ctx_ty:ty, $r_ident:ident, $field_name:ident, $delegate_type:ty) => {
-            let $field_name = <$delegate_type as $crate::transport::packet::PacketComponent<$ctx_ty>>::decode($context, $r_ident).await?;
+            $crate::__trace_field!($field_name = <$delegate_type as $crate::transport::packet::PacketComponent<$ctx_ty>>::decode_unboxed($context, $r_ident).await?);
+        };
+        (@internal @buf_bind $context:ident: $ctx_ty:ty, $b_ident:ident, $field_name:ident, $delegate_type:ty) => {
+            $crate::__trace_field!($field_name = <$delegate_type as $crate::transport::packet::PacketComponent<$ctx_ty>>::decode_from_buf($context, $b_ident)?);
         };
         (@internal @size_bind $context:ident: $ctx_ty:ty, $c_counter:ident, $d_counter:ident, $field_name:ident, $delegate_type:ty) => {
             match <$delegate_type as $crate::transport::packet::PacketComponent<$ctx_ty>>::size($field_name, $context)?
@@ -510,12 +1058,12 @@ pub mod macros {
 
     #[macro_export]
     macro_rules! struct_packet_components {
-        (@internal $(#[$($tt:tt)*])* @ $struct_name:ident) => {
+        (@internal $(#[$($tt:tt)*])* @derives {$($derive:path),*} @ $struct_name:ident) => {
             $(#[$($tt)*])*
-            #[derive(Debug)]
+            #[derive(Debug $(, $derive)*)]
             pub struct $struct_name;
         };
-        (@internal $(#[$($tt:tt)*])* @expand {$($ctx_ty_tt:tt)+} $(
+        (@internal $(#[$($tt:tt)*])* @derives {$($derive:path),*} @expand {$($ctx_ty_tt:tt)+} $(
             $(@describe($description:expr))?
             $field_name:ident: $(#[$($more_tt:tt)*])* $delegate_type:ty,
         )+ @ $struct_name:ident) => {
@@ -526,7 +1074,7 @@ pub mod macros {
             }
 
             $(#[$($tt)*])*
-            #[derive(Debug)]
+            #[derive(Debug $(, $derive)*)]
             pub struct $struct_name {
                 $(
                 $(#[$($more_tt)*])*
@@ -534,99 +1082,275 @@ pub mod macros {
                 )+
             }
         };
-        ($(
+        (
             $(#[$($tt:tt)*])*
+            @json
+            $(@derives($($derive:path),+ $(,)?))?
             $struct_name:ident$(<$ctx_ty:ty>)? {
             $(
-                $(
-                    $(#[$($doc_tt:tt)*])*
-                    $field_name:ident: $(#[$($more_tt:tt)*])* $delegate_type:ty
-                ),+
-            )?
-        })*) => {$(
+                $(#[$($field_tt:tt)*])*
+                $field_name:ident: $field_type:ty
+            ),* $(,)?
+        }) => {
             macro_rules! ctx_type {
                 ($$alt_ty:ty) => {
                     $crate::expand_field!(@internal @ty_bind $$alt_ty; $(@alt $ctx_ty)?)
                 };
             }
 
-            $crate::struct_packet_components!(@internal
-                $(#[$($tt)*])*
-                $(
-                ///
-                /// Component Field Breakdown
-                /// <br />
-                /// ---
-                #[doc="<table style=\"display=flex; justify-content: start; width: 100%\"><thead><tr><th>Field</th><th>Description</th></tr></thead><tbody>"]
-                $(
-                #[doc=concat!(
-                    "<tr><td>",
-                    stringify!($field_name),
-                    "</td><td>"
-                )]
-                #[doc=$crate::expand_field!(@internal @doc $(#[$($doc_tt)*])*)]
-                $(#[$($doc_tt)*])*
-                #[doc="</td></tr>"]
-                )+
-                #[doc="</tbody></table>"]
-                )?
+            $(#[$($tt)*])*
+            ///
+            /// Encoded as a length prefixed JSON document.
+            #[derive(Debug $($(, $derive)+)?)]
+            pub struct $struct_name {
                 $(
-                    @expand {ctx_type!(())} $(
-                        $field_name: $(#[$($more_tt)*])* $delegate_type,
-                    )+
-                )?
-                @ $struct_name
-            );
+                $(#[$($field_tt)*])*
+                pub $field_name: $field_type,
+                )*
+            }
 
-            $crate::expand_field!(@internal @impl_bind $struct_name, C $(@alt $ctx_ty)? {
+            $crate::expand_field!(@internal @impl_bind [] $struct_name, C $(@alt $ctx_ty)? {
                 type ComponentType = Self;
 
                 fn decode<'a, A: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(
                     __context: &'a mut ctx_type!(C),
                     __read: &'a mut A,
-                ) -> $crate::PinnedLivelyResult<'a, Self::ComponentType>
-                where
-                    Self: Sized,
-                {
-                    Box::pin(async move {
-                        $($(
-                            $crate::expand_field!(@internal @de_bind __context: ctx_type!(C), __read, $field_name, $delegate_type);
-                        )+)?
-                        Ok(Self $({
-                            $(
-                                $field_name,
-                            )+
-                        })?)
-                    })
+                ) -> $crate::PinnedLivelyResult<'a, Self::ComponentType> {
+                    <$crate::transport::packet::serde_json::JsonDelegate<Self> as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::decode(__context, __read)
                 }
 
-                fn encode <'a, A: $crate::prelude::AsyncWrite + Unpin + Send + Sync + ?Sized> (
+                fn encode<'a, A: $crate::prelude::AsyncWrite + Unpin + Send + Sync + ?Sized>(
                     component_ref: &'a Self,
                     __context: &'a mut ctx_type!(C),
-                    __write: & 'a mut A,
+                    __write: &'a mut A,
                 ) -> $crate::PinnedLivelyResult<'a, ()> {
-                    Box::pin(async move {
-                        $($(
-                        {
-                            let __temp = &component_ref.$field_name;
-                            $crate::expand_field!(@internal @ser_bind __context: ctx_type!(C), __write, __temp, $delegate_type);
-                        }
-                        )+)?
-                        Ok(())
-                    })
+                    <$crate::transport::packet::serde_json::JsonDelegate<Self> as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::encode(component_ref, __context, __write)
                 }
 
                 fn size(component_ref: &Self, __context: &mut ctx_type!(C)) -> $crate::transport::Result<$crate::transport::packet::Size> {
-                    let constant_counter = 0;
-                    let dynamic_counter = 0;
-
-                    $(
-                    let mut constant_counter = constant_counter;
-                    let mut dynamic_counter = dynamic_counter;
-                    $({
-                        let __temp = & component_ref.$field_name;
-                        $crate::expand_field!(@internal @size_bind __context: ctx_type!(C), constant_counter, dynamic_counter, __temp, $delegate_type);
-                    })+
+                    <$crate::transport::packet::serde_json::JsonDelegate<Self> as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::size(component_ref, __context)
+                }
+            });
+        };
+        (
+            $(#[$($tt:tt)*])*
+            @nbt
+            $(@derives($($derive:path),+ $(,)?))?
+            $struct_name:ident$(<$ctx_ty:ty>)? {
+            $(
+                $(#[$($field_tt:tt)*])*
+                $field_name:ident: $field_type:ty
+            ),* $(,)?
+        }) => {
+            macro_rules! ctx_type {
+                ($$alt_ty:ty) => {
+                    $crate::expand_field!(@internal @ty_bind $$alt_ty; $(@alt $ctx_ty)?)
+                };
+            }
+
+            $(#[$($tt)*])*
+            ///
+            /// Encoded as a compound tag with an entry per field.
+            #[derive(Debug $($(, $derive)+)?)]
+            pub struct $struct_name {
+                $(
+                $(#[$($field_tt)*])*
+                pub $field_name: $field_type,
+                )*
+            }
+
+            impl $crate::nbt::NbtCompound for $struct_name {
+                fn to_compound(&self) -> $crate::nbt::Compound {
+                    $crate::nbt::Compound::new()
+                        $(.with(stringify!($field_name), self.$field_name.clone()))*
+                }
+
+                fn from_compound(compound: $crate::nbt::Compound) -> $crate::prelude::Result<Self> {
+                    Ok(Self {
+                        $(
+                        $field_name: match compound.get(stringify!($field_name)) {
+                            Some(tag) => <$field_type>::try_from(tag)?,
+                            None => $crate::throw_explain!(format!(
+                                "Missing entry {} of {}",
+                                stringify!($field_name),
+                                stringify!($struct_name)
+                            )),
+                        },
+                        )*
+                    })
+                }
+            }
+
+            $crate::expand_field!(@internal @impl_bind [] $struct_name, C $(@alt $ctx_ty)? {
+                type ComponentType = Self;
+
+                fn decode<'a, A: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(
+                    __context: &'a mut ctx_type!(C),
+                    __read: &'a mut A,
+                ) -> $crate::PinnedLivelyResult<'a, Self::ComponentType> {
+                    <$crate::nbt::NbtDelegate<Self> as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::decode(__context, __read)
+                }
+
+                fn encode<'a, A: $crate::prelude::AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                    component_ref: &'a Self,
+                    __context: &'a mut ctx_type!(C),
+                    __write: &'a mut A,
+                ) -> $crate::PinnedLivelyResult<'a, ()> {
+                    <$crate::nbt::NbtDelegate<Self> as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::encode(component_ref, __context, __write)
+                }
+
+                fn size(component_ref: &Self, __context: &mut ctx_type!(C)) -> $crate::transport::Result<$crate::transport::packet::Size> {
+                    <$crate::nbt::NbtDelegate<Self> as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::size(component_ref, __context)
+                }
+
+                fn heap_size(component_ref: &Self) -> usize {
+                    <$crate::nbt::NbtDelegate<Self> as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::heap_size(component_ref)
+                }
+            });
+        };
+        ($(
+            $(#[$($tt:tt)*])*
+            $(@derives($($derive:path),+ $(,)?))?
+            $(@max_size($max_size:expr))?
+            $(@round_trip($test_name:ident $(, $sample:expr)?))?
+            $(@label $($__label:lifetime)?)? $(@arbitrary $($__arbitrary:lifetime)?)?
+            $struct_name:ident$(<$ctx_ty:ty>)? {
+            $(
+                $(
+                    $(#[$($doc_tt:tt)*])*
+                    $field_name:ident: $(#[$($more_tt:tt)*])* $delegate_type:ty
+                ),+
+            )?
+        })*) => {$(
+            macro_rules! ctx_type {
+                ($$alt_ty:ty) => {
+                    $crate::expand_field!(@internal @ty_bind $$alt_ty; $(@alt $ctx_ty)?)
+                };
+            }
+
+            $crate::struct_packet_components!(@internal
+                $(#[$($tt)*])*
+                $(
+                ///
+                /// Component Field Breakdown
+                /// <br />
+                /// ---
+                #[doc="<table style=\"display=flex; justify-content: start; width: 100%\"><thead><tr><th>Field</th><th>Description</th></tr></thead><tbody>"]
+                $(
+                #[doc=concat!(
+                    "<tr><td>",
+                    stringify!($field_name),
+                    "</td><td>"
+                )]
+                #[doc=$crate::expand_field!(@internal @doc $(#[$($doc_tt)*])*)]
+                $(#[$($doc_tt)*])*
+                #[doc="</td></tr>"]
+                )+
+                #[doc="</tbody></table>"]
+                )?
+                @derives {$($($derive),+)?}
+                $(
+                    @expand {ctx_type!(())} $(
+                        $field_name: $(#[$($more_tt)*])* $delegate_type,
+                    )+
+                )?
+                @ $struct_name
+            );
+
+            $crate::__arbitrary_component!([$(@arbitrary $($__arbitrary)?)?] @struct [$(@label $($__label)?)?] $struct_name, ctx_type!(()), C $(@alt $ctx_ty)? {
+                $($($field_name: $delegate_type),+)?
+            });
+
+            $($crate::__round_trip_test!($test_name, ctx_type!(()), $struct_name $(, $sample)?);)?
+
+            $crate::__max_size_test!([$(@arbitrary $($__arbitrary)?)?] [$($max_size)?] $struct_name, ctx_type!(()));
+
+            impl $struct_name {
+                /// The name and delegate type of each field, in encoding order.
+                #[allow(dead_code)]
+                pub const FIELDS: &'static [(&'static str, &'static str)] = &[$($(
+                    (stringify!($field_name), stringify!($delegate_type)),
+                )+)?];
+            }
+
+            $crate::expand_field!(@internal @impl_bind [$(@label $($__label)?)?] $struct_name, C $(@alt $ctx_ty)? {
+                type ComponentType = Self;
+
+                const MAX_SIZE: Option<usize> = $crate::transport::packet::max_size_sum(&[$($(
+                    <$delegate_type as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::MAX_SIZE,
+                )+)?]);
+
+                fn decode<'a, A: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(
+                    __context: &'a mut ctx_type!(C),
+                    __read: &'a mut A,
+                ) -> $crate::PinnedLivelyResult<'a, Self::ComponentType>
+                where
+                    Self: Sized,
+                {
+                    Box::pin(<Self as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::decode_unboxed(__context, __read))
+                }
+
+                fn decode_unboxed<'a, A: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(
+                    __context: &'a mut ctx_type!(C),
+                    __read: &'a mut A,
+                ) -> impl ::std::future::Future<Output = $crate::transport::Result<Self::ComponentType>> + Send + Sync + 'a
+                where
+                    Self::ComponentType: 'a,
+                {
+                    async move {
+                        $($(
+                            $crate::expand_field!(@internal @de_bind __context: ctx_type!(C), __read, $field_name, $delegate_type);
+                        )+)?
+                        Ok(Self $({
+                            $(
+                                $field_name,
+                            )+
+                        })?)
+                    }
+                }
+
+                fn decode_from_buf(
+                    __context: &mut ctx_type!(C),
+                    __buf: &mut $crate::transport::packet::Bytes,
+                ) -> $crate::transport::Result<Self::ComponentType> {
+                    $($(
+                        $crate::expand_field!(@internal @buf_bind __context: ctx_type!(C), __buf, $field_name, $delegate_type);
+                    )+)?
+                    Ok(Self $({
+                        $(
+                            $field_name,
+                        )+
+                    })?)
+                }
+
+                fn encode <'a, A: $crate::prelude::AsyncWrite + Unpin + Send + Sync + ?Sized> (
+                    component_ref: &'a Self,
+                    __context: &'a mut ctx_type!(C),
+                    __write: & 'a mut A,
+                ) -> $crate::PinnedLivelyResult<'a, ()> {
+                    Box::pin(async move {
+                        $crate::expand_field!(@internal @max_size_check [$(@label $($__label)?)?] [$($max_size)?] $struct_name, component_ref, __context: ctx_type!(C));
+                        $($(
+                        {
+                            let __temp = &component_ref.$field_name;
+                            $crate::expand_field!(@internal @ser_bind __context: ctx_type!(C), __write, __temp, $delegate_type);
+                        }
+                        )+)?
+                        Ok(())
+                    })
+                }
+
+                fn size(component_ref: &Self, __context: &mut ctx_type!(C)) -> $crate::transport::Result<$crate::transport::packet::Size> {
+                    let constant_counter = 0;
+                    let dynamic_counter = 0;
+
+                    $(
+                    let mut constant_counter = constant_counter;
+                    let mut dynamic_counter = dynamic_counter;
+                    $({
+                        let __temp = & component_ref.$field_name;
+                        $crate::expand_field!(@internal @size_bind __context: ctx_type!(C), constant_counter, dynamic_counter, __temp, $delegate_type);
+                    })+
                     )?
 
                     if constant_counter == dynamic_counter {
@@ -635,9 +1359,308 @@ pub mod macros {
                         Ok($crate::transport::packet::Size::Dynamic(dynamic_counter))
                     }
                 }
+
+                fn heap_size(component_ref: &Self) -> usize {
+                    0 $($(
+                    + <$delegate_type as $crate::transport::packet::PacketComponent<ctx_type!(C)>>::heap_size(&component_ref.$field_name)
+                    )+)?
+                }
             });
         )*};
     }
+
+    /// Declares structs of bools and small unsigned integers packed into a single integer field
+    /// `$repr`, such as `u8` or `u64`, which is how the struct is encoded. Fields are packed from
+    /// the least significant bit up in declaration order; bools take one bit and integers take the
+    /// width given after `=>`. Encoding an integer which doesn't fit its width is an error.
+    ///
+    /// ```
+    /// # #![feature(macro_metavar_expr)]
+    /// drax::bit_fields! {
+    ///     @derives(Clone, PartialEq)
+    ///     PlayerFlags: u8 {
+    ///         on_ground: bool,
+    ///         collided: bool,
+    ///         mode: u8 => 3
+    ///     }
+    /// }
+    /// # let flags = PlayerFlags { on_ground: true, collided: false, mode: 2 };
+    /// # assert_eq!(flags.pack().unwrap(), 0b1001);
+    /// ```
+    #[macro_export]
+    macro_rules! bit_fields {
+        (@width) => {
+            1
+        };
+        (@width $width:literal) => {
+            $width
+        };
+        (@unpack $packed:ident, $repr:ty, $offset:expr, $field_ty:ty) => {
+            ($packed >> $offset) & 1 != 0
+        };
+        (@unpack $packed:ident, $repr:ty, $offset:expr, $field_ty:ty, $width:literal) => {
+            (($packed >> $offset) & (<$repr>::MAX >> (<$repr>::BITS - $width))) as $field_ty
+        };
+        ($(
+            $(#[$($tt:tt)*])*
+            $(@derives($($derive:path),+ $(,)?))?
+            $name:ident: $repr:ty {
+                $(
+                    $(#[$($field_tt:tt)*])*
+                    $field_name:ident: $field_ty:ty $(=> $width:literal)?
+                ),* $(,)?
+            }
+        )*) => {$(
+            $(#[$($tt)*])*
+            #[derive(Debug $($(, $derive)+)?)]
+            pub struct $name {
+                $(
+                    $(#[$($field_tt)*])*
+                    pub $field_name: $field_ty,
+                )*
+            }
+
+            impl $name {
+                const WIDTHS: &'static [u32] = &[$($crate::bit_fields!(@width $($width)?)),*];
+
+                /// Packs the fields into their encoded integer.
+                #[allow(clippy::unnecessary_cast)]
+                pub fn pack(&self) -> $crate::prelude::Result<$repr> {
+                    let mut packed: $repr = 0;
+                    $(
+                        // checked before narrowing so high bits of wider fields aren't dropped
+                        let wide = self.$field_name as u128;
+                        let width = Self::WIDTHS[${index()}];
+                        if width < u128::BITS && wide >> width != 0 {
+                            $crate::throw_explain!(format!(
+                                "Field {} of {} does not fit in {} bits",
+                                stringify!($field_name),
+                                stringify!($name),
+                                width
+                            ))
+                        }
+                        packed |= (wide as $repr) << $crate::transport::packet::bits::bit_offset(Self::WIDTHS, ${index()});
+                    )*
+                    Ok(packed)
+                }
+
+                /// Unpacks the fields from their encoded integer.
+                pub fn unpack(packed: $repr) -> Self {
+                    Self {
+                        $(
+                            $field_name: $crate::bit_fields!(
+                                @unpack packed,
+                                $repr,
+                                $crate::transport::packet::bits::bit_offset(Self::WIDTHS, ${index()}),
+                                $field_ty
+                                $(, $width)?
+                            ),
+                        )*
+                    }
+                }
+            }
+
+            const _: () = assert!(
+                $crate::transport::packet::bits::bit_offset($name::WIDTHS, $name::WIDTHS.len())
+                    <= <$repr>::BITS,
+                concat!("Fields of ", stringify!($name), " do not fit in ", stringify!($repr))
+            );
+
+            impl<C: Send + Sync> $crate::transport::packet::PacketComponent<C> for $name {
+                type ComponentType = Self;
+
+                const MAX_SIZE: Option<usize> = Some(std::mem::size_of::<$repr>());
+
+                fn decode<'a, A: $crate::prelude::AsyncRead + Unpin + Send + Sync + ?Sized>(
+                    context: &'a mut C,
+                    read: &'a mut A,
+                ) -> $crate::PinnedLivelyResult<'a, Self::ComponentType> {
+                    Box::pin(async move {
+                        let packed =
+                            <$repr as $crate::transport::packet::PacketComponent<C>>::decode(context, read).await?;
+                        Ok(Self::unpack(packed))
+                    })
+                }
+
+                fn encode<'a, A: $crate::prelude::AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                    component_ref: &'a Self,
+                    context: &'a mut C,
+                    write: &'a mut A,
+                ) -> $crate::PinnedLivelyResult<'a, ()> {
+                    Box::pin(async move {
+                        let packed = component_ref.pack()?;
+                        <$repr as $crate::transport::packet::PacketComponent<C>>::encode(&packed, context, write).await
+                    })
+                }
+
+                fn size(_: &Self, _: &mut C) -> $crate::prelude::Result<$crate::prelude::Size> {
+                    Ok($crate::prelude::Size::Constant(std::mem::size_of::<$repr>()))
+                }
+            }
+        )*};
+    }
+
+    /// Generates the `#[test]` requested by `@round_trip(test_name)` or
+    /// `@round_trip(test_name, sample)` on a macro defined component, which round trips the sample,
+    /// or `Default::default()` without one, through `testing::assert_round_trip` with a default
+    /// context. The generated test needs the `test` feature of drax, such as through
+    /// `dev-dependencies`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __round_trip_test {
+        ($test_name:ident, $ctx_ty:ty, $component:ty) => {
+            $crate::__round_trip_test!($test_name, $ctx_ty, $component, Default::default());
+        };
+        ($test_name:ident, $ctx_ty:ty, $component:ty, $sample:expr) => {
+            #[cfg(test)]
+            #[test]
+            fn $test_name() {
+                $crate::testing::assert_round_trip::<$ctx_ty, $component>(
+                    &mut Default::default(),
+                    &$sample,
+                );
+            }
+        };
+    }
+
+    /// Implements `Arbitrary` and `ArbitraryComponent` for macro defined components marked with
+    /// `@arbitrary`, generating each field with its delegate so generated values always respect
+    /// the delegate's bounds. Every field's delegate must implement `ArbitraryComponent`.
+    #[cfg(feature = "arbitrary")]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __arbitrary_component {
+        ([] $($tt:tt)*) => {};
+        ([@arbitrary] $($tt:tt)*) => {
+            $crate::__arbitrary_component!($($tt)*);
+        };
+        (@struct [$($label:tt)*] $name:ident, $ctx:ty, C $(@alt $ctx_ty:ty)? { $($field_name:ident: $delegate_type:ty),* }) => {
+            impl<'__a> $crate::testing::arbitrary::Arbitrary<'__a> for $name {
+                fn arbitrary(
+                    __u: &mut $crate::testing::arbitrary::Unstructured<'__a>,
+                ) -> $crate::testing::arbitrary::Result<Self> {
+                    Ok(Self {
+                        $(
+                        $field_name: <$delegate_type as $crate::testing::arbitrary::ArbitraryComponent<$ctx>>::arbitrary_component(__u)?,
+                        )*
+                    })
+                }
+            }
+
+            $crate::__arbitrary_component!(@bind [$($label)*] $name, C $(@alt $ctx_ty)?);
+        };
+        (@enum [$($label:tt)*] $name:ident, $ctx:ty, C $(@alt $ctx_ty:ty)? {
+            $($variant_name:ident $({ $($field_name:ident: $delegate_type:ty),+ })?),*
+        }) => {
+            impl<'__a> $crate::testing::arbitrary::Arbitrary<'__a> for $name {
+                fn arbitrary(
+                    __u: &mut $crate::testing::arbitrary::Unstructured<'__a>,
+                ) -> $crate::testing::arbitrary::Result<Self> {
+                    let __variants: &[fn(
+                        &mut $crate::testing::arbitrary::Unstructured<'__a>,
+                    ) -> $crate::testing::arbitrary::Result<Self>] = &[$(
+                        |__u| {
+                            let _ = &__u;
+                            Ok(Self::$variant_name $({
+                                $(
+                                $field_name: <$delegate_type as $crate::testing::arbitrary::ArbitraryComponent<$ctx>>::arbitrary_component(__u)?,
+                                )+
+                            })?)
+                        }
+                    ),*];
+                    let __index = __u.choose_index(__variants.len())?;
+                    __variants[__index](__u)
+                }
+            }
+
+            $crate::__arbitrary_component!(@bind [$($label)*] $name, C $(@alt $ctx_ty)?);
+        };
+        (@bind [@label] $name:ident, $c:ident) => {
+            impl<$c: Send + Sync + $crate::transport::error::ContextLabel> $crate::testing::arbitrary::ArbitraryComponent<$c> for $name {
+                fn arbitrary_component(
+                    u: &mut $crate::testing::arbitrary::Unstructured<'_>,
+                ) -> $crate::testing::arbitrary::Result<Self> {
+                    $crate::testing::arbitrary::Arbitrary::arbitrary(u)
+                }
+            }
+        };
+        (@bind [] $name:ident, $c:ident) => {
+            impl<$c: Send + Sync> $crate::testing::arbitrary::ArbitraryComponent<$c> for $name {
+                fn arbitrary_component(
+                    u: &mut $crate::testing::arbitrary::Unstructured<'_>,
+                ) -> $crate::testing::arbitrary::Result<Self> {
+                    $crate::testing::arbitrary::Arbitrary::arbitrary(u)
+                }
+            }
+        };
+        (@bind [$($label:tt)*] $name:ident, $__:ident @alt $ctx_ty:ty) => {
+            impl $crate::testing::arbitrary::ArbitraryComponent<$ctx_ty> for $name {
+                fn arbitrary_component(
+                    u: &mut $crate::testing::arbitrary::Unstructured<'_>,
+                ) -> $crate::testing::arbitrary::Result<Self> {
+                    $crate::testing::arbitrary::Arbitrary::arbitrary(u)
+                }
+            }
+        };
+    }
+
+    #[cfg(not(feature = "arbitrary"))]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __arbitrary_component {
+        ($($tt:tt)*) => {};
+    }
+
+    /// Generates a `#[test]` for macro defined components marked with both `@max_size(N)` and
+    /// `@arbitrary`, asserting that generated values are never sized larger than `N` through
+    /// `testing::arbitrary::assert_max_size`. The test function takes the component's name, which
+    /// is free as the components are braced structs and enums, which only name a type.
+    #[cfg(feature = "arbitrary")]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __max_size_test {
+        ([@arbitrary] [$max_size:expr] $name:ident, $ctx_ty:ty) => {
+            #[cfg(test)]
+            #[test]
+            #[allow(non_snake_case)]
+            fn $name() {
+                $crate::testing::arbitrary::assert_max_size::<$ctx_ty, $name>(
+                    &mut Default::default(),
+                    $max_size,
+                );
+            }
+        };
+        ($($tt:tt)*) => {};
+    }
+
+    #[cfg(not(feature = "arbitrary"))]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __max_size_test {
+        ($($tt:tt)*) => {};
+    }
+
+    /// Binds a field decoded by the component macros, marking its start and end in the trace
+    /// active on the current thread, if any.
+    #[cfg(feature = "debug-trace")]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __trace_field {
+        ($field_name:ident = $decode:expr) => {
+            $crate::transport::debug::enter_field(stringify!($field_name));
+            let $field_name = $decode;
+            $crate::transport::debug::exit_field(&$field_name);
+        };
+    }
+
+    #[cfg(not(feature = "debug-trace"))]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __trace_field {
+        ($field_name:ident = $decode:expr) => {
+            let $field_name = $decode;
+        };
+    }
 }
 
 #[cfg(feature = "tcp-shield")]
@@ -697,6 +1720,7 @@ mod test {
     use std::io::Cursor;
 
     use crate::prelude::{PacketComponent, Size};
+    use crate::transport::packet::option::Maybe;
     use crate::transport::packet::primitive::VarInt;
 
     crate::struct_packet_components! {
@@ -714,6 +1738,7 @@ mod test {
 
     crate::enum_packet_components! {
         #[derive(Eq, PartialEq)]
+        @label
         ExampleEnum {
             key: VarInt,
             Variant1 {
@@ -727,6 +1752,686 @@ mod test {
         }
     }
 
+    crate::struct_packet_components! {
+        @derives(Clone, PartialEq)
+        DerivedExample {
+            v_int: VarInt,
+            name: String
+        }
+    }
+
+    crate::struct_packet_components! {
+        @derives(Default, PartialEq)
+        @round_trip(test_round_trip_default)
+        RoundTripDefault<String> {
+            v_int: VarInt,
+            name: String
+        }
+    }
+
+    crate::components! {
+        @derives(PartialEq)
+        @round_trip(test_round_trip_sample, RoundTripEnum::Named { v_int: 300, name: "drax".to_string() })
+        enum RoundTripEnum<key: VarInt> {
+            Empty {},
+            Named {
+                v_int: VarInt,
+                name: String
+            }
+        }
+    }
+
+    crate::enum_packet_components! {
+        @derives(Clone, PartialEq, Eq)
+        DerivedExampleEnum {
+            key: VarInt,
+            Variant1 {
+                v_int: VarInt
+            },
+            Variant2 {}
+        }
+    }
+
+    crate::struct_packet_components! {
+        MarkedExample {
+            v_int: VarInt,
+            marker: std::marker::PhantomData<String>
+        }
+    }
+
+    crate::enum_packet_components! {
+        HeapEnum {
+            key: VarInt,
+            Names {
+                names: Vec<String>
+            },
+            Empty {}
+        }
+    }
+
+    #[test]
+    fn test_heap_size() {
+        let example = DerivedExample {
+            v_int: 25,
+            name: String::with_capacity(32),
+        };
+        assert_eq!(
+            <DerivedExample as PacketComponent<()>>::heap_size(&example),
+            32
+        );
+
+        let mut names = Vec::with_capacity(4);
+        names.push(String::with_capacity(10));
+        let expected = 4 * std::mem::size_of::<String>() + 10;
+        let names = HeapEnum::Names { names };
+        assert_eq!(
+            <HeapEnum as PacketComponent<()>>::heap_size(&names),
+            expected
+        );
+        assert_eq!(
+            <HeapEnum as PacketComponent<()>>::heap_size(&HeapEnum::Empty {}),
+            0
+        );
+        assert_eq!(
+            <Maybe<Box<String>> as PacketComponent<()>>::heap_size(&Some(Box::new(
+                String::with_capacity(8)
+            ))),
+            std::mem::size_of::<String>() + 8
+        );
+    }
+
+    #[tokio::test]
+    async fn test_phantom_field() -> crate::prelude::Result<()> {
+        let example = MarkedExample {
+            v_int: 25,
+            marker: std::marker::PhantomData,
+        };
+        assert_eq!(MarkedExample::size(&example, &mut ())?, Size::Dynamic(1));
+
+        let mut cursor = Cursor::new(vec![]);
+        MarkedExample::encode(&example, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![25]);
+
+        let mut cursor = Cursor::new(cursor.into_inner());
+        let decoded = MarkedExample::decode(&mut (), &mut cursor).await?;
+        assert_eq!(decoded.v_int, 25);
+        Ok(())
+    }
+
+    crate::enum_packet_components! {
+        #[derive(Eq, PartialEq)]
+        DenseEnum {
+            key: VarInt,
+            @dense(1),
+            2 => Second {},
+            1 => First {
+                v_int: VarInt
+            },
+            3 => Third {}
+        }
+    }
+
+    pub struct VersionContext {
+        table: crate::transport::id::RemapTable,
+    }
+
+    impl crate::transport::id::IdRemapper for VersionContext {
+        fn wire_id(&self, table: &str, key: i32) -> Option<i32> {
+            assert_eq!(table, "play");
+            Some(self.table.wire_id(key))
+        }
+
+        fn declared_key(&self, table: &str, id: i32) -> Option<i32> {
+            assert_eq!(table, "play");
+            self.table.declared_key(id)
+        }
+    }
+
+    crate::enum_packet_components! {
+        #[derive(Eq, PartialEq)]
+        RemappedEnum<VersionContext> {
+            key: VarInt,
+            @remap("play"),
+            Ping {
+                v_int: VarInt
+            },
+            Pong {}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remapped_keys() -> crate::prelude::Result<()> {
+        let mut context = VersionContext {
+            table: crate::transport::id::RemapTable::new([(0, 200), (1, 0)]),
+        };
+        let ping = RemappedEnum::Ping { v_int: 5 };
+        assert_eq!(RemappedEnum::size(&ping, &mut context)?, Size::Dynamic(3));
+
+        let mut cursor = Cursor::new(vec![]);
+        RemappedEnum::encode(&ping, &mut context, &mut cursor).await?;
+        RemappedEnum::encode(&RemappedEnum::Pong {}, &mut context, &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![200, 1, 5, 0]);
+
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(RemappedEnum::decode(&mut context, &mut cursor).await?, ping);
+        assert_eq!(
+            RemappedEnum::decode(&mut context, &mut cursor).await?,
+            RemappedEnum::Pong {}
+        );
+
+        // Declared key 1 is written as 0, so an unmapped 1 on the wire is unknown.
+        let mut cursor = Cursor::new(vec![1]);
+        assert!(RemappedEnum::decode(&mut context, &mut cursor)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    crate::enum_packet_components! {
+        #[derive(Eq, PartialEq)]
+        RemappedFallback<VersionContext> {
+            key: VarInt,
+            @remap("play"),
+            @default(Unknown, rest),
+            Ping {
+                v_int: VarInt
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remapped_default() -> crate::prelude::Result<()> {
+        let mut context = VersionContext {
+            table: crate::transport::id::RemapTable::new([(0, 200), (1, 0)]),
+        };
+        // 1 is only known as the wire id of declared key 0, so it fails to remap.
+        let mut cursor = Cursor::new(vec![1, 9]);
+        let unknown = RemappedFallback::decode(&mut context, &mut cursor).await?;
+        assert_eq!(
+            unknown,
+            RemappedFallback::Unknown {
+                key: 1,
+                rest: vec![9]
+            }
+        );
+
+        let mut cursor = Cursor::new(vec![]);
+        RemappedFallback::encode(&unknown, &mut context, &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![1, 9]);
+
+        // 200 remaps to declared key 0 and decodes as Ping.
+        let mut cursor = Cursor::new(vec![200, 1, 5]);
+        assert_eq!(
+            RemappedFallback::decode(&mut context, &mut cursor).await?,
+            RemappedFallback::Ping { v_int: 5 }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dense_keys() -> crate::prelude::Result<()> {
+        assert!(super::keys_dense(&[2, 1, 3], 1));
+        assert!(!super::keys_dense(&[0, 1, 3], 0));
+        assert!(!super::keys_dense(&[0, 0, 1], 0));
+
+        let mut cursor = Cursor::new(vec![]);
+        DenseEnum::encode(&DenseEnum::First { v_int: 25 }, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![1, 25]);
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(
+            DenseEnum::decode(&mut (), &mut cursor).await?,
+            DenseEnum::First { v_int: 25 }
+        );
+        Ok(())
+    }
+
+    crate::enum_packet_components! {
+        @derives(PartialEq)
+        FallbackEnum {
+            key: VarInt,
+            @default(Unknown, rest),
+            1 => Known {
+                v_int: VarInt
+            }
+        }
+    }
+
+    crate::components! {
+        @derives(PartialEq)
+        enum KeyOnlyFallback<key: String> {
+            @default(Unknown),
+            Brand {
+                @key("minecraft:brand");
+                brand: String
+            }
+        }
+    }
+
+    crate::components! {
+        @derives(PartialEq)
+        enum SharedKeyEnum<key: crate::transport::packet::buf::BytesStr> {
+            Brand {
+                @key("minecraft:brand");
+                brand: String
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_str_key_cases() -> crate::prelude::Result<()> {
+        let brand = SharedKeyEnum::Brand {
+            brand: "drax".to_string(),
+        };
+        let mut cursor = Cursor::new(vec![]);
+        SharedKeyEnum::encode(&brand, &mut (), &mut cursor).await?;
+        cursor.set_position(0);
+        assert_eq!(SharedKeyEnum::decode(&mut (), &mut cursor).await?, brand);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_variant() -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![1, 25]);
+        assert_eq!(
+            FallbackEnum::decode(&mut (), &mut cursor).await?,
+            FallbackEnum::Known { v_int: 25 }
+        );
+
+        let mut cursor = Cursor::new(vec![7, 1, 2, 3]);
+        let unknown = FallbackEnum::decode(&mut (), &mut cursor).await?;
+        assert_eq!(
+            unknown,
+            FallbackEnum::Unknown {
+                key: 7,
+                rest: vec![1, 2, 3]
+            }
+        );
+        assert_eq!(FallbackEnum::size(&unknown, &mut ())?, Size::Dynamic(4));
+        let mut cursor = Cursor::new(vec![]);
+        FallbackEnum::encode(&unknown, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![7, 1, 2, 3]);
+        assert_eq!(FallbackEnum::MAX_VARIANT_SIZE, None);
+
+        let unknown = KeyOnlyFallback::Unknown {
+            key: "drax:custom".to_string(),
+        };
+        let mut cursor = Cursor::new(vec![]);
+        KeyOnlyFallback::encode(&unknown, &mut (), &mut cursor).await?;
+        cursor.get_mut().push(9);
+        cursor.set_position(0);
+        assert_eq!(
+            KeyOnlyFallback::decode(&mut (), &mut cursor).await?,
+            unknown
+        );
+        // only the key is consumed
+        assert_eq!(u8::decode(&mut (), &mut cursor).await?, 9);
+
+        assert_eq!(
+            <KeyOnlyFallback as PacketComponent<()>>::variant_name(&unknown),
+            Some("Unknown")
+        );
+        assert_eq!(
+            <Box<FallbackEnum> as PacketComponent<()>>::variant_name(&Box::new(
+                FallbackEnum::Known { v_int: 1 }
+            )),
+            Some("Known")
+        );
+        assert_eq!(<VarInt as PacketComponent<()>>::variant_name(&1), None);
+        Ok(())
+    }
+
+    crate::enum_packet_components! {
+        @derives(PartialEq)
+        StringKeyed {
+            key: String,
+            "minecraft:brand" => Brand {
+                brand: String
+            },
+            "minecraft:register" => Register {}
+        }
+    }
+
+    crate::components! {
+        @derives(PartialEq)
+        enum LimitedKeyed<key: crate::transport::packet::string::LimitedString<8>> {
+            Short {
+                @key("short");
+                v_int: VarInt
+            },
+            Empty {
+                @key("");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_string_keys() -> crate::prelude::Result<()> {
+        let brand = StringKeyed::Brand {
+            brand: "drax".to_string(),
+        };
+        let mut cursor = Cursor::new(vec![]);
+        StringKeyed::encode(&brand, &mut (), &mut cursor).await?;
+        StringKeyed::encode(&StringKeyed::Register {}, &mut (), &mut cursor).await?;
+        assert_eq!(&cursor.get_ref()[..16], b"\x0fminecraft:brand");
+        assert_eq!(StringKeyed::size(&brand, &mut ())?, Size::Dynamic(16 + 5));
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(StringKeyed::decode(&mut (), &mut cursor).await?, brand);
+        assert_eq!(
+            StringKeyed::decode(&mut (), &mut cursor).await?,
+            StringKeyed::Register {}
+        );
+        let mut cursor = Cursor::new(b"\x05other".to_vec());
+        assert!(StringKeyed::decode(&mut (), &mut cursor).await.is_err());
+
+        let mut cursor = Cursor::new(vec![]);
+        LimitedKeyed::encode(&LimitedKeyed::Short { v_int: 7 }, &mut (), &mut cursor).await?;
+        LimitedKeyed::encode(&LimitedKeyed::Empty {}, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &b"\x05short\x07\x00".to_vec());
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(
+            LimitedKeyed::decode(&mut (), &mut cursor).await?,
+            LimitedKeyed::Short { v_int: 7 }
+        );
+        assert_eq!(
+            LimitedKeyed::decode(&mut (), &mut cursor).await?,
+            LimitedKeyed::Empty {}
+        );
+        Ok(())
+    }
+
+    crate::bit_fields! {
+        @derives(PartialEq)
+        PackedFlags: u8 {
+            on_ground: bool,
+            collided: bool,
+            mode: u8 => 3
+        }
+        WideFlags: u64 {
+            low: u32 => 32,
+            high: u32 => 32
+        }
+        NarrowFlags: u8 {
+            value: u16 => 8
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bit_fields() -> crate::prelude::Result<()> {
+        let flags = PackedFlags {
+            on_ground: true,
+            collided: false,
+            mode: 5,
+        };
+        assert_eq!(flags.pack()?, 0b10101);
+        assert_eq!(PackedFlags::unpack(0b10101), flags);
+        let mut cursor = Cursor::new(vec![]);
+        PackedFlags::encode(&flags, &mut (), &mut cursor).await?;
+        assert!(
+            PackedFlags::encode(&PackedFlags { mode: 8, ..flags }, &mut (), &mut cursor)
+                .await
+                .is_err()
+        );
+        assert_eq!(cursor.get_ref(), &vec![0b10101]);
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(PackedFlags::decode(&mut (), &mut cursor).await?, flags);
+
+        let wide = WideFlags {
+            low: u32::MAX,
+            high: 1,
+        };
+        assert_eq!(wide.pack()?, (1 << 32) | u32::MAX as u64);
+        let unpacked = WideFlags::unpack(wide.pack()?);
+        assert_eq!((unpacked.low, unpacked.high), (u32::MAX, 1));
+        assert_eq!(<WideFlags as PacketComponent<()>>::MAX_SIZE, Some(8));
+
+        assert_eq!(NarrowFlags { value: 255 }.pack()?, 255);
+        assert!(NarrowFlags { value: 256 }.pack().is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    crate::struct_packet_components! {
+        @json
+        @derives(serde::Serialize, serde::Deserialize, PartialEq)
+        JsonExample {
+            name: String,
+            #[serde(default)]
+            tags: Vec<String>
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    crate::struct_packet_components! {
+        #[derive(PartialEq)]
+        JsonFieldExample {
+            v_int: VarInt,
+            settings: JsonExample
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_json_struct() -> crate::prelude::Result<()> {
+        let example = JsonFieldExample {
+            v_int: 25,
+            settings: JsonExample {
+                name: "drax".to_string(),
+                tags: vec![],
+            },
+        };
+        let mut cursor = Cursor::new(vec![]);
+        JsonFieldExample::encode(&example, &mut (), &mut cursor).await?;
+        let encoded = cursor.into_inner();
+        let json = br#"{"name":"drax","tags":[]}"#;
+        assert_eq!(encoded[0], 25);
+        assert_eq!(encoded[1] as usize, json.len());
+        assert_eq!(&encoded[2..], json);
+        assert_eq!(
+            JsonFieldExample::size(&example, &mut ())?,
+            Size::Dynamic(encoded.len())
+        );
+
+        let mut cursor = Cursor::new(encoded);
+        assert_eq!(
+            JsonFieldExample::decode(&mut (), &mut cursor).await?,
+            example
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "nbt")]
+    crate::struct_packet_components! {
+        @nbt
+        @derives(Clone, PartialEq)
+        NbtExample {
+            name: String,
+            level: i32,
+            glowing: bool
+        }
+    }
+
+    #[cfg(feature = "nbt")]
+    crate::struct_packet_components! {
+        #[derive(PartialEq)]
+        NbtFieldExample {
+            v_int: VarInt,
+            display: NbtExample
+        }
+    }
+
+    #[cfg(feature = "nbt")]
+    #[tokio::test]
+    async fn test_nbt_struct() -> crate::prelude::Result<()> {
+        use crate::nbt::{Compound, EnsuredCompoundTag, Tag};
+
+        let example = NbtFieldExample {
+            v_int: 25,
+            display: NbtExample {
+                name: "drax".to_string(),
+                level: 3,
+                glowing: true,
+            },
+        };
+        let mut cursor = Cursor::new(vec![]);
+        NbtFieldExample::encode(&example, &mut (), &mut cursor).await?;
+        let encoded = cursor.into_inner();
+        assert_eq!(encoded[0], 25);
+        let compound = Compound::new()
+            .with("name", "drax")
+            .with("level", 3)
+            .with("glowing", true);
+        let mut expected = Cursor::new(vec![]);
+        EnsuredCompoundTag::<0>::encode(&Some(Tag::from(compound)), &mut (), &mut expected).await?;
+        assert_eq!(&encoded[1..], expected.get_ref().as_slice());
+        assert_eq!(
+            NbtFieldExample::size(&example, &mut ())?,
+            Size::Dynamic(encoded.len())
+        );
+
+        let mut cursor = Cursor::new(encoded);
+        assert_eq!(
+            NbtFieldExample::decode(&mut (), &mut cursor).await?,
+            example
+        );
+
+        let missing = Tag::from(Compound::new().with("name", "drax"));
+        let mut cursor = Cursor::new(vec![]);
+        EnsuredCompoundTag::<0>::encode(&Some(missing), &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert!(NbtExample::decode(&mut (), &mut cursor).await.is_err());
+        Ok(())
+    }
+
+    crate::components! {
+        @max_size(8)
+        struct BoundedExample {
+            name: String
+        },
+        @max_size(2)
+        enum BoundedEnum<key: VarInt> {
+            Empty {},
+            Value {
+                v_int: VarInt
+            }
+        }
+    }
+
+    // LimitedString<4> is at most 12 bytes behind a 1 byte length, and a VarInt at most 5 bytes
+    crate::components! {
+        #[derive(PartialEq)]
+        @max_size(18)
+        @arbitrary
+        struct GeneratedBoundedExample {
+            name: crate::transport::packet::string::LimitedString<4>,
+            v_int: VarInt
+        },
+        #[derive(PartialEq)]
+        @max_size(19)
+        @arbitrary
+        enum GeneratedBoundedEnum<key: VarInt> {
+            Empty {},
+            Value {
+                example: GeneratedBoundedExample
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_declared_max_size() -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![]);
+        let example = BoundedExample {
+            name: "drax".to_string(),
+        };
+        BoundedExample::encode(&example, &mut (), &mut cursor).await?;
+        BoundedEnum::encode(&BoundedEnum::Value { v_int: 25 }, &mut (), &mut cursor).await?;
+
+        let example = BoundedExample {
+            name: "drax drax".to_string(),
+        };
+        let result = BoundedExample::encode(&example, &mut (), &mut cursor).await;
+        let result_enum =
+            BoundedEnum::encode(&BoundedEnum::Value { v_int: 300 }, &mut (), &mut cursor).await;
+        if cfg!(debug_assertions) {
+            assert!(result.is_err());
+            assert!(result_enum.is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_sizes() {
+        assert_eq!(<Example as PacketComponent<String>>::MAX_SIZE, Some(9));
+        assert_eq!(<DerivedExample as PacketComponent<()>>::MAX_SIZE, None);
+        assert_eq!(ExampleEnum::VARIANT_MAX_SIZES, &[Some(14), Some(14)]);
+        assert_eq!(DerivedExampleEnum::VARIANT_MAX_SIZES, &[Some(10), Some(5)]);
+        assert_eq!(DerivedExampleEnum::MAX_VARIANT_SIZE, Some(10));
+        assert_eq!(
+            <DerivedExampleEnum as PacketComponent<()>>::MAX_SIZE,
+            Some(10)
+        );
+        assert_eq!(
+            <Maybe<[VarInt; 4]> as PacketComponent<()>>::MAX_SIZE,
+            Some(21)
+        );
+    }
+
+    #[test]
+    fn test_derives() {
+        let example = DerivedExample {
+            v_int: 25,
+            name: "drax".to_string(),
+        };
+        assert_eq!(example.clone(), example);
+
+        let example = DerivedExampleEnum::Variant1 { v_int: 25 };
+        assert_eq!(example.clone(), example);
+        assert_ne!(example, DerivedExampleEnum::Variant2 {});
+    }
+
+    #[cfg(feature = "serde")]
+    crate::struct_packet_components! {
+        @derives(Clone, PartialEq, serde::Serialize)
+        SerializedExample {
+            v_int: VarInt,
+            name: String
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    crate::enum_packet_components! {
+        @derives(Clone, PartialEq, serde::Serialize)
+        SerializedExampleEnum {
+            key: VarInt,
+            Variant1 {
+                v_int: VarInt
+            },
+            Variant2 {}
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_derives() {
+        let example = SerializedExample {
+            v_int: 25,
+            name: "drax".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_string(&example.clone()).unwrap(),
+            r#"{"v_int":25,"name":"drax"}"#
+        );
+
+        let example = SerializedExampleEnum::Variant1 { v_int: 25 };
+        assert_eq!(
+            serde_json::to_string(&example).unwrap(),
+            r#"{"Variant1":{"v_int":25}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&SerializedExampleEnum::Variant2 {}).unwrap(),
+            r#""Variant2""#
+        );
+    }
+
     #[tokio::test]
     async fn test_decode_packet() -> crate::prelude::Result<()> {
         let mut v = vec![25, 0, 0, 0, 10];
@@ -806,6 +2511,79 @@ mod test {
         Ok(())
     }
 
+    struct LabelledContext(String);
+
+    impl crate::prelude::ContextLabel for LabelledContext {
+        fn label(&self) -> Option<&str> {
+            Some(&self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_labelled_enum_error() {
+        let mut cursor = Cursor::new(vec![5]);
+        let mut context = LabelledContext("127.0.0.1:25565".to_string());
+        let err = ExampleEnum::decode(&mut context, &mut cursor)
+            .await
+            .expect_err("Unknown key should fail to decode");
+        assert_eq!(
+            err.context.to_string(),
+            "`[127.0.0.1:25565] Failed to decode key 5 for type ExampleEnum`"
+        );
+
+        let mut cursor = Cursor::new(vec![5]);
+        let err = ExampleEnum::decode(&mut (), &mut cursor)
+            .await
+            .expect_err("Unknown key should fail to decode");
+        assert_eq!(
+            err.context.to_string(),
+            "`Failed to decode key 5 for type ExampleEnum`"
+        );
+
+        // components declared without @label don't use the label, or need the context to have one
+        struct UnlabelledContext;
+        let mut cursor = Cursor::new(vec![5]);
+        let err = DenseEnum::decode(&mut UnlabelledContext, &mut cursor)
+            .await
+            .expect_err("Unknown key should fail to decode");
+        assert_eq!(
+            err.context.to_string(),
+            "`Failed to decode key 5 for type DenseEnum`"
+        );
+        let mut cursor = Cursor::new(vec![5]);
+        let err = DenseEnum::decode(&mut context, &mut cursor)
+            .await
+            .expect_err("Unknown key should fail to decode");
+        assert_eq!(
+            err.context.to_string(),
+            "`Failed to decode key 5 for type DenseEnum`"
+        );
+    }
+
+    crate::struct_packet_components! {
+        @max_size(2)
+        @label
+        LabelledBounded {
+            name: String
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    async fn test_labelled_max_size_error() {
+        let mut context = LabelledContext("127.0.0.1:25565".to_string());
+        let example = LabelledBounded {
+            name: "drax".to_string(),
+        };
+        let err = LabelledBounded::encode(&example, &mut context, &mut Cursor::new(vec![]))
+            .await
+            .expect_err("An oversized component should fail to encode");
+        assert_eq!(
+            err.context.to_string(),
+            "`[127.0.0.1:25565] LabelledBounded of 5 bytes exceeded its declared maximum size of 2 bytes`"
+        );
+    }
+
     #[tokio::test]
     async fn test_size_enum_packet() -> crate::prelude::Result<()> {
         let example = ExampleEnum::Variant1 {
@@ -0,0 +1,296 @@
+use std::marker::PhantomData;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::transport::buffer::var_num::size_var_int;
+use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
+use crate::transport::packet::vec::{checked_length, PREALLOCATION_LIMIT};
+use crate::transport::packet::{yield_point, PacketComponent, Size};
+use crate::{throw_explain, PinnedLivelyResult};
+
+/// A growable set of bits stored as 64 bit words, least significant bit first. Trailing zero words
+/// are never stored, so sets holding the same bits are always equal and encode identically.
+///
+/// Encoded on its own as a VarInt count of words followed by each word as a big endian long, or
+/// through `FixedBitSet` as a fixed number of bytes.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the bit at the index is set.
+    pub fn get(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    /// Sets or clears the bit at the index, growing the set as needed.
+    pub fn set(&mut self, index: usize, value: bool) {
+        let word = index / 64;
+        if value {
+            if word >= self.words.len() {
+                self.words.resize(word + 1, 0);
+            }
+            self.words[word] |= 1 << (index % 64);
+        } else if word < self.words.len() {
+            self.words[word] &= !(1 << (index % 64));
+            self.trim();
+        }
+    }
+
+    /// The index of the highest set bit plus one, `0` if no bits are set.
+    pub fn len(&self) -> usize {
+        match self.words.last() {
+            Some(last) => self.words.len() * 64 - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The number of set bits.
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// The indices of the set bits in ascending order.
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).filter(|index| self.get(*index))
+    }
+
+    /// The words of the set, least significant first, without trailing zero words.
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    fn trim(&mut self) {
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+    }
+}
+
+impl From<Vec<u64>> for BitSet {
+    fn from(words: Vec<u64>) -> Self {
+        let mut set = Self { words };
+        set.trim();
+        set
+    }
+}
+
+impl From<BitSet> for Vec<u64> {
+    fn from(set: BitSet) -> Self {
+        set.words
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
+        let mut set = Self::new();
+        for index in iter {
+            set.set(index, true);
+        }
+        set
+    }
+}
+
+impl<C: Send + Sync> PacketComponent<C> for BitSet {
+    type ComponentType = BitSet;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let len = checked_length(read.read_var_int().await?)?;
+            let mut words = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            for i in 0..len {
+                yield_point(i).await;
+                words.push(read.read_u64().await?);
+            }
+            Ok(BitSet::from(words))
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            write
+                .write_var_int(i32::try_from(component_ref.words.len())?)
+                .await?;
+            for word in &component_ref.words {
+                write.write_u64(*word).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        let len = input.words.len();
+        Ok(Size::Dynamic(size_var_int(i32::try_from(len)?) + len * 8))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.words.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+/// A `BitSet` of exactly `N` bits, encoded without a length as `N` bits rounded up to whole bytes,
+/// least significant bit of the first byte first. Encoding a set with bits at or past `N` is an
+/// error.
+pub struct FixedBitSet<const N: usize>(PhantomData<BitSet>);
+
+impl<const N: usize> FixedBitSet<N> {
+    const BYTES: usize = N.div_ceil(8);
+}
+
+impl<C: Send + Sync, const N: usize> PacketComponent<C> for FixedBitSet<N> {
+    type ComponentType = BitSet;
+
+    const MAX_SIZE: Option<usize> = Some(Self::BYTES);
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let mut bytes = vec![0; Self::BYTES];
+            read.read_exact(&mut bytes).await?;
+            let set = BitSet::from(
+                bytes
+                    .chunks(8)
+                    .map(|chunk| {
+                        let mut word = [0; 8];
+                        word[..chunk.len()].copy_from_slice(chunk);
+                        u64::from_le_bytes(word)
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            if set.len() > N {
+                throw_explain!(format!(
+                    "Received fixed bit set with bit {} set past its {N} bits",
+                    set.len() - 1
+                ))
+            }
+            Ok(set)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            if component_ref.len() > N {
+                throw_explain!(format!(
+                    "Tried to encode bit {} in a fixed bit set of {N} bits",
+                    component_ref.len() - 1
+                ))
+            }
+            let mut bytes = Vec::with_capacity(Self::BYTES);
+            for word in &component_ref.words {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+            bytes.resize(Self::BYTES, 0);
+            write.write_all(&bytes).await?;
+            Ok(())
+        })
+    }
+
+    fn size(_: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        Ok(Size::Constant(Self::BYTES))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        <BitSet as PacketComponent<C>>::heap_size(input)
+    }
+}
+
+/// The width in bits of each field packed by `bit_fields!`, in declaration order, summed to find
+/// the offset of a field.
+#[doc(hidden)]
+pub const fn bit_offset(widths: &[u32], index: usize) -> u32 {
+    let mut offset = 0;
+    let mut i = 0;
+    while i < index {
+        offset += widths[i];
+        i += 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{BitSet, FixedBitSet};
+    use crate::prelude::{PacketComponent, Size};
+
+    #[test]
+    fn test_bit_set() {
+        let mut set = BitSet::new();
+        set.set(3, true);
+        set.set(70, true);
+        assert!(set.get(3) && set.get(70) && !set.get(4));
+        assert_eq!(set.len(), 71);
+        assert_eq!(set.words(), &[8, 64]);
+        set.set(70, false);
+        assert_eq!(set.words(), &[8]);
+        assert_eq!(BitSet::from(vec![8, 0, 0]), set);
+        assert_eq!([3, 5].into_iter().collect::<BitSet>().count_ones(), 2);
+        assert_eq!(
+            [3, 5]
+                .into_iter()
+                .collect::<BitSet>()
+                .iter_ones()
+                .collect::<Vec<_>>(),
+            vec![3, 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bit_set_components() -> crate::prelude::Result<()> {
+        let set: BitSet = [0, 9, 64].into_iter().collect();
+        let mut cursor = Cursor::new(vec![]);
+        BitSet::encode(&set, &mut (), &mut cursor).await?;
+        assert_eq!(
+            cursor.get_ref(),
+            &vec![2, 0, 0, 0, 0, 0, 0, 2, 1, 0, 0, 0, 0, 0, 0, 0, 1]
+        );
+        assert_eq!(BitSet::size(&set, &mut ())?, Size::Dynamic(17));
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(BitSet::decode(&mut (), &mut cursor).await?, set);
+
+        let mut cursor = Cursor::new(vec![]);
+        FixedBitSet::<70>::encode(&set, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![1, 2, 0, 0, 0, 0, 0, 0, 1]);
+        assert!(FixedBitSet::<64>::encode(&set, &mut (), &mut cursor)
+            .await
+            .is_err());
+        assert_eq!(<FixedBitSet<70> as PacketComponent<()>>::MAX_SIZE, Some(9));
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(FixedBitSet::<70>::decode(&mut (), &mut cursor).await?, set);
+
+        // bits past N in the padding of the last byte
+        let mut cursor = Cursor::new(vec![0, 0x80]);
+        assert!(FixedBitSet::<10>::decode(&mut (), &mut cursor)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Deref;
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::prelude::ErrorType;
+use crate::transport::buffer::var_num::size_var_int;
+use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
+use crate::transport::packet::primitive::VarInt;
+use crate::transport::packet::string::STRING_DEFAULT_CAP;
+use crate::transport::packet::vec::{checked_length, PREALLOCATION_LIMIT};
+use crate::transport::packet::{PacketComponent, Size};
+use crate::{throw, throw_explain, PinnedLivelyResult};
+
+/// Splits a VarInt length prefixed run of bytes off the front of the buffer without copying it.
+pub(crate) fn split_prefixed<C: Send + Sync>(
+    context: &mut C,
+    buf: &mut Bytes,
+    cap: Option<i32>,
+) -> crate::prelude::Result<Bytes> {
+    let len = VarInt::decode_from_buf(context, buf)?;
+    if let Some(cap) = cap.filter(|cap| len > *cap) {
+        throw_explain!(format!("String exceeded length bound {cap}"))
+    }
+    let len = checked_length(len)?;
+    if len > buf.len() {
+        throw!(ErrorType::EOF)
+    }
+    Ok(buf.split_to(len))
+}
+
+/// A VarInt length prefixed byte array, encoded the same as `VecU8`, decoded into `Bytes`. Decoding
+/// through `decode_from_buf` slices the array out of the source buffer instead of copying it.
+pub struct ByteBuf;
+
+impl<C: Send + Sync> PacketComponent<C> for ByteBuf {
+    type ComponentType = Bytes;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let len = checked_length(read.read_var_int().await?)?;
+            let mut buf = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            read.take(len as u64).read_to_end(&mut buf).await?;
+            if buf.len() != len {
+                throw!(ErrorType::EOF)
+            }
+            Ok(Bytes::from(buf))
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            write
+                .write_var_int(i32::try_from(component_ref.len())?)
+                .await?;
+            write.write_all(component_ref).await?;
+            Ok(())
+        })
+    }
+
+    fn size(component_ref: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        let len = i32::try_from(component_ref.len())?;
+        Ok(Size::Dynamic(component_ref.len() + size_var_int(len)))
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        split_prefixed(context, buf, None)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.len()
+    }
+}
+
+/// An immutable UTF-8 string backed by `Bytes`, so it can share the memory of the buffer it was
+/// decoded from. Encoded the same as `String`, including its length bound.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct BytesStr {
+    bytes: Bytes,
+}
+
+impl BytesStr {
+    /// Wraps the bytes if they are valid UTF-8.
+    pub fn from_utf8(bytes: Bytes) -> crate::prelude::Result<Self> {
+        std::str::from_utf8(&bytes)?;
+        Ok(Self { bytes })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // validated when constructed
+        unsafe { std::str::from_utf8_unchecked(&self.bytes) }
+    }
+
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.bytes
+    }
+}
+
+impl Deref for BytesStr {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl Display for BytesStr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for BytesStr {
+    fn from(string: String) -> Self {
+        Self {
+            bytes: Bytes::from(string),
+        }
+    }
+}
+
+impl From<&'static str> for BytesStr {
+    fn from(string: &'static str) -> Self {
+        Self {
+            bytes: Bytes::from_static(string.as_bytes()),
+        }
+    }
+}
+
+impl<C: Send + Sync> PacketComponent<C> for BytesStr {
+    type ComponentType = Self;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let string = String::decode(context, read).await?;
+            Ok(BytesStr::from(string))
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let len = encoded_length(component_ref)?;
+            write.write_var_int(len).await?;
+            write.write_all(&component_ref.bytes).await?;
+            Ok(())
+        })
+    }
+
+    fn size(component_ref: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        let len = encoded_length(component_ref)?;
+        Ok(Size::Dynamic(component_ref.len() + size_var_int(len)))
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        BytesStr::from_utf8(split_prefixed(context, buf, Some(STRING_DEFAULT_CAP))?)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.len()
+    }
+}
+
+fn encoded_length(string: &BytesStr) -> crate::prelude::Result<i32> {
+    match i32::try_from(string.len()) {
+        Ok(len) if len <= STRING_DEFAULT_CAP => Ok(len),
+        _ => throw_explain!(format!(
+            "While encoding; string of {} bytes exceeded byte bound {STRING_DEFAULT_CAP}",
+            string.len()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::{ByteBuf, BytesStr};
+    use crate::prelude::PacketComponent;
+    use crate::transport::frame::encode_body_to_bytes;
+    use crate::transport::packet::option::Maybe;
+    use crate::transport::packet::primitive::VarInt;
+
+    crate::struct_packet_components! {
+        @derives(PartialEq)
+        Chunk {
+            id: VarInt,
+            data: ByteBuf,
+            extra: Maybe<(u8, ByteBuf)>
+        }
+    }
+
+    crate::enum_packet_components! {
+        @derives(PartialEq)
+        Message {
+            key: VarInt,
+            0x00 => Empty {},
+            0x01 => Data {
+                chunk: Box<Chunk>
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_from_buf() -> crate::prelude::Result<()> {
+        let mut body =
+            encode_body_to_bytes::<_, BytesStr>(&mut (), &BytesStr::from("drax")).await?;
+        body.extend(
+            encode_body_to_bytes::<_, Vec<ByteBuf>>(
+                &mut (),
+                &vec![Bytes::from_static(&[1, 2]), Bytes::new()],
+            )
+            .await?,
+        );
+        body.push(7);
+        assert_eq!(
+            String::decode(&mut (), &mut &body[..]).await?,
+            "drax".to_string()
+        );
+
+        let mut buf = Bytes::from(body);
+        let start = buf.as_ptr();
+        let string = BytesStr::decode_from_buf(&mut (), &mut buf)?;
+        assert_eq!(&*string, "drax");
+        // sliced out of the source buffer rather than copied
+        assert_eq!(string.as_bytes().as_ptr(), start.wrapping_add(1));
+        let arrays = Vec::<ByteBuf>::decode_from_buf(&mut (), &mut buf)?;
+        assert_eq!(arrays, vec![Bytes::from_static(&[1, 2]), Bytes::new()]);
+        // the buffer was advanced past exactly the arrays
+        assert_eq!(VarInt::decode_from_buf(&mut (), &mut buf)?, 7);
+        assert!(buf.is_empty());
+
+        let mut truncated = Bytes::from_static(&[4, b'd', b'r']);
+        assert!(ByteBuf::decode_from_buf(&mut (), &mut truncated).is_err());
+        let mut invalid = Bytes::from_static(&[1, 0xFF]);
+        assert!(BytesStr::decode_from_buf(&mut (), &mut invalid).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_macro_components_share_buffer() -> crate::prelude::Result<()> {
+        let message = Message::Data {
+            chunk: Box::new(Chunk {
+                id: 300,
+                data: Bytes::from_static(&[1, 2, 3]),
+                extra: Some((9, Bytes::from_static(&[4, 5]))),
+            }),
+        };
+        let mut body = encode_body_to_bytes::<_, Message>(&mut (), &message).await?;
+        body.push(7);
+
+        let mut buf = Bytes::from(body);
+        let range = buf.as_ptr_range();
+        let decoded = Message::decode_from_buf(&mut (), &mut buf)?;
+        assert_eq!(decoded, message);
+        let Message::Data { chunk } = decoded else {
+            unreachable!()
+        };
+        // every field was decoded through the buffer, so the byte arrays point into it
+        assert!(range.contains(&chunk.data.as_ptr()));
+        let (_, extra) = chunk.extra.expect("extra");
+        assert!(range.contains(&extra.as_ptr()));
+        assert_eq!(&buf[..], &[7]);
+
+        let mut truncated = Bytes::from_static(&[1, 0xAC, 0x02, 3, 1]);
+        assert!(Message::decode_from_buf(&mut (), &mut truncated).is_err());
+        Ok(())
+    }
+}
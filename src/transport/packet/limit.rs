@@ -0,0 +1,298 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use bytes::{Buf, Bytes};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::transport::packet::{PacketComponent, Size};
+use crate::{throw_explain, PinnedLivelyResult};
+
+/// A reader which allows at most `remaining` bytes to be read from the inner reader. Reading past
+/// the budget results in an error rather than an EOF, so a component which consumes more than it
+/// is allowed to can be told apart from a truncated stream. Once the budget is spent any further
+/// read fails without reading from the inner reader, even if it has nothing left.
+pub struct BudgetedReader<'a, R: ?Sized> {
+    inner: &'a mut R,
+    remaining: usize,
+    exceeded: bool,
+}
+
+impl<'a, R: ?Sized> BudgetedReader<'a, R> {
+    pub fn new(inner: &'a mut R, budget: usize) -> Self {
+        Self {
+            inner,
+            remaining: budget,
+            exceeded: false,
+        }
+    }
+
+    /// The number of bytes which can still be read before the budget is exceeded.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Whether a read has been attempted past the end of the budget.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+impl<'a, R: AsyncRead + Unpin + ?Sized> AsyncRead for BudgetedReader<'a, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let me = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        if me.remaining == 0 {
+            // The budget is spent, so fail without touching the inner reader; even a clean EOF
+            // would need a read which could block or consume a byte past the budget.
+            me.exceeded = true;
+            return Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Read exceeded byte budget",
+            )));
+        }
+
+        let max = buf.remaining().min(me.remaining);
+        let mut limited = buf.take(max);
+        ready!(Pin::new(&mut *me.inner).poll_read(cx, &mut limited))?;
+        let read = limited.filled().len();
+        // SAFETY: `limited` is a view over the unfilled portion of `buf`, the inner reader has
+        // initialized the first `read` bytes of it.
+        unsafe {
+            buf.assume_init(read);
+        }
+        buf.advance(read);
+        me.remaining -= read;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Bounds the total number of bytes the delegate `T` may consume while decoding, including
+/// everything decoded by nested delegates. This composes with count based limits such as
+/// `LimitedVec` and `LimitedString` to cap the size of an entire subtree.
+pub struct ByteBudget<T, const N: usize>(PhantomData<T>);
+
+impl<T, C: Send + Sync, const N: usize> PacketComponent<C> for ByteBudget<T, N>
+where
+    T: PacketComponent<C>,
+{
+    type ComponentType = T::ComponentType;
+
+    const MAX_SIZE: Option<usize> = match T::MAX_SIZE {
+        Some(size) if size < N => Some(size),
+        _ => Some(N),
+    };
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let mut budgeted = BudgetedReader::new(read, N);
+            match T::decode(context, &mut budgeted).await {
+                Ok(value) => Ok(value),
+                Err(_) if budgeted.exceeded() => {
+                    throw_explain!(format!("Decoded component exceeded byte budget {N}"))
+                }
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        let size = match T::size(component_ref, context) {
+            Ok(Size::Dynamic(x) | Size::Constant(x)) => x,
+            Err(err) => return Box::pin(async move { Err(err) }),
+        };
+        if size > N {
+            return Box::pin(async move {
+                throw_explain!(format!(
+                    "Tried to encode component of {size} bytes but was bound to {N} bytes"
+                ))
+            });
+        }
+
+        T::encode(component_ref, context, write)
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        T::size(input, context)
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        // decoded from the buffer cut to the budget, so the component can't read past it
+        let truncated = buf.len() > N;
+        let mut budgeted = buf.slice(..buf.len().min(N));
+        match T::decode_from_buf(context, &mut budgeted) {
+            Ok(value) => {
+                buf.advance(buf.len().min(N) - budgeted.len());
+                Ok(value)
+            }
+            Err(_) if truncated => {
+                throw_explain!(format!("Decoded component exceeded byte budget {N}"))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        T::heap_size(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+    use super::{BudgetedReader, ByteBudget};
+    use crate::prelude::PacketComponent;
+    use crate::transport::packet::string::LimitedString;
+    use crate::transport::packet::vec::LimitedVec;
+
+    type Names = ByteBudget<LimitedVec<LimitedString<16>, 10>, 16>;
+
+    /// Hands out at most `chunk` bytes per read, and is pending on every other poll, to exercise
+    /// partial fills.
+    struct ChunkedReader {
+        data: Vec<u8>,
+        position: usize,
+        chunk: usize,
+        pending: bool,
+    }
+
+    impl ChunkedReader {
+        fn new(data: Vec<u8>, chunk: usize) -> Self {
+            Self {
+                data,
+                position: 0,
+                chunk,
+                pending: false,
+            }
+        }
+    }
+
+    impl AsyncRead for ChunkedReader {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            let me = self.get_mut();
+            me.pending = !me.pending;
+            if me.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let end = me
+                .data
+                .len()
+                .min(me.position + me.chunk)
+                .min(me.position + buf.remaining());
+            buf.put_slice(&me.data[me.position..end]);
+            me.position = end;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_budget_partial_reads() {
+        for len in 0..12usize {
+            let data: Vec<u8> = (0..len as u8).collect();
+            for budget in 0..12usize {
+                for chunk in 1..6usize {
+                    let mut inner = ChunkedReader::new(data.clone(), chunk);
+                    let mut reader = BudgetedReader::new(&mut inner, budget);
+                    let mut out = vec![];
+                    let result = reader.read_to_end(&mut out).await;
+                    if len >= budget {
+                        assert!(result.is_err(), "len {len} budget {budget} chunk {chunk}");
+                        assert!(reader.exceeded());
+                        assert_eq!(out, data[..budget]);
+                        assert_eq!(inner.position, budget);
+                    } else {
+                        assert_eq!(result.ok(), Some(len));
+                        assert!(!reader.exceeded());
+                        assert_eq!(reader.remaining(), budget - len);
+                        assert_eq!(out, data);
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_budget_preserves_filled() -> std::io::Result<()> {
+        let mut inner = ChunkedReader::new(vec![4, 5, 6, 7, 8], 2);
+        let mut reader = BudgetedReader::new(&mut inner, 3);
+        let mut storage = [0u8; 8];
+        let mut buf = ReadBuf::new(&mut storage);
+        buf.put_slice(&[1, 2, 3]);
+        while buf.filled().len() < 6 {
+            std::future::poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf)).await?;
+        }
+        assert_eq!(buf.filled(), &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(reader.remaining(), 0);
+
+        let mut rest = [0u8; 2];
+        let mut buf = ReadBuf::new(&mut rest);
+        let result = std::future::poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf)).await;
+        assert!(result.is_err());
+        assert!(buf.filled().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_budget_allows_exact_fit() -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![0, 1, 2, 3]);
+        let mut reader = BudgetedReader::new(&mut cursor, 4);
+        let mut out = [0u8; 4];
+        reader.read_exact(&mut out).await?;
+        assert_eq!(out, [0, 1, 2, 3]);
+        assert!(!reader.exceeded());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_budget_rejects_overflow() {
+        let mut cursor = Cursor::new(vec![0, 1, 2, 3, 4]);
+        let mut reader = BudgetedReader::new(&mut cursor, 4);
+        let mut out = vec![];
+        assert!(reader.read_to_end(&mut out).await.is_err());
+        assert!(reader.exceeded());
+    }
+
+    #[tokio::test]
+    async fn test_nested_budget() -> crate::prelude::Result<()> {
+        let names = vec!["abc".to_string(), "defg".to_string()];
+        let mut cursor = Cursor::new(vec![]);
+        Names::encode(&names, &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(Names::decode(&mut (), &mut cursor).await?, names);
+
+        let names = vec!["abcdefghijklmnop".to_string(), "abc".to_string()];
+        let mut cursor = Cursor::new(vec![]);
+        assert!(Names::encode(&names, &mut (), &mut cursor).await.is_err());
+        LimitedVec::<LimitedString<16>, 10>::encode(&names, &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert!(Names::decode(&mut (), &mut cursor).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::transport::buffer::var_num::size_var_int;
+use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
+use crate::transport::packet::primitive::VarInt;
+use crate::transport::packet::vec::{checked_length, PREALLOCATION_LIMIT};
+use crate::transport::packet::{yield_point, PacketComponent, Size};
+use crate::{throw_explain, PinnedLivelyResult};
+
+/// Decodes a VarInt length followed by that many key/value pairs into a map made by
+/// `$with_len`, rejecting lengths over the bound and duplicate keys. Shared by the map components
+/// so they all decode the same way.
+macro_rules! decode_map {
+    ($key:ty, $value:ty, $context:ident, $read:ident, $with_len:expr, $bound:expr) => {{
+        let len = checked_length($read.read_var_int().await?)?;
+        let bound: Option<usize> = $bound;
+        if let Some(bound) = bound {
+            if len > bound {
+                throw_explain!(format!(
+                    "While decoding; map of length {len} exceeded length bound {bound}"
+                ));
+            }
+        }
+        let mut map = $with_len(len);
+        for i in 0..len {
+            yield_point(i).await;
+            let key = <$key>::decode($context, $read).await?;
+            let value = <$value>::decode($context, $read).await?;
+            if map.insert(key, value).is_some() {
+                throw_explain!(format!("Received duplicate key in map of length {len}"))
+            }
+        }
+        Ok(map)
+    }};
+}
+
+/// Decodes a map as `decode_map!` does, from the front of a buffer with `decode_from_buf`.
+macro_rules! decode_map_from_buf {
+    ($key:ty, $value:ty, $context:ident, $buf:ident, $with_len:expr, $bound:expr) => {{
+        let len = checked_length(VarInt::decode_from_buf($context, $buf)?)?;
+        let bound: Option<usize> = $bound;
+        if let Some(bound) = bound {
+            if len > bound {
+                throw_explain!(format!(
+                    "While decoding; map of length {len} exceeded length bound {bound}"
+                ));
+            }
+        }
+        let mut map = $with_len(len);
+        for _ in 0..len {
+            let key = <$key>::decode_from_buf($context, $buf)?;
+            let value = <$value>::decode_from_buf($context, $buf)?;
+            if map.insert(key, value).is_some() {
+                throw_explain!(format!("Received duplicate key in map of length {len}"))
+            }
+        }
+        Ok(map)
+    }};
+}
+
+/// Encodes the entries of a map after its VarInt length.
+macro_rules! encode_entries {
+    // In iteration order, for maps with a fixed order.
+    (@in_order $key:ty, $value:ty, $map:ident, $context:ident, $write:ident) => {{
+        $write.write_var_int(i32::try_from($map.len())?).await?;
+        for (key, value) in $map {
+            <$key>::encode(key, $context, $write).await?;
+            <$value>::encode(value, $context, $write).await?;
+        }
+        Ok(())
+    }};
+    // Ordered by the bytes of the encoded keys, for maps iterated in an order which changes
+    // between runs, so equal maps always encode to the same bytes.
+    (@sorted $key:ty, $value:ty, $map:ident, $context:ident, $write:ident) => {{
+        let mut entries = Vec::with_capacity($map.len());
+        for (key, value) in $map {
+            let mut key_bytes = vec![];
+            <$key>::encode(key, $context, &mut key_bytes).await?;
+            entries.push((key_bytes, value));
+        }
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        $write.write_var_int(i32::try_from(entries.len())?).await?;
+        for (key_bytes, value) in entries {
+            $write.write_all(&key_bytes).await?;
+            <$value>::encode(value, $context, $write).await?;
+        }
+        Ok(())
+    }};
+}
+
+/// Implements a map component for `$map`, encoded as a VarInt length followed by that many
+/// key/value pairs. Duplicate keys are rejected while decoding rather than silently overwritten,
+/// so a decoded map always re-encodes to the same length.
+macro_rules! impl_map_component {
+    ($map:ident, $with_len:expr, @$order:ident, $capacity:expr, $($key_bound:tt)+) => {
+        impl<C: Send + Sync, K, V> PacketComponent<C> for $map<K, V>
+        where
+            K: PacketComponent<C>,
+            V: PacketComponent<C>,
+            K::ComponentType: $($key_bound)+,
+        {
+            type ComponentType = $map<K::ComponentType, V::ComponentType>;
+
+            fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                context: &'a mut C,
+                read: &'a mut A,
+            ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+                Box::pin(async move { decode_map!(K, V, context, read, $with_len, None) })
+            }
+
+            fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                component_ref: &'a Self::ComponentType,
+                context: &'a mut C,
+                write: &'a mut A,
+            ) -> PinnedLivelyResult<'a, ()> {
+                Box::pin(async move {
+                    encode_entries!(@$order K, V, component_ref, context, write)
+                })
+            }
+
+            fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+                let mut size = size_var_int(i32::try_from(input.len())?);
+                for (key, value) in input {
+                    match K::size(key, context)? {
+                        Size::Constant(x) | Size::Dynamic(x) => size += x,
+                    }
+                    match V::size(value, context)? {
+                        Size::Constant(x) | Size::Dynamic(x) => size += x,
+                    }
+                }
+                Ok(Size::Dynamic(size))
+            }
+
+            fn decode_from_buf(
+                context: &mut C,
+                buf: &mut Bytes,
+            ) -> crate::prelude::Result<Self::ComponentType> {
+                decode_map_from_buf!(K, V, context, buf, $with_len, None)
+            }
+
+            fn heap_size(input: &Self::ComponentType) -> usize {
+                $capacity(input) * std::mem::size_of::<(K::ComponentType, V::ComponentType)>()
+                    + input
+                        .iter()
+                        .map(|(key, value)| K::heap_size(key) + V::heap_size(value))
+                        .sum::<usize>()
+            }
+        }
+    };
+}
+
+// Hash maps iterate in a different order on every run, so their entries are sorted to keep the
+// encoding deterministic.
+impl_map_component!(
+    HashMap,
+    |len: usize| HashMap::with_capacity(len.min(PREALLOCATION_LIMIT)),
+    @sorted,
+    HashMap::capacity,
+    Eq + Hash
+);
+// Nodes aren't exposed, so the entries are used as an estimate of the retained memory.
+impl_map_component!(BTreeMap, |_| BTreeMap::new(), @in_order, BTreeMap::len, Ord);
+
+/// A `HashMap` bounded to at most `N` entries, checked before any entry is decoded or encoded.
+pub struct LimitedMap<K, V, const N: usize>(PhantomData<(K, V)>);
+
+impl<K, V, C: Send + Sync, const N: usize> PacketComponent<C> for LimitedMap<K, V, N>
+where
+    K: PacketComponent<C>,
+    V: PacketComponent<C>,
+    K::ComponentType: Eq + Hash,
+{
+    type ComponentType = HashMap<K::ComponentType, V::ComponentType>;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            decode_map!(
+                K,
+                V,
+                context,
+                read,
+                |len: usize| HashMap::with_capacity(len.min(PREALLOCATION_LIMIT)),
+                Some(N)
+            )
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        if component_ref.len() > N {
+            return Box::pin(async move {
+                throw_explain!(format!(
+                    "Tried to encode map of length {} but was bound to length {N}",
+                    component_ref.len()
+                ))
+            });
+        }
+
+        HashMap::<K, V>::encode(component_ref, context, write)
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        HashMap::<K, V>::size(input, context)
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        decode_map_from_buf!(
+            K,
+            V,
+            context,
+            buf,
+            |len: usize| HashMap::with_capacity(len.min(PREALLOCATION_LIMIT)),
+            Some(N)
+        )
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        HashMap::<K, V>::heap_size(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::io::Cursor;
+
+    use super::LimitedMap;
+    use crate::prelude::{PacketComponent, Size};
+    use crate::transport::packet::primitive::VarInt;
+
+    #[tokio::test]
+    async fn test_maps() -> crate::prelude::Result<()> {
+        let map: BTreeMap<String, i32> = [("a".to_string(), 1), ("bc".to_string(), 300)].into();
+        let mut cursor = Cursor::new(vec![]);
+        BTreeMap::<String, VarInt>::encode(&map, &mut (), &mut cursor).await?;
+        assert_eq!(
+            cursor.get_ref(),
+            &vec![2, 1, b'a', 1, 2, b'b', b'c', 0xAC, 0x02]
+        );
+        assert_eq!(
+            BTreeMap::<String, VarInt>::size(&map, &mut ())?,
+            Size::Dynamic(9)
+        );
+        let mut cursor = Cursor::new(cursor.get_ref().clone());
+        assert_eq!(
+            BTreeMap::<String, VarInt>::decode(&mut (), &mut cursor).await?,
+            map
+        );
+
+        let ordered = cursor.into_inner();
+        let map: HashMap<String, i32> = map.into_iter().collect();
+        let mut cursor = Cursor::new(vec![]);
+        HashMap::<String, VarInt>::encode(&map, &mut (), &mut cursor).await?;
+        // entries are sorted by their encoded keys, which here is the same order as the BTreeMap
+        assert_eq!(cursor.get_ref(), &ordered);
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(
+            HashMap::<String, VarInt>::decode(&mut (), &mut cursor).await?,
+            map
+        );
+
+        let forward: HashMap<u16, u8> = (0..500).map(|i| (i, i as u8)).collect();
+        let backward: HashMap<u16, u8> = (0..500).rev().map(|i| (i, i as u8)).collect();
+        let (mut a, mut b) = (vec![], vec![]);
+        HashMap::<u16, u8>::encode(&forward, &mut (), &mut a).await?;
+        HashMap::<u16, u8>::encode(&backward, &mut (), &mut b).await?;
+        assert_eq!(a, b);
+
+        let mut cursor = Cursor::new(vec![2, 1, 1, 1, 2]);
+        assert!(HashMap::<u8, u8>::decode(&mut (), &mut cursor)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_limited_map() -> crate::prelude::Result<()> {
+        let map: HashMap<u8, u8> = [(1, 2), (3, 4)].into();
+        let mut cursor = Cursor::new(vec![]);
+        LimitedMap::<u8, u8, 2>::encode(&map, &mut (), &mut cursor).await?;
+        assert!(LimitedMap::<u8, u8, 1>::encode(&map, &mut (), &mut cursor)
+            .await
+            .is_err());
+        let bytes = cursor.into_inner();
+        let mut cursor = Cursor::new(bytes.clone());
+        assert_eq!(
+            LimitedMap::<u8, u8, 2>::decode(&mut (), &mut cursor).await?,
+            map
+        );
+        let mut cursor = Cursor::new(bytes);
+        assert!(LimitedMap::<u8, u8, 1>::decode(&mut (), &mut cursor)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -1,11 +1,15 @@
+use std::marker::PhantomData;
+
+use bytes::Bytes;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
+use crate::prelude::ErrorType;
 use crate::transport::packet::{PacketComponent, Size};
-use crate::PinnedLivelyResult;
+use crate::{err_explain, throw, throw_explain, PinnedLivelyResult};
 
-pub struct Maybe<T> {
-    _phantom_t: T,
-}
+/// An optional value prefixed with a bool, `1` if the value of `T` follows and `0` if it is absent.
+/// Any non-zero prefix is read as present.
+pub struct Maybe<T>(PhantomData<T>);
 
 impl<C: Send + Sync, T> PacketComponent<C> for Maybe<T>
 where
@@ -13,6 +17,8 @@ where
 {
     type ComponentType = Option<T::ComponentType>;
 
+    const MAX_SIZE: Option<usize> = super::max_size_sum(&[Some(1), T::MAX_SIZE]);
+
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
         context: &'a mut C,
         read: &'a mut A,
@@ -52,4 +58,294 @@ where
             Size::Dynamic(1)
         })
     }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        if buf.is_empty() {
+            throw!(ErrorType::EOF)
+        }
+        if buf.split_to(1)[0] != 0x0 {
+            Ok(Some(T::decode_from_buf(context, buf)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+/// An optional trailing field with no presence header, present only if bytes remain. Decodes
+/// `None` when the reader is already at its end, so it must be the last component read from a
+/// bounded reader such as a frame body; otherwise it will read into whatever follows.
+pub struct TailOptional<T>(PhantomData<T>);
+
+impl<C: Send + Sync, T> PacketComponent<C> for TailOptional<T>
+where
+    T: PacketComponent<C>,
+{
+    type ComponentType = Option<T::ComponentType>;
+
+    const MAX_SIZE: Option<usize> = T::MAX_SIZE;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let mut first = [0u8; 1];
+            if read.read(&mut first).await? == 0 {
+                return Ok(None);
+            }
+            let mut chained = (&first[..]).chain(read);
+            Ok(Some(T::decode(context, &mut chained).await?))
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        match component_ref {
+            Some(value) => T::encode(value, context, write),
+            None => Box::pin(async move { Ok(()) }),
+        }
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        Ok(match input {
+            Some(value) => match T::size(value, context)? {
+                Size::Dynamic(x) | Size::Constant(x) => Size::Dynamic(x),
+            },
+            None => Size::Dynamic(0),
+        })
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(T::decode_from_buf(context, buf)?))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+fn as_i64<T: Copy + TryInto<i64>>(value: T) -> crate::prelude::Result<i64> {
+    value
+        .try_into()
+        .map_err(|_| err_explain!("Optional value does not fit in an i64"))
+}
+
+fn from_i64<T: TryFrom<i64>>(value: i64) -> crate::prelude::Result<T> {
+    T::try_from(value).map_err(|_| {
+        err_explain!(format!(
+            "Optional value {value} does not fit in {}",
+            std::any::type_name::<T>()
+        ))
+    })
+}
+
+/// Generates an optional component which is written as a value of `T` in its place, converting
+/// between the two through an `i64` with `$to_wire` and `$from_wire`.
+macro_rules! impl_mapped_option {
+    ($name:ident $(, const $const_name:ident: $const_ty:ty)?; $to_wire:expr, $from_wire:expr) => {
+        impl<C: Send + Sync, T $(, const $const_name: $const_ty)?> PacketComponent<C>
+            for $name<T $(, $const_name)?>
+        where
+            T: PacketComponent<C>,
+            T::ComponentType: Copy + TryInto<i64> + TryFrom<i64>,
+        {
+            type ComponentType = Option<T::ComponentType>;
+
+            const MAX_SIZE: Option<usize> = T::MAX_SIZE;
+
+            fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                context: &'a mut C,
+                read: &'a mut A,
+            ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+                Box::pin(async move {
+                    let wire = T::decode(context, read).await?;
+                    let from_wire: fn(T::ComponentType) -> crate::prelude::Result<_> = $from_wire;
+                    from_wire(wire)
+                })
+            }
+
+            fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                component_ref: &'a Self::ComponentType,
+                context: &'a mut C,
+                write: &'a mut A,
+            ) -> PinnedLivelyResult<'a, ()> {
+                Box::pin(async move {
+                    let to_wire: fn(&Self::ComponentType) -> crate::prelude::Result<_> = $to_wire;
+                    T::encode(&to_wire(component_ref)?, context, write).await
+                })
+            }
+
+            fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+                let to_wire: fn(&Self::ComponentType) -> crate::prelude::Result<_> = $to_wire;
+                T::size(&to_wire(input)?, context)
+            }
+
+            fn decode_from_buf(
+                context: &mut C,
+                buf: &mut Bytes,
+            ) -> crate::prelude::Result<Self::ComponentType> {
+                let wire = T::decode_from_buf(context, buf)?;
+                let from_wire: fn(T::ComponentType) -> crate::prelude::Result<_> = $from_wire;
+                from_wire(wire)
+            }
+        }
+    };
+}
+
+/// An optional value written as `T`, with `SENTINEL` standing in for `None`, such as the `-1`
+/// used for absent indices. Encoding `Some(SENTINEL)` is an error since it would decode as `None`.
+pub struct SentinelOption<T, const SENTINEL: i64>(PhantomData<T>);
+
+impl_mapped_option! {
+    SentinelOption, const SENTINEL: i64;
+    |input| match input {
+        Some(value) if as_i64(*value).ok() == Some(SENTINEL) => throw_explain!(format!(
+            "Tried to encode the value {SENTINEL} which is reserved for None"
+        )),
+        Some(value) => Ok(*value),
+        None => from_i64(SENTINEL),
+    },
+    |wire| Ok((as_i64(wire).ok() != Some(SENTINEL)).then_some(wire))
+}
+
+/// An optional id written as `T`, with `0` standing in for `None` and every id written as one more
+/// than its value.
+pub struct IdPlusOneOption<T>(PhantomData<T>);
+
+impl_mapped_option! {
+    IdPlusOneOption;
+    |input| match input {
+        Some(value) => match as_i64(*value)?.checked_add(1) {
+            Some(wire) => from_i64(wire),
+            None => throw_explain!("Tried to encode an id too large to be incremented"),
+        },
+        None => from_i64(0),
+    },
+    |wire| match as_i64(wire)? {
+        0 => Ok(None),
+        wire => Ok(Some(from_i64(wire - 1)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{IdPlusOneOption, Maybe, SentinelOption, TailOptional};
+    use crate::prelude::{PacketComponent, Size};
+    use crate::transport::frame::{FrameReader, FrameWriter};
+    use crate::transport::packet::primitive::VarInt;
+
+    crate::struct_packet_components! {
+        #[derive(PartialEq)]
+        Extended {
+            id: VarInt,
+            extra: TailOptional<String>
+        }
+    }
+
+    #[tokio::test]
+    async fn test_maybe() -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![]);
+        Maybe::<VarInt>::encode(&Some(300), &mut (), &mut cursor).await?;
+        Maybe::<VarInt>::encode(&None, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![1, 0xAC, 0x02, 0]);
+        assert_eq!(
+            Maybe::<VarInt>::size(&Some(300), &mut ())?,
+            Size::Dynamic(3)
+        );
+        assert_eq!(Maybe::<VarInt>::size(&None, &mut ())?, Size::Dynamic(1));
+
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(
+            Maybe::<VarInt>::decode(&mut (), &mut cursor).await?,
+            Some(300)
+        );
+        assert_eq!(Maybe::<VarInt>::decode(&mut (), &mut cursor).await?, None);
+        let mut cursor = Cursor::new(vec![2, 7]);
+        assert_eq!(Maybe::<u8>::decode(&mut (), &mut cursor).await?, Some(7));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tail_optional() -> crate::prelude::Result<()> {
+        let without = Extended { id: 1, extra: None };
+        let with = Extended {
+            id: 2,
+            extra: Some("drax".to_string()),
+        };
+        let mut writer = FrameWriter::new(vec![]);
+        writer
+            .encode_frame::<_, Extended>(&mut (), &without)
+            .await?;
+        writer.encode_frame::<_, Extended>(&mut (), &with).await?;
+        let bytes = writer.into_inner();
+        assert_eq!(&bytes[..2], &[1, 1]);
+
+        let mut reader = FrameReader::new(std::io::Cursor::new(bytes));
+        assert_eq!(
+            reader.decode_frame::<_, Extended>(&mut ()).await?,
+            Some(without)
+        );
+        assert_eq!(
+            reader.decode_frame::<_, Extended>(&mut ()).await?,
+            Some(with)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sentinel_options() -> crate::prelude::Result<()> {
+        type Index = SentinelOption<VarInt, -1>;
+        let mut cursor = Cursor::new(vec![]);
+        Index::encode(&None, &mut (), &mut cursor).await?;
+        Index::encode(&Some(3), &mut (), &mut cursor).await?;
+        assert!(Index::encode(&Some(-1), &mut (), &mut cursor)
+            .await
+            .is_err());
+        assert_eq!(Index::size(&None, &mut ())?, Size::Dynamic(5));
+        assert_eq!(cursor.get_ref(), &vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 3]);
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(Index::decode(&mut (), &mut cursor).await?, None);
+        assert_eq!(Index::decode(&mut (), &mut cursor).await?, Some(3));
+
+        // the sentinel must be representable by the delegate to encode None
+        let mut cursor = Cursor::new(vec![]);
+        assert!(
+            SentinelOption::<u8, -1>::encode(&None, &mut (), &mut cursor)
+                .await
+                .is_err()
+        );
+
+        type Id = IdPlusOneOption<VarInt>;
+        let mut cursor = Cursor::new(vec![]);
+        Id::encode(&None, &mut (), &mut cursor).await?;
+        Id::encode(&Some(0), &mut (), &mut cursor).await?;
+        Id::encode(&Some(41), &mut (), &mut cursor).await?;
+        assert!(Id::encode(&Some(i32::MAX), &mut (), &mut cursor)
+            .await
+            .is_err());
+        assert_eq!(cursor.get_ref(), &vec![0, 1, 42]);
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(Id::decode(&mut (), &mut cursor).await?, None);
+        assert_eq!(Id::decode(&mut (), &mut cursor).await?, Some(0));
+        assert_eq!(Id::decode(&mut (), &mut cursor).await?, Some(41));
+        Ok(())
+    }
 }
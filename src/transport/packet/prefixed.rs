@@ -0,0 +1,161 @@
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+use crate::prelude::ErrorType;
+use crate::transport::buffer::var_num::size_var_int;
+use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
+use crate::transport::packet::primitive::VarInt;
+use crate::transport::packet::vec::checked_length;
+use crate::transport::packet::{PacketComponent, Size};
+use crate::{throw, throw_explain, PinnedLivelyResult};
+
+/// Decides what happens to bytes left over inside a `LengthPrefixed` component once its inner
+/// component has been decoded. Newer peers may append fields an older reader doesn't know about;
+/// a context can choose to skip them rather than reject the packet.
+pub trait TrailingDataPolicy {
+    /// Called with the type name of the inner component and the number of unread bytes. Returning
+    /// `true` skips the bytes, `false` rejects the component. Defaults to rejecting.
+    fn on_trailing_data(&mut self, component: &'static str, trailing: usize) -> bool {
+        let _ = (component, trailing);
+        false
+    }
+}
+
+impl TrailingDataPolicy for () {}
+
+/// Wraps the component `T` with a VarInt prefix holding its encoded length. The inner component
+/// can't read past its length, and any bytes it leaves unread are handled by the context's
+/// `TrailingDataPolicy`.
+pub struct LengthPrefixed<T>(PhantomData<T>);
+
+impl<T, C: Send + Sync + TrailingDataPolicy> PacketComponent<C> for LengthPrefixed<T>
+where
+    T: PacketComponent<C>,
+{
+    type ComponentType = T::ComponentType;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let len = checked_length(read.read_var_int().await?)?;
+            let mut limited = read.take(len as u64);
+            let value = T::decode(context, &mut limited).await?;
+
+            let trailing = limited.limit() as usize;
+            if trailing > 0 {
+                if !context.on_trailing_data(std::any::type_name::<T>(), trailing) {
+                    throw_explain!(format!(
+                        "Found {trailing} unread bytes after decoding {}",
+                        std::any::type_name::<T>()
+                    ))
+                }
+                let skipped = tokio::io::copy(&mut limited, &mut tokio::io::sink()).await?;
+                if skipped as usize != trailing {
+                    throw!(ErrorType::EOF)
+                }
+            }
+            Ok(value)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let size = match T::size(component_ref, context)? {
+                Size::Dynamic(x) | Size::Constant(x) => x,
+            };
+            write.write_var_int(i32::try_from(size)?).await?;
+            T::encode(component_ref, context, write).await
+        })
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        let size = match T::size(input, context)? {
+            Size::Dynamic(x) | Size::Constant(x) => x,
+        };
+        Ok(Size::Dynamic(size_var_int(i32::try_from(size)?) + size))
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let len = checked_length(VarInt::decode_from_buf(context, buf)?)?;
+        if len > buf.len() {
+            throw!(ErrorType::EOF)
+        }
+        let mut limited = buf.split_to(len);
+        let value = T::decode_from_buf(context, &mut limited)?;
+
+        let trailing = limited.len();
+        if trailing > 0 && !context.on_trailing_data(std::any::type_name::<T>(), trailing) {
+            throw_explain!(format!(
+                "Found {trailing} unread bytes after decoding {}",
+                std::any::type_name::<T>()
+            ))
+        }
+        Ok(value)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        T::heap_size(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{LengthPrefixed, TrailingDataPolicy};
+    use crate::prelude::PacketComponent;
+    use crate::transport::packet::primitive::VarInt;
+
+    #[derive(Default)]
+    struct Tolerant {
+        skipped: usize,
+    }
+
+    impl TrailingDataPolicy for Tolerant {
+        fn on_trailing_data(&mut self, _: &'static str, trailing: usize) -> bool {
+            self.skipped += trailing;
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trailing_data() -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![]);
+        LengthPrefixed::<VarInt>::encode(&300, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![2, 0xAC, 0x02]);
+
+        // a newer peer appended two bytes to the framed structure, followed by the next field
+        let bytes = vec![4, 0xAC, 0x02, 0x01, 0x02, 0x05];
+        let mut cursor = Cursor::new(bytes.clone());
+        assert!(LengthPrefixed::<VarInt>::decode(&mut (), &mut cursor)
+            .await
+            .is_err());
+
+        let mut context = Tolerant::default();
+        let mut cursor = Cursor::new(bytes);
+        assert_eq!(
+            LengthPrefixed::<VarInt>::decode(&mut context, &mut cursor).await?,
+            300
+        );
+        assert_eq!(context.skipped, 2);
+        assert_eq!(VarInt::decode(&mut context, &mut cursor).await?, 5);
+
+        // the inner component can't read past the prefixed length
+        let mut cursor = Cursor::new(vec![1, 0xAC, 0x02]);
+        assert!(LengthPrefixed::<VarInt>::decode(&mut context, &mut cursor)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
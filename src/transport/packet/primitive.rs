@@ -1,11 +1,24 @@
+use std::marker::PhantomData;
 use std::mem::size_of;
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU128, NonZeroU16,
+    NonZeroU32, NonZeroU64, NonZeroU8,
+};
+use std::sync::atomic::{
+    AtomicBool, AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicU16, AtomicU32, AtomicU64,
+    AtomicU8, Ordering,
+};
 
+use bytes::{Buf, Bytes};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use uuid::Uuid;
 
-use crate::transport::buffer::var_num::{size_var_int, size_var_long};
+use crate::prelude::ErrorType;
+use crate::transport::buffer::var_num::{
+    get_var_num, size_var_int, size_var_long, VarIntScheme, VarLongScheme, VarNumScheme,
+};
 use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
-use crate::PinnedLivelyResult;
+use crate::{throw, throw_explain, PinnedLivelyResult};
 
 use super::{PacketComponent, Size};
 
@@ -14,18 +27,29 @@ macro_rules! define_primitive_bind {
         $(
             impl<C: Send + Sync> PacketComponent<C> for $prim {
                 type ComponentType = $prim;
+
+                const MAX_SIZE: Option<usize> = Some(size_of::<Self>());
+
                 fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
-                    _: &'a mut C,
+                    context: &'a mut C,
                     read: &'a mut A,
                 ) -> PinnedLivelyResult<'a, Self::ComponentType>
                 where
                     Self: Sized,
                 {
-                    Box::pin(async move {
-                        let mut buf = [0; size_of::<Self>()];
-                        read.read_exact(&mut buf).await?;
-                        Ok(Self::from_be_bytes(buf))
-                    })
+                    Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+                }
+
+                async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                    _: &'a mut C,
+                    read: &'a mut A,
+                ) -> crate::prelude::Result<Self::ComponentType>
+                where
+                    Self::ComponentType: 'a,
+                {
+                    let mut buf = [0; size_of::<Self>()];
+                    read.read_exact(&mut buf).await?;
+                    Ok(Self::from_be_bytes(buf))
                 }
                 fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
                     component_ref: &'a Self,
@@ -40,16 +64,30 @@ macro_rules! define_primitive_bind {
                 fn size(_: &Self, __: &mut C) -> crate::prelude::Result<Size> {
                     Ok(Size::Constant(size_of::<Self>()))
                 }
+
+                fn decode_from_buf(
+                    _: &mut C,
+                    buf: &mut Bytes,
+                ) -> crate::prelude::Result<Self::ComponentType> {
+                    let mut bytes = [0; size_of::<Self>()];
+                    if buf.len() < bytes.len() {
+                        throw!(ErrorType::EOF)
+                    }
+                    buf.copy_to_slice(&mut bytes);
+                    Ok(Self::from_be_bytes(bytes))
+                }
             }
         )*
     }
 }
 
-define_primitive_bind!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+define_primitive_bind!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
 
 impl<C: Send + Sync> PacketComponent<C> for () {
     type ComponentType = ();
 
+    const MAX_SIZE: Option<usize> = Some(0);
+
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
         _: &'a mut C,
         _: &'a mut A,
@@ -70,17 +108,52 @@ impl<C: Send + Sync> PacketComponent<C> for () {
     }
 }
 
+impl<C: Send + Sync, T: Send + Sync> PacketComponent<C> for PhantomData<T> {
+    type ComponentType = PhantomData<T>;
+
+    const MAX_SIZE: Option<usize> = Some(0);
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        _: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move { Ok(PhantomData) })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        _: &'a Self::ComponentType,
+        _: &'a mut C,
+        _: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn size(_: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        Ok(Size::Constant(0))
+    }
+}
+
 impl<C: Send + Sync> PacketComponent<C> for bool {
     type ComponentType = bool;
 
+    const MAX_SIZE: Option<usize> = Some(1);
+
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
-        _: &'a mut C,
+        context: &'a mut C,
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType> {
-        Box::pin(async move {
-            let b = read.read_u8().await?;
-            Ok(b != 0x0)
-        })
+        Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+    }
+
+    async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self::ComponentType: 'a,
+    {
+        let b = read.read_u8().await?;
+        Ok(b != 0x0)
     }
 
     fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
@@ -99,6 +172,69 @@ impl<C: Send + Sync> PacketComponent<C> for bool {
     fn size(_: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
         Ok(Size::Constant(1))
     }
+
+    fn decode_from_buf(_: &mut C, buf: &mut Bytes) -> crate::prelude::Result<Self::ComponentType> {
+        if !buf.has_remaining() {
+            throw!(ErrorType::EOF)
+        }
+        Ok(buf.get_u8() != 0x0)
+    }
+}
+
+/// A char is encoded as its UTF-8 bytes with no length prefix, the length being given by the first
+/// byte. Overlong encodings, surrogates and values past `char::MAX` are rejected.
+impl<C: Send + Sync> PacketComponent<C> for char {
+    type ComponentType = char;
+
+    const MAX_SIZE: Option<usize> = Some(4);
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+    }
+
+    async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self::ComponentType: 'a,
+    {
+        let mut buf = [0; 4];
+        buf[0] = read.read_u8().await?;
+        let len = match buf[0] {
+            0x00..=0x7F => 1,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            lead => throw_explain!(format!("Invalid UTF-8 lead byte {lead:#04x} for a char")),
+        };
+        read.read_exact(&mut buf[1..len]).await?;
+        match std::str::from_utf8(&buf[..len])?.chars().next() {
+            Some(value) => Ok(value),
+            None => throw_explain!("Decoded an empty char"),
+        }
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let mut buf = [0; 4];
+            write
+                .write_all(component_ref.encode_utf8(&mut buf).as_bytes())
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        Ok(Size::Dynamic(input.len_utf8()))
+    }
 }
 
 pub struct VarInt;
@@ -106,11 +242,23 @@ pub struct VarInt;
 impl<C: Send + Sync> PacketComponent<C> for VarInt {
     type ComponentType = i32;
 
+    const MAX_SIZE: Option<usize> = Some(5);
+
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
-        _: &'a mut C,
+        context: &'a mut C,
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType> {
-        Box::pin(async move { read.read_var_int().await })
+        Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+    }
+
+    async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self::ComponentType: 'a,
+    {
+        read.read_var_int().await
     }
 
     fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
@@ -124,6 +272,10 @@ impl<C: Send + Sync> PacketComponent<C> for VarInt {
     fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
         Ok(Size::Dynamic(size_var_int(*input)))
     }
+
+    fn decode_from_buf(_: &mut C, buf: &mut Bytes) -> crate::prelude::Result<Self::ComponentType> {
+        get_var_num::<VarIntScheme>(buf)
+    }
 }
 
 pub struct VarLong;
@@ -131,11 +283,23 @@ pub struct VarLong;
 impl<C: Send + Sync> PacketComponent<C> for VarLong {
     type ComponentType = i64;
 
+    const MAX_SIZE: Option<usize> = Some(10);
+
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
-        _: &'a mut C,
+        context: &'a mut C,
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType> {
-        Box::pin(async move { read.read_var_long().await })
+        Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+    }
+
+    async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self::ComponentType: 'a,
+    {
+        read.read_var_long().await
     }
 
     fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
@@ -149,23 +313,332 @@ impl<C: Send + Sync> PacketComponent<C> for VarLong {
     fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
         Ok(Size::Dynamic(size_var_long(*input)))
     }
+
+    fn decode_from_buf(_: &mut C, buf: &mut Bytes) -> crate::prelude::Result<Self::ComponentType> {
+        get_var_num::<VarLongScheme>(buf)
+    }
 }
 
-impl<C: Send + Sync> PacketComponent<C> for Uuid {
-    type ComponentType = Uuid;
+/// A value encoded with the var num scheme `S`, see `VarNumScheme`.
+pub struct VarNum<S>(PhantomData<S>);
+
+impl<C: Send + Sync, S: VarNumScheme> PacketComponent<C> for VarNum<S> {
+    type ComponentType = S::Value;
+
+    const MAX_SIZE: Option<usize> = Some(S::MAX_BYTES);
 
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
         _: &'a mut C,
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move { read.read_var_num::<S>().await })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move { write.write_var_num::<S>(*component_ref).await })
+    }
+
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        Ok(Size::Dynamic(S::size(*input)))
+    }
+
+    fn decode_from_buf(_: &mut C, buf: &mut Bytes) -> crate::prelude::Result<Self::ComponentType> {
+        get_var_num::<S>(buf)
+    }
+}
+
+macro_rules! define_strict_var_num {
+    ($($(#[$($doc_tt:tt)*])* $name:ident, $typing:ty, $max_size:literal, $read_fn:ident, $write_fn:ident, $size_fn:ident);*) => {$(
+        $(#[$($doc_tt)*])*
+        pub struct $name;
+
+        impl<C: Send + Sync> PacketComponent<C> for $name {
+            type ComponentType = $typing;
+
+            const MAX_SIZE: Option<usize> = Some($max_size);
+
+            fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                context: &'a mut C,
+                read: &'a mut A,
+            ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+                Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+            }
+
+            async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                _: &'a mut C,
+                read: &'a mut A,
+            ) -> crate::prelude::Result<Self::ComponentType>
+            where
+                Self::ComponentType: 'a,
+            {
+                read.$read_fn().await
+            }
+
+            fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                component_ref: &'a Self::ComponentType,
+                _: &'a mut C,
+                write: &'a mut A,
+            ) -> PinnedLivelyResult<'a, ()> {
+                Box::pin(async move { write.$write_fn(*component_ref).await })
+            }
+
+            fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+                Ok(Size::Dynamic($size_fn(*input)))
+            }
+        }
+    )*};
+}
+
+define_strict_var_num!(
+    /// A `VarInt` which rejects over-long encodings when decoded, since they are never produced by
+    /// a well behaved writer and are a common signature of malformed or adversarial traffic.
+    StrictVarInt, i32, 5, read_var_int_strict, write_var_int, size_var_int;
+    /// A `VarLong` which rejects over-long encodings when decoded.
+    StrictVarLong, i64, 10, read_var_long_strict, write_var_long, size_var_long
+);
+
+/// A `VarInt` limited to at most `MAX_BYTES` bytes when both decoded and encoded, such as the
+/// length prefix of a frame header. Decoding stops with an error as soon as the byte past the limit
+/// would be read, and values which need more bytes, including every negative value below the full
+/// five bytes, can't be encoded.
+pub struct BoundedVarInt<const MAX_BYTES: usize>;
+
+/// A `VarInt` of at most 3 bytes, holding values up to 21 bits, as used by Minecraft frame headers.
+pub type VarInt21 = BoundedVarInt<3>;
+
+impl<C: Send + Sync, const MAX_BYTES: usize> PacketComponent<C> for BoundedVarInt<MAX_BYTES> {
+    type ComponentType = i32;
+
+    const MAX_SIZE: Option<usize> = Some(MAX_BYTES);
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+    }
+
+    async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self::ComponentType: 'a,
+    {
+        let mut state = Default::default();
+        for _ in 0..MAX_BYTES {
+            if let Some(value) = VarIntScheme::decode_byte(&mut state, read.read_u8().await?)? {
+                return Ok(value);
+            }
+        }
+        throw_explain!(format!("VarInt exceeded {MAX_BYTES} bytes"))
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
         Box::pin(async move {
-            let mut buf = [0; 16];
-            read.read_exact(&mut buf).await?;
-            let uuid = Uuid::from_slice(&buf)?;
-            Ok(uuid)
+            if size_var_int(*component_ref) > MAX_BYTES {
+                throw_explain!(format!(
+                    "VarInt {component_ref} does not fit in {MAX_BYTES} bytes"
+                ))
+            }
+            write.write_var_int(*component_ref).await
         })
     }
 
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        Ok(Size::Dynamic(size_var_int(*input)))
+    }
+}
+
+/// Delegates to `D`, rejecting decoded values outside of `MIN..=MAX` and refusing to encode them.
+pub struct Ranged<D, const MIN: i64, const MAX: i64>(PhantomData<D>);
+
+/// A `VarInt` limited to `MIN..=MAX`.
+pub type RangedVarInt<const MIN: i64, const MAX: i64> = Ranged<VarInt, MIN, MAX>;
+
+fn check_range<const MIN: i64, const MAX: i64>(value: i64) -> crate::prelude::Result<()> {
+    if !(MIN..=MAX).contains(&value) {
+        throw_explain!(format!("Value {value} is out of range {MIN}..={MAX}"))
+    }
+    Ok(())
+}
+
+impl<C: Send + Sync, D, const MIN: i64, const MAX: i64> PacketComponent<C> for Ranged<D, MIN, MAX>
+where
+    D: PacketComponent<C>,
+    D::ComponentType: Copy + Into<i64>,
+{
+    type ComponentType = D::ComponentType;
+
+    const MAX_SIZE: Option<usize> = D::MAX_SIZE;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let value = D::decode(context, read).await?;
+            check_range::<MIN, MAX>(value.into())?;
+            Ok(value)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            check_range::<MIN, MAX>((*component_ref).into())?;
+            D::encode(component_ref, context, write).await
+        })
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        D::size(input, context)
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let value = D::decode_from_buf(context, buf)?;
+        check_range::<MIN, MAX>(value.into())?;
+        Ok(value)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        D::heap_size(input)
+    }
+}
+
+macro_rules! define_non_zero_bind {
+    ($($non_zero:ty => $prim:ty),*) => {
+        $(
+            impl<C: Send + Sync> PacketComponent<C> for $non_zero {
+                type ComponentType = $non_zero;
+
+                const MAX_SIZE: Option<usize> = Some(size_of::<Self>());
+
+                fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                    context: &'a mut C,
+                    read: &'a mut A,
+                ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+                    Box::pin(async move {
+                        let value = <$prim as PacketComponent<C>>::decode(context, read).await?;
+                        match <$non_zero>::new(value) {
+                            Some(value) => Ok(value),
+                            None => throw_explain!(concat!(
+                                "Expected a non-zero ",
+                                stringify!($prim),
+                                ", found 0"
+                            )),
+                        }
+                    })
+                }
+
+                fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                    component_ref: &'a Self::ComponentType,
+                    context: &'a mut C,
+                    write: &'a mut A,
+                ) -> PinnedLivelyResult<'a, ()> {
+                    Box::pin(async move {
+                        let value = component_ref.get();
+                        <$prim as PacketComponent<C>>::encode(&value, context, write).await
+                    })
+                }
+
+                fn size(_: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+                    Ok(Size::Constant(size_of::<Self>()))
+                }
+            }
+        )*
+    }
+}
+
+define_non_zero_bind!(
+    NonZeroU8 => u8, NonZeroU16 => u16, NonZeroU32 => u32, NonZeroU64 => u64, NonZeroU128 => u128,
+    NonZeroI8 => i8, NonZeroI16 => i16, NonZeroI32 => i32, NonZeroI64 => i64, NonZeroI128 => i128
+);
+
+/// Atomics are encoded as the value they hold when encoding starts, the same as the primitive they
+/// wrap, so state shared with other tasks can be encoded without copying it out first. Encodings
+/// are fixed width, so a value changing between sizing and encoding can't change the size.
+macro_rules! define_atomic_bind {
+    ($($atomic:ty => $prim:ty),*) => {
+        $(
+            impl<C: Send + Sync> PacketComponent<C> for $atomic {
+                type ComponentType = $atomic;
+
+                const MAX_SIZE: Option<usize> = <$prim as PacketComponent<C>>::MAX_SIZE;
+
+                fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                    context: &'a mut C,
+                    read: &'a mut A,
+                ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+                    Box::pin(async move {
+                        let value = <$prim as PacketComponent<C>>::decode(context, read).await?;
+                        Ok(<$atomic>::new(value))
+                    })
+                }
+
+                fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                    component_ref: &'a Self::ComponentType,
+                    context: &'a mut C,
+                    write: &'a mut A,
+                ) -> PinnedLivelyResult<'a, ()> {
+                    Box::pin(async move {
+                        let value = component_ref.load(Ordering::Acquire);
+                        <$prim as PacketComponent<C>>::encode(&value, context, write).await
+                    })
+                }
+
+                fn size(_: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+                    <$prim as PacketComponent<C>>::size(&Default::default(), context)
+                }
+            }
+        )*
+    }
+}
+
+define_atomic_bind!(
+    AtomicBool => bool, AtomicU8 => u8, AtomicU16 => u16, AtomicU32 => u32, AtomicU64 => u64,
+    AtomicI8 => i8, AtomicI16 => i16, AtomicI32 => i32, AtomicI64 => i64
+);
+
+impl<C: Send + Sync> PacketComponent<C> for Uuid {
+    type ComponentType = Uuid;
+
+    const MAX_SIZE: Option<usize> = Some(16);
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+    }
+
+    async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self::ComponentType: 'a,
+    {
+        let mut buf = [0; 16];
+        read.read_exact(&mut buf).await?;
+        let uuid = Uuid::from_slice(&buf)?;
+        Ok(uuid)
+    }
+
     fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
         component_ref: &'a Self::ComponentType,
         _: &'a mut C,
@@ -181,3 +654,130 @@ impl<C: Send + Sync> PacketComponent<C> for Uuid {
         Ok(Size::Constant(size_of::<u64>() * 2))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use std::num::{NonZeroI32, NonZeroU128, NonZeroU8};
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+    use super::{BoundedVarInt, Ranged, RangedVarInt, VarInt21};
+    use crate::prelude::{PacketComponent, Size};
+
+    #[tokio::test]
+    async fn test_bounded_var_int() -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![]);
+        VarInt21::encode(&2097151, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![0xFF, 0xFF, 0x7F]);
+        assert!(VarInt21::encode(&2097152, &mut (), &mut cursor)
+            .await
+            .is_err());
+        assert!(VarInt21::encode(&-1, &mut (), &mut cursor).await.is_err());
+        assert!(BoundedVarInt::<5>::encode(&-1, &mut (), &mut cursor)
+            .await
+            .is_ok());
+
+        let mut cursor = Cursor::new(vec![0xFF, 0xFF, 0x7F]);
+        assert_eq!(VarInt21::decode(&mut (), &mut cursor).await?, 2097151);
+        let mut cursor = Cursor::new(vec![0x80, 0x80, 0x80, 0x00]);
+        assert!(VarInt21::decode(&mut (), &mut cursor).await.is_err());
+        // the byte past the limit is left unread
+        assert_eq!(cursor.position(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ranged_and_non_zero() -> crate::prelude::Result<()> {
+        type ViewDistance = RangedVarInt<2, 32>;
+
+        let mut cursor = Cursor::new(vec![]);
+        ViewDistance::encode(&32, &mut (), &mut cursor).await?;
+        let err = ViewDistance::encode(&33, &mut (), &mut cursor)
+            .await
+            .unwrap_err();
+        assert!(format!("{err}").contains("33"));
+        assert_eq!(cursor.get_ref(), &vec![32]);
+        cursor.set_position(0);
+        assert_eq!(ViewDistance::decode(&mut (), &mut cursor).await?, 32);
+        let mut cursor = Cursor::new(vec![1]);
+        assert!(ViewDistance::decode(&mut (), &mut cursor).await.is_err());
+        let mut cursor = Cursor::new(vec![0xFF]);
+        assert!(Ranged::<u8, 0, 100>::decode(&mut (), &mut cursor)
+            .await
+            .is_err());
+
+        let mut cursor = Cursor::new(vec![]);
+        NonZeroI32::encode(&NonZeroI32::new(-5).unwrap(), &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &(-5i32).to_be_bytes().to_vec());
+        cursor.set_position(0);
+        assert_eq!(NonZeroI32::decode(&mut (), &mut cursor).await?.get(), -5);
+        let mut cursor = Cursor::new(vec![0]);
+        assert!(NonZeroU8::decode(&mut (), &mut cursor).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_atomics() -> crate::prelude::Result<()> {
+        let health = AtomicI32::new(20);
+        health.fetch_sub(3, Ordering::Relaxed);
+        let mut cursor = Cursor::new(vec![]);
+        AtomicI32::encode(&health, &mut (), &mut cursor).await?;
+        AtomicBool::encode(&AtomicBool::new(true), &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![0, 0, 0, 17, 1]);
+        assert_eq!(
+            <AtomicI32 as PacketComponent<()>>::MAX_SIZE,
+            Some(std::mem::size_of::<i32>())
+        );
+
+        cursor.set_position(0);
+        let decoded = AtomicI32::decode(&mut (), &mut cursor).await?;
+        assert_eq!(decoded.load(Ordering::Relaxed), 17);
+        assert!(AtomicBool::decode(&mut (), &mut cursor)
+            .await?
+            .load(Ordering::Relaxed));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wide_ints_and_chars() -> crate::prelude::Result<()> {
+        let session = 0x0123_4567_89AB_CDEF_FEDC_BA98_7654_3210u128;
+        let mut cursor = Cursor::new(vec![]);
+        u128::encode(&session, &mut (), &mut cursor).await?;
+        i128::encode(&i128::MIN, &mut (), &mut cursor).await?;
+        assert_eq!(&cursor.get_ref()[..16], &session.to_be_bytes());
+        assert_eq!(cursor.get_ref().len(), 32);
+        cursor.set_position(0);
+        assert_eq!(u128::decode(&mut (), &mut cursor).await?, session);
+        assert_eq!(i128::decode(&mut (), &mut cursor).await?, i128::MIN);
+        let mut cursor = Cursor::new(vec![0; 16]);
+        assert!(NonZeroU128::decode(&mut (), &mut cursor).await.is_err());
+
+        let mut cursor = Cursor::new(vec![]);
+        for value in ['a', 'é', '€', '🦀'] {
+            char::encode(&value, &mut (), &mut cursor).await?;
+            assert_eq!(
+                char::size(&value, &mut ())?,
+                Size::Dynamic(value.len_utf8())
+            );
+        }
+        assert_eq!(cursor.get_ref(), "aé€🦀".as_bytes());
+        cursor.set_position(0);
+        for value in ['a', 'é', '€', '🦀'] {
+            assert_eq!(char::decode(&mut (), &mut cursor).await?, value);
+        }
+
+        // a continuation byte, an overlong encoding, a surrogate and a value past char::MAX
+        for bytes in [
+            vec![0x80],
+            vec![0xC0, 0x80],
+            vec![0xED, 0xA0, 0x80],
+            vec![0xF4, 0x90, 0x80, 0x80],
+        ] {
+            assert!(char::decode(&mut (), &mut Cursor::new(bytes))
+                .await
+                .is_err());
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,36 @@
 use std::marker::PhantomData;
 
 use serde::{Deserialize, Serialize};
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::transport::packet::vec::VecU8;
+use crate::prelude::{DraxReadExt, DraxWriteExt, ErrorType};
+use crate::transport::buffer::var_num::size_var_int;
+use crate::transport::packet::vec::{checked_length, VecU8};
 use crate::transport::packet::{PacketComponent, Size};
-use crate::PinnedLivelyResult;
+use crate::{throw, throw_explain, PinnedLivelyResult};
+
+/// Estimates the heap memory retained by a decoded JSON value as the length of its encoding, which
+/// tracks the strings, arrays and objects it holds, without allocating the encoding.
+fn json_heap_size<T: Serialize>(value: &T) -> usize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    match serde_json::to_writer(&mut counter, value) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
+}
 
 pub struct JsonDelegate<T> {
     _phantom_t: PhantomData<T>,
@@ -46,4 +71,444 @@ where
     fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
         VecU8::size(&serde_json::to_vec(&input)?, context)
     }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        json_heap_size(input)
+    }
+}
+
+/// A JSON document decoded into a `serde_json::Value`, for payloads which aren't bound to a fixed
+/// structure.
+pub type JsonValue = JsonDelegate<serde_json::Value>;
+
+/// Checks that the string is a single well formed JSON document without building it.
+fn validate_raw(raw: &str, stage: &str) -> crate::prelude::Result<()> {
+    match serde_json::from_str::<serde::de::IgnoredAny>(raw) {
+        Ok(_) => Ok(()),
+        Err(err) => throw!(
+            format!(
+                "While {stage}; invalid JSON at line {} column {}",
+                err.line(),
+                err.column()
+            ),
+            ErrorType::SerdeJsonError(err)
+        ),
+    }
+}
+
+/// A JSON document kept as the exact string it was received as. The document is checked to be
+/// well formed when decoded and encoded but is never rebuilt, so proxies can inspect or pass through
+/// payloads without reordering keys or changing whitespace.
+pub struct RawJson;
+
+impl<C: Send + Sync> PacketComponent<C> for RawJson {
+    type ComponentType = String;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let raw = String::from_utf8(VecU8::decode(context, read).await?)?;
+            validate_raw(&raw, "decoding")?;
+            Ok(raw)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            validate_raw(component_ref, "encoding")?;
+            write
+                .write_var_int(i32::try_from(component_ref.len())?)
+                .await?;
+            write.write_all(component_ref.as_bytes()).await?;
+            Ok(())
+        })
+    }
+
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        Ok(Size::Dynamic(
+            size_var_int(i32::try_from(input.len())?) + input.len(),
+        ))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.capacity()
+    }
+}
+
+/// A JSON delegate which bounds the encoded document to `BYTES` bytes and its nesting of arrays and
+/// objects to `DEPTH` levels. Both limits are checked before the document is handed to serde, so an
+/// oversized or overly nested payload fails with an error describing the limit and where it was
+/// exceeded rather than an opaque recursion error. `serde_json` applies its own recursion limit of
+/// 128 on top of `DEPTH`.
+pub struct LimitedJsonDelegate<T, const DEPTH: usize, const BYTES: usize> {
+    _phantom_t: PhantomData<T>,
+}
+
+/// Finds the offset of the first array or object which opens past `limit` levels of nesting.
+fn exceeded_depth(bytes: &[u8], limit: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (offset, byte) in bytes.iter().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > limit {
+                    return Some(offset);
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn check_limits<const DEPTH: usize, const BYTES: usize>(
+    bytes: &[u8],
+    stage: &str,
+) -> crate::prelude::Result<()> {
+    if bytes.len() > BYTES {
+        throw_explain!(format!(
+            "While {stage}; JSON of {} bytes exceeded byte bound {BYTES}",
+            bytes.len()
+        ))
+    }
+    if let Some(offset) = exceeded_depth(bytes, DEPTH) {
+        throw_explain!(format!(
+            "While {stage}; JSON exceeded nesting bound {DEPTH} at byte {offset}"
+        ))
+    }
+    Ok(())
+}
+
+impl<C: Send + Sync, T, const DEPTH: usize, const BYTES: usize> PacketComponent<C>
+    for LimitedJsonDelegate<T, DEPTH, BYTES>
+where
+    T: for<'de> Deserialize<'de>,
+    T: Serialize + Send + Sync,
+{
+    type ComponentType = T;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType>
+    where
+        Self: Sized,
+    {
+        Box::pin(async move {
+            let len = read.read_var_int().await?;
+            if len as i64 > BYTES as i64 {
+                throw_explain!(format!(
+                    "While decoding; JSON of {len} bytes exceeded byte bound {BYTES}"
+                ))
+            }
+            let mut bytes = vec![0; checked_length(len)?];
+            read.read_exact(&mut bytes).await?;
+            check_limits::<DEPTH, BYTES>(&bytes, "decoding")?;
+            match serde_json::from_slice(&bytes) {
+                Ok(value) => Ok(value),
+                Err(err) => throw!(
+                    format!(
+                        "While decoding; invalid JSON at line {} column {}",
+                        err.line(),
+                        err.column()
+                    ),
+                    ErrorType::SerdeJsonError(err)
+                ),
+            }
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let bytes = serde_json::to_vec(&component_ref)?;
+            check_limits::<DEPTH, BYTES>(&bytes, "encoding")?;
+            VecU8::encode(&bytes, context, write).await
+        })
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        VecU8::size(&serde_json::to_vec(&input)?, context)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        json_heap_size(input)
+    }
+}
+
+/// The largest document a `GzJson` accepts by default, compressed or decompressed.
+#[cfg(feature = "compression")]
+pub const DEFAULT_GZ_JSON_BYTES: usize = 2_097_152;
+
+/// A JSON document compressed with gzip, encoded as the compressed bytes prefixed with their length
+/// as a VarInt. Both the compressed bytes and the decompressed document are bounded to `BYTES`
+/// bytes, so a small payload can't inflate into an unbounded document. Only available with the
+/// `compression` feature.
+///
+/// Compression runs synchronously on the task encoding the document, and sizing the document
+/// compresses it as well, so a document which is sized and then encoded is compressed twice.
+/// Large documents are best encoded off the executor, such as with `frame::encode_sync` inside
+/// `spawn_blocking`.
+#[cfg(feature = "compression")]
+pub struct GzJson<T, const BYTES: usize = DEFAULT_GZ_JSON_BYTES> {
+    _phantom_t: PhantomData<T>,
+}
+
+#[cfg(feature = "compression")]
+fn gzip(json: &[u8]) -> crate::prelude::Result<Vec<u8>> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(json)?;
+    Ok(encoder.finish()?)
+}
+
+/// Serializes and compresses the document, checking that both the document and its compressed
+/// bytes fit in `BYTES` so nothing is encoded which its own decode would reject.
+#[cfg(feature = "compression")]
+fn gzipped<T: Serialize, const BYTES: usize>(value: &T) -> crate::prelude::Result<Vec<u8>> {
+    let json = serde_json::to_vec(value)?;
+    if json.len() > BYTES {
+        throw_explain!(format!(
+            "While encoding; JSON of {} bytes exceeded byte bound {BYTES}",
+            json.len()
+        ))
+    }
+    let compressed = gzip(&json)?;
+    if compressed.len() > BYTES {
+        throw_explain!(format!(
+            "While encoding; gzipped JSON of {} bytes exceeded byte bound {BYTES}",
+            compressed.len()
+        ))
+    }
+    Ok(compressed)
+}
+
+#[cfg(feature = "compression")]
+fn gunzip(compressed: &[u8], limit: usize) -> crate::prelude::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut json = vec![];
+    flate2::read::GzDecoder::new(compressed)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut json)?;
+    if json.len() > limit {
+        throw_explain!(format!(
+            "While decoding; gzipped JSON inflated past byte bound {limit}"
+        ))
+    }
+    Ok(json)
+}
+
+#[cfg(feature = "compression")]
+impl<C: Send + Sync, T, const BYTES: usize> PacketComponent<C> for GzJson<T, BYTES>
+where
+    T: for<'de> Deserialize<'de>,
+    T: Serialize + Send + Sync,
+{
+    type ComponentType = T;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType>
+    where
+        Self: Sized,
+    {
+        Box::pin(async move {
+            let len = read.read_var_int().await?;
+            if len as i64 > BYTES as i64 {
+                throw_explain!(format!(
+                    "While decoding; gzipped JSON of {len} bytes exceeded byte bound {BYTES}"
+                ))
+            }
+            let mut compressed = vec![0; checked_length(len)?];
+            read.read_exact(&mut compressed).await?;
+            let json = gunzip(&compressed, BYTES)?;
+            Ok(serde_json::from_slice(&json)?)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let compressed = gzipped::<T, BYTES>(component_ref)?;
+            VecU8::encode(&compressed, context, write).await
+        })
+    }
+
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        let len = gzipped::<T, BYTES>(input)?.len();
+        Ok(Size::Dynamic(size_var_int(i32::try_from(len)?) + len))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        json_heap_size(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::{json, Value};
+
+    use super::{exceeded_depth, JsonValue, LimitedJsonDelegate, RawJson};
+    use crate::prelude::{ErrorType, PacketComponent};
+    use crate::transport::packet::vec::VecU8;
+
+    type Limited = LimitedJsonDelegate<Value, 3, 64>;
+
+    async fn decode_raw(raw: &str) -> crate::prelude::Result<Value> {
+        let mut cursor = Cursor::new(vec![]);
+        VecU8::encode(&raw.as_bytes().to_vec(), &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        Limited::decode(&mut (), &mut cursor).await
+    }
+
+    #[test]
+    fn test_exceeded_depth() {
+        assert_eq!(exceeded_depth(br#"{"a":[1,{"b":2}]}"#, 3), None);
+        assert_eq!(exceeded_depth(br#"{"a":[1,{"b":2}]}"#, 2), Some(8));
+        assert_eq!(exceeded_depth(br#"{"a":"[[[[\"[["}"#, 1), None);
+    }
+
+    #[tokio::test]
+    async fn test_limited_json() -> crate::prelude::Result<()> {
+        let value = json!({"a": [1, {"b": 2}]});
+        let mut cursor = Cursor::new(vec![]);
+        Limited::encode(&value, &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(Limited::decode(&mut (), &mut cursor).await?, value);
+
+        let mut cursor = Cursor::new(vec![]);
+        assert!(Limited::encode(&json!([[[[1]]]]), &mut (), &mut cursor)
+            .await
+            .is_err());
+
+        assert!(decode_raw("[[[[1]]]]").await.is_err());
+        assert!(decode_raw(&format!("\"{}\"", "a".repeat(64)))
+            .await
+            .is_err());
+
+        let err = decode_raw("{\"a\": }").await.expect_err("Invalid JSON");
+        assert!(matches!(err.error_type, ErrorType::SerdeJsonError(_)));
+        assert_eq!(
+            err.context.to_string(),
+            "`While decoding; invalid JSON at line 1 column 7`"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_json_value() -> crate::prelude::Result<()> {
+        let value = json!({"text": "drax", "extra": [{"bold": true}]});
+        let mut cursor = Cursor::new(vec![]);
+        JsonValue::encode(&value, &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(JsonValue::decode(&mut (), &mut cursor).await?, value);
+        assert_eq!(
+            <JsonValue as PacketComponent<()>>::heap_size(&value),
+            value.to_string().len()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_json() -> crate::prelude::Result<()> {
+        let raw = "{ \"z\": 1,\n  \"a\": [true] }".to_string();
+        let mut cursor = Cursor::new(vec![]);
+        RawJson::encode(&raw, &mut (), &mut cursor).await?;
+        assert_eq!(
+            RawJson::size(&raw, &mut ())?,
+            crate::prelude::Size::Dynamic(cursor.get_ref().len())
+        );
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(RawJson::decode(&mut (), &mut cursor).await?, raw);
+
+        let mut cursor = Cursor::new(vec![]);
+        assert!(
+            RawJson::encode(&"{\"a\":".to_string(), &mut (), &mut cursor)
+                .await
+                .is_err()
+        );
+        VecU8::encode(&b"[1, 2".to_vec(), &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        let err = RawJson::decode(&mut (), &mut cursor)
+            .await
+            .expect_err("Invalid JSON");
+        assert!(matches!(err.error_type, ErrorType::SerdeJsonError(_)));
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_gz_json() -> crate::prelude::Result<()> {
+        use super::GzJson;
+
+        let value = json!({"entries": vec!["drax"; 64]});
+        let mut bytes = vec![];
+        GzJson::<Value>::encode(&value, &mut (), &mut bytes).await?;
+        assert_eq!(
+            GzJson::<Value>::size(&value, &mut ())?,
+            crate::prelude::Size::Dynamic(bytes.len())
+        );
+        assert!(bytes.len() < value.to_string().len());
+        assert_eq!(
+            GzJson::<Value>::decode(&mut (), &mut Cursor::new(bytes.clone())).await?,
+            value
+        );
+
+        // the compressed payload fits in 128 bytes but inflates past them
+        assert!(bytes.len() <= 128);
+        assert!(
+            GzJson::<Value, 128>::decode(&mut (), &mut Cursor::new(bytes))
+                .await
+                .is_err()
+        );
+        assert!(GzJson::<Value, 128>::encode(&value, &mut (), &mut vec![])
+            .await
+            .is_err());
+
+        let mut bytes = vec![];
+        VecU8::encode(&b"{}".to_vec(), &mut (), &mut bytes).await?;
+        assert!(GzJson::<Value>::decode(&mut (), &mut Cursor::new(bytes))
+            .await
+            .is_err());
+
+        // a short document gains more from the gzip header than it saves
+        let value = json!("drax");
+        let json = value.to_string();
+        assert!(super::gzip(json.as_bytes())?.len() > 16 && json.len() <= 16);
+        assert!(GzJson::<Value, 16>::size(&value, &mut ()).is_err());
+        assert!(GzJson::<Value, 16>::encode(&value, &mut (), &mut vec![])
+            .await
+            .is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,190 @@
+use std::fmt::{Debug, Formatter};
+use std::io::{Cursor, SeekFrom};
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, Take};
+
+use crate::transport::buffer::var_num::size_var_int;
+use crate::transport::buffer::DraxWriteExt;
+use crate::transport::packet::buf::ByteBuf;
+use crate::transport::packet::{PacketComponent, Size};
+use crate::{throw_explain, PinnedLivelyResult};
+
+/// A seekable reader a `ByteStream` takes its bytes from. Sources are seekable so the bytes left
+/// in them can be checked before the stream's length is written.
+pub trait StreamSource: AsyncRead + AsyncSeek + Unpin + Send + Sync {}
+
+impl<T: AsyncRead + AsyncSeek + Unpin + Send + Sync> StreamSource for T {}
+
+type Source = Box<dyn StreamSource>;
+
+/// A byte array encoded the same as `VecU8` whose bytes are streamed from a source when encoding
+/// rather than held in memory.
+///
+/// Decoding reads the bytes into the stream, which owns them, so components following it decode
+/// from the bytes after it. Decoding through `decode_from_buf`, as the frame readers do, shares the
+/// memory of the frame rather than copying out of it.
+pub struct StreamingBytes;
+
+/// A length prefixed run of bytes streamed by `StreamingBytes`.
+pub struct ByteStream {
+    len: usize,
+    source: Mutex<Option<Source>>,
+}
+
+impl ByteStream {
+    /// A stream encoding the next `len` bytes of the source. The source is consumed by the first
+    /// encode, so the stream can only be encoded once.
+    pub fn new<R: StreamSource + 'static>(len: usize, source: R) -> Self {
+        Self {
+            len,
+            source: Mutex::new(Some(Box::new(source))),
+        }
+    }
+
+    /// A stream of the given bytes, as decoded streams are.
+    pub fn from_bytes(bytes: Bytes) -> Self {
+        Self::new(bytes.len(), Cursor::new(bytes))
+    }
+
+    /// The number of bytes in the stream.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Takes a reader over the bytes of the stream, bounded to its length, if it hasn't been
+    /// taken or encoded yet.
+    pub fn take_body(&self) -> Option<Take<Source>> {
+        let source = self.source.lock().ok()?.take()?;
+        Some(source.take(self.len as u64))
+    }
+}
+
+impl Debug for ByteStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ByteStream")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<C: Send + Sync> PacketComponent<C> for StreamingBytes {
+    type ComponentType = ByteStream;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let bytes = <ByteBuf as PacketComponent<C>>::decode(context, read).await?;
+            Ok(ByteStream::from_bytes(bytes))
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let len = i32::try_from(component_ref.len)?;
+            let mut source = match component_ref.source.lock().ok().and_then(|mut s| s.take()) {
+                Some(source) => source,
+                None => throw_explain!("Byte stream has no source left to encode"),
+            };
+            let start = source.stream_position().await?;
+            let end = source.seek(SeekFrom::End(0)).await?;
+            source.seek(SeekFrom::Start(start)).await?;
+            let available = end.saturating_sub(start);
+            if available < len as u64 {
+                throw_explain!(format!(
+                    "Byte stream of {len} bytes has only {available} bytes left in its source"
+                ))
+            }
+            write.write_var_int(len).await?;
+            let copied = tokio::io::copy(&mut source.take(len as u64), write).await?;
+            if copied != len as u64 {
+                throw_explain!(format!(
+                    "Byte stream of {len} bytes ended after {copied} bytes"
+                ))
+            }
+            Ok(())
+        })
+    }
+
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        Ok(Size::Dynamic(
+            input.len + size_var_int(i32::try_from(input.len)?),
+        ))
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let bytes = <ByteBuf as PacketComponent<C>>::decode_from_buf(context, buf)?;
+        Ok(ByteStream::from_bytes(bytes))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bytes::Bytes;
+    use tokio::io::AsyncReadExt;
+
+    use super::{ByteStream, StreamingBytes};
+    use crate::prelude::PacketComponent;
+
+    #[tokio::test]
+    async fn test_streaming_bytes() -> crate::prelude::Result<()> {
+        let payload: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        let stream = ByteStream::new(payload.len(), Cursor::new(payload.clone()));
+        let mut bytes = vec![];
+        StreamingBytes::encode(&stream, &mut (), &mut bytes).await?;
+        assert_eq!(&bytes[..3], &[0xA0, 0x8D, 0x06]);
+        // the source was consumed by the first encode
+        assert!(StreamingBytes::encode(&stream, &mut (), &mut vec![])
+            .await
+            .is_err());
+        bytes.push(7);
+
+        // the payload is owned by the decoded stream, so what follows it decodes in place
+        let mut cursor = Cursor::new(bytes.clone());
+        let stream = StreamingBytes::decode(&mut (), &mut cursor).await?;
+        assert_eq!(u8::decode(&mut (), &mut cursor).await?, 7);
+        assert_eq!(stream.len(), payload.len());
+        let mut body = vec![];
+        stream
+            .take_body()
+            .expect("decoded body")
+            .read_to_end(&mut body)
+            .await?;
+        assert_eq!(body, payload);
+        assert!(stream.take_body().is_none());
+
+        let mut buf = Bytes::from(bytes);
+        let stream = StreamingBytes::decode_from_buf(&mut (), &mut buf)?;
+        assert_eq!(stream.len(), payload.len());
+        assert_eq!(&buf[..], &[7]);
+
+        // a short source is rejected before anything is written
+        let short = ByteStream::new(10, Cursor::new(vec![1, 2, 3]));
+        let mut written = vec![];
+        assert!(StreamingBytes::encode(&short, &mut (), &mut written)
+            .await
+            .is_err());
+        assert!(written.is_empty());
+        Ok(())
+    }
+}
@@ -1,31 +1,47 @@
+use bytes::Bytes;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
+use crate::prelude::ErrorType;
 use crate::transport::buffer::var_num::size_var_int;
 use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
+use crate::transport::packet::buf::split_prefixed;
+use crate::transport::packet::primitive::VarInt;
+use crate::transport::packet::vec::checked_length;
 use crate::transport::packet::{PacketComponent, Size};
-use crate::{throw_explain, PinnedLivelyResult};
+use crate::{throw, throw_explain, PinnedLivelyResult};
 
-const STRING_DEFAULT_CAP: i32 = 32767 * 4;
+pub(crate) const STRING_DEFAULT_CAP: i32 = 32767 * 4;
 
+/// Strings are prefixed with the number of bytes in their UTF-8 encoding as a VarInt, not the
+/// number of characters. `size` reports the same byte count so multi-byte content is sized exactly
+/// as it is encoded.
 impl<C: Send + Sync> PacketComponent<C> for String {
     type ComponentType = Self;
 
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
-        _: &'a mut C,
+        context: &'a mut C,
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType>
     where
         Self: Sized,
     {
-        Box::pin(async move {
-            let len = read.read_var_int().await?;
-            if len > STRING_DEFAULT_CAP {
-                throw_explain!(format!("String exceeded length bound {STRING_DEFAULT_CAP}"))
-            }
-            let mut buf = vec![0; len as usize];
-            read.read_exact(&mut buf).await?;
-            Ok(String::from_utf8(buf)?)
-        })
+        Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+    }
+
+    async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self::ComponentType: 'a,
+    {
+        let len = read.read_var_int().await?;
+        if len > STRING_DEFAULT_CAP {
+            throw_explain!(format!("String exceeded length bound {STRING_DEFAULT_CAP}"))
+        }
+        let mut buf = vec![0; checked_length(len)?];
+        read.read_exact(&mut buf).await?;
+        Ok(String::from_utf8(buf)?)
     }
 
     fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
@@ -34,41 +50,77 @@ impl<C: Send + Sync> PacketComponent<C> for String {
         write: &'a mut A,
     ) -> PinnedLivelyResult<'a, ()> {
         Box::pin(async move {
-            write.write_var_int(component_ref.len() as i32).await?;
+            let len = encoded_length(component_ref)?;
+            write.write_var_int(len).await?;
             write.write_all(component_ref.as_bytes()).await?;
             Ok(())
         })
     }
 
     fn size(component_ref: &Self, _: &mut C) -> crate::prelude::Result<Size> {
-        Ok(Size::Dynamic(
-            component_ref.len() + size_var_int(component_ref.len() as i32),
-        ))
+        let len = encoded_length(component_ref)?;
+        Ok(Size::Dynamic(component_ref.len() + size_var_int(len)))
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let bytes = split_prefixed(context, buf, Some(STRING_DEFAULT_CAP))?;
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.capacity()
+    }
+}
+
+/// The byte length written as the prefix of an encoded string, rejecting strings which would not
+/// be accepted when decoded.
+fn encoded_length(string: &str) -> crate::prelude::Result<i32> {
+    match i32::try_from(string.len()) {
+        Ok(len) if len <= STRING_DEFAULT_CAP => Ok(len),
+        _ => throw_explain!(format!(
+            "While encoding; string of {} bytes exceeded byte bound {STRING_DEFAULT_CAP}",
+            string.len()
+        )),
     }
 }
 
+/// Counts the UTF-16 code units required to represent the string, which is the unit the protocol
+/// uses to express string length limits.
+pub(crate) fn utf16_len(string: &str) -> usize {
+    if string.is_ascii() {
+        string.len()
+    } else {
+        string.encode_utf16().count()
+    }
+}
+
+/// A string bounded to at most `N` UTF-16 code units. Every UTF-16 code unit takes at most 3 bytes
+/// of UTF-8, so the encoded byte length is bound to `N * 3` before the string is decoded.
 pub struct LimitedString<const N: usize>;
 
 impl<C: Send + Sync, const N: usize> PacketComponent<C> for LimitedString<N> {
     type ComponentType = String;
 
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
-        _: &'a mut C,
+        context: &'a mut C,
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType> {
-        Box::pin(async move {
-            let string_size = read.read_var_int().await?;
-            if string_size > N as i32 * 4 {
-                throw_explain!(format!(
-                    "While encoding; string exceeded length bound {}",
-                    N * 4
-                ))
-            }
+        Box::pin(<Self as PacketComponent<C>>::decode_unboxed(context, read))
+    }
 
-            let mut buf = vec![0; string_size as usize];
-            read.read_exact(&mut buf).await?;
-            Ok(String::from_utf8(buf)?)
-        })
+    async fn decode_unboxed<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> crate::prelude::Result<Self::ComponentType>
+    where
+        Self::ComponentType: 'a,
+    {
+        let mut buf = vec![0; limited_byte_length::<N>(read.read_var_int().await?)?];
+        read.read_exact(&mut buf).await?;
+        limited_utf16_length::<N>(String::from_utf8(buf)?)
     }
 
     fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
@@ -76,13 +128,17 @@ impl<C: Send + Sync, const N: usize> PacketComponent<C> for LimitedString<N> {
         context: &'a mut C,
         write: &'a mut A,
     ) -> PinnedLivelyResult<'a, ()> {
-        if component_ref.len() > N * 4 {
-            return Box::pin(async move {
-                throw_explain!(format!(
-                    "While decoding; string exceeded length bound {}",
-                    N * 4
-                ))
-            });
+        // UTF-16 never needs more code units than UTF-8 needs bytes, so short strings can skip
+        // counting entirely.
+        if component_ref.len() > N {
+            let len = utf16_len(component_ref);
+            if len > N {
+                return Box::pin(async move {
+                    throw_explain!(format!(
+                        "While encoding; string of length {len} exceeded length bound {N}"
+                    ))
+                });
+            }
         }
 
         String::encode(component_ref, context, write)
@@ -91,4 +147,109 @@ impl<C: Send + Sync, const N: usize> PacketComponent<C> for LimitedString<N> {
     fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
         String::size(input, context)
     }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let len = limited_byte_length::<N>(VarInt::decode_from_buf(context, buf)?)?;
+        if len > buf.len() {
+            throw!(ErrorType::EOF)
+        }
+        limited_utf16_length::<N>(String::from_utf8(buf.split_to(len).to_vec())?)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.capacity()
+    }
+}
+
+/// Checks the byte length prefix of a `LimitedString<N>` against the most bytes it can take.
+fn limited_byte_length<const N: usize>(string_size: i32) -> crate::prelude::Result<usize> {
+    if string_size as i64 > N as i64 * 3 {
+        throw_explain!(format!(
+            "While decoding; string of {string_size} bytes exceeded byte bound {}",
+            N * 3
+        ))
+    }
+    checked_length(string_size)
+}
+
+/// Checks a decoded `LimitedString<N>` against its length bound in UTF-16 code units.
+fn limited_utf16_length<const N: usize>(string: String) -> crate::prelude::Result<String> {
+    let len = utf16_len(&string);
+    if len > N {
+        throw_explain!(format!(
+            "While decoding; string of length {len} exceeded length bound {N}"
+        ))
+    }
+    Ok(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::STRING_DEFAULT_CAP;
+    use crate::prelude::{DraxReadExt, PacketComponent, Size};
+    use crate::transport::packet::string::LimitedString;
+
+    async fn round_trip<const N: usize>(value: &str) -> crate::prelude::Result<String> {
+        let mut cursor = Cursor::new(vec![]);
+        LimitedString::<N>::encode(&value.to_string(), &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        LimitedString::<N>::decode(&mut (), &mut cursor).await
+    }
+
+    #[tokio::test]
+    async fn test_string_size_matches_encoding() -> crate::prelude::Result<()> {
+        for value in ["", "drax", "éééé", "✓✓✓✓", "🦀🦀", &"é".repeat(200)] {
+            let value = value.to_string();
+            let mut cursor = Cursor::new(vec![]);
+            String::encode(&value, &mut (), &mut cursor).await?;
+            let encoded = cursor.into_inner();
+            assert_eq!(String::size(&value, &mut ())?, Size::Dynamic(encoded.len()));
+            assert_eq!(
+                LimitedString::<400>::size(&value, &mut ())?,
+                Size::Dynamic(encoded.len())
+            );
+            let mut cursor = Cursor::new(encoded);
+            assert_eq!(cursor.read_var_int().await? as usize, value.len());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_string_rejects_oversized_encode() {
+        let value = "a".repeat(STRING_DEFAULT_CAP as usize + 1);
+        let mut cursor = Cursor::new(vec![]);
+        assert!(String::encode(&value, &mut (), &mut cursor).await.is_err());
+        assert!(String::size(&value, &mut ()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_limited_string_counts_code_units() -> crate::prelude::Result<()> {
+        assert_eq!(round_trip::<4>("drax").await?, "drax");
+        // 4 characters, 8 bytes of UTF-8
+        assert_eq!(round_trip::<4>("éééé").await?, "éééé");
+        // 4 characters, 12 bytes of UTF-8
+        assert_eq!(round_trip::<4>("✓✓✓✓").await?, "✓✓✓✓");
+        // a single character outside the BMP takes two UTF-16 code units
+        assert_eq!(round_trip::<2>("🦀").await?, "🦀");
+        assert!(round_trip::<1>("🦀").await.is_err());
+        assert!(round_trip::<4>("drax!").await.is_err());
+        assert!(round_trip::<4>("éééé!").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_limited_string_rejects_overlong_decode() -> crate::prelude::Result<()> {
+        let mut cursor = Cursor::new(vec![]);
+        String::encode(&"ééééé".to_string(), &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert!(LimitedString::<4>::decode(&mut (), &mut cursor)
+            .await
+            .is_err());
+        Ok(())
+    }
 }
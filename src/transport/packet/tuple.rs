@@ -0,0 +1,109 @@
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::transport::packet::{PacketComponent, Size};
+use crate::PinnedLivelyResult;
+
+/// Implements `PacketComponent` for a tuple of components, encoding each element after the one
+/// before it with no header, as a struct with the same fields would be. Elements decode through
+/// `decode_unboxed`, so a tuple of primitives decodes with a single boxed future. This also covers
+/// header fields carried in every frame: `type Frame = (TraceId, ServerboundPacket);` writes the
+/// header after the frame length and before the packet.
+macro_rules! impl_tuple_component {
+    ($($t:ident $index:tt),+) => {
+        impl<C: Send + Sync, $($t),+> PacketComponent<C> for ($($t,)+)
+        where
+            $($t: PacketComponent<C>,)+
+        {
+            type ComponentType = ($($t::ComponentType,)+);
+
+            const MAX_SIZE: Option<usize> = super::max_size_sum(&[$($t::MAX_SIZE),+]);
+
+            fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+                context: &'a mut C,
+                read: &'a mut A,
+            ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+                Box::pin(async move { Ok(($($t::decode_unboxed(context, read).await?,)+)) })
+            }
+
+            fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+                component_ref: &'a Self::ComponentType,
+                context: &'a mut C,
+                write: &'a mut A,
+            ) -> PinnedLivelyResult<'a, ()> {
+                Box::pin(async move {
+                    $($t::encode(&component_ref.$index, context, write).await?;)+
+                    Ok(())
+                })
+            }
+
+            fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+                let mut size = Size::Constant(0);
+                $(size = size + $t::size(&input.$index, context)?;)+
+                Ok(size)
+            }
+
+            fn decode_from_buf(
+                context: &mut C,
+                buf: &mut Bytes,
+            ) -> crate::prelude::Result<Self::ComponentType> {
+                Ok(($($t::decode_from_buf(context, buf)?,)+))
+            }
+
+            fn heap_size(input: &Self::ComponentType) -> usize {
+                0 $(+ $t::heap_size(&input.$index))+
+            }
+        }
+    };
+}
+
+impl_tuple_component!(T0 0);
+impl_tuple_component!(T0 0, T1 1);
+impl_tuple_component!(T0 0, T1 1, T2 2);
+impl_tuple_component!(T0 0, T1 1, T2 2, T3 3);
+impl_tuple_component!(T0 0, T1 1, T2 2, T3 3, T4 4);
+impl_tuple_component!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5);
+impl_tuple_component!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6);
+impl_tuple_component!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7);
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::prelude::{PacketComponent, Size};
+    use crate::transport::packet::primitive::VarInt;
+    use crate::transport::packet::vec::LimitedVec;
+
+    type Entry = (VarInt, String, bool);
+
+    #[tokio::test]
+    async fn test_tuples() -> crate::prelude::Result<()> {
+        let entry = (300, "drax".to_string(), true);
+        let mut bytes = vec![];
+        Entry::encode(&entry, &mut (), &mut bytes).await?;
+        assert_eq!(bytes, vec![0xAC, 0x02, 4, b'd', b'r', b'a', b'x', 1]);
+        assert_eq!(Entry::size(&entry, &mut ())?, Size::Dynamic(bytes.len()));
+        assert_eq!(
+            Entry::decode(&mut (), &mut Cursor::new(bytes)).await?,
+            entry
+        );
+
+        assert_eq!(<(u8, i32, u16) as PacketComponent<()>>::MAX_SIZE, Some(7));
+        assert_eq!(
+            <(u8, i32, u16)>::size(&(1, 2, 3), &mut ())?,
+            Size::Constant(7)
+        );
+        assert_eq!(<(u8, VarInt) as PacketComponent<()>>::MAX_SIZE, Some(6));
+        assert_eq!(<(u8, String) as PacketComponent<()>>::MAX_SIZE, None);
+
+        let pairs = vec![(1, 2), (3, 4)];
+        let mut bytes = vec![];
+        LimitedVec::<(u8, u8), 4>::encode(&pairs, &mut (), &mut bytes).await?;
+        assert_eq!(bytes, vec![2, 1, 2, 3, 4]);
+        assert_eq!(
+            LimitedVec::<(u8, u8), 4>::decode(&mut (), &mut Cursor::new(bytes)).await?,
+            pairs
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,38 @@
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 
+use bytes::{Buf, Bytes};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::transport::buffer::var_num::size_var_int;
+use crate::prelude::ErrorType;
+use crate::transport::buffer::var_num::{put_var_int, size_var_int, size_var_long};
 use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
-use crate::transport::packet::{PacketComponent, Size};
-use crate::{throw_explain, PinnedLivelyResult};
+use crate::transport::packet::limit::BudgetedReader;
+use crate::transport::packet::primitive::{VarInt, VarLong};
+use crate::transport::packet::{yield_point, PacketComponent, Size};
+use crate::{throw, throw_explain, PinnedLivelyResult};
+
+/// Upper bound on the number of elements reserved up front when decoding a length prefixed
+/// collection. Lengths come straight off the wire, so larger collections grow as they're read.
+pub(crate) const PREALLOCATION_LIMIT: usize = 4096;
+
+pub(crate) fn checked_length(length: i32) -> crate::prelude::Result<usize> {
+    if length < 0 {
+        throw_explain!(format!("Received invalid negative length {length}"))
+    }
+    Ok(length as usize)
+}
+
+pub(crate) fn checked_long_length(length: i64) -> crate::prelude::Result<usize> {
+    if length < 0 {
+        throw_explain!(format!("Received invalid negative length {length}"))
+    }
+    Ok(usize::try_from(length)?)
+}
+
+fn long_prefix(length: usize) -> crate::prelude::Result<i64> {
+    Ok(i64::try_from(length)?)
+}
 
 pub struct ByteDrain;
 
@@ -41,6 +67,10 @@ impl<C: Send + Sync> PacketComponent<C> for ByteDrain {
     fn size(component_ref: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
         Ok(Size::Dynamic(component_ref.len()))
     }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        super::vec_heap_size::<C, u8>(input)
+    }
 }
 
 pub struct SliceU8<const N: usize>;
@@ -48,6 +78,8 @@ pub struct SliceU8<const N: usize>;
 impl<C: Send + Sync, const N: usize> PacketComponent<C> for SliceU8<N> {
     type ComponentType = [u8; N];
 
+    const MAX_SIZE: Option<usize> = Some(N);
+
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
         _: &'a mut C,
         read: &'a mut A,
@@ -84,6 +116,11 @@ where
 {
     type ComponentType = [T::ComponentType; N];
 
+    const MAX_SIZE: Option<usize> = match T::MAX_SIZE {
+        Some(size) => size.checked_mul(N),
+        None => None,
+    };
+
     fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
         context: &'a mut C,
         read: &'a mut A,
@@ -123,6 +160,23 @@ where
         }
         Ok(Size::Dynamic(dynamic_counter))
     }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let vec = (0..N)
+            .map(|_| T::decode_from_buf(context, buf))
+            .collect::<crate::prelude::Result<Vec<_>>>()?;
+        match vec.try_into() {
+            Ok(arr) => Ok(arr),
+            Err(_) => unreachable!("decoded exactly N elements"),
+        }
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.iter().map(T::heap_size).sum()
+    }
 }
 
 pub struct VecU8;
@@ -138,9 +192,12 @@ impl<C: Send + Sync> PacketComponent<C> for VecU8 {
         Self: Sized,
     {
         Box::pin(async move {
-            let len = read.read_var_int().await?;
-            let mut buf = vec![0u8; len as usize];
-            read.read_exact(&mut buf).await?;
+            let len = checked_length(read.read_var_int().await?)?;
+            let mut buf = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            read.take(len as u64).read_to_end(&mut buf).await?;
+            if buf.len() != len {
+                throw!(ErrorType::EOF)
+            }
             Ok(buf)
         })
     }
@@ -162,6 +219,10 @@ impl<C: Send + Sync> PacketComponent<C> for VecU8 {
             component_ref.len() + size_var_int(component_ref.len() as i32),
         ))
     }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        super::vec_heap_size::<C, u8>(input)
+    }
 }
 
 impl<C: Send + Sync, T> PacketComponent<C> for Vec<T>
@@ -178,9 +239,10 @@ where
         Self: Sized,
     {
         Box::pin(async move {
-            let len = read.read_var_int().await?;
-            let mut vec = Vec::with_capacity(len as usize);
-            for _ in 0..len {
+            let len = checked_length(read.read_var_int().await?)?;
+            let mut vec = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            for i in 0..len {
+                yield_point(i).await;
                 vec.push(T::decode(context, read).await?);
             }
             Ok(vec)
@@ -214,6 +276,22 @@ where
         }
         Ok(Size::Dynamic(dynamic_counter))
     }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let len = checked_length(VarInt::decode_from_buf(context, buf)?)?;
+        let mut vec = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+        for _ in 0..len {
+            vec.push(T::decode_from_buf(context, buf)?);
+        }
+        Ok(vec)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        super::vec_heap_size::<C, T>(input)
+    }
 }
 
 pub struct LimitedVec<T, const N: usize>(PhantomData<T>);
@@ -229,7 +307,7 @@ where
         read: &'a mut A,
     ) -> PinnedLivelyResult<'a, Self::ComponentType> {
         Box::pin(async move {
-            let vec_size = read.read_var_int().await? as usize;
+            let vec_size = checked_length(read.read_var_int().await?)?;
             if vec_size > N {
                 throw_explain!(format!(
                     "Tried to encode vec of length {vec_size} but was bound to length {N}"
@@ -237,7 +315,8 @@ where
             }
 
             let mut vec = Vec::with_capacity(vec_size);
-            for _ in 0..vec_size {
+            for i in 0..vec_size {
+                yield_point(i).await;
                 vec.push(T::decode(context, read).await?);
             }
             Ok(vec)
@@ -265,4 +344,447 @@ where
     fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
         Vec::<T>::size(input, context)
     }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let vec_size = checked_length(VarInt::decode_from_buf(context, buf)?)?;
+        if vec_size > N {
+            throw_explain!(format!(
+                "Tried to encode vec of length {vec_size} but was bound to length {N}"
+            ));
+        }
+
+        let mut vec = Vec::with_capacity(vec_size);
+        for _ in 0..vec_size {
+            vec.push(T::decode_from_buf(context, buf)?);
+        }
+        Ok(vec)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        super::vec_heap_size::<C, T>(input)
+    }
+}
+
+/// A vec bounded to at most `MAX_ITEMS` elements whose encoded elements take at most `MAX_BYTES`
+/// bytes combined, not counting the length prefix. A count alone still allows a handful of huge
+/// elements, so both are enforced while decoding and before encoding.
+pub struct BoundedVec<T, const MAX_ITEMS: usize, const MAX_BYTES: usize>(PhantomData<T>);
+
+impl<T, C: Send + Sync, const MAX_ITEMS: usize, const MAX_BYTES: usize> PacketComponent<C>
+    for BoundedVec<T, MAX_ITEMS, MAX_BYTES>
+where
+    T: PacketComponent<C>,
+{
+    type ComponentType = Vec<T::ComponentType>;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let vec_size = checked_length(read.read_var_int().await?)?;
+            if vec_size > MAX_ITEMS {
+                throw_explain!(format!(
+                    "While decoding; vec of length {vec_size} exceeded length bound {MAX_ITEMS}"
+                ));
+            }
+
+            let mut budgeted = BudgetedReader::new(read, MAX_BYTES);
+            let mut vec = Vec::with_capacity(vec_size);
+            for i in 0..vec_size {
+                yield_point(i).await;
+                match T::decode(context, &mut budgeted).await {
+                    Ok(item) => vec.push(item),
+                    Err(_) if budgeted.exceeded() => throw_explain!(format!(
+                        "While decoding; vec elements exceeded byte bound {MAX_BYTES}"
+                    )),
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(vec)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        if component_ref.len() > MAX_ITEMS {
+            return Box::pin(async move {
+                throw_explain!(format!(
+                    "While encoding; vec of length {} exceeded length bound {MAX_ITEMS}",
+                    component_ref.len()
+                ))
+            });
+        }
+
+        let mut bytes = 0;
+        for item in component_ref {
+            match T::size(item, context) {
+                Ok(Size::Dynamic(x) | Size::Constant(x)) => bytes += x,
+                Err(err) => return Box::pin(async move { Err(err) }),
+            }
+        }
+        if bytes > MAX_BYTES {
+            return Box::pin(async move {
+                throw_explain!(format!(
+                    "While encoding; vec elements of {bytes} bytes exceeded byte bound {MAX_BYTES}"
+                ))
+            });
+        }
+
+        Vec::<T>::encode(component_ref, context, write)
+    }
+
+    fn size(input: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        Vec::<T>::size(input, context)
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let vec_size = checked_length(VarInt::decode_from_buf(context, buf)?)?;
+        if vec_size > MAX_ITEMS {
+            throw_explain!(format!(
+                "While decoding; vec of length {vec_size} exceeded length bound {MAX_ITEMS}"
+            ));
+        }
+
+        // the elements are decoded from the buffer cut to the byte bound, so none can read past it
+        let truncated = buf.len() > MAX_BYTES;
+        let mut budgeted = buf.slice(..buf.len().min(MAX_BYTES));
+        let mut vec = Vec::with_capacity(vec_size);
+        for _ in 0..vec_size {
+            match T::decode_from_buf(context, &mut budgeted) {
+                Ok(item) => vec.push(item),
+                Err(_) if truncated => throw_explain!(format!(
+                    "While decoding; vec elements exceeded byte bound {MAX_BYTES}"
+                )),
+                Err(err) => return Err(err),
+            }
+        }
+        buf.advance(buf.len().min(MAX_BYTES) - budgeted.len());
+        Ok(vec)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        super::vec_heap_size::<C, T>(input)
+    }
+}
+
+/// A byte buffer prefixed with its length as a VarLong rather than a VarInt, for protocols which
+/// need buffers past `i32::MAX` bytes.
+pub struct LongPrefixedVecU8;
+
+impl<C: Send + Sync> PacketComponent<C> for LongPrefixedVecU8 {
+    type ComponentType = Vec<u8>;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let len = checked_long_length(read.read_var_long().await?)?;
+            let mut buf = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            read.take(len as u64).read_to_end(&mut buf).await?;
+            if buf.len() != len {
+                throw!(ErrorType::EOF)
+            }
+            Ok(buf)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            write
+                .write_var_long(long_prefix(component_ref.len())?)
+                .await?;
+            write.write_all(component_ref).await?;
+            Ok(())
+        })
+    }
+
+    fn size(component_ref: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        Ok(Size::Dynamic(
+            component_ref.len() + size_var_long(long_prefix(component_ref.len())?),
+        ))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        super::vec_heap_size::<C, u8>(input)
+    }
+}
+
+/// A vec prefixed with its length as a VarLong rather than a VarInt, for protocols which need
+/// collections past `i32::MAX` elements.
+pub struct LongPrefixedVec<T>(PhantomData<T>);
+
+impl<T, C: Send + Sync> PacketComponent<C> for LongPrefixedVec<T>
+where
+    T: PacketComponent<C>,
+{
+    type ComponentType = Vec<T::ComponentType>;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        context: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let len = checked_long_length(read.read_var_long().await?)?;
+            let mut vec = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            for i in 0..len {
+                yield_point(i).await;
+                vec.push(T::decode(context, read).await?);
+            }
+            Ok(vec)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        context: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            write
+                .write_var_long(long_prefix(component_ref.len())?)
+                .await?;
+            for item in component_ref {
+                T::encode(item, context, write).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn size(component_ref: &Self::ComponentType, context: &mut C) -> crate::prelude::Result<Size> {
+        let mut dynamic_counter = size_var_long(long_prefix(component_ref.len())?);
+        for item in component_ref {
+            match T::size(item, context)? {
+                Size::Dynamic(x) | Size::Constant(x) => dynamic_counter += x,
+            }
+        }
+        Ok(Size::Dynamic(dynamic_counter))
+    }
+
+    fn decode_from_buf(
+        context: &mut C,
+        buf: &mut Bytes,
+    ) -> crate::prelude::Result<Self::ComponentType> {
+        let len = checked_long_length(VarLong::decode_from_buf(context, buf)?)?;
+        let mut vec = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+        for _ in 0..len {
+            vec.push(T::decode_from_buf(context, buf)?);
+        }
+        Ok(vec)
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        super::vec_heap_size::<C, T>(input)
+    }
+}
+
+/// Upper bound on the bytes read per batch by `VarIntVec`.
+const VAR_INT_BATCH: usize = 4096;
+
+/// A VarInt prefixed list of VarInts, decoded in batches from a scratch buffer rather than polling
+/// the reader for every byte of every value. Each outstanding value takes at least one more byte,
+/// so the decoder reads one byte per outstanding value at a time and never reads past the end of
+/// the list. Equivalent on the wire to `Vec<VarInt>`.
+pub struct VarIntVec;
+
+impl<C: Send + Sync> PacketComponent<C> for VarIntVec {
+    type ComponentType = Vec<i32>;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let len = checked_length(read.read_var_int().await?)?;
+            let mut values = Vec::with_capacity(len.min(PREALLOCATION_LIMIT));
+            let mut scratch = vec![0u8; len.min(VAR_INT_BATCH)];
+            let mut value = 0i32;
+            let mut shift = 0u32;
+            while values.len() < len {
+                let batch = (len - values.len()).min(VAR_INT_BATCH);
+                read.read_exact(&mut scratch[..batch]).await?;
+                for byte in &scratch[..batch] {
+                    if shift >= 35 {
+                        throw_explain!("VarInt too large")
+                    }
+                    value |= i32::from(byte & 0b0111_1111).overflowing_shl(shift).0;
+                    shift += 7;
+                    if byte & 0b1000_0000 == 0 {
+                        values.push(value);
+                        value = 0;
+                        shift = 0;
+                    }
+                }
+            }
+            Ok(values)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let mut buffer = Vec::with_capacity(component_ref.len() + 5);
+            put_var_int(&mut buffer, i32::try_from(component_ref.len())?);
+            for value in component_ref {
+                put_var_int(&mut buffer, *value);
+            }
+            write.write_all(&buffer).await?;
+            Ok(())
+        })
+    }
+
+    fn size(component_ref: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        let mut size = size_var_int(i32::try_from(component_ref.len())?);
+        for value in component_ref {
+            size += size_var_int(*value);
+        }
+        Ok(Size::Dynamic(size))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        super::vec_heap_size::<C, VarInt>(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{BoundedVec, LongPrefixedVec, LongPrefixedVecU8, VarIntVec};
+    use crate::prelude::{DraxReadExt, DraxWriteExt, PacketComponent, Size};
+    use crate::transport::packet::primitive::VarInt;
+
+    type Names = BoundedVec<String, 4, 16>;
+
+    #[test]
+    fn test_decode_yields() {
+        let mut bytes = vec![];
+        crate::transport::buffer::var_num::put_var_int(&mut bytes, 3000);
+        bytes.resize(bytes.len() + 3000, 0);
+        let mut cursor = Cursor::new(bytes);
+        let mut context = ();
+        let mut decode = Vec::<VarInt>::decode(&mut context, &mut cursor);
+
+        let mut waker_context = std::task::Context::from_waker(std::task::Waker::noop());
+        let mut pending = 0;
+        let values = loop {
+            match decode.as_mut().poll(&mut waker_context) {
+                std::task::Poll::Ready(values) => break values.expect("Failed to decode"),
+                std::task::Poll::Pending => pending += 1,
+            }
+        };
+        assert_eq!(values.len(), 3000);
+        assert_eq!(pending, 2);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_vec() -> crate::prelude::Result<()> {
+        let names = vec!["abc".to_string(), "defgh".to_string()];
+        let mut cursor = Cursor::new(vec![]);
+        Names::encode(&names, &mut (), &mut cursor).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(Names::decode(&mut (), &mut cursor).await?, names);
+
+        let too_many = vec!["a".to_string(); 5];
+        let too_large = vec!["abcdefghijklmnop".to_string()];
+        for names in [too_many, too_large] {
+            let mut cursor = Cursor::new(vec![]);
+            assert!(Names::encode(&names, &mut (), &mut cursor).await.is_err());
+            Vec::<String>::encode(&names, &mut (), &mut cursor).await?;
+            let mut cursor = Cursor::new(cursor.into_inner());
+            assert!(Names::decode(&mut (), &mut cursor).await.is_err());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_long_prefixed() -> crate::prelude::Result<()> {
+        let bytes = vec![1u8, 2, 3];
+        let mut cursor = Cursor::new(vec![]);
+        LongPrefixedVecU8::encode(&bytes, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![3, 1, 2, 3]);
+        assert_eq!(LongPrefixedVecU8::size(&bytes, &mut ())?, Size::Dynamic(4));
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(
+            LongPrefixedVecU8::decode(&mut (), &mut cursor).await?,
+            bytes
+        );
+
+        let values = vec![1, 300, i32::MAX];
+        let mut cursor = Cursor::new(vec![]);
+        LongPrefixedVec::<VarInt>::encode(&values, &mut (), &mut cursor).await?;
+        let encoded = cursor.into_inner();
+        assert_eq!(
+            LongPrefixedVec::<VarInt>::size(&values, &mut ())?,
+            Size::Dynamic(encoded.len())
+        );
+        let mut cursor = Cursor::new(encoded);
+        assert_eq!(
+            LongPrefixedVec::<VarInt>::decode(&mut (), &mut cursor).await?,
+            values
+        );
+
+        // a length past i32::MAX is accepted by the prefix but the buffer is truncated
+        let mut cursor = Cursor::new(vec![]);
+        cursor.write_var_long(1 << 40).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert!(LongPrefixedVecU8::decode(&mut (), &mut cursor)
+            .await
+            .is_err());
+        let mut cursor = Cursor::new(vec![]);
+        cursor.write_var_long(-1).await?;
+        let mut cursor = Cursor::new(cursor.into_inner());
+        assert_eq!(cursor.read_var_long().await?, -1);
+        cursor.set_position(0);
+        assert!(LongPrefixedVec::<VarInt>::decode(&mut (), &mut cursor)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_var_int_vec() -> crate::prelude::Result<()> {
+        let values = vec![0, 1, 127, 128, 300, 55324, i32::MAX, -1, i32::MIN];
+        let mut cursor = Cursor::new(vec![]);
+        VarIntVec::encode(&values, &mut (), &mut cursor).await?;
+        let encoded = cursor.into_inner();
+
+        let mut cursor = Cursor::new(vec![]);
+        Vec::<VarInt>::encode(&values, &mut (), &mut cursor).await?;
+        assert_eq!(encoded, cursor.into_inner());
+        assert_eq!(
+            VarIntVec::size(&values, &mut ())?,
+            Size::Dynamic(encoded.len())
+        );
+
+        // trailing data must be left untouched for the next component
+        let mut trailing = encoded.clone();
+        trailing.extend_from_slice(&[0xFF, 0xFF]);
+        let mut cursor = Cursor::new(trailing);
+        assert_eq!(VarIntVec::decode(&mut (), &mut cursor).await?, values);
+        assert_eq!(cursor.position() as usize, encoded.len());
+
+        let mut cursor = Cursor::new(vec![1, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert!(VarIntVec::decode(&mut (), &mut cursor).await.is_err());
+        let mut cursor = Cursor::new(vec![2, 0x01]);
+        assert!(VarIntVec::decode(&mut (), &mut cursor).await.is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,313 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::prelude::{PacketComponent, TransportError};
+use crate::transport::connection::DraxConnection;
+use crate::transport::source::{ClockSource, SystemClock};
+use crate::PinnedLivelyResult;
+
+/// Why a read loop ended.
+#[derive(Debug)]
+pub enum Disconnect {
+    /// The peer closed the connection between frames.
+    Closed,
+    /// A frame couldn't be read or decoded, or a handler returned an error.
+    ProtocolError(TransportError),
+    /// The peer sent more frames than the rate limit allows.
+    RateLimited,
+    /// No frame arrived within the read timeout.
+    TimedOut,
+    /// A handler asked for the connection to be closed, with its reason.
+    Requested(String),
+}
+
+/// What a read loop handler wants to happen after handling a packet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Flow {
+    Continue,
+    Close(String),
+}
+
+/// The limits a read loop enforces on the peer, none by default.
+#[derive(Clone)]
+pub struct ReadLoopOptions {
+    read_timeout: Option<Duration>,
+    rate_limit: Option<(u32, Duration)>,
+    clock: Arc<dyn ClockSource + Send + Sync>,
+}
+
+impl Default for ReadLoopOptions {
+    fn default() -> Self {
+        Self {
+            read_timeout: None,
+            rate_limit: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl Debug for ReadLoopOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadLoopOptions")
+            .field("read_timeout", &self.read_timeout)
+            .field("rate_limit", &self.rate_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReadLoopOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the longest the loop waits for each frame before ending with `Disconnect::TimedOut`.
+    #[cfg(feature = "idle")]
+    pub fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// Ends the loop with `Disconnect::RateLimited` once more than `max_frames` frames are read in
+    /// a single window of length `window`.
+    pub fn with_rate_limit(mut self, max_frames: u32, window: Duration) -> Self {
+        self.rate_limit = Some((max_frames, window));
+        self
+    }
+
+    /// Sets the clock rate limit windows are measured with, the system clock by default.
+    pub fn with_clock(mut self, clock: impl ClockSource + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+/// Counts frames in fixed windows starting from the first frame of each window.
+struct RateWindow {
+    started: Instant,
+    frames: u32,
+}
+
+impl RateWindow {
+    fn allow(&mut self, now: Instant, max_frames: u32, window: Duration) -> bool {
+        if now.duration_since(self.started) >= window {
+            self.started = now;
+            self.frames = 0;
+        }
+        self.frames += 1;
+        self.frames <= max_frames
+    }
+}
+
+async fn read_next<C, P, R, W>(
+    connection: &mut DraxConnection<R, W>,
+    context: &mut C,
+    _read_timeout: Option<Duration>,
+) -> Result<Option<P::ComponentType>, Disconnect>
+where
+    C: Send + Sync,
+    P: PacketComponent<C>,
+    R: AsyncRead + Unpin + Send + Sync,
+    W: AsyncWrite + Unpin + Send + Sync,
+{
+    let read = connection.read_packet::<C, P>(context);
+    #[cfg(feature = "idle")]
+    if let Some(read_timeout) = _read_timeout {
+        return match tokio::time::timeout(read_timeout, read).await {
+            Ok(packet) => packet.map_err(Disconnect::ProtocolError),
+            Err(_) => Err(Disconnect::TimedOut),
+        };
+    }
+    read.await.map_err(Disconnect::ProtocolError)
+}
+
+/// Reads packets from the connection, decoding each as `P` and passing it to the handler, until
+/// the connection ends for any reason, which is returned rather than treated as an error.
+///
+/// The handler is given the connection as well as the packet so it can write responses. Rate limit
+/// windows are measured with the clock of the options.
+///
+/// # Parameters
+/// * `connection` - The connection to read packets from.
+/// * `context` - The context to decode and handle packets with.
+/// * `options` - The limits to enforce on the peer.
+/// * `handler` - Called with each decoded packet, returning whether to keep reading.
+pub async fn run_read_loop<C, P, R, W, H>(
+    connection: &mut DraxConnection<R, W>,
+    context: &mut C,
+    options: ReadLoopOptions,
+    mut handler: H,
+) -> Disconnect
+where
+    C: Send + Sync,
+    P: PacketComponent<C>,
+    R: AsyncRead + Unpin + Send + Sync,
+    W: AsyncWrite + Unpin + Send + Sync,
+    H: for<'a> FnMut(
+        &'a mut C,
+        &'a mut DraxConnection<R, W>,
+        P::ComponentType,
+    ) -> PinnedLivelyResult<'a, Flow>,
+{
+    let mut window = RateWindow {
+        started: options.clock.now(),
+        frames: 0,
+    };
+    loop {
+        let packet = match read_next::<C, P, R, W>(connection, context, options.read_timeout).await
+        {
+            Ok(Some(packet)) => packet,
+            Ok(None) => return Disconnect::Closed,
+            Err(disconnect) => return disconnect,
+        };
+        if let Some((max_frames, per)) = options.rate_limit {
+            if !window.allow(options.clock.now(), max_frames, per) {
+                return Disconnect::RateLimited;
+            }
+        }
+        match handler(context, connection, packet).await {
+            Ok(Flow::Continue) => {}
+            Ok(Flow::Close(reason)) => return Disconnect::Requested(reason),
+            Err(err) => return Disconnect::ProtocolError(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use super::{run_read_loop, Disconnect, Flow, ReadLoopOptions};
+    use crate::transport::connection::DraxConnection;
+    use crate::transport::packet::primitive::VarInt;
+    use crate::transport::source::{ClockSource, ManualClock};
+
+    /// A manual clock shared between the loop options and the handler which moves it.
+    #[derive(Clone)]
+    struct SharedClock(Arc<Mutex<ManualClock>>);
+
+    impl ClockSource for SharedClock {
+        fn now(&self) -> Instant {
+            self.0.lock().unwrap().now()
+        }
+    }
+
+    /// Records the packets handled, moving the clock forward by `tick` after each.
+    struct Seen {
+        values: Vec<i32>,
+        clock: SharedClock,
+        tick: Duration,
+    }
+
+    impl Seen {
+        fn new(tick: Duration) -> Self {
+            Self {
+                values: vec![],
+                clock: SharedClock(Arc::new(Mutex::new(ManualClock::new(Duration::ZERO)))),
+                tick,
+            }
+        }
+
+        fn options(&self) -> ReadLoopOptions {
+            ReadLoopOptions::new().with_clock(self.clock.clone())
+        }
+    }
+
+    async fn connection_with(
+        values: &[i32],
+    ) -> crate::prelude::Result<DraxConnection<std::io::Cursor<Vec<u8>>, Vec<u8>>> {
+        let mut writer = DraxConnection::new(tokio::io::empty(), vec![]);
+        for value in values {
+            writer.write_packet::<_, VarInt>(&mut (), value).await?;
+        }
+        let (_, bytes) = writer.into_inner();
+        Ok(DraxConnection::new(std::io::Cursor::new(bytes), vec![]))
+    }
+
+    fn echo<'a, R, W>(
+        seen: &'a mut Seen,
+        _: &'a mut DraxConnection<R, W>,
+        value: i32,
+    ) -> crate::PinnedLivelyResult<'a, Flow> {
+        Box::pin(async move {
+            seen.values.push(value);
+            seen.clock.0.lock().unwrap().advance(seen.tick);
+            match value {
+                0 => Ok(Flow::Close("bye".to_string())),
+                -1 => crate::throw_explain!("negative"),
+                _ => Ok(Flow::Continue),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_read_loop() -> crate::prelude::Result<()> {
+        let mut connection = connection_with(&[1, 2, 3]).await?;
+        let mut seen = Seen::new(Duration::ZERO);
+        let disconnect = run_read_loop::<_, VarInt, _, _, _>(
+            &mut connection,
+            &mut seen,
+            ReadLoopOptions::new(),
+            echo,
+        )
+        .await;
+        assert!(matches!(disconnect, Disconnect::Closed));
+        assert_eq!(seen.values, vec![1, 2, 3]);
+
+        let mut connection = connection_with(&[1, 0, 2]).await?;
+        let disconnect = run_read_loop::<_, VarInt, _, _, _>(
+            &mut connection,
+            &mut Seen::new(Duration::ZERO),
+            ReadLoopOptions::new(),
+            echo,
+        )
+        .await;
+        assert!(matches!(disconnect, Disconnect::Requested(reason) if reason == "bye"));
+
+        let mut connection = connection_with(&[1, -1]).await?;
+        let disconnect = run_read_loop::<_, VarInt, _, _, _>(
+            &mut connection,
+            &mut Seen::new(Duration::ZERO),
+            ReadLoopOptions::new(),
+            echo,
+        )
+        .await;
+        assert!(matches!(disconnect, Disconnect::ProtocolError(_)));
+
+        let mut connection = connection_with(&[1, 2, 3, 4]).await?;
+        let mut seen = Seen::new(Duration::ZERO);
+        let options = seen.options().with_rate_limit(2, Duration::from_secs(60));
+        let disconnect =
+            run_read_loop::<_, VarInt, _, _, _>(&mut connection, &mut seen, options, echo).await;
+        assert!(matches!(disconnect, Disconnect::RateLimited));
+        assert_eq!(seen.values, vec![1, 2]);
+
+        // two frames land in each window once the clock moves half a window per frame
+        let mut connection = connection_with(&[1, 2, 3, 4]).await?;
+        let mut seen = Seen::new(Duration::from_secs(30));
+        let options = seen.options().with_rate_limit(2, Duration::from_secs(60));
+        let disconnect =
+            run_read_loop::<_, VarInt, _, _, _>(&mut connection, &mut seen, options, echo).await;
+        assert!(matches!(disconnect, Disconnect::Closed));
+        assert_eq!(seen.values, vec![1, 2, 3, 4]);
+
+        #[cfg(feature = "idle")]
+        {
+            let (read, _write) = tokio::io::duplex(64);
+            let mut connection = DraxConnection::new(read, vec![]);
+            let options = ReadLoopOptions::new().with_read_timeout(Duration::from_millis(10));
+            let disconnect = run_read_loop::<_, VarInt, _, _, _>(
+                &mut connection,
+                &mut Seen::new(Duration::ZERO),
+                options,
+                echo,
+            )
+            .await;
+            assert!(matches!(disconnect, Disconnect::TimedOut));
+        }
+        Ok(())
+    }
+}
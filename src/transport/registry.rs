@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use tokio::io::AsyncRead;
+
+use crate::prelude::{DraxReadExt, PacketComponent};
+use crate::transport::counting::CountingReader;
+use crate::transport::frame::{decode_whole, FrameReader};
+use crate::transport::id::PacketId;
+use crate::{throw_explain, PinnedLivelyResult};
+
+/// Decodes the body of a registered packet and passes it to its handler.
+trait Handler<C>: Send + Sync {
+    fn handle<'a>(&'a self, context: &'a mut C, body: &'a [u8]) -> PinnedLivelyResult<'a, ()>;
+}
+
+struct TypedHandler<P, H> {
+    handler: H,
+    _phantom_p: PhantomData<fn() -> P>,
+}
+
+impl<C: Send + Sync, P, H> Handler<C> for TypedHandler<P, H>
+where
+    P: PacketComponent<C>,
+    H: for<'a> Fn(&'a mut C, P::ComponentType) -> PinnedLivelyResult<'a, ()> + Send + Sync,
+{
+    fn handle<'a>(&'a self, context: &'a mut C, body: &'a [u8]) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            let packet = decode_whole::<C, P>("Packet body", context, body).await?;
+            (self.handler)(context, packet).await
+        })
+    }
+}
+
+type Fallback<C> =
+    Box<dyn for<'a> Fn(&'a mut C, PacketId, &'a [u8]) -> PinnedLivelyResult<'a, ()> + Send + Sync>;
+
+/// Routes frames to handlers by the VarInt packet id at the start of their bodies, decoding the
+/// rest of the body with the component registered for that id.
+///
+/// Handlers return pinned futures, like the methods of `PacketComponent`, so they can borrow the
+/// context while they run:
+/// `registry.register::<String, _>(PacketId(0), |context, message| Box::pin(async move { .. }))`.
+pub struct PacketRegistry<C: Send + Sync> {
+    handlers: HashMap<PacketId, Box<dyn Handler<C>>>,
+    fallback: Option<Fallback<C>>,
+}
+
+impl<C: Send + Sync> Default for PacketRegistry<C> {
+    fn default() -> Self {
+        Self {
+            handlers: HashMap::new(),
+            fallback: None,
+        }
+    }
+}
+
+impl<C: Send + Sync> PacketRegistry<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the component and handler for packets with the given id, replacing any
+    /// previously registered for it.
+    ///
+    /// # Parameters
+    /// * `id` - The packet id to handle.
+    /// * `handler` - Called with the context and the decoded packet.
+    pub fn register<P, H>(&mut self, id: PacketId, handler: H)
+    where
+        P: PacketComponent<C> + 'static,
+        H: for<'a> Fn(&'a mut C, P::ComponentType) -> PinnedLivelyResult<'a, ()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.handlers.insert(
+            id,
+            Box::new(TypedHandler::<P, H> {
+                handler,
+                _phantom_p: PhantomData,
+            }),
+        );
+    }
+
+    /// Registers the component and handler for packets with the given id, see `register`.
+    pub fn with<P, H>(mut self, id: PacketId, handler: H) -> Self
+    where
+        P: PacketComponent<C> + 'static,
+        H: for<'a> Fn(&'a mut C, P::ComponentType) -> PinnedLivelyResult<'a, ()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.register::<P, H>(id, handler);
+        self
+    }
+
+    /// Sets the handler for packets with unregistered ids, called with the id and the rest of the
+    /// body. Without one, unregistered ids are an error.
+    pub fn set_fallback<F>(&mut self, fallback: F)
+    where
+        F: for<'a> Fn(&'a mut C, PacketId, &'a [u8]) -> PinnedLivelyResult<'a, ()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.fallback = Some(Box::new(fallback));
+    }
+
+    /// Whether a handler is registered for the id.
+    pub fn is_registered(&self, id: PacketId) -> bool {
+        self.handlers.contains_key(&id)
+    }
+
+    /// Reads the packet id from the start of the body, then decodes the rest of the body with the
+    /// registered component, which must consume all of it, and passes it to the handler.
+    ///
+    /// # Parameters
+    /// * `context` - The context to decode and handle the packet with.
+    /// * `body` - The frame body, starting with the packet id.
+    pub async fn dispatch_body(
+        &self,
+        context: &mut C,
+        body: &[u8],
+    ) -> crate::prelude::Result<PacketId> {
+        let mut reader = CountingReader::new(body);
+        let id = PacketId(reader.read_var_int().await?);
+        let rest = &body[reader.bytes_transferred() as usize..];
+        match (self.handlers.get(&id), &self.fallback) {
+            (Some(handler), _) => handler.handle(context, rest).await?,
+            (None, Some(fallback)) => fallback(context, id, rest).await?,
+            (None, None) => throw_explain!(format!("Received unregistered packet id {id}")),
+        }
+        Ok(id)
+    }
+
+    /// Reads the next frame and dispatches its body, see `dispatch_body`. Returns the id of the
+    /// dispatched packet, or `None` if the reader ends cleanly between frames.
+    ///
+    /// # Parameters
+    /// * `context` - The context to decode and handle the packet with.
+    /// * `reader` - The reader to read the frame from.
+    pub async fn dispatch<R: AsyncRead + Unpin + Send + Sync>(
+        &self,
+        context: &mut C,
+        reader: &mut FrameReader<R>,
+    ) -> crate::prelude::Result<Option<PacketId>> {
+        match reader.read_frame().await? {
+            Some(body) => Ok(Some(self.dispatch_body(context, &body).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PacketRegistry;
+    use crate::prelude::DraxWriteExt;
+    use crate::transport::frame::{FrameReader, FrameWriter};
+    use crate::transport::id::PacketId;
+    use crate::transport::packet::primitive::VarInt;
+
+    #[derive(Default)]
+    struct Received {
+        messages: Vec<String>,
+        sums: Vec<i32>,
+        unknown: Vec<(PacketId, Vec<u8>)>,
+    }
+
+    async fn frame(id: i32, body: &[u8]) -> crate::prelude::Result<Vec<u8>> {
+        let mut frame = vec![];
+        frame.write_var_int(id).await?;
+        frame.extend_from_slice(body);
+        Ok(frame)
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() -> crate::prelude::Result<()> {
+        let mut registry = PacketRegistry::<Received>::new()
+            .with::<String, _>(PacketId(0), |received, message| {
+                Box::pin(async move {
+                    received.messages.push(message);
+                    Ok(())
+                })
+            })
+            .with::<Vec<VarInt>, _>(PacketId(300), |received, values| {
+                Box::pin(async move {
+                    received.sums.push(values.iter().sum());
+                    Ok(())
+                })
+            });
+        assert!(registry.is_registered(PacketId(300)));
+
+        let mut writer = FrameWriter::new(vec![]);
+        writer
+            .write_frame(&frame(0, &[4, b'd', b'r', b'a', b'x']).await?)
+            .await?;
+        writer.write_frame(&frame(300, &[2, 1, 2]).await?).await?;
+        writer.write_frame(&frame(7, &[9, 9]).await?).await?;
+        writer.write_frame(&frame(0, &[1, b'a', 0]).await?).await?;
+        let bytes = writer.into_inner();
+
+        let mut received = Received::default();
+        let mut reader = FrameReader::new(std::io::Cursor::new(bytes.clone()));
+        assert_eq!(
+            registry.dispatch(&mut received, &mut reader).await?,
+            Some(PacketId(0))
+        );
+        assert_eq!(
+            registry.dispatch(&mut received, &mut reader).await?,
+            Some(PacketId(300))
+        );
+        assert!(registry.dispatch(&mut received, &mut reader).await.is_err());
+        assert_eq!(received.messages, vec!["drax".to_string()]);
+        assert_eq!(received.sums, vec![3]);
+
+        registry.set_fallback(|received, id, body| {
+            Box::pin(async move {
+                received.unknown.push((id, body.to_vec()));
+                Ok(())
+            })
+        });
+        let mut received = Received::default();
+        let mut reader = FrameReader::new(std::io::Cursor::new(bytes));
+        for _ in 0..3 {
+            registry.dispatch(&mut received, &mut reader).await?;
+        }
+        assert_eq!(received.unknown, vec![(PacketId(7), vec![9, 9])]);
+        // trailing bytes after the registered component
+        assert!(registry.dispatch(&mut received, &mut reader).await.is_err());
+        assert_eq!(registry.dispatch(&mut received, &mut reader).await?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt::{Debug, Formatter};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::transport::buffer::var_num::size_var_int;
+use crate::transport::buffer::{DraxReadExt, DraxWriteExt};
+use crate::transport::packet::vec::checked_length;
+use crate::transport::packet::{PacketComponent, Size};
+use crate::{throw_explain, PinnedLivelyResult};
+
+/// The longest secret, in bytes, which will be decoded. Secrets are read into a buffer of exactly
+/// their length, so the buffer is never reallocated and never leaves copies of the secret behind.
+pub const MAX_SECRET_LEN: usize = 1024;
+
+/// Compares the bytes in time depending only on their lengths, not on where they first differ.
+#[cfg(feature = "subtle")]
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    use subtle::ConstantTimeEq;
+
+    a.ct_eq(b).into()
+}
+
+/// Bytes such as a shared secret or verify token, encoded the same as `VecU8`. Their `Debug`
+/// output only shows their length, equality is constant-time with the `subtle` feature and they
+/// are zeroed when dropped with the `zeroize` feature.
+#[derive(Clone, Default)]
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// The secret itself, named so uses of it stand out.
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Debug for SecretBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.0.len())
+    }
+}
+
+#[cfg(feature = "subtle")]
+impl PartialEq for SecretBytes {
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(feature = "subtle")]
+impl Eq for SecretBytes {}
+
+#[cfg(feature = "zeroize")]
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
+
+impl<C: Send + Sync> PacketComponent<C> for SecretBytes {
+    type ComponentType = SecretBytes;
+
+    fn decode<'a, A: AsyncRead + Unpin + Send + Sync + ?Sized>(
+        _: &'a mut C,
+        read: &'a mut A,
+    ) -> PinnedLivelyResult<'a, Self::ComponentType> {
+        Box::pin(async move {
+            let len = checked_length(read.read_var_int().await?)?;
+            if len > MAX_SECRET_LEN {
+                throw_explain!(format!(
+                    "Secret of {len} bytes exceeded maximum length {MAX_SECRET_LEN}"
+                ))
+            }
+            // wrapped before reading so a failed read still zeroes what was read
+            let mut secret = SecretBytes(vec![0; len]);
+            read.read_exact(&mut secret.0).await?;
+            Ok(secret)
+        })
+    }
+
+    fn encode<'a, A: AsyncWrite + Unpin + Send + Sync + ?Sized>(
+        component_ref: &'a Self::ComponentType,
+        _: &'a mut C,
+        write: &'a mut A,
+    ) -> PinnedLivelyResult<'a, ()> {
+        Box::pin(async move {
+            write
+                .write_var_int(i32::try_from(component_ref.0.len())?)
+                .await?;
+            write.write_all(&component_ref.0).await?;
+            Ok(())
+        })
+    }
+
+    fn size(input: &Self::ComponentType, _: &mut C) -> crate::prelude::Result<Size> {
+        let len = input.0.len();
+        Ok(Size::Dynamic(len + size_var_int(i32::try_from(len)?)))
+    }
+
+    fn heap_size(input: &Self::ComponentType) -> usize {
+        input.0.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::SecretBytes;
+    use crate::prelude::PacketComponent;
+
+    #[tokio::test]
+    async fn test_secret_bytes() -> crate::prelude::Result<()> {
+        let secret = SecretBytes::new(vec![1, 2, 3]);
+        assert_eq!(format!("{secret:?}"), "SecretBytes([REDACTED; 3])");
+
+        let mut cursor = Cursor::new(vec![]);
+        SecretBytes::encode(&secret, &mut (), &mut cursor).await?;
+        assert_eq!(cursor.get_ref(), &vec![3, 1, 2, 3]);
+        cursor.set_position(0);
+        let decoded = SecretBytes::decode(&mut (), &mut cursor).await?;
+        assert_eq!(decoded.expose(), secret.expose());
+        #[cfg(feature = "subtle")]
+        {
+            assert_eq!(decoded, secret);
+            assert_ne!(decoded, SecretBytes::new(vec![1, 2, 4]));
+            assert!(!super::ct_eq(&[1, 2], &[1, 2, 3]));
+        }
+
+        let mut cursor = Cursor::new(vec![0x80, 0x10]);
+        assert!(SecretBytes::decode(&mut (), &mut cursor).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A context which supplies the time to protocol logic, such as rate limits and keep alives, so a
+/// test can fix or step it instead of sleeping. Defaults to the system clock.
+pub trait ClockSource {
+    /// The current time, for measuring durations.
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// The time since the Unix epoch, for timestamps sent over the wire.
+    fn unix_time(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+impl ClockSource for () {}
+
+/// The system clock, for APIs taking a clock rather than reading it from the context.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl ClockSource for SystemClock {}
+
+/// A context which supplies random values to protocol logic, such as keep alive ids and nonces, so
+/// a test can seed them. Defaults to values derived from the standard library's randomly keyed
+/// hasher, which are unpredictable but not suitable for secrets; see `encryption::handshake` for
+/// those.
+pub trait RngSource {
+    fn next_u64(&mut self) -> u64 {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.finish()
+    }
+
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let value = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+    }
+}
+
+impl RngSource for () {}
+
+/// A clock which only moves when advanced, for contexts under test to delegate `ClockSource` to.
+#[derive(Clone, Copy, Debug)]
+pub struct ManualClock {
+    origin: Instant,
+    unix_origin: Duration,
+    elapsed: Duration,
+}
+
+impl ManualClock {
+    /// A clock reading the given time since the Unix epoch.
+    pub fn new(unix_time: Duration) -> Self {
+        Self {
+            origin: Instant::now(),
+            unix_origin: unix_time,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn advance(&mut self, by: Duration) {
+        self.elapsed += by;
+    }
+}
+
+impl ClockSource for ManualClock {
+    fn now(&self) -> Instant {
+        self.origin + self.elapsed
+    }
+
+    fn unix_time(&self) -> Duration {
+        self.unix_origin + self.elapsed
+    }
+}
+
+/// A SplitMix64 generator producing the same values for the same seed, for contexts under test to
+/// delegate `RngSource` to.
+#[derive(Clone, Copy, Debug)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl RngSource for SeededRng {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ClockSource, ManualClock, RngSource, SeededRng};
+
+    #[test]
+    fn test_sources() {
+        let mut clock = ManualClock::new(Duration::from_secs(1_700_000_000));
+        let start = clock.now();
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(clock.now() - start, Duration::from_millis(250));
+        assert_eq!(clock.unix_time().as_millis(), 1_700_000_000_250);
+
+        let mut rng = SeededRng::new(7);
+        let mut bytes = [0; 12];
+        rng.fill_bytes(&mut bytes);
+        let mut replay = SeededRng::new(7);
+        assert_eq!(bytes[..8], replay.next_u64().to_le_bytes());
+        assert_eq!(bytes[8..], replay.next_u64().to_le_bytes()[..4]);
+
+        assert_ne!(().next_u64(), ().next_u64());
+        assert!(().unix_time() > Duration::from_secs(1_700_000_000));
+    }
+}